@@ -178,3 +178,14 @@ impl From<RegistrationInit> for Registration {
         }
     }
 }
+/// Generate an [OpenAPI] description of all the endpoints of the Application Service API.
+///
+/// [OpenAPI]: https://spec.openapis.org/oas/v3.1.0
+#[cfg(feature = "openapi")]
+pub fn openapi() -> ruma_common::api::openapi::OpenApi {
+    ruma_common::api::openapi::OpenApi::for_crate(
+        module_path!(),
+        "Matrix Application Service API",
+        env!("CARGO_PKG_VERSION"),
+    )
+}
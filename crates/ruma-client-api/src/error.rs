@@ -13,6 +13,7 @@ use ruma_common::{
             IntoHttpError, MatrixErrorBody,
         },
     },
+    push::ApplyPushRuleError,
     serde::StringEnum,
 };
 use serde::{Deserialize, Serialize};
@@ -22,6 +23,7 @@ use web_time::{Duration, SystemTime};
 use crate::{
     PrivOwnedStr,
     http_headers::{http_date_to_system_time, system_time_to_http_date},
+    push::SetPusherError,
 };
 
 /// Deserialize and Serialize implementations for ErrorKind.
@@ -984,6 +986,28 @@ impl fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+impl From<ApplyPushRuleError> for Error {
+    /// Convert the error to an `M_NOT_FOUND` error if a rule could not be found, or to an
+    /// `M_INVALID_PARAM` error otherwise, as expected by the push rules endpoints.
+    fn from(error: ApplyPushRuleError) -> Self {
+        let (status_code, kind) = if error.is_not_found() {
+            (http::StatusCode::NOT_FOUND, ErrorKind::NotFound)
+        } else {
+            (http::StatusCode::BAD_REQUEST, ErrorKind::InvalidParam)
+        };
+
+        ErrorBody::Standard(StandardErrorBody::new(kind, error.to_string())).into_error(status_code)
+    }
+}
+
+impl From<SetPusherError> for Error {
+    /// Convert the error to an `M_INVALID_PARAM` error, as expected by the `set_pusher` endpoint.
+    fn from(error: SetPusherError) -> Self {
+        ErrorBody::Standard(StandardErrorBody::new(ErrorKind::InvalidParam, error.to_string()))
+            .into_error(http::StatusCode::BAD_REQUEST)
+    }
+}
+
 impl ErrorBody {
     /// Convert the ErrorBody into an Error by adding the http status code.
     ///
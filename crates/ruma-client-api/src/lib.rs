@@ -62,6 +62,18 @@ use std::fmt;
 
 pub use error::Error;
 
+/// Generate an [OpenAPI] description of all the endpoints of the Client-Server API.
+///
+/// [OpenAPI]: https://spec.openapis.org/oas/v3.1.0
+#[cfg(feature = "openapi")]
+pub fn openapi() -> ruma_common::api::openapi::OpenApi {
+    ruma_common::api::openapi::OpenApi::for_crate(
+        module_path!(),
+        "Matrix Client-Server API",
+        env!("CARGO_PKG_VERSION"),
+    )
+}
+
 // Wrapper around `Box<str>` that cannot be used in a meaningful way outside of
 // this crate. Used for string enums because their `_Custom` variant can't be
 // truly private (only `#[doc(hidden)]`).
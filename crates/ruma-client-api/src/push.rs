@@ -13,6 +13,8 @@ use ruma_common::{
 use serde::{Deserialize, Serialize};
 use smallstr::SmallString;
 
+pub use self::pusher_registry::{PusherRegistry, SetPusherError};
+
 pub mod delete_pushrule;
 pub mod get_notifications;
 pub mod get_pushers;
@@ -21,6 +23,7 @@ pub mod get_pushrule_actions;
 pub mod get_pushrule_enabled;
 pub mod get_pushrules_all;
 pub mod get_pushrules_global_scope;
+mod pusher_registry;
 mod pusher_serde;
 pub mod set_pusher;
 pub mod set_pushrule;
@@ -0,0 +1,292 @@
+//! An in-memory registry of pushers, following the semantics of the pushers endpoints.
+
+use std::collections::BTreeMap;
+
+use ruma_common::{OwnedUserId, UserId};
+use url::Url;
+
+use super::{
+    Pusher, PusherIds, PusherKind, get_pushers,
+    set_pusher::v3::{PusherAction, PusherPostData, Request as SetPusherRequest},
+};
+
+/// The maximum allowed length of a pushkey, in bytes.
+const MAX_PUSHKEY_LENGTH: usize = 512;
+
+/// The maximum allowed length of an app ID, in bytes.
+const MAX_APP_ID_LENGTH: usize = 64;
+
+/// The path that the URL of HTTP pushers must have.
+const PUSH_GATEWAY_PATH: &str = "/_matrix/push/v1/notify";
+
+/// An in-memory registry of the pushers of users.
+///
+/// It applies the requests of the `set_pusher` endpoint with the semantics of the specification
+/// and can produce the responses of the `get_pushers` endpoint. It can be used by homeservers as a
+/// reference implementation of those endpoints.
+#[derive(Clone, Debug, Default)]
+pub struct PusherRegistry {
+    /// The pushers, by user ID.
+    pushers: BTreeMap<OwnedUserId, Vec<Pusher>>,
+}
+
+impl PusherRegistry {
+    /// Creates an empty `PusherRegistry`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the given `set_pusher` request, sent by the given user.
+    ///
+    /// Returns an error if the pusher in the request is invalid.
+    pub fn set_pusher(
+        &mut self,
+        user_id: &UserId,
+        request: SetPusherRequest,
+    ) -> Result<(), SetPusherError> {
+        match request.action {
+            PusherAction::Post(data) => self.post_pusher(user_id, data),
+            PusherAction::Delete(ids) => {
+                self.remove_pusher(user_id, &ids);
+                Ok(())
+            }
+        }
+    }
+
+    /// Create or update a pusher for the given user.
+    ///
+    /// If the user already has a pusher with the same pushkey and app ID, it is replaced. Unless
+    /// `append` is `true`, the pushers with the same pushkey and app ID of other users are
+    /// removed.
+    ///
+    /// Returns an error if the pusher is invalid.
+    pub fn post_pusher(
+        &mut self,
+        user_id: &UserId,
+        data: PusherPostData,
+    ) -> Result<(), SetPusherError> {
+        let PusherPostData { pusher, append } = data;
+        validate_pusher(&pusher)?;
+
+        if !append {
+            self.pushers.retain(|other_user_id, pushers| {
+                if other_user_id != user_id {
+                    pushers.retain(|other| other.ids != pusher.ids);
+                }
+                !pushers.is_empty()
+            });
+        }
+
+        let pushers = self.pushers.entry(user_id.to_owned()).or_default();
+
+        if let Some(existing) = pushers.iter_mut().find(|existing| existing.ids == pusher.ids) {
+            *existing = pusher;
+        } else {
+            pushers.push(pusher);
+        }
+
+        Ok(())
+    }
+
+    /// Remove the pusher of the given user with the given IDs.
+    ///
+    /// Returns the removed pusher, if any.
+    pub fn remove_pusher(&mut self, user_id: &UserId, ids: &PusherIds) -> Option<Pusher> {
+        let pushers = self.pushers.get_mut(user_id)?;
+        let position = pushers.iter().position(|pusher| pusher.ids == *ids)?;
+        let pusher = pushers.remove(position);
+
+        if pushers.is_empty() {
+            self.pushers.remove(user_id);
+        }
+
+        Some(pusher)
+    }
+
+    /// Remove the pushers of all the users with the given IDs.
+    ///
+    /// This should be called for the pushkeys that were rejected by a push gateway.
+    pub fn remove_pushkey(&mut self, ids: &PusherIds) {
+        self.pushers.retain(|_, pushers| {
+            pushers.retain(|pusher| pusher.ids != *ids);
+            !pushers.is_empty()
+        });
+    }
+
+    /// The pushers of the given user.
+    pub fn pushers(&self, user_id: &UserId) -> &[Pusher] {
+        self.pushers.get(user_id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The users that have a pusher with the given IDs, with the corresponding pusher.
+    pub fn pushers_with_ids<'a>(
+        &'a self,
+        ids: &'a PusherIds,
+    ) -> impl Iterator<Item = (&'a UserId, &'a Pusher)> + 'a {
+        self.pushers.iter().filter_map(move |(user_id, pushers)| {
+            let pusher = pushers.iter().find(|pusher| pusher.ids == *ids)?;
+            Some((user_id.as_ref(), pusher))
+        })
+    }
+
+    /// Construct the response of the `get_pushers` endpoint for the given user.
+    pub fn get_pushers(&self, user_id: &UserId) -> get_pushers::v3::Response {
+        get_pushers::v3::Response::new(self.pushers(user_id).to_vec())
+    }
+}
+
+/// Check that the given pusher is valid.
+fn validate_pusher(pusher: &Pusher) -> Result<(), SetPusherError> {
+    if pusher.ids.pushkey.len() > MAX_PUSHKEY_LENGTH {
+        return Err(SetPusherError::PushkeyTooLong);
+    }
+
+    if pusher.ids.app_id.len() > MAX_APP_ID_LENGTH {
+        return Err(SetPusherError::AppIdTooLong);
+    }
+
+    match &pusher.kind {
+        PusherKind::Http(data) => {
+            let url = Url::parse(&data.url).map_err(|_| SetPusherError::InvalidUrl)?;
+
+            if url.scheme() != "https" {
+                return Err(SetPusherError::NotHttpsUrl);
+            }
+
+            if url.path() != PUSH_GATEWAY_PATH {
+                return Err(SetPusherError::InvalidUrlPath);
+            }
+        }
+        PusherKind::Email(_) => {}
+        PusherKind::_Custom(_) => return Err(SetPusherError::UnsupportedKind),
+    }
+
+    Ok(())
+}
+
+/// The error type returned when a `set_pusher` request can't be applied to a [`PusherRegistry`].
+#[derive(Debug, Clone, thiserror::Error)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum SetPusherError {
+    /// The pushkey is longer than 512 bytes.
+    #[error("pushkey must not be longer than 512 bytes")]
+    PushkeyTooLong,
+
+    /// The app ID is longer than 64 bytes.
+    #[error("app_id must not be longer than 64 bytes")]
+    AppIdTooLong,
+
+    /// The URL of the HTTP pusher is not a valid URL.
+    #[error("url is not a valid URL")]
+    InvalidUrl,
+
+    /// The URL of the HTTP pusher does not use the `https` scheme.
+    #[error("url must use the `https` scheme")]
+    NotHttpsUrl,
+
+    /// The URL of the HTTP pusher does not have the `/_matrix/push/v1/notify` path.
+    #[error("url must have a path of `/_matrix/push/v1/notify`")]
+    InvalidUrlPath,
+
+    /// The kind of the pusher is not supported.
+    #[error("unsupported pusher kind")]
+    UnsupportedKind,
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_matches;
+    use ruma_common::{push::HttpPusherData, user_id};
+
+    use super::{PusherRegistry, SetPusherError};
+    use crate::push::{
+        EmailPusherData, Pusher, PusherIds, PusherInit, PusherKind,
+        set_pusher::v3::{PusherAction, Request},
+    };
+
+    fn pusher(pushkey: &str, kind: PusherKind) -> Pusher {
+        PusherInit {
+            ids: PusherIds::new(pushkey.to_owned(), "org.example.app".to_owned()),
+            kind,
+            app_display_name: "Example".into(),
+            device_display_name: "Phone".into(),
+            profile_tag: None,
+            lang: "en".into(),
+        }
+        .into()
+    }
+
+    fn http_pusher(pushkey: &str, url: &str) -> Pusher {
+        pusher(pushkey, PusherKind::Http(HttpPusherData::new(url.to_owned())))
+    }
+
+    fn post(pusher: Pusher, append: bool) -> Request {
+        let PusherAction::Post(mut data) = Request::post(pusher).action else {
+            unreachable!();
+        };
+        data.append = append;
+        Request::new(PusherAction::Post(data))
+    }
+
+    #[test]
+    fn set_pushers() {
+        let alice = user_id!("@alice:localhost");
+        let bob = user_id!("@bob:localhost");
+        let url = "https://push.example.org/_matrix/push/v1/notify";
+        let mut registry = PusherRegistry::new();
+
+        registry.set_pusher(alice, Request::post(http_pusher("key", url))).unwrap();
+        registry
+            .set_pusher(
+                alice,
+                Request::post(pusher("a@example.org", PusherKind::Email(EmailPusherData::new()))),
+            )
+            .unwrap();
+        assert_eq!(registry.get_pushers(alice).pushers.len(), 2);
+
+        // Updating the pusher of the same user replaces it.
+        let mut updated = http_pusher("key", url);
+        updated.lang = "fr".into();
+        registry.set_pusher(alice, Request::post(updated)).unwrap();
+        let pushers = registry.pushers(alice);
+        assert_eq!(pushers.len(), 2);
+        assert_eq!(pushers[0].lang, "fr");
+
+        // With `append`, the pushers of other users are kept.
+        registry.set_pusher(bob, post(http_pusher("key", url), true)).unwrap();
+        let ids = PusherIds::new("key".to_owned(), "org.example.app".to_owned());
+        assert_eq!(registry.pushers_with_ids(&ids).count(), 2);
+
+        // Without `append`, the pushers of other users are removed.
+        registry.set_pusher(bob, post(http_pusher("key", url), false)).unwrap();
+        let users = registry.pushers_with_ids(&ids).map(|(user_id, _)| user_id).collect::<Vec<_>>();
+        assert_eq!(users, [bob]);
+        assert_eq!(registry.pushers(alice).len(), 1);
+
+        registry.set_pusher(bob, Request::delete(ids.clone())).unwrap();
+        assert!(registry.pushers(bob).is_empty());
+        assert_eq!(registry.pushers_with_ids(&ids).count(), 0);
+    }
+
+    #[test]
+    fn invalid_pushers() {
+        let alice = user_id!("@alice:localhost");
+        let mut registry = PusherRegistry::new();
+
+        let request = Request::post(http_pusher("key", "https://push.example.org/notify"));
+        assert_matches!(registry.set_pusher(alice, request), Err(SetPusherError::InvalidUrlPath));
+
+        let request =
+            Request::post(http_pusher("key", "http://push.example.org/_matrix/push/v1/notify"));
+        assert_matches!(registry.set_pusher(alice, request), Err(SetPusherError::NotHttpsUrl));
+
+        let request = Request::post(http_pusher("key", "not a url"));
+        assert_matches!(registry.set_pusher(alice, request), Err(SetPusherError::InvalidUrl));
+
+        let pushkey = "a".repeat(513);
+        let request = Request::post(pusher(&pushkey, PusherKind::Email(EmailPusherData::new())));
+        assert_matches!(registry.set_pusher(alice, request), Err(SetPusherError::PushkeyTooLong));
+
+        assert!(registry.pushers(alice).is_empty());
+    }
+}
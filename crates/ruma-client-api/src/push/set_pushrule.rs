@@ -10,7 +10,7 @@ pub mod v3 {
     use ruma_common::{
         api::{Metadata, auth_scheme::AccessToken, response},
         metadata,
-        push::{Actions, NewPushRule, Pattern, PushConditions},
+        push::{Actions, NewPushRule, Pattern, PushConditions, PushRuleUpdate},
     };
     use serde::{Deserialize, Serialize};
 
@@ -59,6 +59,13 @@ pub mod v3 {
         }
     }
 
+    impl From<Request> for PushRuleUpdate {
+        fn from(request: Request) -> Self {
+            let Request { rule, before, after } = request;
+            Self::Set { rule, before, after }
+        }
+    }
+
     #[cfg(feature = "client")]
     impl ruma_common::api::OutgoingRequest for Request {
         type EndpointError = crate::Error;
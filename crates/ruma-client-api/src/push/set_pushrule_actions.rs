@@ -11,7 +11,7 @@ pub mod v3 {
     use ruma_common::{
         api::{auth_scheme::AccessToken, request, response},
         metadata,
-        push::Action,
+        push::{Action, PushRuleUpdate},
     };
 
     use crate::push::RuleKind;
@@ -59,4 +59,11 @@ pub mod v3 {
             Self {}
         }
     }
+
+    impl From<Request> for PushRuleUpdate {
+        fn from(request: Request) -> Self {
+            let Request { kind, rule_id, actions } = request;
+            Self::SetActions { kind, rule_id, actions: actions.into() }
+        }
+    }
 }
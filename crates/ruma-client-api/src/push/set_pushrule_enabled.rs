@@ -10,6 +10,7 @@ pub mod v3 {
     use ruma_common::{
         api::{auth_scheme::AccessToken, request, response},
         metadata,
+        push::PushRuleUpdate,
     };
 
     use crate::push::RuleKind;
@@ -67,4 +68,11 @@ pub mod v3 {
             Self {}
         }
     }
+
+    impl From<Request> for PushRuleUpdate {
+        fn from(request: Request) -> Self {
+            let Request { kind, rule_id, enabled } = request;
+            Self::SetEnabled { kind, rule_id, enabled }
+        }
+    }
 }
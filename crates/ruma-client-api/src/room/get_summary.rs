@@ -128,6 +128,34 @@ pub mod v1 {
             Ok(Self { summary, membership })
         }
     }
+
+    #[cfg(feature = "openapi")]
+    impl ruma_common::api::openapi::DescribeResponse for Response {
+        fn response_status() -> http::StatusCode {
+            http::StatusCode::OK
+        }
+
+        fn response_fields() -> Vec<ruma_common::api::openapi::FieldDescription> {
+            use ruma_common::api::openapi::{
+                DescribeSchema, FieldDescription, FieldLocation, Schema,
+            };
+
+            vec![
+                FieldDescription::new(
+                    "summary",
+                    FieldLocation::FlattenedBody,
+                    true,
+                    RoomSummary::schema(),
+                ),
+                FieldDescription::new(
+                    "membership",
+                    FieldLocation::Body,
+                    false,
+                    Schema::opaque::<MembershipState>(),
+                ),
+            ]
+        }
+    }
 }
 
 #[cfg(all(test, feature = "client"))]
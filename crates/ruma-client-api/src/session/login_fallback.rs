@@ -90,3 +90,16 @@ impl ruma_common::api::IncomingResponse for Response {
         Ok(Self { body })
     }
 }
+
+#[cfg(feature = "openapi")]
+impl ruma_common::api::openapi::DescribeResponse for Response {
+    fn response_status() -> http::StatusCode {
+        http::StatusCode::OK
+    }
+
+    fn response_fields() -> Vec<ruma_common::api::openapi::FieldDescription> {
+        use ruma_common::api::openapi::{FieldDescription, FieldLocation, Schema};
+
+        vec![FieldDescription::new("body", FieldLocation::RawBody, true, Schema::binary())]
+    }
+}
@@ -138,6 +138,20 @@ pub mod v3 {
         }
     }
 
+    /// Only the HTML page is described, since the redirect has no body.
+    #[cfg(feature = "openapi")]
+    impl ruma_common::api::openapi::DescribeResponse for Response {
+        fn response_status() -> http::StatusCode {
+            http::StatusCode::OK
+        }
+
+        fn response_fields() -> Vec<ruma_common::api::openapi::FieldDescription> {
+            use ruma_common::api::openapi::{FieldDescription, FieldLocation, Schema};
+
+            vec![FieldDescription::new("body", FieldLocation::RawBody, true, Schema::binary())]
+        }
+    }
+
     #[cfg(all(test, any(feature = "client", feature = "server")))]
     mod tests {
         use assert_matches2::assert_matches;
@@ -1,2 +1,3 @@
 mod headers;
+mod spec_examples;
 mod uiaa;
@@ -0,0 +1,202 @@
+//! Conformance tests using the examples of the [Matrix Client-Server API specification].
+//!
+//! The examples are vendored in `tests/it/fixtures/spec`, see the README there. Each example is
+//! deserialized into the type of the corresponding endpoint, then serialized again, and the result
+//! must match the example, except for the known differences listed by the test.
+//!
+//! [Matrix Client-Server API specification]: https://spec.matrix.org/latest/client-server-api/
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use ruma_client_api::{
+    account::{register, whoami},
+    alias::get_alias,
+    device::get_devices,
+    discovery::{get_capabilities, get_supported_versions},
+    membership::joined_members,
+    message::send_message_event,
+    profile::get_profile,
+    room::{aliases, create_room},
+    session::login,
+    user_directory::search_users,
+};
+use ruma_common::{
+    api::{
+        IncomingRequest, IncomingResponse, OutgoingRequest, OutgoingResponse, SupportedVersions,
+        auth_scheme::{AuthScheme, SendAccessToken},
+        path_builder::{PathBuilder, SinglePath, VersionHistory},
+    },
+    serde::test::{JsonDifference, assert_json_round_trip, load_json_example},
+};
+use serde_json::{Value as JsonValue, from_slice as from_json_slice, to_vec as to_json_vec};
+
+const FIXTURES_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/it/fixtures/spec");
+
+/// Load the example at the given path, relative to `tests/it/fixtures/spec`.
+fn load_example(path: &str) -> JsonValue {
+    load_json_example(FIXTURES_PATH, path)
+}
+
+/// A path builder that can provide an input to build the path of a request in tests.
+trait ExamplePathBuilder: PathBuilder {
+    fn example_input() -> Self::Input<'static>;
+}
+
+impl ExamplePathBuilder for VersionHistory {
+    fn example_input() -> Self::Input<'static> {
+        Cow::Owned(SupportedVersions::from_parts(&["v1.15".to_owned()], &BTreeMap::new()))
+    }
+}
+
+impl ExamplePathBuilder for SinglePath {
+    fn example_input() -> Self::Input<'static> {}
+}
+
+/// Check that the request body example at the given path round-trips through the request type
+/// `R`.
+fn check_request<R>(path: &str, path_args: &[&str], known_differences: &[JsonDifference])
+where
+    R: IncomingRequest + OutgoingRequest,
+    R::Authentication: for<'a> AuthScheme<Input<'a> = SendAccessToken<'a>>,
+    R::PathBuilder: ExamplePathBuilder,
+{
+    let example = load_example(path);
+    let body = to_json_vec(&example).unwrap();
+    let http_request = http::Request::builder()
+        .method(R::METHOD)
+        .uri("https://homeserver.tld/")
+        .header(http::header::AUTHORIZATION, "Bearer token")
+        .body(body.as_slice())
+        .unwrap();
+
+    let request = R::try_from_http_request(http_request, path_args)
+        .unwrap_or_else(|error| panic!("failed to deserialize request `{path}`: {error}"));
+    let http_request = request
+        .try_into_http_request::<Vec<u8>>(
+            "https://homeserver.tld",
+            SendAccessToken::Always("token"),
+            R::PathBuilder::example_input(),
+        )
+        .unwrap_or_else(|error| panic!("failed to serialize request `{path}`: {error}"));
+
+    assert_json_round_trip(
+        path,
+        &example,
+        &from_json_slice::<JsonValue>(http_request.body()).unwrap(),
+        known_differences,
+    );
+}
+
+/// Check that the response body example at the given path round-trips through the response type
+/// `R`.
+fn check_response<R>(path: &str, known_differences: &[JsonDifference])
+where
+    R: IncomingResponse + OutgoingResponse,
+{
+    let example = load_example(path);
+    let body = to_json_vec(&example).unwrap();
+    let http_response = http::Response::builder().body(body.as_slice()).unwrap();
+
+    let response = R::try_from_http_response(http_response)
+        .unwrap_or_else(|error| panic!("failed to deserialize response `{path}`: {error}"));
+    let http_response = response
+        .try_into_http_response::<Vec<u8>>()
+        .unwrap_or_else(|error| panic!("failed to serialize response `{path}`: {error}"));
+
+    assert_json_round_trip(
+        path,
+        &example,
+        &from_json_slice::<JsonValue>(http_response.body()).unwrap(),
+        known_differences,
+    );
+}
+
+#[test]
+fn get_supported_versions() {
+    check_response::<get_supported_versions::Response>("versions/getVersions.response.json", &[]);
+}
+
+#[test]
+fn get_capabilities() {
+    check_response::<get_capabilities::v3::Response>(
+        "capabilities/getCapabilities.response.json",
+        // These capabilities are always serialized, even with their default value.
+        &[
+            JsonDifference::Added("/capabilities/m.3pid_changes".to_owned()),
+            JsonDifference::Added("/capabilities/m.get_login_token".to_owned()),
+        ],
+    );
+}
+
+#[test]
+fn login() {
+    check_request::<login::v3::Request>("login/login.request.json", &[], &[]);
+    check_response::<login::v3::Response>("login/login.response.json", &[]);
+}
+
+#[test]
+fn register() {
+    check_request::<register::v3::Request>("registration/register.request.json", &[], &[]);
+    check_response::<register::v3::Response>("registration/register.response.json", &[]);
+}
+
+#[test]
+fn whoami() {
+    check_response::<whoami::v3::Response>("whoami/getTokenOwner.response.json", &[]);
+}
+
+#[test]
+fn create_room() {
+    check_request::<create_room::v3::Request>("create_room/createRoom.request.json", &[], &[]);
+    check_response::<create_room::v3::Response>("create_room/createRoom.response.json", &[]);
+}
+
+#[test]
+fn room_aliases() {
+    check_response::<aliases::v3::Response>("directory/getLocalAliases.response.json", &[]);
+}
+
+#[test]
+fn send_message_event() {
+    check_request::<send_message_event::v3::Request>(
+        "room_send/sendMessage.request.json",
+        &["!636q39766251:example.com", "m.room.message", "35"],
+        &[],
+    );
+    check_response::<send_message_event::v3::Response>("room_send/sendMessage.response.json", &[]);
+}
+
+#[test]
+fn joined_members() {
+    check_response::<joined_members::v3::Response>(
+        "rooms/getJoinedMembersByRoom.response.json",
+        &[],
+    );
+}
+
+#[test]
+fn search_users() {
+    check_request::<search_users::v3::Request>(
+        "users/searchUserDirectory.request.json",
+        &[],
+        // The limit is not serialized when it is the default value.
+        &[JsonDifference::Dropped("/limit".to_owned())],
+    );
+    check_response::<search_users::v3::Response>("users/searchUserDirectory.response.json", &[]);
+}
+
+#[test]
+fn get_devices() {
+    check_response::<get_devices::v3::Response>("device_management/getDevices.response.json", &[]);
+}
+
+#[test]
+fn get_alias() {
+    check_response::<get_alias::v3::Response>("directory/getRoomIdByAlias.response.json", &[]);
+}
+
+#[test]
+fn get_profile() {
+    check_response::<get_profile::v3::Response>("profile/getUserProfile.response.json", &[]);
+}
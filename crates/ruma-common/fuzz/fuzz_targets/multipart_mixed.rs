@@ -0,0 +1,61 @@
+//! Fuzz the `multipart/mixed` parser.
+//!
+//! The parser must not panic, and the result must be the same when the data is pushed all at once
+//! or one byte at a time.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ruma_common::api::multipart::{
+    BodyPart, Boundary, MultipartMixedEvent, MultipartMixedParser, parse_body,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let boundary = Boundary::new("abc").unwrap();
+
+    let buffered = parse_body(&boundary, data);
+    let incremental = parse_byte_by_byte(&boundary, data);
+
+    match (buffered, incremental) {
+        (Ok(buffered), Some(incremental)) => assert_eq!(buffered, incremental),
+        (Err(_), None) => {}
+        (buffered, incremental) => {
+            panic!("results differ: {buffered:?} != {incremental:?}")
+        }
+    }
+});
+
+/// Parse the given data, pushing it one byte at a time.
+///
+/// Returns `None` if parsing failed.
+fn parse_byte_by_byte(boundary: &Boundary, data: &[u8]) -> Option<Vec<BodyPart>> {
+    let mut parser = MultipartMixedParser::new(boundary);
+    let mut remaining = data;
+    let mut parts = Vec::new();
+    let mut current_part = None;
+
+    loop {
+        let Some(event) = parser.next_event().ok()? else {
+            match remaining.split_first() {
+                Some((byte, rest)) => {
+                    parser.push(&[*byte][..]);
+                    remaining = rest;
+                }
+                None => parser.end_input(),
+            }
+
+            continue;
+        };
+
+        match event {
+            MultipartMixedEvent::PartStart(headers) => current_part = Some((headers, Vec::new())),
+            MultipartMixedEvent::PartData(chunk) => {
+                current_part.as_mut().unwrap().1.extend_from_slice(&chunk);
+            }
+            MultipartMixedEvent::PartEnd => {
+                let (headers, content) = current_part.take().unwrap();
+                parts.push(BodyPart::new(headers, content.into()));
+            }
+            MultipartMixedEvent::End => return Some(parts),
+        }
+    }
+}
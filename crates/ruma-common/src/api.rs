@@ -19,6 +19,8 @@ use bytes::BufMut;
 ///
 /// The `OutgoingRequest` impl is feature-gated behind `cfg(feature = "client")`.
 /// The `IncomingRequest` impl is feature-gated behind `cfg(feature = "server")`.
+/// The [`DescribeRequest`](openapi::DescribeRequest) impl is feature-gated behind
+/// `cfg(feature = "openapi")`.
 ///
 /// The generated code expects the `Request` type to implement [`Metadata`], alongside a
 /// `Response` type that implements [`OutgoingResponse`] (for `cfg(feature = "server")`) and /
@@ -69,7 +71,9 @@ use bytes::BufMut;
 ///   object).
 /// * `#[ruma_api(raw_body)]`: Like `body` in that the field annotated with it represents the
 ///   entire request body, but this attribute is for endpoints where the body can be anything,
-///   not just JSON. The field type must be `Vec<u8>`.
+///   not just JSON. The field type must be `Vec<u8>`. [`OutgoingRawBodyRequest`] and
+///   [`IncomingRawBodyRequest`] are also implemented for the request, to provide or receive
+///   the body separately.
 ///
 /// ## Examples
 ///
@@ -146,6 +150,8 @@ pub use ruma_macros::request;
 ///
 /// The `OutgoingResponse` impl is feature-gated behind `cfg(feature = "server")`.
 /// The `IncomingResponse` impl is feature-gated behind `cfg(feature = "client")`.
+/// The [`DescribeResponse`](openapi::DescribeResponse) impl is feature-gated behind
+/// `cfg(feature = "openapi")`.
 ///
 /// The `Content-Type` header of the `OutgoingResponse` defaults to `application/json`, except
 /// if the `raw_body` attribute is set on a field, in which case it defaults to
@@ -184,7 +190,9 @@ pub use ruma_macros::request;
 ///   object).
 /// * `#[ruma_api(raw_body)]`: Like `body` in that the field annotated with it represents the
 ///   entire response body, but this attribute is for endpoints where the body can be anything,
-///   not just JSON. The field type must be `Vec<u8>`.
+///   not just JSON. The field type must be `Vec<u8>`. [`OutgoingRawBodyResponse`] and
+///   [`IncomingRawBodyResponse`] are also implemented for the response, to provide or receive
+///   the body separately.
 ///
 /// ## Examples
 ///
@@ -256,9 +264,19 @@ use crate::{DeviceId, UserId};
 pub mod auth_scheme;
 pub mod error;
 mod metadata;
+pub mod multipart;
+#[cfg(feature = "openapi")]
+pub mod openapi;
 pub mod path_builder;
+mod raw_body;
 
-pub use self::metadata::{FeatureFlag, MatrixVersion, Metadata, SupportedVersions};
+pub use self::{
+    metadata::{FeatureFlag, MatrixVersion, Metadata, SupportedVersions},
+    raw_body::{
+        IncomingRawBodyRequest, IncomingRawBodyResponse, OutgoingRawBodyRequest,
+        OutgoingRawBodyResponse, collect_body, error_from_http_response_with_body,
+    },
+};
 
 /// A request type for a Matrix API endpoint, used for sending requests.
 pub trait OutgoingRequest: Metadata + Clone {
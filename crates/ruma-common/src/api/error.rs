@@ -249,6 +249,14 @@ pub enum DeserializationError {
     /// Deserialization of `multipart/mixed` response failed.
     #[error(transparent)]
     MultipartMixed(#[from] MultipartMixedDeserializationError),
+
+    /// Reading the body failed.
+    #[error("failed to read body: {0}")]
+    Body(Box<dyn std::error::Error + Send + Sync>),
+
+    /// The body is longer than the maximum allowed length, in bytes.
+    #[error("body exceeds the maximum length of {0} bytes")]
+    BodyTooLarge(usize),
 }
 
 impl From<std::convert::Infallible> for DeserializationError {
@@ -336,6 +344,14 @@ pub enum MultipartMixedDeserializationError {
     /// A header failed to parse.
     #[error("invalid multipart/mixed header: {0}")]
     InvalidHeader(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    /// The body ended before the final boundary.
+    #[error("multipart/mixed body ended before the final boundary")]
+    MissingFinalBoundary,
+
+    /// A body part, or the part of it that needs to be buffered, is too large.
+    #[error("multipart/mixed body part is too large")]
+    BodyPartTooLarge,
 }
 
 /// An error that happens when Ruma cannot understand a Matrix version.
@@ -0,0 +1,1196 @@
+//! Types to parse and build `multipart/mixed` bodies, according to [RFC 2046].
+//!
+//! A `multipart/mixed` body is made of several body parts, each with its own headers and content,
+//! delimited by a boundary that is declared in the `Content-Type` header of the HTTP message.
+//!
+//! [`MultipartMixedBuilder`] allows to serialize the body parts, and [`MultipartMixedParser`]
+//! allows to deserialize them incrementally, without buffering the content of the body parts. For
+//! bodies that are already buffered, [`parse_body()`] returns the list of body parts.
+//!
+//! [RFC 2046]: https://datatracker.ietf.org/doc/html/rfc2046#section-5.1
+
+use std::{error::Error as StdError, fmt, future::poll_fn, pin::Pin, str, task::Context};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use http::{
+    HeaderMap, HeaderName, HeaderValue,
+    header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+};
+use http_body::Body;
+use thiserror::Error;
+
+use super::error::{
+    DeserializationError, HeaderDeserializationError, MultipartMixedDeserializationError,
+};
+use crate::http_headers::ContentDisposition;
+
+/// The `multipart/mixed` MIME type essence.
+pub const MULTIPART_MIXED: &str = "multipart/mixed";
+
+/// The maximum number of headers to parse in a body part.
+const MAX_HEADERS_COUNT: usize = 32;
+
+/// The maximum length of the headers of a body part, including the end of the line of the
+/// boundary.
+const MAX_HEADERS_LENGTH: usize = 16 * 1024;
+
+/// The maximum length of a boundary, according to RFC 2046.
+const MAX_BOUNDARY_LENGTH: usize = 70;
+
+/// The length of a generated boundary.
+#[cfg(feature = "rand")]
+const GENERATED_BOUNDARY_LENGTH: usize = 30;
+
+/// The boundary delimiting the body parts of a `multipart/mixed` body.
+///
+/// A boundary is made of 1 to 70 characters that are ASCII alphanumerics, spaces or one of
+/// `'()+_,-./:=?`, and doesn't end with a space.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Boundary(Box<str>);
+
+impl Boundary {
+    /// Construct a `Boundary` from the given string.
+    ///
+    /// Returns an error if the string is not a valid boundary.
+    pub fn new(boundary: impl Into<Box<str>>) -> Result<Self, InvalidBoundaryError> {
+        let boundary = boundary.into();
+
+        if boundary.is_empty() || boundary.len() > MAX_BOUNDARY_LENGTH {
+            return Err(InvalidBoundaryError::InvalidLength);
+        }
+
+        if !boundary.bytes().all(is_boundary_char) {
+            return Err(InvalidBoundaryError::InvalidCharacter);
+        }
+
+        if boundary.ends_with(' ') {
+            return Err(InvalidBoundaryError::TrailingSpace);
+        }
+
+        Ok(Self(boundary))
+    }
+
+    /// Generate a random `Boundary`.
+    ///
+    /// The generated boundary is made of 30 ASCII alphanumeric characters.
+    #[cfg(feature = "rand")]
+    pub fn generate() -> Self {
+        use rand::Rng as _;
+
+        let boundary = rand::thread_rng()
+            .sample_iter(&rand::distributions::Alphanumeric)
+            .map(char::from)
+            .take(GENERATED_BOUNDARY_LENGTH)
+            .collect::<String>();
+
+        Self(boundary.into())
+    }
+
+    /// Get the boundary of a `multipart/mixed` body from the `Content-Type` header in the given
+    /// headers.
+    ///
+    /// Returns an error if the header is missing, if it is not a `multipart/mixed` content type, or
+    /// if its `boundary` parameter is missing or invalid.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, HeaderDeserializationError> {
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .ok_or_else(|| HeaderDeserializationError::MissingHeader("Content-Type".to_owned()))?
+            .to_str()?
+            .parse::<mime::Mime>()
+            .map_err(|e| HeaderDeserializationError::InvalidHeader(e.into()))?;
+
+        if !content_type.essence_str().eq_ignore_ascii_case(MULTIPART_MIXED) {
+            return Err(HeaderDeserializationError::InvalidHeaderValue {
+                header: "Content-Type".to_owned(),
+                expected: MULTIPART_MIXED.to_owned(),
+                unexpected: content_type.essence_str().to_owned(),
+            });
+        }
+
+        let boundary = content_type
+            .get_param(mime::BOUNDARY)
+            .ok_or(HeaderDeserializationError::MissingMultipartBoundary)?;
+
+        Self::new(boundary.as_str())
+            .map_err(|e| HeaderDeserializationError::InvalidHeader(e.into()))
+    }
+
+    /// The boundary as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The value of the `Content-Type` header of a `multipart/mixed` body with this boundary.
+    pub fn content_type(&self) -> HeaderValue {
+        let value = if self.0.bytes().all(|b| b.is_ascii_alphanumeric() || b"'+_-.".contains(&b)) {
+            format!("{MULTIPART_MIXED}; boundary={}", self.0)
+        } else {
+            // The boundary contains characters that are not allowed in a token.
+            format!("{MULTIPART_MIXED}; boundary=\"{}\"", self.0)
+        };
+
+        HeaderValue::from_str(&value).expect("boundary should only contain valid characters")
+    }
+
+    /// The delimiter of the body parts, made of a CRLF, two dashes and the boundary.
+    fn delimiter(&self) -> Box<[u8]> {
+        [b"\r\n--", self.0.as_bytes()].concat().into()
+    }
+}
+
+impl fmt::Display for Boundary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Whether the given byte is allowed in a boundary, according to RFC 2046.
+fn is_boundary_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&b)
+}
+
+/// An error when constructing a [`Boundary`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum InvalidBoundaryError {
+    /// The boundary is empty or longer than 70 characters.
+    #[error("multipart boundary must contain between 1 and 70 characters")]
+    InvalidLength,
+
+    /// The boundary contains a character that is not allowed.
+    #[error("multipart boundary contains a forbidden character")]
+    InvalidCharacter,
+
+    /// The boundary ends with a space.
+    #[error("multipart boundary must not end with a space")]
+    TrailingSpace,
+}
+
+/// The headers of a body part of a `multipart/mixed` body.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct BodyPartHeaders(HeaderMap);
+
+impl BodyPartHeaders {
+    /// Creates a new empty `BodyPartHeaders`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the value of the header with the given name.
+    ///
+    /// If there are several values for this header, the first one is returned.
+    pub fn get(&self, name: impl http::header::AsHeaderName) -> Option<&HeaderValue> {
+        self.0.get(name)
+    }
+
+    /// Get the value of the header with the given name as a string.
+    ///
+    /// Unlike [`HeaderValue::to_str()`], this allows any UTF-8 string.
+    ///
+    /// Returns an error if the value is not valid UTF-8.
+    pub fn get_str(
+        &self,
+        name: impl http::header::AsHeaderName,
+    ) -> Result<Option<&str>, MultipartMixedDeserializationError> {
+        self.0
+            .get(name)
+            .map(|value| str::from_utf8(value.as_bytes()))
+            .transpose()
+            .map_err(|e| MultipartMixedDeserializationError::InvalidHeader(e.into()))
+    }
+
+    /// Get the value of the `Content-Type` header.
+    ///
+    /// Returns an error if the value is not valid UTF-8.
+    pub fn content_type(&self) -> Result<Option<&str>, MultipartMixedDeserializationError> {
+        self.get_str(CONTENT_TYPE)
+    }
+
+    /// Get and parse the value of the `Content-Disposition` header.
+    ///
+    /// Returns an error if the value is not a valid `Content-Disposition`.
+    pub fn content_disposition(
+        &self,
+    ) -> Result<Option<ContentDisposition>, MultipartMixedDeserializationError> {
+        self.0
+            .get(CONTENT_DISPOSITION)
+            .map(|value| ContentDisposition::try_from(value.as_bytes()))
+            .transpose()
+            .map_err(|e| MultipartMixedDeserializationError::InvalidHeader(e.into()))
+    }
+
+    /// Get a reference to the inner `HeaderMap`.
+    pub fn as_map(&self) -> &HeaderMap {
+        &self.0
+    }
+
+    /// Get a mutable reference to the inner `HeaderMap`.
+    pub fn as_map_mut(&mut self) -> &mut HeaderMap {
+        &mut self.0
+    }
+
+    /// Get the inner `HeaderMap`.
+    pub fn into_map(self) -> HeaderMap {
+        self.0
+    }
+
+    /// Parse the given bytes, that must contain the headers followed by an empty line.
+    fn parse(bytes: &[u8]) -> Result<Self, MultipartMixedDeserializationError> {
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS_COUNT];
+
+        let headers = match httparse::parse_headers(bytes, &mut headers) {
+            Ok(httparse::Status::Complete((_, headers))) => headers,
+            Ok(httparse::Status::Partial) => {
+                return Err(MultipartMixedDeserializationError::MissingBodyPartInnerSeparator);
+            }
+            Err(e) => return Err(MultipartMixedDeserializationError::InvalidHeader(e.into())),
+        };
+
+        let mut map = HeaderMap::with_capacity(headers.len());
+
+        for header in headers {
+            let name = HeaderName::from_bytes(header.name.as_bytes())
+                .map_err(|e| MultipartMixedDeserializationError::InvalidHeader(e.into()))?;
+            let value = HeaderValue::from_bytes(header.value)
+                .map_err(|e| MultipartMixedDeserializationError::InvalidHeader(e.into()))?;
+
+            map.append(name, value);
+        }
+
+        Ok(Self(map))
+    }
+}
+
+impl From<HeaderMap> for BodyPartHeaders {
+    fn from(value: HeaderMap) -> Self {
+        Self(value)
+    }
+}
+
+/// A body part of a `multipart/mixed` body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct BodyPart {
+    /// The headers of the body part.
+    pub headers: BodyPartHeaders,
+
+    /// The content of the body part.
+    pub content: Bytes,
+}
+
+impl BodyPart {
+    /// Creates a new `BodyPart` with the given headers and content.
+    pub fn new(headers: BodyPartHeaders, content: Bytes) -> Self {
+        Self { headers, content }
+    }
+}
+
+/// A builder for a `multipart/mixed` body.
+///
+/// The body parts can be written at once with [`MultipartMixedBuilder::build()`], or piece by
+/// piece, to stream the content of the body parts:
+///
+/// 1. For each body part, write the delimiter and the headers with
+///    [`MultipartMixedBuilder::write_part_head()`], then write the content.
+/// 2. Write the final delimiter with [`MultipartMixedBuilder::write_end()`].
+///
+/// The `Content-Type` header of the HTTP message is returned by
+/// [`MultipartMixedBuilder::content_type()`].
+#[derive(Clone, Debug)]
+pub struct MultipartMixedBuilder {
+    boundary: Boundary,
+}
+
+impl MultipartMixedBuilder {
+    /// Creates a new `MultipartMixedBuilder` with the given boundary.
+    ///
+    /// The boundary must not appear in the content of the body parts.
+    pub fn new(boundary: Boundary) -> Self {
+        Self { boundary }
+    }
+
+    /// Creates a new `MultipartMixedBuilder` with a random boundary.
+    #[cfg(feature = "rand")]
+    pub fn with_generated_boundary() -> Self {
+        Self::new(Boundary::generate())
+    }
+
+    /// The boundary of the body.
+    pub fn boundary(&self) -> &Boundary {
+        &self.boundary
+    }
+
+    /// The value of the `Content-Type` header of the body.
+    pub fn content_type(&self) -> HeaderValue {
+        self.boundary.content_type()
+    }
+
+    /// Write the delimiter and the given headers of a body part.
+    ///
+    /// The content of the body part must be written right after.
+    pub fn write_part_head<T: BufMut>(&self, headers: &HeaderMap, dst: &mut T) {
+        dst.put_slice(b"\r\n--");
+        dst.put_slice(self.boundary.as_str().as_bytes());
+        dst.put_slice(b"\r\n");
+
+        for (name, value) in headers {
+            dst.put_slice(name.as_str().as_bytes());
+            dst.put_slice(b": ");
+            dst.put_slice(value.as_bytes());
+            dst.put_slice(b"\r\n");
+        }
+
+        // Add empty line separator after headers.
+        dst.put_slice(b"\r\n");
+    }
+
+    /// Write the final delimiter of the body.
+    pub fn write_end<T: BufMut>(&self, dst: &mut T) {
+        dst.put_slice(b"\r\n--");
+        dst.put_slice(self.boundary.as_str().as_bytes());
+        dst.put_slice(b"--");
+    }
+
+    /// Serialize the given body parts.
+    pub fn build<'a, T>(&self, parts: impl IntoIterator<Item = &'a BodyPart>) -> T
+    where
+        T: Default + BufMut,
+    {
+        let mut body = T::default();
+
+        for part in parts {
+            self.write_part_head(part.headers.as_map(), &mut body);
+            body.put_slice(&part.content);
+        }
+
+        self.write_end(&mut body);
+
+        body
+    }
+}
+
+/// Parse all the body parts of the given `multipart/mixed` body, delimited by the given boundary.
+///
+/// Returns an error if the body is invalid or if it doesn't end with the final delimiter.
+pub fn parse_body(
+    boundary: &Boundary,
+    body: &[u8],
+) -> Result<Vec<BodyPart>, MultipartMixedDeserializationError> {
+    let mut parser = MultipartMixedParser::new(boundary);
+    parser.push(body);
+    parser.end_input();
+
+    let mut parts = Vec::new();
+    let mut current_part = None;
+
+    loop {
+        let event = parser.next_event()?.expect("the end of the input was reached");
+
+        match event {
+            MultipartMixedEvent::PartStart(headers) => {
+                current_part = Some((headers, BytesMut::new()));
+            }
+            MultipartMixedEvent::PartData(data) => {
+                if let Some((_, content)) = &mut current_part {
+                    content.put(data);
+                }
+            }
+            MultipartMixedEvent::PartEnd => {
+                if let Some((headers, content)) = current_part.take() {
+                    parts.push(BodyPart::new(headers, content.freeze()));
+                }
+            }
+            MultipartMixedEvent::End => return Ok(parts),
+        }
+    }
+}
+
+/// An event returned by [`MultipartMixedParser`].
+#[derive(Clone, Debug)]
+#[allow(clippy::exhaustive_enums)]
+pub enum MultipartMixedEvent {
+    /// The start of a body part, with its headers.
+    PartStart(BodyPartHeaders),
+
+    /// A chunk of the content of the current body part.
+    ///
+    /// This can be returned several times for the same body part.
+    PartData(Bytes),
+
+    /// The end of the current body part.
+    PartEnd,
+
+    /// The end of the body.
+    ///
+    /// The data after the final delimiter is ignored. This event is returned for every subsequent
+    /// call.
+    End,
+}
+
+/// The state of a [`MultipartMixedParser`].
+#[derive(Clone, Copy, Debug)]
+enum ParserState {
+    /// Looking for the first delimiter.
+    Preamble {
+        /// Whether no data was discarded yet.
+        ///
+        /// If there is no preamble, the first delimiter may omit the preceding CRLF.
+        at_start: bool,
+    },
+
+    /// After a delimiter, looking for the end of the line or the end of the body.
+    DelimiterLine,
+
+    /// Reading the headers of a body part.
+    Headers,
+
+    /// Reading the content of a body part.
+    Content,
+
+    /// The final delimiter was reached.
+    End,
+}
+
+/// An incremental parser for a `multipart/mixed` body.
+///
+/// The data of the body is provided with [`MultipartMixedParser::push()`], until the end of the
+/// input is signaled with [`MultipartMixedParser::end_input()`]. The parsed events are returned by
+/// [`MultipartMixedParser::next_event()`].
+///
+/// To parse a body implementing [`http_body::Body`], [`MultipartMixedParser::poll_next_event()`]
+/// and [`MultipartMixedParser::next_event_from_body()`] read the data from the body as needed.
+///
+/// Only the headers of the body parts are buffered, the content is returned as soon as it is
+/// known to not contain the delimiter.
+///
+/// After an error is returned, the parser should not be used anymore.
+#[derive(Debug)]
+pub struct MultipartMixedParser {
+    /// The delimiter of the body parts.
+    delimiter: Box<[u8]>,
+
+    /// The data that was pushed and not parsed yet.
+    buffer: BytesMut,
+
+    /// The current state of the parser.
+    state: ParserState,
+
+    /// Whether the end of the input was reached.
+    input_ended: bool,
+}
+
+impl MultipartMixedParser {
+    /// Creates a new `MultipartMixedParser` for a body with the given boundary.
+    pub fn new(boundary: &Boundary) -> Self {
+        Self {
+            delimiter: boundary.delimiter(),
+            buffer: BytesMut::new(),
+            state: ParserState::Preamble { at_start: true },
+            input_ended: false,
+        }
+    }
+
+    /// Push the given data of the body.
+    ///
+    /// The data is ignored if the end of the body was reached.
+    pub fn push(&mut self, data: impl Buf) {
+        if !matches!(self.state, ParserState::End) {
+            self.buffer.put(data);
+        }
+    }
+
+    /// Signal that the end of the input was reached.
+    pub fn end_input(&mut self) {
+        self.input_ended = true;
+    }
+
+    /// Get the next event of the body.
+    ///
+    /// Returns `Ok(None)` if more data is needed. Once the end of the input is signaled, this
+    /// never returns `Ok(None)`.
+    pub fn next_event(
+        &mut self,
+    ) -> Result<Option<MultipartMixedEvent>, MultipartMixedDeserializationError> {
+        loop {
+            match self.state {
+                ParserState::Preamble { at_start } => {
+                    let dash_boundary = &self.delimiter[2..];
+
+                    if at_start {
+                        if !self.input_ended
+                            && self.buffer.len() < dash_boundary.len()
+                            && dash_boundary.starts_with(&self.buffer)
+                        {
+                            return Ok(None);
+                        }
+
+                        if self.buffer.starts_with(dash_boundary) {
+                            self.buffer.advance(dash_boundary.len());
+                            self.state = ParserState::DelimiterLine;
+                            continue;
+                        }
+
+                        self.state = ParserState::Preamble { at_start: false };
+                    }
+
+                    if let Some(pos) = memchr::memmem::find(&self.buffer, &self.delimiter) {
+                        self.buffer.advance(pos + self.delimiter.len());
+                        self.state = ParserState::DelimiterLine;
+                        continue;
+                    }
+
+                    if self.input_ended {
+                        return Err(MultipartMixedDeserializationError::MissingFinalBoundary);
+                    }
+
+                    // Discard the preamble, but keep the bytes that could be the start of the
+                    // delimiter.
+                    let discarded = self.buffer.len().saturating_sub(self.delimiter.len() - 1);
+                    self.buffer.advance(discarded);
+
+                    return Ok(None);
+                }
+                ParserState::DelimiterLine => {
+                    if !self.input_ended && self.buffer.len() < 2 && b"--".starts_with(&self.buffer)
+                    {
+                        return Ok(None);
+                    }
+
+                    if self.buffer.starts_with(b"--") {
+                        self.buffer.clear();
+                        self.state = ParserState::End;
+                        continue;
+                    }
+
+                    // We need to ignore characters before the end of the line in case of
+                    // transport padding, and for compatibility it might not have a CR.
+                    if let Some(pos) = memchr::memchr(b'\n', &self.buffer) {
+                        self.buffer.advance(pos + 1);
+                        self.state = ParserState::Headers;
+                        continue;
+                    }
+
+                    return self.need_more_headers_data(self.buffer.len());
+                }
+                ParserState::Headers => {
+                    let dash_boundary = &self.delimiter[2..];
+
+                    // Let's find an empty line.
+                    let mut line_start = 0;
+
+                    while let Some(pos) = memchr::memchr(b'\n', &self.buffer[line_start..]) {
+                        let line_end = line_start + pos + 1;
+                        let line = &self.buffer[line_start..line_end];
+
+                        if matches!(line, b"\r\n" | b"\n") {
+                            let headers = BodyPartHeaders::parse(&self.buffer[..line_end])?;
+                            self.buffer.advance(line_end);
+                            self.state = ParserState::Content;
+
+                            return Ok(Some(MultipartMixedEvent::PartStart(headers)));
+                        }
+
+                        if line.starts_with(dash_boundary) {
+                            // We reached the next delimiter.
+                            return Err(
+                                MultipartMixedDeserializationError::MissingBodyPartInnerSeparator,
+                            );
+                        }
+
+                        line_start = line_end;
+                    }
+
+                    return self.need_more_headers_data(self.buffer.len());
+                }
+                ParserState::Content => {
+                    if let Some(pos) = memchr::memmem::find(&self.buffer, &self.delimiter) {
+                        if pos > 0 {
+                            let data = self.buffer.split_to(pos).freeze();
+                            return Ok(Some(MultipartMixedEvent::PartData(data)));
+                        }
+
+                        self.buffer.advance(self.delimiter.len());
+                        self.state = ParserState::DelimiterLine;
+
+                        return Ok(Some(MultipartMixedEvent::PartEnd));
+                    }
+
+                    // Keep the bytes that could be the start of the delimiter.
+                    let available = self.buffer.len().saturating_sub(self.delimiter.len() - 1);
+                    if available > 0 {
+                        let data = self.buffer.split_to(available).freeze();
+                        return Ok(Some(MultipartMixedEvent::PartData(data)));
+                    }
+
+                    if self.input_ended {
+                        return Err(MultipartMixedDeserializationError::MissingFinalBoundary);
+                    }
+
+                    return Ok(None);
+                }
+                ParserState::End => return Ok(Some(MultipartMixedEvent::End)),
+            }
+        }
+    }
+
+    /// The result when more data is needed to parse the headers of a body part.
+    fn need_more_headers_data(
+        &self,
+        headers_len: usize,
+    ) -> Result<Option<MultipartMixedEvent>, MultipartMixedDeserializationError> {
+        if self.input_ended {
+            Err(MultipartMixedDeserializationError::MissingFinalBoundary)
+        } else if headers_len > MAX_HEADERS_LENGTH {
+            Err(MultipartMixedDeserializationError::BodyPartTooLarge)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Poll the next event of the body, reading data from the given HTTP body as needed.
+    ///
+    /// The trailers of the HTTP body are ignored.
+    pub fn poll_next_event<B>(
+        &mut self,
+        mut body: Pin<&mut B>,
+        cx: &mut Context<'_>,
+    ) -> std::task::Poll<Result<MultipartMixedEvent, DeserializationError>>
+    where
+        B: Body + ?Sized,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        use std::task::Poll;
+
+        loop {
+            if let Some(event) = self.next_event()? {
+                return Poll::Ready(Ok(event));
+            }
+
+            match std::task::ready!(body.as_mut().poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    if let Ok(data) = frame.into_data() {
+                        self.push(data);
+                    }
+                }
+                Some(Err(error)) => {
+                    return Poll::Ready(Err(DeserializationError::Body(error.into())));
+                }
+                None => self.end_input(),
+            }
+        }
+    }
+
+    /// Get the next event of the body, reading data from the given HTTP body as needed.
+    ///
+    /// The trailers of the HTTP body are ignored.
+    pub async fn next_event_from_body<B>(
+        &mut self,
+        body: &mut B,
+    ) -> Result<MultipartMixedEvent, DeserializationError>
+    where
+        B: Body + Unpin + ?Sized,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        poll_fn(|cx| self.poll_next_event(Pin::new(&mut *body), cx)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use assert_matches2::assert_matches;
+    use bytes::{BufMut, Bytes, BytesMut};
+    use http::{
+        HeaderMap, HeaderValue,
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION},
+    };
+    use http_body::Frame;
+    use macro_rules_attribute::apply;
+    use smol_macros::test;
+
+    use super::{
+        BodyPart, BodyPartHeaders, Boundary, InvalidBoundaryError, MultipartMixedBuilder,
+        MultipartMixedEvent, MultipartMixedParser, parse_body,
+    };
+    use crate::{
+        api::error::MultipartMixedDeserializationError,
+        http_headers::{ContentDisposition, ContentDispositionType},
+    };
+
+    /// A small pseudo-random number generator, to generate reproducible inputs.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A random number in `0..max`.
+        fn below(&mut self, max: usize) -> usize {
+            (self.next() % max as u64) as usize
+        }
+    }
+
+    /// Parse the given body, pushing it in chunks of random sizes.
+    fn parse_body_in_chunks(
+        boundary: &Boundary,
+        body: &[u8],
+        rng: &mut XorShift,
+    ) -> Result<Vec<BodyPart>, MultipartMixedDeserializationError> {
+        let mut parser = MultipartMixedParser::new(boundary);
+        let mut remaining = body;
+        let mut parts = Vec::new();
+        let mut current_part = None;
+
+        loop {
+            let Some(event) = parser.next_event()? else {
+                let chunk_size = (rng.below(8) + 1).min(remaining.len());
+                let (chunk, rest) = remaining.split_at(chunk_size);
+                parser.push(chunk);
+                remaining = rest;
+
+                if remaining.is_empty() {
+                    parser.end_input();
+                }
+
+                continue;
+            };
+
+            match event {
+                MultipartMixedEvent::PartStart(headers) => {
+                    assert!(current_part.is_none());
+                    current_part = Some((headers, BytesMut::new()));
+                }
+                MultipartMixedEvent::PartData(data) => {
+                    assert!(!data.is_empty());
+                    current_part.as_mut().unwrap().1.put(data);
+                }
+                MultipartMixedEvent::PartEnd => {
+                    let (headers, content) = current_part.take().unwrap();
+                    parts.push(BodyPart::new(headers, content.freeze()));
+                }
+                MultipartMixedEvent::End => return Ok(parts),
+            }
+        }
+    }
+
+    fn headers(headers: &[(http::HeaderName, &str)]) -> BodyPartHeaders {
+        headers
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect::<HeaderMap>()
+            .into()
+    }
+
+    #[test]
+    fn boundary_validation() {
+        Boundary::new("abcdef").unwrap();
+        Boundary::new("gc0p4Jq0M2Yt08jU534c0p").unwrap();
+        Boundary::new("simple boundary").unwrap();
+        Boundary::new("'()+_,-./:=?").unwrap();
+        Boundary::new("a".repeat(70)).unwrap();
+
+        assert_matches!(Boundary::new(""), Err(InvalidBoundaryError::InvalidLength));
+        assert_matches!(Boundary::new("a".repeat(71)), Err(InvalidBoundaryError::InvalidLength));
+        assert_matches!(Boundary::new("abc\r\n"), Err(InvalidBoundaryError::InvalidCharacter));
+        assert_matches!(Boundary::new("abc\"def"), Err(InvalidBoundaryError::InvalidCharacter));
+        assert_matches!(Boundary::new("ȵ⌾Ⱦ"), Err(InvalidBoundaryError::InvalidCharacter));
+        assert_matches!(Boundary::new("abcdef "), Err(InvalidBoundaryError::TrailingSpace));
+    }
+
+    #[test]
+    fn boundary_content_type() {
+        let boundary = Boundary::new("abc-def").unwrap();
+        assert_eq!(boundary.content_type(), "multipart/mixed; boundary=abc-def");
+
+        let boundary = Boundary::new("abc def:ghi").unwrap();
+        assert_eq!(boundary.content_type(), "multipart/mixed; boundary=\"abc def:ghi\"");
+
+        // Round-trip.
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, boundary.content_type());
+        assert_eq!(Boundary::from_headers(&headers).unwrap(), boundary);
+    }
+
+    #[test]
+    fn boundary_from_headers() {
+        let mut headers = HeaderMap::new();
+        Boundary::from_headers(&headers).unwrap_err();
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("multipart/mixed"));
+        Boundary::from_headers(&headers).unwrap_err();
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("multipart/form-data; boundary=a"));
+        Boundary::from_headers(&headers).unwrap_err();
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("multipart/mixed; boundary=\"\""));
+        Boundary::from_headers(&headers).unwrap_err();
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("Multipart/Mixed; boundary=abcdef"));
+        assert_eq!(Boundary::from_headers(&headers).unwrap().as_str(), "abcdef");
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn generated_boundary() {
+        let boundary = Boundary::generate();
+        assert_eq!(boundary.as_str().len(), 30);
+        assert_eq!(Boundary::new(boundary.as_str()).unwrap(), boundary);
+    }
+
+    #[test]
+    fn build_and_parse() {
+        let builder = MultipartMixedBuilder::new(Boundary::new("abcdef").unwrap());
+        let content_disposition = ContentDisposition::new(ContentDispositionType::Attachment)
+            .with_filename(Some("my_file.txt".to_owned()));
+
+        let parts = [
+            BodyPart::new(
+                headers(&[(CONTENT_TYPE, "application/json")]),
+                Bytes::from_static(b"{}"),
+            ),
+            BodyPart::new(
+                headers(&[
+                    (CONTENT_TYPE, "text/plain"),
+                    (CONTENT_DISPOSITION, &content_disposition.to_string()),
+                ]),
+                Bytes::from_static(b"some plain text"),
+            ),
+            BodyPart::new(headers(&[(LOCATION, "https://server.local/file")]), Bytes::new()),
+        ];
+
+        let body = builder.build::<Vec<u8>>(&parts);
+        assert_eq!(
+            String::from_utf8(body.clone()).unwrap(),
+            "\r\n--abcdef\r\ncontent-type: application/json\r\n\r\n{}\
+             \r\n--abcdef\r\ncontent-type: text/plain\r\n\
+             content-disposition: attachment; filename=my_file.txt\r\n\r\nsome plain text\
+             \r\n--abcdef\r\nlocation: https://server.local/file\r\n\r\n\
+             \r\n--abcdef--"
+        );
+
+        let parsed_parts = parse_body(builder.boundary(), &body).unwrap();
+        assert_eq!(parsed_parts, parts);
+
+        let part = &parsed_parts[1];
+        assert_eq!(part.headers.content_type().unwrap(), Some("text/plain"));
+        assert_eq!(part.headers.content_disposition().unwrap(), Some(content_disposition));
+        assert_eq!(
+            parsed_parts[2].headers.get_str(LOCATION).unwrap(),
+            Some("https://server.local/file")
+        );
+        assert_eq!(parsed_parts[2].headers.content_type().unwrap(), None);
+    }
+
+    #[test]
+    fn parse_valid() {
+        let boundary = Boundary::new("abcdef").unwrap();
+
+        // No leading CRLF and no preamble.
+        let parts = parse_body(&boundary, b"--abcdef\r\n\r\n{}\r\n--abcdef--").unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].headers, BodyPartHeaders::new());
+        assert_eq!(parts[0].content, "{}");
+
+        // Preamble containing the boundary text without a leading CRLF, transport padding and
+        // epilogue.
+        let body = "foo--abcdef\r\n--abcdef  \r\ncontent-type: text/plain\r\n\r\nsome text\r\n--abcdef--  \r\nepilogue";
+        let parts = parse_body(&boundary, body.as_bytes()).unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].headers.content_type().unwrap(), Some("text/plain"));
+        assert_eq!(parts[0].content, "some text");
+
+        // Missing CR except in delimiters.
+        let body =
+            "\r\n--abcdef\ncontent-type: text/plain\n\nsome text\r\n--abcdef\n\n\r\n--abcdef--";
+        let parts = parse_body(&boundary, body.as_bytes()).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].headers.content_type().unwrap(), Some("text/plain"));
+        assert_eq!(parts[0].content, "some text");
+        assert_eq!(parts[1].content, "");
+
+        // Case-insensitive headers and raw UTF-8 filename.
+        let body = "\r\n--abcdef\r\nCONTENT-type: text/plain\r\ncoNtenT-disPosItioN: inline; filename=\"ȵ⌾Ⱦԩ💈Ňɠ\"\r\n\r\nsome text\r\n--abcdef--";
+        let parts = parse_body(&boundary, body.as_bytes()).unwrap();
+        assert_eq!(parts[0].headers.content_type().unwrap(), Some("text/plain"));
+        let content_disposition = parts[0].headers.content_disposition().unwrap().unwrap();
+        assert_eq!(content_disposition.disposition_type, ContentDispositionType::Inline);
+        assert_eq!(content_disposition.filename.unwrap(), "ȵ⌾Ⱦԩ💈Ňɠ");
+
+        // Content containing the start of the delimiter.
+        let body = "\r\n--abcdef\r\n\r\nsome\r\n--abcde text\r\n--abcdef\r\n\r\n\r\n--abcdef--";
+        let parts = parse_body(&boundary, body.as_bytes()).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].content, "some\r\n--abcde text");
+        assert_eq!(parts[1].content, "");
+
+        // No body parts.
+        let parts = parse_body(&boundary, b"--abcdef--").unwrap();
+        assert_eq!(parts.len(), 0);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let boundary = Boundary::new("abcdef").unwrap();
+
+        // No delimiter.
+        assert_matches!(
+            parse_body(&boundary, b"some plain text"),
+            Err(MultipartMixedDeserializationError::MissingFinalBoundary)
+        );
+
+        // Missing final delimiter.
+        assert_matches!(
+            parse_body(&boundary, b"\r\n--abcdef\r\n\r\nsome plain text"),
+            Err(MultipartMixedDeserializationError::MissingFinalBoundary)
+        );
+        assert_matches!(
+            parse_body(&boundary, b"\r\n--abcdef\r\n\r\nsome plain text\r\n--abcdef"),
+            Err(MultipartMixedDeserializationError::MissingFinalBoundary)
+        );
+
+        // Missing separator between headers and content.
+        assert_matches!(
+            parse_body(&boundary, b"\r\n--abcdef\r\n{}\r\n--abcdef\r\n\r\ntext\r\n--abcdef--"),
+            Err(MultipartMixedDeserializationError::MissingBodyPartInnerSeparator)
+        );
+
+        // Control character in header.
+        assert_matches!(
+            parse_body(
+                &boundary,
+                b"\r\n--abcdef\r\ncontent-disposition: inline; filename=\"my\0file\"\r\n\r\ntext\r\n--abcdef--"
+            ),
+            Err(MultipartMixedDeserializationError::InvalidHeader(_))
+        );
+
+        // Too many headers.
+        let body =
+            format!("\r\n--abcdef\r\n{}\r\ntext\r\n--abcdef--", "x-header: value\r\n".repeat(33));
+        assert_matches!(
+            parse_body(&boundary, body.as_bytes()),
+            Err(MultipartMixedDeserializationError::InvalidHeader(_))
+        );
+
+        // Invalid content disposition.
+        let parts = parse_body(
+            &boundary,
+            b"\r\n--abcdef\r\ncontent-disposition: ;;\r\n\r\ntext\r\n--abcdef--",
+        )
+        .unwrap();
+        assert_matches!(
+            parts[0].headers.content_disposition(),
+            Err(MultipartMixedDeserializationError::InvalidHeader(_))
+        );
+    }
+
+    #[test]
+    fn parse_headers_too_large() {
+        let boundary = Boundary::new("abcdef").unwrap();
+        let mut parser = MultipartMixedParser::new(&boundary);
+
+        parser.push(&b"\r\n--abcdef\r\nx-header: "[..]);
+        assert_matches!(parser.next_event(), Ok(None));
+
+        parser.push(&[b'a'; 16 * 1024][..]);
+        assert_matches!(
+            parser.next_event(),
+            Err(MultipartMixedDeserializationError::BodyPartTooLarge)
+        );
+    }
+
+    #[test]
+    fn parse_streams_content() {
+        let boundary = Boundary::new("abcdef").unwrap();
+        let mut parser = MultipartMixedParser::new(&boundary);
+
+        parser.push(&b"preamble\r\n--abcdef\r\n\r\n"[..]);
+        assert_matches!(parser.next_event(), Ok(Some(MultipartMixedEvent::PartStart(_))));
+        assert_matches!(parser.next_event(), Ok(None));
+
+        // The content is returned except the bytes that could be the start of the delimiter.
+        parser.push(&b"0123456789\r\n--"[..]);
+        assert_matches!(parser.next_event(), Ok(Some(MultipartMixedEvent::PartData(data))));
+        assert_eq!(data, "01234");
+        assert_matches!(parser.next_event(), Ok(None));
+
+        parser.push(&b"abc"[..]);
+        assert_matches!(parser.next_event(), Ok(Some(MultipartMixedEvent::PartData(data))));
+        assert_eq!(data, "567");
+        assert_matches!(parser.next_event(), Ok(None));
+
+        parser.push(&b"def--"[..]);
+        assert_matches!(parser.next_event(), Ok(Some(MultipartMixedEvent::PartData(data))));
+        assert_eq!(data, "89");
+        assert_matches!(parser.next_event(), Ok(Some(MultipartMixedEvent::PartEnd)));
+        assert_matches!(parser.next_event(), Ok(Some(MultipartMixedEvent::End)));
+
+        // Data after the end is ignored.
+        parser.push(&b"\r\n--abcdef\r\n\r\n"[..]);
+        assert_matches!(parser.next_event(), Ok(Some(MultipartMixedEvent::End)));
+    }
+
+    /// A body that returns the given chunks, one per frame.
+    struct ChunkedBody(Vec<&'static [u8]>);
+
+    impl http_body::Body for ChunkedBody {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+            let chunk = (!self.0.is_empty()).then(|| self.0.remove(0));
+            Poll::Ready(chunk.map(|chunk| Ok(Frame::data(Bytes::from_static(chunk)))))
+        }
+    }
+
+    #[apply(test!)]
+    async fn parse_from_body() {
+        let boundary = Boundary::new("abcdef").unwrap();
+        let mut parser = MultipartMixedParser::new(&boundary);
+        let mut body = ChunkedBody(vec![b"\r\n--abc", b"def\r\n\r\nsome ", b"text\r\n--abcdef--"]);
+
+        assert_matches!(
+            parser.next_event_from_body(&mut body).await,
+            Ok(MultipartMixedEvent::PartStart(_))
+        );
+        let mut content = Vec::new();
+        loop {
+            match parser.next_event_from_body(&mut body).await.unwrap() {
+                MultipartMixedEvent::PartData(data) => content.extend_from_slice(&data),
+                MultipartMixedEvent::PartEnd => break,
+                event => panic!("unexpected event: {event:?}"),
+            }
+        }
+        assert_eq!(content, b"some text");
+        assert_matches!(parser.next_event_from_body(&mut body).await, Ok(MultipartMixedEvent::End));
+
+        // Missing final delimiter.
+        let mut parser = MultipartMixedParser::new(&boundary);
+        let mut body = ChunkedBody(vec![b"\r\n--abcdef\r\n\r\nsome text that is truncated"]);
+
+        assert_matches!(
+            parser.next_event_from_body(&mut body).await,
+            Ok(MultipartMixedEvent::PartStart(_))
+        );
+        assert_matches!(
+            parser.next_event_from_body(&mut body).await,
+            Ok(MultipartMixedEvent::PartData(_))
+        );
+        parser.next_event_from_body(&mut body).await.unwrap_err();
+    }
+
+    /// Fuzz the parser with random bytes made of pieces of a `multipart/mixed` body.
+    ///
+    /// The parser must not panic, and the result must not depend on the size of the chunks of the
+    /// input.
+    #[test]
+    fn fuzz_random_input() {
+        const PIECES: &[&[u8]] = &[
+            b"\r\n",
+            b"\n",
+            b"\r",
+            b"-",
+            b"--",
+            b"ab",
+            b"abc",
+            b"--abc",
+            b"\r\n--abc",
+            b"\r\n--ab",
+            b":",
+            b" ",
+            b"x",
+            b"content-type",
+            b"\0",
+            b"\xff",
+            b"{}",
+        ];
+
+        let boundary = Boundary::new("abc").unwrap();
+        let mut rng = XorShift(0x005e_ed0f_f022);
+
+        for _ in 0..20_000 {
+            let mut body = Vec::new();
+            for _ in 0..rng.below(40) {
+                body.extend_from_slice(PIECES[rng.below(PIECES.len())]);
+            }
+
+            let expected = parse_body(&boundary, &body);
+
+            for _ in 0..4 {
+                let result = parse_body_in_chunks(&boundary, &body, &mut rng);
+
+                match (&expected, &result) {
+                    (Ok(expected), Ok(result)) => assert_eq!(expected, result, "body: {body:?}"),
+                    (Err(expected), Err(result)) => {
+                        assert_eq!(expected.to_string(), result.to_string(), "body: {body:?}");
+                    }
+                    _ => panic!("results differ for body {body:?}: {expected:?} != {result:?}"),
+                }
+            }
+        }
+    }
+
+    /// Fuzz the parser with random valid bodies.
+    ///
+    /// The parsed body parts must be the same as the ones used to build the body, regardless of
+    /// the size of the chunks of the input.
+    #[test]
+    fn fuzz_random_valid_body() {
+        const CONTENT_BYTES: &[u8] = b"\r\n-abc \0\xff";
+        const HEADER_VALUES: &[&str] = &["text/plain", "application/json", "attachment", ""];
+
+        let builder = MultipartMixedBuilder::new(Boundary::new("abc").unwrap());
+        let mut rng = XorShift(0xbad_c0ffee);
+
+        let mut count = 0;
+        while count < 5_000 {
+            let mut parts = Vec::new();
+            for _ in 0..rng.below(4) {
+                let mut headers = HeaderMap::new();
+                for _ in 0..rng.below(3) {
+                    let name = [CONTENT_TYPE, CONTENT_DISPOSITION, LOCATION][rng.below(3)].clone();
+                    let value = HEADER_VALUES[rng.below(HEADER_VALUES.len())];
+                    headers.append(name, HeaderValue::from_static(value));
+                }
+
+                let content = (0..rng.below(30))
+                    .map(|_| CONTENT_BYTES[rng.below(CONTENT_BYTES.len())])
+                    .collect::<Vec<_>>();
+
+                parts.push(BodyPart::new(headers.into(), content.into()));
+            }
+
+            // The delimiter must not appear in the content.
+            if parts.iter().any(|part| memchr::memmem::find(&part.content, b"\r\n--abc").is_some())
+            {
+                continue;
+            }
+            count += 1;
+
+            let body = builder.build::<Vec<u8>>(&parts);
+
+            // Add a random preamble and epilogue.
+            let mut full_body = b"preamble --abc\r\n".repeat(rng.below(2));
+            full_body.extend_from_slice(&body);
+            full_body.extend_from_slice(&b"\r\n--abc\r\nepilogue".repeat(rng.below(2)));
+
+            assert_eq!(parse_body(builder.boundary(), &full_body).unwrap(), parts);
+            assert_eq!(
+                parse_body_in_chunks(builder.boundary(), &full_body, &mut rng).unwrap(),
+                parts
+            );
+        }
+    }
+}
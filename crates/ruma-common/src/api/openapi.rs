@@ -0,0 +1,880 @@
+//! Generation of [OpenAPI 3] descriptions from endpoint definitions.
+//!
+//! The [`request`](super::request) and [`response`](super::response) macros implement
+//! [`DescribeRequest`] and [`DescribeResponse`] for the types they are used on when the crate
+//! using them enables its `openapi` Cargo feature. They also register every endpoint, so that an
+//! OpenAPI document covering a whole API crate can be generated with [`OpenApi::for_crate()`].
+//!
+//! The schemas of the fields are provided by their type's implementation of [`DescribeSchema`],
+//! which can be derived for the types used in request and response bodies. Types that don't
+//! implement it are represented as opaque schemas with an `x-rust-type` extension containing the
+//! name of the type. This allows to compare Ruma against the official OpenAPI description of the
+//! Matrix specification, to spot missing fields or fields with the wrong location, requirement or
+//! type.
+//!
+//! Currently, only a few types implement [`DescribeSchema`] besides the primitive types and
+//! identifiers. In particular, the event types and [`Raw`] are always represented as opaque
+//! schemas, so the generated documents describe the location and requirement of the fields of
+//! requests and responses, but not the contents of the events in their bodies.
+//!
+//! [OpenAPI 3]: https://spec.openapis.org/oas/v3.1.0
+
+use std::{
+    any::Any,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use http::{HeaderName, Method, StatusCode};
+use indexmap::IndexMap;
+use js_int::{Int, UInt};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use super::{
+    Metadata,
+    path_builder::{PathBuilder, VersionHistory},
+};
+use crate::{
+    AnyKeyName, Base64PublicKey, Base64PublicKeyOrDeviceId, ClientSecret, DeviceId,
+    EventEncryptionAlgorithm, EventId, KeyId, MilliSecondsSinceUnixEpoch, MxcUri, OneTimeKeyName,
+    OwnedBase64PublicKey, OwnedBase64PublicKeyOrDeviceId, OwnedClientSecret, OwnedDeviceId,
+    OwnedEventId, OwnedKeyId, OwnedMxcUri, OwnedOneTimeKeyName, OwnedRoomAliasId, OwnedRoomId,
+    OwnedRoomOrAliasId, OwnedServerName, OwnedServerSigningKeyVersion, OwnedSessionId,
+    OwnedSpaceChildOrder, OwnedTransactionId, OwnedUserId, OwnedVoipId, RoomAliasId, RoomId,
+    RoomOrAliasId, RoomVersionId, SecondsSinceUnixEpoch, ServerName, ServerSigningKeyVersion,
+    SessionId, SpaceChildOrder, TransactionId, UserId, VoipId,
+    identifiers::{KeyAlgorithm, KeyName},
+    room::RoomType,
+    serde::{Base64, JsonObject, Raw},
+};
+
+/// The version of the OpenAPI specification that the generated documents follow.
+const OPENAPI_VERSION: &str = "3.1.0";
+
+/// A request type that can describe its fields for an OpenAPI document.
+///
+/// This is implemented by the [`request`](super::request) macro behind `cfg(feature = "openapi")`.
+pub trait DescribeRequest: Metadata {
+    /// The fields of the request.
+    fn request_fields() -> Vec<FieldDescription>;
+
+    /// The HTTP status code and the fields of a successful response, if the response type of this
+    /// endpoint implements [`DescribeResponse`].
+    fn response_description() -> Option<(StatusCode, Vec<FieldDescription>)>;
+}
+
+/// A response type that can describe its fields for an OpenAPI document.
+///
+/// This is implemented by the [`response`](super::response) macro behind
+/// `cfg(feature = "openapi")`.
+pub trait DescribeResponse {
+    /// The HTTP status code of a successful response.
+    fn response_status() -> StatusCode;
+
+    /// The fields of the response.
+    fn response_fields() -> Vec<FieldDescription>;
+}
+
+/// The description of a field of a request or response.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct FieldDescription {
+    /// The name of the field, as it appears in the HTTP request or response.
+    pub name: String,
+
+    /// The part of the HTTP request or response where the field is located.
+    pub location: FieldLocation,
+
+    /// Whether the field must be present.
+    pub required: bool,
+
+    /// The schema of the value of the field.
+    pub schema: Schema,
+}
+
+impl FieldDescription {
+    /// Creates a new `FieldDescription` with the given name, location, requirement and schema.
+    pub fn new(
+        name: impl Into<String>,
+        location: FieldLocation,
+        required: bool,
+        schema: Schema,
+    ) -> Self {
+        Self { name: name.into(), location, required, schema }
+    }
+}
+
+/// The part of an HTTP request or response where a field is located.
+#[derive(Clone, Debug)]
+#[allow(clippy::exhaustive_enums)]
+pub enum FieldLocation {
+    /// A variable in the path of the endpoint.
+    Path,
+
+    /// A parameter in the query string.
+    Query,
+
+    /// A type representing the whole query string.
+    QueryAll,
+
+    /// An HTTP header with the given name.
+    Header(HeaderName),
+
+    /// A field of the JSON object in the body.
+    Body,
+
+    /// A type whose fields are flattened into the JSON object in the body.
+    FlattenedBody,
+
+    /// A type representing the whole JSON body.
+    NewtypeBody,
+
+    /// Arbitrary bytes representing the whole body.
+    RawBody,
+}
+
+/// An [OpenAPI document].
+///
+/// [OpenAPI document]: https://spec.openapis.org/oas/v3.1.0#openapi-object
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct OpenApi {
+    /// The version of the OpenAPI specification that this document follows.
+    pub openapi: String,
+
+    /// Metadata about the API.
+    pub info: Info,
+
+    /// The paths of the endpoints of the API.
+    pub paths: BTreeMap<String, PathItem>,
+}
+
+impl OpenApi {
+    /// Creates an empty `OpenApi` document with the given title and version.
+    pub fn new(title: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            openapi: OPENAPI_VERSION.to_owned(),
+            info: Info { title: title.into(), version: version.into() },
+            paths: BTreeMap::new(),
+        }
+    }
+
+    /// Creates an `OpenApi` document containing all the endpoints that were registered by the
+    /// request macro in the given module and its submodules.
+    ///
+    /// `module_path` is usually the name of an API crate, like `ruma_client_api`.
+    pub fn for_crate(
+        module_path: &str,
+        title: impl Into<String>,
+        version: impl Into<String>,
+    ) -> Self {
+        let mut openapi = Self::new(title, version);
+
+        for registration in inventory::iter::<EndpointRegistration> {
+            let is_in_module = registration
+                .module_path
+                .strip_prefix(module_path)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"));
+
+            if is_in_module {
+                (registration.add)(&mut openapi);
+            }
+        }
+
+        openapi
+    }
+
+    /// Adds the endpoint with the given request type to this document.
+    ///
+    /// An operation is added for every path of the endpoint.
+    pub fn add_endpoint<R>(&mut self)
+    where
+        R: DescribeRequest,
+        R::PathBuilder: 'static,
+    {
+        let path_builder = R::PATH_BUILDER;
+        let history = (&path_builder as &dyn Any).downcast_ref::<VersionHistory>();
+        let operation = Operation::for_endpoint::<R>();
+        let method = R::METHOD.as_str().to_ascii_lowercase();
+
+        for path in path_builder.all_paths() {
+            let mut operation = operation.clone();
+
+            if let Some(history) = history {
+                operation.deprecated = history.deprecated_in().is_some();
+                operation.added_in = history
+                    .stable_paths()
+                    .find(|(_, stable_path)| *stable_path == path)
+                    .and_then(|(selector, _)| selector.version())
+                    // Matrix 1.0 doesn't have a version string.
+                    .map(|version| version.as_str().unwrap_or("v1.0").to_owned());
+                operation.unstable = history.unstable_paths().any(|(_, p)| p == path);
+            }
+
+            self.paths.entry(path.to_owned()).or_default().insert(method.clone(), operation);
+        }
+    }
+
+    /// Get the operation with the given path and method, if any.
+    pub fn operation(&self, path: &str, method: &Method) -> Option<&Operation> {
+        self.paths.get(path)?.get(&method.as_str().to_ascii_lowercase())
+    }
+}
+
+/// Metadata about an API.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct Info {
+    /// The title of the API.
+    pub title: String,
+
+    /// The version of the API.
+    pub version: String,
+}
+
+/// The operations available on a single path, keyed by lowercase HTTP method.
+pub type PathItem = BTreeMap<String, Operation>;
+
+/// A single API operation on a path.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct Operation {
+    /// The path, query and header parameters of the operation.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<Parameter>,
+
+    /// The body of the request, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<RequestBody>,
+
+    /// The possible responses, keyed by HTTP status code.
+    pub responses: BTreeMap<String, Response>,
+
+    /// Whether this operation is deprecated.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
+
+    /// Whether the endpoint is rate-limited.
+    #[serde(rename = "x-ruma-rate-limited")]
+    pub rate_limited: bool,
+
+    /// The name of the authentication scheme of the endpoint.
+    #[serde(rename = "x-ruma-authentication")]
+    pub authentication: String,
+
+    /// The Matrix version that added this path, if any.
+    #[serde(rename = "x-ruma-added-in", skip_serializing_if = "Option::is_none")]
+    pub added_in: Option<String>,
+
+    /// Whether this path is an unstable path.
+    #[serde(rename = "x-ruma-unstable", skip_serializing_if = "std::ops::Not::not")]
+    pub unstable: bool,
+}
+
+impl Operation {
+    /// Creates the `Operation` for the endpoint with the given request type.
+    ///
+    /// The version-specific fields are not set.
+    pub fn for_endpoint<R: DescribeRequest>() -> Self {
+        let mut operation = Self {
+            rate_limited: R::RATE_LIMITED,
+            authentication: short_type_name::<R::Authentication>().to_owned(),
+            ..Default::default()
+        };
+
+        let mut body = Schema::object();
+        let mut body_content_type = None;
+
+        for field in R::request_fields() {
+            let location = match &field.location {
+                FieldLocation::Path => ParameterLocation::Path,
+                FieldLocation::Query | FieldLocation::QueryAll => ParameterLocation::Query,
+                FieldLocation::Header(_) => ParameterLocation::Header,
+                FieldLocation::Body => {
+                    body.add_property(&field);
+                    body_content_type = Some("application/json");
+                    continue;
+                }
+                FieldLocation::FlattenedBody => {
+                    body.all_of.push(field.schema.clone());
+                    body_content_type = Some("application/json");
+                    continue;
+                }
+                FieldLocation::NewtypeBody => {
+                    body = field.schema.clone();
+                    body_content_type = Some("application/json");
+                    continue;
+                }
+                FieldLocation::RawBody => {
+                    body = Schema::binary();
+                    body_content_type = Some("application/octet-stream");
+                    continue;
+                }
+            };
+
+            let name = match &field.location {
+                FieldLocation::Header(name) => name.as_str().to_owned(),
+                _ => field.name.clone(),
+            };
+
+            operation.parameters.push(Parameter {
+                name,
+                location,
+                required: field.required || matches!(field.location, FieldLocation::Path),
+                schema: field.schema.clone(),
+            });
+        }
+
+        operation.request_body = body_content_type.map(|content_type| RequestBody {
+            content: BTreeMap::from([(content_type.to_owned(), MediaType { schema: body })]),
+            required: true,
+        });
+
+        if let Some((status, fields)) = R::response_description() {
+            operation.responses.insert(status.as_str().to_owned(), Response::for_fields(fields));
+        }
+
+        operation
+    }
+}
+
+/// A parameter of an operation.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct Parameter {
+    /// The name of the parameter.
+    pub name: String,
+
+    /// The location of the parameter.
+    #[serde(rename = "in")]
+    pub location: ParameterLocation,
+
+    /// Whether the parameter must be present.
+    pub required: bool,
+
+    /// The schema of the value of the parameter.
+    pub schema: Schema,
+}
+
+/// The location of a [`Parameter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(clippy::exhaustive_enums)]
+pub enum ParameterLocation {
+    /// The parameter is a variable in the path.
+    Path,
+
+    /// The parameter is in the query string.
+    Query,
+
+    /// The parameter is an HTTP header.
+    Header,
+}
+
+/// The body of a request.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct RequestBody {
+    /// The schemas of the body, keyed by content type.
+    pub content: BTreeMap<String, MediaType>,
+
+    /// Whether the body must be present.
+    pub required: bool,
+}
+
+/// A response of an operation.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct Response {
+    /// The description of the response.
+    pub description: String,
+
+    /// The headers of the response, keyed by name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, Header>,
+
+    /// The schemas of the body, keyed by content type.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub content: BTreeMap<String, MediaType>,
+}
+
+impl Response {
+    fn for_fields(fields: Vec<FieldDescription>) -> Self {
+        let mut headers = BTreeMap::new();
+        let mut body = Schema::object();
+        let mut body_content_type = None;
+
+        for field in fields {
+            match &field.location {
+                FieldLocation::Header(name) => {
+                    headers.insert(
+                        name.as_str().to_owned(),
+                        Header { required: field.required, schema: field.schema.clone() },
+                    );
+                }
+                FieldLocation::FlattenedBody => {
+                    body.all_of.push(field.schema.clone());
+                    body_content_type = Some("application/json");
+                }
+                FieldLocation::NewtypeBody => {
+                    body = field.schema.clone();
+                    body_content_type = Some("application/json");
+                }
+                FieldLocation::RawBody => {
+                    body = Schema::binary();
+                    body_content_type = Some("application/octet-stream");
+                }
+                FieldLocation::Body
+                | FieldLocation::Path
+                | FieldLocation::Query
+                | FieldLocation::QueryAll => {
+                    body.add_property(&field);
+                    body_content_type = Some("application/json");
+                }
+            }
+        }
+
+        // Responses always have a JSON body, even without fields.
+        let content_type = body_content_type.unwrap_or("application/json");
+
+        Self {
+            description: String::new(),
+            headers,
+            content: BTreeMap::from([(content_type.to_owned(), MediaType { schema: body })]),
+        }
+    }
+}
+
+/// A header of a response.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct Header {
+    /// Whether the header must be present.
+    pub required: bool,
+
+    /// The schema of the value of the header.
+    pub schema: Schema,
+}
+
+/// The schema of a body for a content type.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct MediaType {
+    /// The schema of the body.
+    pub schema: Schema,
+}
+
+/// A [JSON Schema] describing a value.
+///
+/// [JSON Schema]: https://spec.openapis.org/oas/v3.1.0#schema-object
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct Schema {
+    /// The JSON type of the value.
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+
+    /// The format of the value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+
+    /// The schema of the items of an array.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Box<Schema>>,
+
+    /// The schemas of the known properties of an object.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, Schema>,
+
+    /// The required properties of an object.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+
+    /// The schema of the values of a map.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub additional_properties: Option<Box<Schema>>,
+
+    /// Schemas that the value must also match, used for flattened types.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub all_of: Vec<Schema>,
+
+    /// The name of the Rust type of the value, for types that don't map to a JSON type.
+    #[serde(rename = "x-rust-type", skip_serializing_if = "Option::is_none")]
+    pub rust_type: Option<String>,
+}
+
+impl Schema {
+    fn with_type(type_: &str) -> Self {
+        Self { type_: Some(type_.to_owned()), ..Default::default() }
+    }
+
+    /// Creates a schema for an object without known properties.
+    pub fn object() -> Self {
+        Self::with_type("object")
+    }
+
+    /// Creates a schema for arbitrary bytes.
+    pub fn binary() -> Self {
+        Self { format: Some("binary".to_owned()), ..Self::with_type("string") }
+    }
+
+    fn add_property(&mut self, field: &FieldDescription) {
+        self.insert_property(field.name.clone(), field.schema.clone(), field.required);
+    }
+
+    /// Creates an empty schema with the `x-rust-type` extension containing the name of the given
+    /// type.
+    pub fn opaque<T: ?Sized>() -> Self {
+        Self { rust_type: Some(rust_type_name::<T>()), ..Default::default() }
+    }
+
+    /// Creates a schema for an array whose items match the given schema.
+    pub fn array(items: Schema) -> Self {
+        Self { items: Some(Box::new(items)), ..Self::with_type("array") }
+    }
+
+    /// Creates a schema for a map whose values match the given schema.
+    pub fn map(values: Schema) -> Self {
+        Self { additional_properties: Some(Box::new(values)), ..Self::object() }
+    }
+
+    /// Creates a schema for a string with the given format.
+    pub fn string_with_format(format: &str) -> Self {
+        Self { format: Some(format.to_owned()), ..Self::with_type("string") }
+    }
+
+    /// Adds the property with the given name and schema to this object schema.
+    pub fn insert_property(&mut self, name: impl Into<String>, schema: Schema, required: bool) {
+        let name = name.into();
+
+        if required {
+            self.required.push(name.clone());
+        }
+        self.properties.insert(name, schema);
+    }
+}
+
+/// A type that can describe its JSON representation with a [`Schema`].
+///
+/// This is implemented for the standard types and for the types of ruma-common that are used in
+/// requests and responses. It can be derived for structs with named fields, taking the `rename`,
+/// `rename_all`, `default`, `flatten`, `skip` and `skip_serializing` serde attributes into account.
+/// Fields with the `skip_deserializing` serde attribute are not required.
+///
+/// The types of the fields of requests and responses that don't implement this trait are
+/// represented by an empty schema with an `x-rust-type` extension containing the name of the
+/// type.
+pub trait DescribeSchema {
+    /// The schema of this type.
+    fn schema() -> Schema;
+}
+
+/// Derive macro for the [`DescribeSchema`] trait.
+pub use ruma_macros::DescribeSchema;
+
+macro_rules! impl_describe_schema {
+    ($schema:expr => $($ty:ty),* $(,)?) => {
+        $(
+            impl DescribeSchema for $ty {
+                fn schema() -> Schema {
+                    $schema
+                }
+            }
+        )*
+    };
+}
+
+impl_describe_schema!(Schema::with_type("boolean") => bool);
+impl_describe_schema!(
+    Schema::with_type("integer") =>
+    u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, UInt, Int,
+    MilliSecondsSinceUnixEpoch, SecondsSinceUnixEpoch,
+);
+impl_describe_schema!(Schema::with_type("number") => f32, f64);
+impl_describe_schema!(
+    Schema::with_type("string") =>
+    str, String, DeviceId, OwnedDeviceId, TransactionId, OwnedTransactionId, SessionId,
+    OwnedSessionId, ClientSecret, OwnedClientSecret, VoipId, OwnedVoipId, SpaceChildOrder,
+    OwnedSpaceChildOrder, ServerSigningKeyVersion, OwnedServerSigningKeyVersion, OneTimeKeyName,
+    OwnedOneTimeKeyName, AnyKeyName, Base64PublicKey, OwnedBase64PublicKey,
+    Base64PublicKeyOrDeviceId, OwnedBase64PublicKeyOrDeviceId, RoomOrAliasId, OwnedRoomOrAliasId,
+    RoomVersionId, RoomType, EventEncryptionAlgorithm,
+);
+impl_describe_schema!(Schema::string_with_format("mx-user-id") => UserId, OwnedUserId);
+impl_describe_schema!(Schema::string_with_format("mx-room-id") => RoomId, OwnedRoomId);
+impl_describe_schema!(Schema::string_with_format("mx-event-id") => EventId, OwnedEventId);
+impl_describe_schema!(Schema::string_with_format("mx-room-alias") => RoomAliasId, OwnedRoomAliasId);
+impl_describe_schema!(Schema::string_with_format("mx-server-name") => ServerName, OwnedServerName);
+impl_describe_schema!(Schema::string_with_format("mx-mxc-uri") => MxcUri, OwnedMxcUri);
+impl_describe_schema!(Schema::string_with_format("uri") => url::Url);
+impl_describe_schema!(Schema::object() => JsonObject);
+impl_describe_schema!(Schema::default() => JsonValue);
+#[cfg(feature = "canonical-json")]
+impl_describe_schema!(Schema::default() => crate::CanonicalJsonValue);
+
+impl<A: KeyAlgorithm, K: KeyName + ?Sized> DescribeSchema for KeyId<A, K> {
+    fn schema() -> Schema {
+        Schema::with_type("string")
+    }
+}
+
+impl<A: KeyAlgorithm, K: KeyName + ?Sized> DescribeSchema for OwnedKeyId<A, K> {
+    fn schema() -> Schema {
+        Schema::with_type("string")
+    }
+}
+
+impl<C, B> DescribeSchema for Base64<C, B> {
+    fn schema() -> Schema {
+        Schema::with_type("string")
+    }
+}
+
+impl<T> DescribeSchema for Raw<T> {
+    fn schema() -> Schema {
+        // Raw JSON can contain anything, the type is only a hint.
+        Schema::opaque::<Self>()
+    }
+}
+
+impl<T: DescribeSchema + ?Sized> DescribeSchema for &T {
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T: DescribeSchema + ?Sized> DescribeSchema for Box<T> {
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T: DescribeSchema + ?Sized> DescribeSchema for Arc<T> {
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T: DescribeSchema + ToOwned + ?Sized> DescribeSchema for Cow<'_, T> {
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T: DescribeSchema> DescribeSchema for Option<T> {
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T: DescribeSchema> DescribeSchema for [T] {
+    fn schema() -> Schema {
+        Schema::array(T::schema())
+    }
+}
+
+impl<T: DescribeSchema> DescribeSchema for Vec<T> {
+    fn schema() -> Schema {
+        Schema::array(T::schema())
+    }
+}
+
+impl<T: DescribeSchema> DescribeSchema for BTreeSet<T> {
+    fn schema() -> Schema {
+        Schema::array(T::schema())
+    }
+}
+
+impl<K, V: DescribeSchema> DescribeSchema for BTreeMap<K, V> {
+    fn schema() -> Schema {
+        Schema::map(V::schema())
+    }
+}
+
+impl<K, V: DescribeSchema, S> DescribeSchema for IndexMap<K, V, S> {
+    fn schema() -> Schema {
+        Schema::map(V::schema())
+    }
+}
+
+/// Helper to get the schema of a type, falling back to [`Schema::opaque()`] if it doesn't
+/// implement [`DescribeSchema`].
+///
+/// Use it as `(&SchemaOf::<T>::new()).schema()`, with both [`SchemaOfDescribed`] and
+/// [`SchemaOfOpaque`] in scope.
+#[doc(hidden)]
+pub struct SchemaOf<T: ?Sized>(PhantomData<T>);
+
+impl<T: ?Sized> SchemaOf<T> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[doc(hidden)]
+pub trait SchemaOfDescribed {
+    fn schema(&self) -> Schema;
+}
+
+impl<T: DescribeSchema + ?Sized> SchemaOfDescribed for SchemaOf<T> {
+    fn schema(&self) -> Schema {
+        T::schema()
+    }
+}
+
+#[doc(hidden)]
+pub trait SchemaOfOpaque {
+    fn schema(&self) -> Schema;
+}
+
+impl<T: ?Sized> SchemaOfOpaque for &SchemaOf<T> {
+    fn schema(&self) -> Schema {
+        Schema::opaque::<T>()
+    }
+}
+
+/// Helper to get the description of a response type, falling back to `None` if it doesn't
+/// implement [`DescribeResponse`].
+///
+/// Use it as `(&ResponseOf::<T>::new()).description()`, with both [`ResponseOfDescribed`] and
+/// [`ResponseOfUndescribed`] in scope.
+#[doc(hidden)]
+pub struct ResponseOf<T>(PhantomData<T>);
+
+impl<T> ResponseOf<T> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[doc(hidden)]
+pub trait ResponseOfDescribed {
+    fn description(&self) -> Option<(StatusCode, Vec<FieldDescription>)>;
+}
+
+impl<T: DescribeResponse> ResponseOfDescribed for ResponseOf<T> {
+    fn description(&self) -> Option<(StatusCode, Vec<FieldDescription>)> {
+        Some((T::response_status(), T::response_fields()))
+    }
+}
+
+#[doc(hidden)]
+pub trait ResponseOfUndescribed {
+    fn description(&self) -> Option<(StatusCode, Vec<FieldDescription>)>;
+}
+
+impl<T> ResponseOfUndescribed for &ResponseOf<T> {
+    fn description(&self) -> Option<(StatusCode, Vec<FieldDescription>)> {
+        None
+    }
+}
+
+/// The name of the given type, without the module paths.
+fn rust_type_name<T: ?Sized>() -> String {
+    let name = std::any::type_name::<T>();
+    let mut result = String::with_capacity(name.len());
+    let mut segment_start = 0;
+    let mut chars = name.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            // Drop the module of the following segment.
+            chars.next();
+            result.truncate(segment_start);
+        } else {
+            result.push(c);
+
+            if !(c.is_alphanumeric() || c == '_') {
+                segment_start = result.len();
+            }
+        }
+    }
+
+    result
+}
+
+/// The last path segment of the name of the given type.
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// An endpoint registered by the request macro.
+#[doc(hidden)]
+pub struct EndpointRegistration {
+    module_path: &'static str,
+    add: fn(&mut OpenApi),
+}
+
+impl EndpointRegistration {
+    /// Creates a new `EndpointRegistration` for the given request type in the given module.
+    pub const fn new<R>(module_path: &'static str) -> Self
+    where
+        R: DescribeRequest,
+        R::PathBuilder: 'static,
+    {
+        Self { module_path, add: OpenApi::add_endpoint::<R> }
+    }
+}
+
+inventory::collect!(EndpointRegistration);
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use js_int::UInt;
+    use serde_json::{json, to_value as to_json_value};
+
+    use super::{DescribeSchema, Schema, SchemaOf, SchemaOfDescribed, SchemaOfOpaque};
+    use crate::{OwnedRoomId, OwnedUserId, serde::Raw};
+
+    struct NotDescribed;
+
+    #[test]
+    fn describe_schema() {
+        assert_eq!(to_json_value(String::schema()).unwrap(), json!({ "type": "string" }));
+        assert_eq!(to_json_value(Option::<UInt>::schema()).unwrap(), json!({ "type": "integer" }));
+        assert_eq!(
+            to_json_value(Vec::<OwnedUserId>::schema()).unwrap(),
+            json!({ "type": "array", "items": { "type": "string", "format": "mx-user-id" } })
+        );
+        assert_eq!(
+            to_json_value(BTreeMap::<OwnedRoomId, Vec<Raw<NotDescribed>>>::schema()).unwrap(),
+            json!({
+                "type": "object",
+                "additionalProperties": {
+                    "type": "array",
+                    "items": { "x-rust-type": "Raw<NotDescribed>" },
+                },
+            })
+        );
+    }
+
+    #[test]
+    // The borrow is only needed for the fallback, but it is always used in the generated code.
+    #[allow(clippy::needless_borrow)]
+    fn schema_of_fallback() {
+        assert_eq!(
+            to_json_value((&SchemaOf::<Option<bool>>::new()).schema()).unwrap(),
+            json!({ "type": "boolean" })
+        );
+        assert_eq!(
+            to_json_value((&SchemaOf::<Vec<NotDescribed>>::new()).schema()).unwrap(),
+            json!({ "x-rust-type": "Vec<NotDescribed>" })
+        );
+    }
+
+    #[test]
+    fn opaque_schema() {
+        assert_eq!(
+            to_json_value(Schema::opaque::<BTreeMap<String, NotDescribed>>()).unwrap(),
+            json!({ "x-rust-type": "BTreeMap<String, NotDescribed>" })
+        );
+    }
+}
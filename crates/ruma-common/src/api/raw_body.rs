@@ -0,0 +1,218 @@
+//! Traits to send and receive the raw body of requests and responses as a stream.
+//!
+//! The [`OutgoingRequest`], [`IncomingRequest`], [`OutgoingResponse`] and [`IncomingResponse`]
+//! traits buffer the whole body of the HTTP requests and responses. For endpoints whose body can be
+//! very large, like the ones to upload or download media, the traits in this module allow to
+//! convert only the head of the HTTP requests and responses, and to provide or receive the body
+//! separately, for example as an [`http_body::Body`] that is streamed.
+
+use std::{error::Error as StdError, future::Future, pin::Pin};
+
+use bytes::Buf;
+use http_body::Body;
+
+use super::{
+    EndpointError, IncomingRequest, IncomingResponse, OutgoingRequest, OutgoingResponse,
+    auth_scheme,
+    error::{DeserializationError, FromHttpRequestError, FromHttpResponseError, IntoHttpError},
+    path_builder,
+};
+
+/// A request type for a Matrix API endpoint with a raw body, used for sending requests with a body
+/// provided separately.
+///
+/// This is implemented by the [`request`](super::request) macro for requests with a
+/// `#[ruma_api(raw_body)]` field.
+pub trait OutgoingRawBodyRequest: OutgoingRequest {
+    /// Tries to convert this request into an `http::Request` with the given body.
+    ///
+    /// The raw body field of this request is ignored, and `body` is used instead.
+    ///
+    /// The authentication is computed with an empty body, so this should not be used for
+    /// authentication schemes that depend on the body of the request.
+    ///
+    /// ## Errors
+    ///
+    /// This method returns an error in the same cases as
+    /// [`OutgoingRequest::try_into_http_request()`].
+    fn try_into_http_request_with_body<B>(
+        self,
+        base_url: &str,
+        authentication_input: <Self::Authentication as auth_scheme::AuthScheme>::Input<'_>,
+        path_builder_input: <Self::PathBuilder as path_builder::PathBuilder>::Input<'_>,
+        body: B,
+    ) -> Result<http::Request<B>, IntoHttpError>;
+}
+
+/// A response type for a Matrix API endpoint with a raw body, used for receiving responses with a
+/// body consumed separately.
+///
+/// This is implemented by the [`response`](super::response) macro for responses with a
+/// `#[ruma_api(raw_body)]` field.
+pub trait IncomingRawBodyResponse: IncomingResponse {
+    /// The type of the body returned alongside the response, containing the raw body.
+    ///
+    /// This is the HTTP body for most endpoints, but it can be a type wrapping the HTTP body when
+    /// the raw body is only a part of it.
+    type RawBody<B>;
+
+    /// Tries to convert the given `http::Response` into this response type, without reading the
+    /// raw body.
+    ///
+    /// The raw body field of the returned response is left empty, the raw body should be read from
+    /// the returned body instead.
+    ///
+    /// If the response has an error status code, the body is collected to construct the
+    /// [`EndpointError`].
+    #[allow(clippy::type_complexity)]
+    fn try_from_http_response_with_body<B>(
+        response: http::Response<B>,
+    ) -> impl Future<
+        Output = Result<(Self, Self::RawBody<B>), FromHttpResponseError<Self::EndpointError>>,
+    > + Send
+    where
+        B: Body + Send + Unpin,
+        B::Data: Send,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>;
+}
+
+/// A request type for a Matrix API endpoint with a raw body, used for receiving requests with a
+/// body consumed separately.
+///
+/// This is implemented by the [`request`](super::request) macro for requests with a
+/// `#[ruma_api(raw_body)]` field.
+pub trait IncomingRawBodyRequest: IncomingRequest {
+    /// Tries to turn the given `http::Request` into this request type, together with the
+    /// corresponding path arguments, without reading the body.
+    ///
+    /// The raw body field of the returned request is left empty, the raw body should be read from
+    /// the returned body instead.
+    ///
+    /// Note: The strings in path_args need to be percent-decoded.
+    fn try_from_http_request_with_body<B, S>(
+        req: http::Request<B>,
+        path_args: &[S],
+    ) -> Result<(Self, B), FromHttpRequestError>
+    where
+        S: AsRef<str>;
+}
+
+/// A response type for a Matrix API endpoint with a raw body, used for sending responses with a
+/// body provided separately.
+///
+/// This is implemented by the [`response`](super::response) macro for responses with a
+/// `#[ruma_api(raw_body)]` field.
+pub trait OutgoingRawBodyResponse: OutgoingResponse {
+    /// The type of the body of the HTTP response, containing the raw body.
+    ///
+    /// This is the raw body for most endpoints, but it can be a type wrapping the raw body when it
+    /// is only a part of the HTTP body.
+    type HttpBody<B>;
+
+    /// Tries to convert this response into an `http::Response` with the given raw body.
+    ///
+    /// The raw body field of this response is ignored, and `body` is used instead.
+    fn try_into_http_response_with_body<B>(
+        self,
+        body: B,
+    ) -> Result<http::Response<Self::HttpBody<B>>, IntoHttpError>;
+}
+
+/// The maximum length of the body of an error response that is collected.
+const MAX_ERROR_BODY_LENGTH: usize = 1024 * 1024;
+
+/// Collect all the data of the given body.
+///
+/// Trailers are ignored.
+///
+/// Returns an error if the body is longer than `max_len` bytes, so that a remote peer cannot make
+/// us buffer an arbitrary amount of data.
+pub async fn collect_body<B>(mut body: B, max_len: usize) -> Result<Vec<u8>, DeserializationError>
+where
+    B: Body + Unpin,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    let mut bytes = Vec::new();
+
+    while let Some(frame) = std::future::poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+        let frame = frame.map_err(|error| DeserializationError::Body(error.into()))?;
+
+        if let Ok(mut data) = frame.into_data() {
+            while data.has_remaining() {
+                let chunk = data.chunk();
+
+                if bytes.len() + chunk.len() > max_len {
+                    return Err(DeserializationError::BodyTooLarge(max_len));
+                }
+
+                bytes.extend_from_slice(chunk);
+
+                let len = chunk.len();
+                data.advance(len);
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Construct the error of an `http::Response` with an error status code and a body that is not
+/// collected yet.
+#[doc(hidden)]
+pub async fn error_from_http_response_with_body<B, E>(
+    response: http::Response<B>,
+) -> FromHttpResponseError<E>
+where
+    B: Body + Unpin,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    E: EndpointError,
+{
+    let (parts, body) = response.into_parts();
+
+    match collect_body(body, MAX_ERROR_BODY_LENGTH).await {
+        Ok(body) => FromHttpResponseError::Server(E::from_http_response(
+            http::Response::from_parts(parts, body),
+        )),
+        Err(error) => error.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use bytes::Bytes;
+    use http_body::Frame;
+    use macro_rules_attribute::apply;
+    use smol_macros::test;
+
+    use super::collect_body;
+
+    /// A body that returns the given chunks, one per frame.
+    struct ChunkedBody(Vec<&'static [u8]>);
+
+    impl http_body::Body for ChunkedBody {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+            let chunk = (!self.0.is_empty()).then(|| self.0.remove(0));
+            std::task::Poll::Ready(chunk.map(|chunk| Ok(Frame::data(Bytes::from_static(chunk)))))
+        }
+    }
+
+    #[apply(test!)]
+    async fn collect_chunked_body() {
+        let body = ChunkedBody(vec![b"Hello", b", ", b"", b"World!"]);
+        assert_eq!(collect_body(body, 13).await.unwrap(), b"Hello, World!");
+    }
+
+    #[apply(test!)]
+    async fn collect_too_large_body() {
+        let body = ChunkedBody(vec![b"Hello", b", ", b"", b"World!"]);
+        collect_body(body, 12).await.unwrap_err();
+    }
+}
@@ -72,6 +72,10 @@ pub mod exports {
     pub use bytes;
     #[cfg(feature = "api")]
     pub use http;
+    #[cfg(feature = "api")]
+    pub use http_body;
+    #[cfg(feature = "openapi")]
+    pub use inventory;
     pub use ruma_macros;
     pub use serde;
     pub use serde_html_form;
@@ -29,7 +29,10 @@ use crate::{
 };
 
 mod action;
+mod compiled;
 mod condition;
+mod explain;
+mod highlight;
 mod iter;
 mod predefined;
 
@@ -37,14 +40,20 @@ mod predefined;
 pub use self::condition::RoomVersionFeature;
 pub use self::{
     action::{Action, Tweak},
+    compiled::{CompiledRuleset, EventEvaluator},
     condition::{
         _CustomPushCondition, ComparisonOperator, FlattenedJson, FlattenedJsonValue, PushCondition,
         PushConditionPowerLevelsCtx, PushConditionRoomCtx, RoomMemberCountIs, ScalarJsonValue,
     },
+    explain::{
+        ComparedValues, PushConditionExplanation, PushRuleExplanation, PushRuleSkipReason,
+        RulesetExplanation,
+    },
+    highlight::{HighlightKind, HighlightSpan},
     iter::{AnyPushRule, AnyPushRuleRef, RulesetIntoIter, RulesetIter},
     predefined::{
         PredefinedContentRuleId, PredefinedOverrideRuleId, PredefinedRuleId,
-        PredefinedUnderrideRuleId,
+        PredefinedUnderrideRuleId, ServerDefaultRulesChanges,
     },
 };
 
@@ -368,6 +377,47 @@ impl Ruleset {
         self.get_match(event, context).await.map(|rule| rule.actions()).unwrap_or(&[])
     }
 
+    /// Get synchronously the first push rule that applies to this event, if any.
+    ///
+    /// This is the same as [`Ruleset::get_match()`], except that the conditions are evaluated with
+    /// [`PushCondition::applies_sync()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The raw JSON of a room message event.
+    /// * `context` - The context of the message and room at the time of the event.
+    #[instrument(level = "debug", skip_all, fields(context.room_id = %context.room_id))]
+    pub fn get_match_sync<T>(
+        &self,
+        event: &Raw<T>,
+        context: &PushConditionRoomCtx,
+    ) -> Option<AnyPushRuleRef<'_>> {
+        let event = FlattenedJson::from_raw(event);
+
+        if event.get_str("sender").is_some_and(|sender| sender == context.user_id) {
+            // no need to look at the rules if the event was by the user themselves
+            return None;
+        }
+
+        self.iter().find(|rule| rule.applies_sync(&event, context))
+    }
+
+    /// Get synchronously the push actions that apply to this event.
+    ///
+    /// This is the same as [`Ruleset::get_actions()`], except that the conditions are evaluated
+    /// with [`PushCondition::applies_sync()`].
+    ///
+    /// Returns an empty slice if no push rule applies.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The raw JSON of a room message event.
+    /// * `context` - The context of the message and room at the time of the event.
+    #[instrument(level = "debug", skip_all, fields(context.room_id = %context.room_id))]
+    pub fn get_actions_sync<T>(&self, event: &Raw<T>, context: &PushConditionRoomCtx) -> &[Action] {
+        self.get_match_sync(event, context).map(|rule| rule.actions()).unwrap_or(&[])
+    }
+
     /// Removes a user-defined rule in the rule set.
     ///
     /// Returns an error if the parameters are invalid.
@@ -412,6 +462,30 @@ impl Ruleset {
 
         Ok(())
     }
+
+    /// Applies the given update to the user-defined rules or the state of the rules of this rule
+    /// set.
+    ///
+    /// This follows the semantics of the corresponding push rules endpoints of the Client-Server
+    /// API. The rule set is left unchanged if an error is returned.
+    pub fn apply(&mut self, update: impl Into<PushRuleUpdate>) -> Result<(), ApplyPushRuleError> {
+        match update.into() {
+            PushRuleUpdate::Set { rule, before, after } => {
+                self.insert(rule, after.as_deref(), before.as_deref())?;
+            }
+            PushRuleUpdate::SetEnabled { kind, rule_id, enabled } => {
+                self.set_enabled(kind, rule_id, enabled)?;
+            }
+            PushRuleUpdate::SetActions { kind, rule_id, actions } => {
+                self.set_actions(kind, rule_id, actions)?;
+            }
+            PushRuleUpdate::Delete { kind, rule_id } => {
+                self.remove(kind, rule_id)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// A push rule is a single rule that states under what conditions an event should be passed onto a
@@ -538,10 +612,43 @@ impl ConditionalPushRule {
     /// * `event` - The flattened JSON representation of a room message event.
     /// * `context` - The context of the room at the time of the event.
     pub async fn applies(&self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
-        if !self.enabled {
+        if self.skip_reason(event, context).is_some() {
             return false;
         }
 
+        for cond in &self.conditions {
+            if !cond.applies(event, context).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Check synchronously if the push rule applies to the event.
+    ///
+    /// This is the same as [`ConditionalPushRule::applies()`], except that the conditions are
+    /// evaluated with [`PushCondition::applies_sync()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the room at the time of the event.
+    pub fn applies_sync(&self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
+        self.skip_reason(event, context).is_none()
+            && self.conditions.iter().all(|cond| cond.applies_sync(event, context))
+    }
+
+    /// Get the reason why this push rule is skipped for the event, if any.
+    fn skip_reason(
+        &self,
+        event: &FlattenedJson,
+        #[cfg_attr(not(feature = "unstable-msc3932"), allow(unused_variables))]
+        context: &PushConditionRoomCtx,
+    ) -> Option<PushRuleSkipReason> {
+        if !self.enabled {
+            return Some(PushRuleSkipReason::Disabled);
+        }
+
         #[cfg(feature = "unstable-msc3932")]
         {
             // These 3 rules always apply.
@@ -560,7 +667,7 @@ impl ConditionalPushRule {
                 });
 
                 if room_supports_ext_ev && !rule_has_room_version_supports {
-                    return false;
+                    return Some(PushRuleSkipReason::ExtensibleEvents);
                 }
             }
         }
@@ -571,15 +678,10 @@ impl ConditionalPushRule {
             || self.rule_id == PredefinedOverrideRuleId::ContainsDisplayName.as_ref())
             && event.contains_mentions()
         {
-            return false;
+            return Some(PushRuleSkipReason::LegacyMentions);
         }
 
-        for cond in &self.conditions {
-            if !cond.applies(event, context).await {
-                return false;
-            }
-        }
-        true
+        None
     }
 }
 
@@ -677,11 +779,7 @@ impl PatternedPushRule {
         event: &FlattenedJson,
         context: &PushConditionRoomCtx,
     ) -> bool {
-        // The old mention rules are disabled when an m.mentions field is present.
-        #[allow(deprecated)]
-        if self.rule_id == PredefinedContentRuleId::ContainsUserName.as_ref()
-            && event.contains_mentions()
-        {
+        if self.skip_reason(event).is_some() {
             return false;
         }
 
@@ -689,7 +787,24 @@ impl PatternedPushRule {
             return false;
         }
 
-        self.enabled && condition::check_event_match(event, key, &self.pattern, context)
+        condition::check_event_match(event, key, &self.pattern, context)
+    }
+
+    /// Get the reason why this push rule is skipped for the event, if any.
+    fn skip_reason(&self, event: &FlattenedJson) -> Option<PushRuleSkipReason> {
+        if !self.enabled {
+            return Some(PushRuleSkipReason::Disabled);
+        }
+
+        // The old mention rules are disabled when an m.mentions field is present.
+        #[allow(deprecated)]
+        if self.rule_id == PredefinedContentRuleId::ContainsUserName.as_ref()
+            && event.contains_mentions()
+        {
+            return Some(PushRuleSkipReason::LegacyMentions);
+        }
+
+        None
     }
 }
 
@@ -984,6 +1099,91 @@ pub enum InsertPushRuleError {
     BeforeHigherThanAfter,
 }
 
+/// An update of the push rules of a [`Ruleset`], that can be applied with [`Ruleset::apply()`].
+///
+/// Each variant mirrors one of the push rules endpoints of the Client-Server API.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum PushRuleUpdate {
+    /// Insert or replace a user-defined rule, like the `set_pushrule` endpoint.
+    Set {
+        /// The rule to insert.
+        rule: NewPushRule,
+
+        /// The ID of the user-defined rule that the rule should be placed before.
+        before: Option<String>,
+
+        /// The ID of the user-defined rule that the rule should be placed after.
+        after: Option<String>,
+    },
+
+    /// Enable or disable a rule, like the `set_pushrule_enabled` endpoint.
+    SetEnabled {
+        /// The kind of the rule.
+        kind: RuleKind,
+
+        /// The ID of the rule.
+        rule_id: String,
+
+        /// Whether the rule should be enabled.
+        enabled: bool,
+    },
+
+    /// Set the actions of a rule, like the `set_pushrule_actions` endpoint.
+    SetActions {
+        /// The kind of the rule.
+        kind: RuleKind,
+
+        /// The ID of the rule.
+        rule_id: String,
+
+        /// The new actions of the rule.
+        actions: Actions,
+    },
+
+    /// Delete a user-defined rule, like the `delete_pushrule` endpoint.
+    Delete {
+        /// The kind of the rule.
+        kind: RuleKind,
+
+        /// The ID of the rule.
+        rule_id: String,
+    },
+}
+
+/// The error type returned when applying a [`PushRuleUpdate`] to a `Ruleset` fails.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ApplyPushRuleError {
+    /// The rule could not be inserted.
+    #[error(transparent)]
+    Insert(#[from] InsertPushRuleError),
+
+    /// The rule could not be found.
+    #[error(transparent)]
+    NotFound(#[from] RuleNotFoundError),
+
+    /// The rule could not be removed.
+    #[error(transparent)]
+    Remove(#[from] RemovePushRuleError),
+}
+
+impl ApplyPushRuleError {
+    /// Whether this error is caused by a rule that could not be found.
+    ///
+    /// This is the case when the rule to update or delete, or the `before` or `after` rule, does
+    /// not exist. Homeservers should respond to these errors with `M_NOT_FOUND`, and to the other
+    /// errors with `M_INVALID_PARAM`.
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            Self::Insert(InsertPushRuleError::UnknownRuleId)
+                | Self::NotFound(_)
+                | Self::Remove(RemovePushRuleError::NotFound)
+        )
+    }
+}
+
 /// The error type returned when trying modify a push rule that could not be found in a `Ruleset`.
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -1002,8 +1202,8 @@ where
     T: Hash + Eq,
     str: Equivalent<T>,
 {
-    let (from, replaced) = set.replace_full(rule);
-
+    // Resolve the position before inserting the rule, so the set is unchanged on error. Inserting
+    // the rule doesn't change the indexes of the other rules.
     let mut to = default_position;
 
     if let Some(rule_id) = after {
@@ -1020,9 +1220,17 @@ where
         to = idx;
     }
 
+    let (from, replaced) = set.replace_full(rule);
+
+    // The position was computed with the existing rule still in place, so moving it forward
+    // shifts the following rules back by one.
+    if replaced.is_some() && from < to {
+        to -= 1;
+    }
+
     // Only move the item if it's new or if it was positioned.
     if replaced.is_none() || after.is_some() || before.is_some() {
-        set.move_index(from, to);
+        set.move_index(from, to.min(set.len() - 1));
     }
 
     Ok(())
@@ -1055,7 +1263,9 @@ mod tests {
     use smol_macros::test;
 
     use super::{
-        AnyPushRule, ConditionalPushRule, PatternedPushRule, Ruleset, SimplePushRule,
+        AnyPushRule, ApplyPushRuleError, ConditionalPushRule, InsertPushRuleError,
+        NewConditionalPushRule, NewPushRule, NewSimplePushRule, PatternedPushRule, PushRuleUpdate,
+        RemovePushRuleError, RuleKind, Ruleset, SimplePushRule,
         action::{Action, Tweak},
         condition::{
             PushCondition, PushConditionPowerLevelsCtx, PushConditionRoomCtx, RoomMemberCountIs,
@@ -1618,6 +1828,52 @@ mod tests {
         assert_matches!(set.get_actions(&empty, &CONTEXT_ONE_TO_ONE).await, []);
     }
 
+    #[test]
+    fn default_ruleset_applies_sync() {
+        let set = Ruleset::server_default(user_id!("@jj:server.name"));
+
+        let message = serde_json::from_str::<Raw<JsonValue>>(
+            r#"{
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": {
+                    "msgtype": "m.text",
+                    "body": "Hi"
+                },
+                "event_id": "$event"
+            }"#,
+        )
+        .unwrap();
+
+        assert_matches!(
+            set.get_actions_sync(&message, &CONTEXT_ONE_TO_ONE),
+            [
+                Action::Notify,
+                Action::SetTweak(Tweak::Sound(_)),
+                Action::SetTweak(Tweak::Highlight(false))
+            ]
+        );
+        assert_eq!(
+            set.get_match_sync(&message, &CONTEXT_PUBLIC_ROOM).map(|rule| rule.rule_id()),
+            Some(".m.rule.message")
+        );
+
+        // Events sent by the user never match.
+        let own_message = serde_json::from_str::<Raw<JsonValue>>(
+            r#"{
+                "type": "m.room.message",
+                "sender": "@jj:server.name",
+                "content": {
+                    "msgtype": "m.text",
+                    "body": "Hi"
+                },
+                "event_id": "$event"
+            }"#,
+        )
+        .unwrap();
+        assert_matches!(set.get_match_sync(&own_message, &CONTEXT_ONE_TO_ONE), None);
+    }
+
     #[apply(test!)]
     async fn custom_ruleset_applies() {
         let message = serde_json::from_str::<Raw<JsonValue>>(
@@ -1963,4 +2219,188 @@ mod tests {
             PredefinedOverrideRuleId::InviteForMe.as_ref()
         );
     }
+
+    #[test]
+    fn apply_updates() {
+        let mut set = Ruleset::server_default(user_id!("@alice:localhost"));
+        let room_rule = |room_id: &str| {
+            NewPushRule::Room(NewSimplePushRule::new(
+                room_id.try_into().unwrap(),
+                vec![Action::Notify].into(),
+            ))
+        };
+
+        set.apply(PushRuleUpdate::Set {
+            rule: room_rule("!a:localhost"),
+            before: None,
+            after: None,
+        })
+        .unwrap();
+        set.apply(PushRuleUpdate::Set {
+            rule: room_rule("!b:localhost"),
+            before: None,
+            after: Some("!a:localhost".to_owned()),
+        })
+        .unwrap();
+        let room_ids = set.room.iter().map(|rule| rule.rule_id.as_str()).collect::<Vec<_>>();
+        assert_eq!(room_ids, ["!a:localhost", "!b:localhost"]);
+
+        // The before rule doesn't exist, the rule set is unchanged.
+        let error = set
+            .apply(PushRuleUpdate::Set {
+                rule: room_rule("!c:localhost"),
+                before: Some("!unknown:localhost".to_owned()),
+                after: None,
+            })
+            .unwrap_err();
+        assert!(error.is_not_found());
+        assert_matches!(error, ApplyPushRuleError::Insert(InsertPushRuleError::UnknownRuleId));
+        assert_eq!(set.room.len(), 2);
+
+        // Server-default rules can be disabled, but not deleted.
+        let master = PredefinedOverrideRuleId::Master.as_ref();
+        set.apply(PushRuleUpdate::SetEnabled {
+            kind: RuleKind::Override,
+            rule_id: master.to_owned(),
+            enabled: true,
+        })
+        .unwrap();
+        assert!(set.get(RuleKind::Override, master).unwrap().enabled());
+
+        let error = set
+            .apply(PushRuleUpdate::Delete { kind: RuleKind::Override, rule_id: master.to_owned() })
+            .unwrap_err();
+        assert!(!error.is_not_found());
+        assert_matches!(error, ApplyPushRuleError::Remove(RemovePushRuleError::ServerDefault));
+
+        set.apply(PushRuleUpdate::SetActions {
+            kind: RuleKind::Room,
+            rule_id: "!b:localhost".to_owned(),
+            actions: vec![].into(),
+        })
+        .unwrap();
+        assert!(set.get(RuleKind::Room, "!b:localhost").unwrap().actions().is_empty());
+
+        set.apply(PushRuleUpdate::Delete {
+            kind: RuleKind::Room,
+            rule_id: "!a:localhost".to_owned(),
+        })
+        .unwrap();
+        let error = set
+            .apply(PushRuleUpdate::SetEnabled {
+                kind: RuleKind::Room,
+                rule_id: "!a:localhost".to_owned(),
+                enabled: false,
+            })
+            .unwrap_err();
+        assert!(error.is_not_found());
+
+        // Server-default rule IDs are reserved.
+        let error = set
+            .apply(PushRuleUpdate::Set {
+                rule: NewPushRule::Override(NewConditionalPushRule::new(
+                    ".m.rule.custom".into(),
+                    vec![].into(),
+                    vec![].into(),
+                )),
+                before: None,
+                after: None,
+            })
+            .unwrap_err();
+        assert_matches!(
+            error,
+            ApplyPushRuleError::Insert(InsertPushRuleError::ServerDefaultRuleId)
+        );
+    }
+
+    #[test]
+    fn apply_moves_existing_rules() {
+        let room_rule = |room_id: &str| {
+            NewPushRule::Room(NewSimplePushRule::new(
+                room_id.try_into().unwrap(),
+                vec![Action::Notify].into(),
+            ))
+        };
+        let ruleset = |room_ids: &[&str]| {
+            let mut set = Ruleset::new();
+            let mut previous: Option<&str> = None;
+
+            for room_id in room_ids {
+                set.apply(PushRuleUpdate::Set {
+                    rule: room_rule(room_id),
+                    before: None,
+                    after: previous.map(ToOwned::to_owned),
+                })
+                .unwrap();
+                previous = Some(room_id);
+            }
+
+            set
+        };
+        let room_ids = |set: &Ruleset| {
+            set.room.iter().map(|rule| rule.rule_id.to_string()).collect::<Vec<_>>()
+        };
+
+        // Move a rule forward after another rule.
+        let mut set = ruleset(&["!a:localhost", "!r:localhost", "!b:localhost", "!c:localhost"]);
+        set.apply(PushRuleUpdate::Set {
+            rule: room_rule("!r:localhost"),
+            before: None,
+            after: Some("!b:localhost".to_owned()),
+        })
+        .unwrap();
+        assert_eq!(
+            room_ids(&set),
+            ["!a:localhost", "!b:localhost", "!r:localhost", "!c:localhost"]
+        );
+
+        // Move a rule forward before another rule.
+        let mut set = ruleset(&["!r:localhost", "!a:localhost", "!b:localhost", "!c:localhost"]);
+        set.apply(PushRuleUpdate::Set {
+            rule: room_rule("!r:localhost"),
+            before: Some("!c:localhost".to_owned()),
+            after: None,
+        })
+        .unwrap();
+        assert_eq!(
+            room_ids(&set),
+            ["!a:localhost", "!b:localhost", "!r:localhost", "!c:localhost"]
+        );
+
+        // Move a rule backward after another rule.
+        let mut set = ruleset(&["!a:localhost", "!b:localhost", "!c:localhost", "!r:localhost"]);
+        set.apply(PushRuleUpdate::Set {
+            rule: room_rule("!r:localhost"),
+            before: None,
+            after: Some("!a:localhost".to_owned()),
+        })
+        .unwrap();
+        assert_eq!(
+            room_ids(&set),
+            ["!a:localhost", "!r:localhost", "!b:localhost", "!c:localhost"]
+        );
+
+        // Move a rule backward before another rule.
+        let mut set = ruleset(&["!a:localhost", "!b:localhost", "!r:localhost", "!c:localhost"]);
+        set.apply(PushRuleUpdate::Set {
+            rule: room_rule("!r:localhost"),
+            before: Some("!a:localhost".to_owned()),
+            after: None,
+        })
+        .unwrap();
+        assert_eq!(
+            room_ids(&set),
+            ["!r:localhost", "!a:localhost", "!b:localhost", "!c:localhost"]
+        );
+
+        // Move a rule to the last position.
+        let mut set = ruleset(&["!r:localhost", "!a:localhost", "!b:localhost"]);
+        set.apply(PushRuleUpdate::Set {
+            rule: room_rule("!r:localhost"),
+            before: None,
+            after: Some("!b:localhost".to_owned()),
+        })
+        .unwrap();
+        assert_eq!(room_ids(&set), ["!a:localhost", "!b:localhost", "!r:localhost"]);
+    }
 }
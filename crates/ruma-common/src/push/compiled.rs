@@ -0,0 +1,487 @@
+//! Push rulesets prepared for repeated evaluation.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use tracing::instrument;
+
+use super::{
+    Action, AnyPushRule, AnyPushRuleRef, FlattenedJson, PushCondition, PushConditionRoomCtx,
+    PushRuleSkipReason, Ruleset, condition::PatternMatcher,
+};
+use crate::serde::Raw;
+
+/// A [`Ruleset`] prepared to be evaluated against many events.
+///
+/// The glob patterns of the push rules are compiled once when this is constructed, instead of
+/// every time a push rule is evaluated.
+///
+/// To evaluate the same event against the rulesets of several users in the same room, use an
+/// [`EventEvaluator`].
+#[derive(Clone, Debug)]
+pub struct CompiledRuleset {
+    /// The compiled push rules, in priority order.
+    rules: Vec<CompiledPushRule>,
+}
+
+impl CompiledRuleset {
+    /// Compile the given `Ruleset`.
+    pub fn new(ruleset: Ruleset) -> Self {
+        let rules = ruleset
+            .into_iter()
+            .map(|rule| {
+                let conditions = match &rule {
+                    AnyPushRule::Override(rule) | AnyPushRule::Underride(rule) => {
+                        rule.conditions.iter().map(CompiledPushCondition::new).collect()
+                    }
+                    #[cfg(feature = "unstable-msc4306")]
+                    AnyPushRule::PostContent(rule) => {
+                        rule.conditions.iter().map(CompiledPushCondition::new).collect()
+                    }
+                    // The other kinds of rules have an implicit `event_match` condition.
+                    AnyPushRule::Content(rule) => {
+                        vec![CompiledPushCondition::new(&PushCondition::EventMatch {
+                            key: "content.body".to_owned(),
+                            pattern: rule.pattern.to_string(),
+                        })]
+                    }
+                    AnyPushRule::Room(rule) => {
+                        vec![CompiledPushCondition::new(&PushCondition::EventMatch {
+                            key: "room_id".to_owned(),
+                            pattern: rule.rule_id.to_string(),
+                        })]
+                    }
+                    AnyPushRule::Sender(rule) => {
+                        vec![CompiledPushCondition::new(&PushCondition::EventMatch {
+                            key: "sender".to_owned(),
+                            pattern: rule.rule_id.to_string(),
+                        })]
+                    }
+                };
+
+                CompiledPushRule { rule, conditions }
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    /// Creates a borrowing iterator over all push rules in this `CompiledRuleset`, in priority
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = AnyPushRuleRef<'_>> {
+        self.rules.iter().map(|rule| rule.rule.as_ref())
+    }
+
+    /// Get the first push rule that applies to this event, if any.
+    ///
+    /// This is equivalent to [`Ruleset::get_match()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the message and room at the time of the event.
+    #[instrument(level = "debug", skip_all, fields(context.room_id = %context.room_id))]
+    pub async fn get_match(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> Option<AnyPushRuleRef<'_>> {
+        self.get_match_inner(event, context, None).await
+    }
+
+    /// Get the push actions that apply to this event.
+    ///
+    /// Returns an empty slice if no push rule applies.
+    ///
+    /// This is equivalent to [`Ruleset::get_actions()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the message and room at the time of the event.
+    pub async fn get_actions(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> &[Action] {
+        self.get_match(event, context).await.map(|rule| rule.actions()).unwrap_or(&[])
+    }
+
+    /// Get the first push rule that applies to this event, if any, using the given cache for the
+    /// results of the conditions that don't depend on the user.
+    async fn get_match_inner(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+        mut cache: Option<&mut BTreeMap<Arc<str>, bool>>,
+    ) -> Option<AnyPushRuleRef<'_>> {
+        if event.get_str("sender").is_some_and(|sender| sender == context.user_id) {
+            // no need to look at the rules if the event was by the user themselves
+            return None;
+        }
+
+        'rules: for rule in &self.rules {
+            if rule.skip_reason(event, context).is_some() {
+                continue;
+            }
+
+            for condition in &rule.conditions {
+                let cached = condition
+                    .cache_key
+                    .as_ref()
+                    .zip(cache.as_deref())
+                    .and_then(|(key, cache)| cache.get(key).copied());
+
+                let applies = match cached {
+                    Some(applies) => applies,
+                    None => {
+                        let applies = condition.applies(event, context).await;
+
+                        if let Some((key, cache)) =
+                            condition.cache_key.as_ref().zip(cache.as_deref_mut())
+                        {
+                            cache.insert(key.clone(), applies);
+                        }
+
+                        applies
+                    }
+                };
+
+                if !applies {
+                    continue 'rules;
+                }
+            }
+
+            return Some(rule.rule.as_ref());
+        }
+
+        None
+    }
+}
+
+impl From<Ruleset> for CompiledRuleset {
+    fn from(ruleset: Ruleset) -> Self {
+        Self::new(ruleset)
+    }
+}
+
+/// A push rule prepared to be evaluated against many events.
+#[derive(Clone, Debug)]
+struct CompiledPushRule {
+    /// The push rule.
+    rule: AnyPushRule,
+
+    /// The compiled conditions of the push rule.
+    ///
+    /// For content, room and sender rules, this contains the implicit `event_match` condition of
+    /// the rule.
+    conditions: Vec<CompiledPushCondition>,
+}
+
+impl CompiledPushRule {
+    /// Get the reason why this push rule is skipped for the event, if any.
+    fn skip_reason(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> Option<PushRuleSkipReason> {
+        match &self.rule {
+            AnyPushRule::Override(rule) | AnyPushRule::Underride(rule) => {
+                rule.skip_reason(event, context)
+            }
+            #[cfg(feature = "unstable-msc4306")]
+            AnyPushRule::PostContent(rule) => rule.skip_reason(event, context),
+            AnyPushRule::Content(rule) => rule.skip_reason(event),
+            AnyPushRule::Room(rule) => (!rule.enabled).then_some(PushRuleSkipReason::Disabled),
+            AnyPushRule::Sender(rule) => (!rule.enabled).then_some(PushRuleSkipReason::Disabled),
+        }
+    }
+}
+
+/// A push condition prepared to be evaluated against many events.
+#[derive(Clone, Debug)]
+struct CompiledPushCondition {
+    /// The compiled condition.
+    kind: CompiledPushConditionKind,
+
+    /// The key of the result of this condition in the cache of an [`EventEvaluator`].
+    ///
+    /// This is `None` if the result of this condition depends on the user.
+    cache_key: Option<Arc<str>>,
+}
+
+impl CompiledPushCondition {
+    /// Compile the given condition.
+    fn new(condition: &PushCondition) -> Self {
+        #[allow(deprecated)]
+        let is_user_dependent = match condition {
+            PushCondition::ContainsDisplayName => true,
+            #[cfg(feature = "unstable-msc4306")]
+            PushCondition::ThreadSubscription { .. } => true,
+            _ => false,
+        };
+        let cache_key = (!is_user_dependent)
+            .then(|| serde_json::to_string(condition).ok())
+            .flatten()
+            .map(Into::into);
+
+        let kind = match condition {
+            PushCondition::EventMatch { key, pattern } => CompiledPushConditionKind::EventMatch {
+                key: key.clone(),
+                matcher: PatternMatcher::new(pattern, key == "content.body"),
+            },
+            condition => CompiledPushConditionKind::Other(condition.clone()),
+        };
+
+        Self { kind, cache_key }
+    }
+
+    /// Check if this condition applies to the event.
+    async fn applies(&self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
+        match &self.kind {
+            CompiledPushConditionKind::EventMatch { key, matcher } => {
+                let value = match key.as_str() {
+                    "room_id" => Some(context.room_id.as_str()),
+                    _ => event.get_str(key),
+                };
+
+                value.is_some_and(|value| matcher.matches(value))
+            }
+            CompiledPushConditionKind::Other(condition) => condition.applies(event, context).await,
+        }
+    }
+}
+
+/// The kinds of compiled push conditions.
+#[derive(Clone, Debug)]
+enum CompiledPushConditionKind {
+    /// An `event_match` condition, with its compiled pattern.
+    EventMatch {
+        /// The key of the property of the event to match.
+        key: String,
+
+        /// The compiled pattern.
+        matcher: PatternMatcher,
+    },
+
+    /// Another condition, that doesn't need to be compiled.
+    Other(PushCondition),
+}
+
+/// An event to evaluate against the [`CompiledRuleset`]s of several users in the same room.
+///
+/// The event is only flattened once, and the results of the push conditions that don't depend on
+/// the user are shared between all the evaluations.
+///
+/// Because the results of the conditions are shared, all the [`PushConditionRoomCtx`]s used with
+/// the same `EventEvaluator` must only differ by the data about the user: `user_id`,
+/// `user_display_name` and the function to check thread subscriptions.
+#[derive(Clone, Debug)]
+pub struct EventEvaluator {
+    /// The flattened event.
+    event: FlattenedJson,
+
+    /// The cached results of the conditions that don't depend on the user.
+    cache: BTreeMap<Arc<str>, bool>,
+}
+
+impl EventEvaluator {
+    /// Create a new `EventEvaluator` for the given flattened event.
+    pub fn new(event: FlattenedJson) -> Self {
+        Self { event, cache: BTreeMap::new() }
+    }
+
+    /// Create a new `EventEvaluator` for the given raw event.
+    pub fn from_raw<T>(event: &Raw<T>) -> Self {
+        Self::new(FlattenedJson::from_raw(event))
+    }
+
+    /// The flattened event.
+    pub fn event(&self) -> &FlattenedJson {
+        &self.event
+    }
+
+    /// Get the first push rule of the given ruleset that applies to the event, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `ruleset` - The compiled push ruleset of the user.
+    /// * `context` - The context of the message and room at the time of the event, for the user.
+    #[instrument(level = "debug", skip_all, fields(context.room_id = %context.room_id))]
+    pub async fn get_match<'a>(
+        &mut self,
+        ruleset: &'a CompiledRuleset,
+        context: &PushConditionRoomCtx,
+    ) -> Option<AnyPushRuleRef<'a>> {
+        ruleset.get_match_inner(&self.event, context, Some(&mut self.cache)).await
+    }
+
+    /// Get the push actions of the given ruleset that apply to the event.
+    ///
+    /// Returns an empty slice if no push rule applies.
+    ///
+    /// # Arguments
+    ///
+    /// * `ruleset` - The compiled push ruleset of the user.
+    /// * `context` - The context of the message and room at the time of the event, for the user.
+    pub async fn get_actions<'a>(
+        &mut self,
+        ruleset: &'a CompiledRuleset,
+        context: &PushConditionRoomCtx,
+    ) -> &'a [Action] {
+        self.get_match(ruleset, context).await.map(|rule| rule.actions()).unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use js_int::{int, uint};
+    use macro_rules_attribute::apply;
+    use serde_json::{Value as JsonValue, json};
+    use smol_macros::test;
+
+    use super::{CompiledRuleset, EventEvaluator};
+    use crate::{
+        OwnedUserId, owned_room_id, owned_user_id,
+        power_levels::NotificationPowerLevels,
+        push::{
+            FlattenedJson, NewPatternedPushRule, NewPushRule, NewSimplePushRule,
+            PushConditionPowerLevelsCtx, PushConditionRoomCtx, Ruleset,
+        },
+        room_version_rules::{AuthorizationRules, RoomPowerLevelsRules},
+        serde::Raw,
+    };
+
+    fn context(user_id: OwnedUserId, display_name: &str) -> PushConditionRoomCtx {
+        PushConditionRoomCtx::new(
+            owned_room_id!("!far_west:server.name"),
+            uint!(100),
+            user_id,
+            display_name.to_owned(),
+        )
+        .with_power_levels(Some(PushConditionPowerLevelsCtx::new(
+            BTreeMap::from([(owned_user_id!("@rantanplan:server.name"), int!(100))]),
+            int!(0),
+            NotificationPowerLevels { room: int!(50) },
+            RoomPowerLevelsRules::new(&AuthorizationRules::V1, None),
+        )))
+    }
+
+    fn ruleset(user_id: &OwnedUserId) -> Ruleset {
+        let mut set = Ruleset::server_default(user_id);
+        set.insert(
+            NewPushRule::Content(NewPatternedPushRule::new(
+                "lunch".into(),
+                "lunc?*".into(),
+                Default::default(),
+            )),
+            None,
+            None,
+        )
+        .unwrap();
+        set.insert(
+            NewPushRule::Sender(NewSimplePushRule::new(
+                owned_user_id!("@spammer:server.name"),
+                Default::default(),
+            )),
+            None,
+            None,
+        )
+        .unwrap();
+        set
+    }
+
+    fn events() -> Vec<Raw<JsonValue>> {
+        [
+            json!({ "type": "m.room.message", "sender": "@rantanplan:server.name" }),
+            json!({
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": { "body": "Lunch plans?" },
+            }),
+            json!({
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": { "body": "Hi Jolly Jumper!" },
+            }),
+            json!({
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": {
+                    "body": "Hi jj!",
+                    "m.mentions": { "user_ids": ["@jj:server.name"] },
+                },
+            }),
+            json!({
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": { "body": "@room Attention please!", "m.mentions": { "room": true } },
+            }),
+            json!({
+                "type": "m.room.message",
+                "sender": "@spammer:server.name",
+                "content": { "body": "Buy now!" },
+            }),
+            json!({
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": { "msgtype": "m.notice", "body": "Notice" },
+            }),
+            json!({ "type": "m.room.message", "sender": "@jj:server.name" }),
+            json!({}),
+        ]
+        .into_iter()
+        .map(|event| Raw::new(&event).unwrap())
+        .collect()
+    }
+
+    #[apply(test!)]
+    async fn compiled_ruleset_matches_ruleset() {
+        let user_id = owned_user_id!("@jj:server.name");
+        let set = ruleset(&user_id);
+        let compiled = CompiledRuleset::new(set.clone());
+        let context = context(user_id, "Jolly Jumper");
+
+        assert_eq!(compiled.iter().count(), set.iter().count());
+
+        for event in events() {
+            let flattened = FlattenedJson::from_raw(&event);
+
+            assert_eq!(
+                compiled.get_match(&flattened, &context).await.map(|rule| rule.rule_id()),
+                set.get_match(&event, &context).await.map(|rule| rule.rule_id())
+            );
+        }
+    }
+
+    #[apply(test!)]
+    async fn event_evaluator_shares_results() {
+        let users = [
+            (owned_user_id!("@jj:server.name"), "Jolly Jumper"),
+            (owned_user_id!("@lucky_luke:server.name"), "Lucky Luke"),
+            (owned_user_id!("@rantanplan:server.name"), "Rantanplan"),
+        ];
+        let rulesets = users
+            .iter()
+            .map(|(user_id, display_name)| {
+                (
+                    ruleset(user_id),
+                    CompiledRuleset::new(ruleset(user_id)),
+                    context(user_id.clone(), display_name),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for event in events() {
+            let mut evaluator = EventEvaluator::from_raw(&event);
+
+            for (set, compiled, context) in &rulesets {
+                assert_eq!(
+                    evaluator.get_match(compiled, context).await.map(|rule| rule.rule_id()),
+                    set.get_match(&event, context).await.map(|rule| rule.rule_id())
+                );
+            }
+        }
+    }
+}
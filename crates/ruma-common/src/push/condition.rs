@@ -1,5 +1,11 @@
 use std::{
-    collections::BTreeMap, future::Future, ops::RangeBounds, pin::Pin, str::FromStr, sync::Arc,
+    borrow::Cow,
+    collections::BTreeMap,
+    future::Future,
+    ops::{Range, RangeBounds},
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
 };
 
 use js_int::{Int, UInt};
@@ -10,13 +16,15 @@ use serde::{Deserialize, Serialize};
 use serde_json::value::Value as JsonValue;
 use wildmatch::WildMatch;
 
+use super::explain::{ComparedValues, PushConditionExplanation};
+#[cfg(feature = "unstable-msc3931")]
+use crate::PrivOwnedStr;
 use crate::{
-    EventId, OwnedRoomId, OwnedUserId, UserId,
+    EventId, OwnedRoomId, OwnedUserId, RoomVersionId, UserId,
     power_levels::{NotificationPowerLevels, NotificationPowerLevelsKey},
-    room_version_rules::RoomPowerLevelsRules,
+    room_version_rules::{RoomPowerLevelsRules, RoomVersionRules},
+    serde::Raw,
 };
-#[cfg(feature = "unstable-msc3931")]
-use crate::{PrivOwnedStr, RoomVersionId};
 
 mod flattened_json;
 mod push_condition_serde;
@@ -49,24 +57,26 @@ pub enum RoomVersionFeature {
 #[cfg(feature = "unstable-msc3931")]
 impl RoomVersionFeature {
     /// Get the default features for the given room version.
+    ///
+    /// Returns an empty list if the rules of the room version are unknown.
     pub fn list_for_room_version(version: &RoomVersionId) -> Vec<Self> {
-        match version {
-            RoomVersionId::V1
-            | RoomVersionId::V2
-            | RoomVersionId::V3
-            | RoomVersionId::V4
-            | RoomVersionId::V5
-            | RoomVersionId::V6
-            | RoomVersionId::V7
-            | RoomVersionId::V8
-            | RoomVersionId::V9
-            | RoomVersionId::V10
-            | RoomVersionId::V11
-            | RoomVersionId::V12
-            | RoomVersionId::_Custom(_) => vec![],
-            #[cfg(feature = "unstable-msc2870")]
-            RoomVersionId::MSC2870 => vec![],
+        version.rules().map(|rules| Self::list_for_room_version_rules(&rules)).unwrap_or_default()
+    }
+
+    /// Get the features supported by a room version with the given rules.
+    pub fn list_for_room_version_rules(rules: &RoomVersionRules) -> Vec<Self> {
+        #[allow(unused_mut)]
+        let mut features = Vec::new();
+
+        #[cfg(feature = "unstable-msc3932")]
+        if rules.extensible_events {
+            features.push(Self::ExtensibleEvents);
         }
+
+        #[cfg(not(feature = "unstable-msc3932"))]
+        let _ = rules;
+
+        features
     }
 }
 
@@ -183,58 +193,227 @@ impl PushCondition {
             return false;
         }
 
+        self.evaluate(event, context).await.0
+    }
+
+    /// Check synchronously if this condition applies to the event.
+    ///
+    /// This is the same as [`PushCondition::applies()`], except that the thread subscription
+    /// conditions of [MSC4306] only apply if a synchronous function was set with
+    /// [`PushConditionRoomCtx::with_has_thread_subscription_sync_fn()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the room at the time of the event. If the power levels context
+    ///   is missing from it, conditions that depend on it will never apply.
+    ///
+    /// [MSC4306]: https://github.com/matrix-org/matrix-spec-proposals/pull/4306
+    pub fn applies_sync(&self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
+        if event.get_str("sender").is_some_and(|sender| sender == context.user_id) {
+            return false;
+        }
+
+        self.evaluate_sync(event, context).0
+    }
+}
+
+/// Get the ID of the thread root of the event, if it has a relation of type `m.thread`.
+#[cfg(feature = "unstable-msc4306")]
+fn thread_root(event: &FlattenedJson) -> Option<&EventId> {
+    if event.get_str("content.m\\.relates_to.rel_type") != Some("m.thread") {
+        return None;
+    }
+
+    event.get_str("content.m\\.relates_to.event_id").and_then(|id| <&EventId>::try_from(id).ok())
+}
+
+impl PushCondition {
+    /// Explain how this condition applies to the event.
+    ///
+    /// Contrary to [`PushCondition::applies()`], this doesn't check whether the event was sent by
+    /// the user themselves, because this check is done for the whole push rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the room at the time of the event.
+    pub async fn explain(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> PushConditionExplanation<'_> {
+        let (passed, compared_values) = self.evaluate(event, context).await;
+        PushConditionExplanation { condition: Cow::Borrowed(self), passed, compared_values }
+    }
+
+    /// Evaluate this condition against the event.
+    ///
+    /// Returns whether the condition passed, and the values that were compared.
+    pub(super) async fn evaluate(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> (bool, ComparedValues) {
+        #[cfg(feature = "unstable-msc4306")]
+        let is_subscribed_to_thread =
+            match (self, thread_root(event), &context.has_thread_subscription_fn) {
+                (Self::ThreadSubscription { .. }, Some(thread_root), Some(has_subscription_fn)) => {
+                    Some(has_subscription_fn(thread_root).await)
+                }
+                _ => None,
+            };
+        #[cfg(not(feature = "unstable-msc4306"))]
+        let is_subscribed_to_thread = None;
+
+        self.evaluate_with_thread_subscription(event, context, is_subscribed_to_thread)
+    }
+
+    /// Evaluate this condition synchronously against the event.
+    ///
+    /// This is the same as [`PushCondition::evaluate()`], except that the thread subscription
+    /// status is looked up with the synchronous function of the context.
+    pub(super) fn evaluate_sync(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> (bool, ComparedValues) {
+        #[cfg(feature = "unstable-msc4306")]
+        let is_subscribed_to_thread =
+            match (self, thread_root(event), &context.has_thread_subscription_sync_fn) {
+                (Self::ThreadSubscription { .. }, Some(thread_root), Some(has_subscription_fn)) => {
+                    Some(has_subscription_fn(thread_root))
+                }
+                _ => None,
+            };
+        #[cfg(not(feature = "unstable-msc4306"))]
+        let is_subscribed_to_thread = None;
+
+        self.evaluate_with_thread_subscription(event, context, is_subscribed_to_thread)
+    }
+
+    /// Evaluate this condition against the event, with the thread subscription status that was
+    /// looked up by the caller.
+    ///
+    /// `is_subscribed_to_thread` is only used for the thread subscription conditions of
+    /// [MSC4306], and is `None` if the status could not be determined.
+    ///
+    /// [MSC4306]: https://github.com/matrix-org/matrix-spec-proposals/pull/4306
+    #[cfg_attr(not(feature = "unstable-msc4306"), allow(unused_variables))]
+    fn evaluate_with_thread_subscription(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+        is_subscribed_to_thread: Option<bool>,
+    ) -> (bool, ComparedValues) {
         match self {
-            Self::EventMatch { key, pattern } => check_event_match(event, key, pattern, context),
+            Self::EventMatch { key, pattern } => {
+                let value = match key.as_str() {
+                    "room_id" => Some(context.room_id.as_str()),
+                    _ => event.get_str(key),
+                };
+                let passed = value
+                    .is_some_and(|value| value.matches_pattern(pattern, key == "content.body"));
+
+                (passed, ComparedValues::EventMatch { value: value.map(ToOwned::to_owned) })
+            }
             #[allow(deprecated)]
             Self::ContainsDisplayName => {
-                let Some(value) = event.get_str("content.body") else { return false };
-                value.matches_pattern(&context.user_display_name, true)
+                let body = event.get_str("content.body");
+                let passed =
+                    body.is_some_and(|body| body.matches_pattern(&context.user_display_name, true));
+
+                (
+                    passed,
+                    ComparedValues::ContainsDisplayName {
+                        body: body.map(ToOwned::to_owned),
+                        display_name: context.user_display_name.clone(),
+                    },
+                )
             }
-            Self::RoomMemberCount { is } => is.contains(&context.member_count),
+            Self::RoomMemberCount { is } => (
+                is.contains(&context.member_count),
+                ComparedValues::RoomMemberCount { member_count: context.member_count },
+            ),
             Self::SenderNotificationPermission { key } => {
-                let Some(power_levels) = &context.power_levels else { return false };
-                let Some(sender_id) = event.get_str("sender") else { return false };
-                let Ok(sender_id) = <&UserId>::try_from(sender_id) else { return false };
+                let sender_id = event
+                    .get_str("sender")
+                    .and_then(|sender_id| <&UserId>::try_from(sender_id).ok());
+
+                let Some(power_levels) = &context.power_levels else {
+                    return (
+                        false,
+                        ComparedValues::SenderNotificationPermission {
+                            sender_power_level: None,
+                            required_power_level: None,
+                            sender_is_privileged_creator: false,
+                        },
+                    );
+                };
 
-                power_levels.has_sender_notification_permission(sender_id, key)
+                let passed = sender_id.is_some_and(|sender_id| {
+                    power_levels.has_sender_notification_permission(sender_id, key)
+                });
+                let sender_power_level = sender_id.map(|sender_id| {
+                    *power_levels.users.get(sender_id).unwrap_or(&power_levels.users_default)
+                });
+                let sender_is_privileged_creator = sender_id.is_some_and(|sender_id| {
+                    power_levels
+                        .rules
+                        .privileged_creators
+                        .as_ref()
+                        .is_some_and(|creators| creators.contains(sender_id))
+                });
+
+                (
+                    passed,
+                    ComparedValues::SenderNotificationPermission {
+                        sender_power_level,
+                        required_power_level: power_levels.notifications.get(key).copied(),
+                        sender_is_privileged_creator,
+                    },
+                )
             }
             #[cfg(feature = "unstable-msc3931")]
-            Self::RoomVersionSupports { feature } => match feature {
-                RoomVersionFeature::ExtensibleEvents => {
-                    context.supported_features.contains(&RoomVersionFeature::ExtensibleEvents)
-                }
-                RoomVersionFeature::_Custom(_) => false,
-            },
-            Self::EventPropertyIs { key, value } => event.get(key).is_some_and(|v| v == value),
-            Self::EventPropertyContains { key, value } => event
-                .get(key)
-                .and_then(FlattenedJsonValue::as_array)
-                .is_some_and(|a| a.contains(value)),
-            #[cfg(feature = "unstable-msc4306")]
-            Self::ThreadSubscription { subscribed: must_be_subscribed } => {
-                let Some(has_thread_subscription_fn) = &context.has_thread_subscription_fn else {
-                    // If we don't have a function to check thread subscriptions, we can't
-                    // determine if the condition applies.
-                    return false;
+            Self::RoomVersionSupports { feature } => {
+                let passed = match feature {
+                    RoomVersionFeature::ExtensibleEvents => {
+                        context.supported_features.contains(&RoomVersionFeature::ExtensibleEvents)
+                    }
+                    RoomVersionFeature::_Custom(_) => false,
                 };
 
-                // The event must have a relation of type `m.thread`.
-                if event.get_str("content.m\\.relates_to.rel_type") != Some("m.thread") {
-                    return false;
-                }
-
-                // Retrieve the thread root event ID.
-                let Some(Ok(thread_root)) =
-                    event.get_str("content.m\\.relates_to.event_id").map(<&EventId>::try_from)
-                else {
-                    return false;
-                };
+                (
+                    passed,
+                    ComparedValues::RoomVersionSupports {
+                        supported_features: context.supported_features.clone(),
+                    },
+                )
+            }
+            Self::EventPropertyIs { key, value } => {
+                let event_value = event.get(key);
+                let passed = event_value.is_some_and(|v| v == value);
 
-                let is_subscribed = has_thread_subscription_fn(thread_root).await;
+                (passed, ComparedValues::EventProperty { value: event_value.cloned() })
+            }
+            Self::EventPropertyContains { key, value } => {
+                let event_value = event.get(key);
+                let passed = event_value
+                    .and_then(FlattenedJsonValue::as_array)
+                    .is_some_and(|a| a.contains(value));
 
-                *must_be_subscribed == is_subscribed
+                (passed, ComparedValues::EventProperty { value: event_value.cloned() })
             }
-            Self::_Custom(_) => false,
+            #[cfg(feature = "unstable-msc4306")]
+            Self::ThreadSubscription { subscribed: must_be_subscribed } => (
+                is_subscribed_to_thread
+                    .is_some_and(|is_subscribed| *must_be_subscribed == is_subscribed),
+                ComparedValues::ThreadSubscription {
+                    thread_root: thread_root(event).map(ToOwned::to_owned),
+                    is_subscribed: is_subscribed_to_thread,
+                },
+            ),
+            Self::_Custom(_) => (false, ComparedValues::Unknown),
         }
     }
 }
@@ -285,6 +464,11 @@ pub struct PushConditionRoomCtx {
     #[cfg(feature = "unstable-msc4306")]
     has_thread_subscription_fn: Option<Arc<HasThreadSubscriptionFn>>,
 
+    /// A closure that indicates synchronously if the given thread is subscribed to by the current
+    /// user, used for the synchronous evaluation of push rules.
+    #[cfg(feature = "unstable-msc4306")]
+    has_thread_subscription_sync_fn: Option<Arc<HasThreadSubscriptionSyncFn>>,
+
     /// When the `unstable-msc4306` feature is enabled with the field above, it changes the auto
     /// trait implementations of the struct to `!RefUnwindSafe` and `!UnwindSafe`. So we use
     /// `PhantomData` to keep the same bounds on the field when the feature is disabled, to always
@@ -306,6 +490,12 @@ type HasThreadSubscriptionFn =
 #[cfg(target_family = "wasm")]
 type HasThreadSubscriptionFn = dyn for<'a> Fn(&'a EventId) -> HasThreadSubscriptionFuture<'a>;
 
+#[cfg(all(feature = "unstable-msc4306", not(target_family = "wasm")))]
+type HasThreadSubscriptionSyncFn = dyn Fn(&EventId) -> bool + Send + Sync;
+
+#[cfg(all(feature = "unstable-msc4306", target_family = "wasm"))]
+type HasThreadSubscriptionSyncFn = dyn Fn(&EventId) -> bool;
+
 impl std::fmt::Debug for PushConditionRoomCtx {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut debug_struct = f.debug_struct("PushConditionRoomCtx");
@@ -341,6 +531,8 @@ impl PushConditionRoomCtx {
             #[cfg(feature = "unstable-msc3931")]
             supported_features: Vec::new(),
             has_thread_subscription_fn: Default::default(),
+            #[cfg(feature = "unstable-msc4306")]
+            has_thread_subscription_sync_fn: None,
         }
     }
 
@@ -364,13 +556,98 @@ impl PushConditionRoomCtx {
         ) -> HasThreadSubscriptionFuture<'a>
         + 'static,
     ) -> Self {
-        Self { has_thread_subscription_fn: Some(Arc::new(has_thread_subscription_fn)), ..self }
+        Self {
+            has_thread_subscription_fn: Some(Arc::new(has_thread_subscription_fn)),
+            has_thread_subscription_sync_fn: None,
+            ..self
+        }
+    }
+
+    /// Set a synchronous function to check if the user is subscribed to a thread, so as to define
+    /// the push rules defined in [MSC4306].
+    ///
+    /// Contrary to [`PushConditionRoomCtx::with_has_thread_subscription_fn()`], this allows to
+    /// evaluate the thread subscription conditions with the synchronous evaluation methods, like
+    /// [`Ruleset::get_actions_sync()`](super::Ruleset::get_actions_sync). The function is also used
+    /// by the asynchronous evaluation methods.
+    ///
+    /// [MSC4306]: https://github.com/matrix-org/matrix-spec-proposals/pull/4306
+    #[cfg(feature = "unstable-msc4306")]
+    pub fn with_has_thread_subscription_sync_fn(
+        self,
+        #[cfg(not(target_family = "wasm"))] has_thread_subscription_fn: impl Fn(&EventId) -> bool
+        + Send
+        + Sync
+        + 'static,
+        #[cfg(target_family = "wasm")] has_thread_subscription_fn: impl Fn(&EventId) -> bool + 'static,
+    ) -> Self {
+        let has_thread_subscription_sync_fn: Arc<HasThreadSubscriptionSyncFn> =
+            Arc::new(has_thread_subscription_fn);
+        let sync_fn = has_thread_subscription_sync_fn.clone();
+
+        Self {
+            has_thread_subscription_fn: Some(Arc::new(move |event_id: &EventId| {
+                let is_subscribed = sync_fn(event_id);
+                Box::pin(async move { is_subscribed }) as HasThreadSubscriptionFuture<'_>
+            })),
+            has_thread_subscription_sync_fn: Some(has_thread_subscription_sync_fn),
+            ..self
+        }
     }
 
     /// Add the given power levels context to this `PushConditionRoomCtx`.
     pub fn with_power_levels(self, power_levels: Option<PushConditionPowerLevelsCtx>) -> Self {
         Self { power_levels, ..self }
     }
+
+    /// Set the features supported by the room from the rules of its room version.
+    ///
+    /// This replaces the `supported_features` when the `unstable-msc3931` feature is enabled, and
+    /// does nothing otherwise.
+    pub fn with_room_version_rules(self, rules: &RoomVersionRules) -> Self {
+        #[cfg(feature = "unstable-msc3931")]
+        return Self {
+            supported_features: RoomVersionFeature::list_for_room_version_rules(rules),
+            ..self
+        };
+
+        #[cfg(not(feature = "unstable-msc3931"))]
+        {
+            let _ = rules;
+            self
+        }
+    }
+
+    /// Set the features supported by the room from its room version, as found in the
+    /// `m.room.create` event of the room.
+    ///
+    /// If the rules of the room version are unknown, the room is considered to not support any
+    /// feature.
+    pub fn with_room_version(self, room_version: &RoomVersionId) -> Self {
+        match room_version.rules() {
+            Some(rules) => self.with_room_version_rules(&rules),
+            #[cfg(feature = "unstable-msc3931")]
+            None => Self { supported_features: Vec::new(), ..self },
+            #[cfg(not(feature = "unstable-msc3931"))]
+            None => self,
+        }
+    }
+
+    /// Set the features supported by the room from the content of the `m.room.create` event of
+    /// the room.
+    ///
+    /// The room version is read from the `room_version` field of the content, and defaults to
+    /// room version 1 if it is missing. If the room version can't be read or its rules are
+    /// unknown, the room is considered to not support any feature.
+    pub fn with_room_create_content<T>(self, content: &Raw<T>) -> Self {
+        match content.get_field::<RoomVersionId>("room_version") {
+            Ok(room_version) => self.with_room_version(&room_version.unwrap_or(RoomVersionId::V1)),
+            #[cfg(feature = "unstable-msc3931")]
+            Err(_) => Self { supported_features: Vec::new(), ..self },
+            #[cfg(not(feature = "unstable-msc3931"))]
+            Err(_) => self,
+        }
+    }
 }
 
 /// The room power levels context to be able to test the corresponding push conditions.
@@ -511,14 +788,7 @@ impl StrExt for str {
     }
 
     fn matches_pattern(&self, pattern: &str, match_words: bool) -> bool {
-        let value = &self.to_lowercase();
-        let pattern = &pattern.to_lowercase();
-
-        if match_words {
-            value.matches_word(pattern)
-        } else {
-            WildMatch::new(pattern).matches(value)
-        }
+        PatternMatcher::new(pattern, match_words).matches(self)
     }
 
     fn matches_word(&self, pattern: &str) -> bool {
@@ -532,40 +802,7 @@ impl StrExt for str {
         let has_wildcards = pattern.contains(['?', '*']);
 
         if has_wildcards {
-            let mut chunks: Vec<String> = vec![];
-            let mut prev_wildcard = false;
-            let mut chunk_start = 0;
-
-            for (i, c) in pattern.char_indices() {
-                if matches!(c, '?' | '*') && !prev_wildcard {
-                    if i != 0 {
-                        chunks.push(regex::escape(&pattern[chunk_start..i]));
-                        chunk_start = i;
-                    }
-
-                    prev_wildcard = true;
-                } else if prev_wildcard {
-                    let chunk = &pattern[chunk_start..i];
-                    chunks.push(chunk.wildcards_to_regex());
-
-                    chunk_start = i;
-                    prev_wildcard = false;
-                }
-            }
-
-            let len = pattern.len();
-            if !prev_wildcard {
-                chunks.push(regex::escape(&pattern[chunk_start..len]));
-            } else if prev_wildcard {
-                let chunk = &pattern[chunk_start..len];
-                chunks.push(chunk.wildcards_to_regex());
-            }
-
-            // The word characters in ASCII compatible mode (with the `-u` flag) match the
-            // definition in the spec: any character not in the set `[A-Za-z0-9_]`.
-            let regex = format!(r"(?-u:^|\W|\b){}(?-u:\b|\W|$)", chunks.concat());
-            let re = Regex::new(&regex).expect("regex construction should succeed");
-            re.is_match(self.as_bytes())
+            word_regex(pattern).is_match(self.as_bytes())
         } else {
             match self.find(pattern) {
                 Some(start) => {
@@ -617,6 +854,135 @@ impl StrExt for str {
     }
 }
 
+/// Construct the regex to match the given pattern, that contains wildcards, with word boundaries.
+fn word_regex(pattern: &str) -> Regex {
+    // The word characters in ASCII compatible mode (with the `-u` flag) match the
+    // definition in the spec: any character not in the set `[A-Za-z0-9_]`.
+    let regex = format!(r"(?-u:^|\W|\b){}(?-u:\b|\W|$)", pattern_to_regex(pattern));
+    Regex::new(&regex).expect("regex construction should succeed")
+}
+
+/// Find the byte ranges of the words in `value` that match `pattern`.
+///
+/// The pattern can be a glob with wildcards `*` and `?`. The match is case insensitive and uses
+/// the same word boundaries as [`StrExt::matches_word()`].
+pub(super) fn find_word_matches(value: &str, pattern: &str) -> Vec<Range<usize>> {
+    let pattern = pattern.to_lowercase();
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+
+    // Lowercase the value while keeping track of the offset in the original value of each byte,
+    // because lowercasing can change the length of characters.
+    let mut lowercase = String::with_capacity(value.len());
+    let mut offsets = Vec::with_capacity(value.len() + 1);
+    for (offset, c) in value.char_indices() {
+        lowercase.extend(c.to_lowercase());
+        offsets.resize(lowercase.len(), offset);
+    }
+    offsets.push(value.len());
+
+    let regex = format!(r"(?-u:^|\W|\b)({})(?-u:\b|\W|$)", pattern_to_regex(&pattern));
+    let regex = Regex::new(&regex).expect("regex construction should succeed");
+
+    regex
+        .captures_iter(lowercase.as_bytes())
+        .filter_map(|captures| captures.get(1))
+        .filter(|word| !word.is_empty())
+        .map(|word| offsets[word.start()]..offsets[word.end()])
+        .collect()
+}
+
+/// Translate the given glob pattern to a regex, without word boundaries.
+fn pattern_to_regex(pattern: &str) -> String {
+    let mut chunks: Vec<String> = vec![];
+    let mut prev_wildcard = false;
+    let mut chunk_start = 0;
+
+    for (i, c) in pattern.char_indices() {
+        if matches!(c, '?' | '*') && !prev_wildcard {
+            if i != 0 {
+                chunks.push(regex::escape(&pattern[chunk_start..i]));
+                chunk_start = i;
+            }
+
+            prev_wildcard = true;
+        } else if prev_wildcard {
+            let chunk = &pattern[chunk_start..i];
+            chunks.push(chunk.wildcards_to_regex());
+
+            chunk_start = i;
+            prev_wildcard = false;
+        }
+    }
+
+    let len = pattern.len();
+    if !prev_wildcard {
+        chunks.push(regex::escape(&pattern[chunk_start..len]));
+    } else if prev_wildcard {
+        let chunk = &pattern[chunk_start..len];
+        chunks.push(chunk.wildcards_to_regex());
+    }
+
+    chunks.concat()
+}
+
+/// A glob pattern prepared to match strings against it.
+///
+/// This allows to reuse the matcher for several strings.
+#[derive(Clone, Debug)]
+pub(super) enum PatternMatcher {
+    /// Match the whole string against a glob.
+    Glob(WildMatch),
+
+    /// Match a word in the string against a pattern without wildcards.
+    Word(String),
+
+    /// Match a word in the string against a pattern with wildcards.
+    WordWithWildcards {
+        /// The lowercase pattern.
+        pattern: String,
+
+        /// The regex equivalent to the pattern.
+        regex: Regex,
+    },
+}
+
+impl PatternMatcher {
+    /// Construct a `PatternMatcher` for the given pattern.
+    ///
+    /// The pattern can be a glob with wildcards `*` and `?`.
+    ///
+    /// If `match_words` is `true`, checks that the pattern is separated from other words.
+    pub(super) fn new(pattern: &str, match_words: bool) -> Self {
+        let pattern = pattern.to_lowercase();
+
+        if !match_words {
+            Self::Glob(WildMatch::new(&pattern))
+        } else if !pattern.is_empty() && pattern.contains(['?', '*']) {
+            let regex = word_regex(&pattern);
+            Self::WordWithWildcards { pattern, regex }
+        } else {
+            Self::Word(pattern)
+        }
+    }
+
+    /// Matches the given string against this pattern.
+    ///
+    /// The match is case insensitive.
+    pub(super) fn matches(&self, value: &str) -> bool {
+        let value = value.to_lowercase();
+
+        match self {
+            Self::Glob(glob) => glob.matches(&value),
+            Self::Word(pattern) => value.matches_word(pattern),
+            Self::WordWithWildcards { pattern, regex } => {
+                value == *pattern || regex.is_match(value.as_bytes())
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
@@ -977,6 +1343,61 @@ mod tests {
         assert!(!room_version_condition.applies(&simple_event, &context_not_matching).await);
     }
 
+    #[cfg(feature = "unstable-msc3932")]
+    #[test]
+    fn room_version_supports_from_rules() {
+        use crate::{RoomVersionId, room_version_rules::RoomVersionRules};
+
+        let event = FlattenedJson::from_value(json!({
+            "sender": "@worthy_whale:server.name",
+            "content": {
+                "msgtype": "org.matrix.msc3932.extensible_events",
+                "body": "Hello",
+            },
+        }));
+        let condition = PushCondition::RoomVersionSupports {
+            feature: super::RoomVersionFeature::ExtensibleEvents,
+        };
+
+        let context = push_context().with_room_version(&RoomVersionId::V12);
+        assert_eq!(context.supported_features, []);
+        assert!(!condition.applies_sync(&event, &context));
+
+        let mut rules = RoomVersionRules::V12;
+        rules.extensible_events = true;
+        let context = push_context().with_room_version_rules(&rules);
+        assert_eq!(context.supported_features, [super::RoomVersionFeature::ExtensibleEvents]);
+        assert!(condition.applies_sync(&event, &context));
+
+        // Unknown room versions don't support any feature.
+        let context = context.with_room_version(&"org.example.custom".try_into().unwrap());
+        assert_eq!(context.supported_features, []);
+    }
+
+    #[cfg(feature = "unstable-msc1767")]
+    #[test]
+    fn room_version_supports_from_create_content() {
+        use crate::{RoomVersionId, serde::Raw};
+
+        let context = push_context().with_room_version(&RoomVersionId::MSC1767);
+        assert_eq!(context.supported_features, [super::RoomVersionFeature::ExtensibleEvents]);
+
+        let content = Raw::<serde_json::Value>::new(&json!({ "room_version": "10" })).unwrap();
+        let context = context.with_room_create_content(&content);
+        assert_eq!(context.supported_features, []);
+
+        let content =
+            Raw::<serde_json::Value>::new(&json!({ "room_version": "org.matrix.msc1767.10" }))
+                .unwrap();
+        let context = context.with_room_create_content(&content);
+        assert_eq!(context.supported_features, [super::RoomVersionFeature::ExtensibleEvents]);
+
+        // The room version defaults to 1.
+        let content = Raw::<serde_json::Value>::new(&json!({})).unwrap();
+        let context = context.with_room_create_content(&content);
+        assert_eq!(context.supported_features, []);
+    }
+
     #[apply(test!)]
     async fn event_property_is_applies() {
         use crate::push::condition::ScalarJsonValue;
@@ -1183,4 +1604,41 @@ mod tests {
         assert!(!unsubscribed_thread_condition.applies(&subscribed_thread_event, &context).await);
         assert!(!unsubscribed_thread_condition.applies(&non_thread_related_event, &context).await);
     }
+
+    #[cfg(feature = "unstable-msc4306")]
+    #[apply(test!)]
+    async fn thread_subscriptions_match_sync() {
+        use crate::{EventId, event_id};
+
+        let thread_event = FlattenedJson::from_value(json!({
+            "event_id": "$thread_response",
+            "sender": "@worthy_whale:server.name",
+            "content": {
+                "msgtype": "m.text",
+                "body": "response in thread $subscribed_thread",
+                "m.relates_to": {
+                    "rel_type": "m.thread",
+                    "event_id": "$subscribed_thread",
+                },
+            },
+        }));
+        let subscribed_thread_condition = PushCondition::ThreadSubscription { subscribed: true };
+
+        // The asynchronous function can't be used synchronously.
+        let context = push_context().with_has_thread_subscription_fn(|event_id: &EventId| {
+            Box::pin(async move { event_id == event_id!("$subscribed_thread") })
+        });
+        assert!(subscribed_thread_condition.applies(&thread_event, &context).await);
+        assert!(!subscribed_thread_condition.applies_sync(&thread_event, &context));
+
+        // The synchronous function can be used both ways.
+        let context = push_context().with_has_thread_subscription_sync_fn(|event_id: &EventId| {
+            event_id == event_id!("$subscribed_thread")
+        });
+        assert!(subscribed_thread_condition.applies(&thread_event, &context).await);
+        assert!(subscribed_thread_condition.applies_sync(&thread_event, &context));
+
+        let unsubscribed_thread_condition = PushCondition::ThreadSubscription { subscribed: false };
+        assert!(!unsubscribed_thread_condition.applies_sync(&thread_event, &context));
+    }
 }
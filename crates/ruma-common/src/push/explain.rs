@@ -0,0 +1,434 @@
+//! Types to explain how push rules apply to an event.
+
+use std::borrow::Cow;
+
+use js_int::{Int, UInt};
+use tracing::instrument;
+
+#[cfg(feature = "unstable-msc3931")]
+use super::RoomVersionFeature;
+use super::{
+    Action, AnyPushRuleRef, FlattenedJson, FlattenedJsonValue, PushCondition, PushConditionRoomCtx,
+    Ruleset,
+};
+#[cfg(feature = "unstable-msc4306")]
+use crate::OwnedEventId;
+
+impl Ruleset {
+    /// Explain how each push rule in this `Ruleset` applies to this event.
+    ///
+    /// Contrary to [`Ruleset::get_match()`], this evaluates all the push rules, in priority order,
+    /// and all their conditions, even after a rule or a condition that doesn't apply. This is
+    /// meant to be used to show why an event did or did not trigger a notification.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the room at the time of the event.
+    #[instrument(level = "debug", skip_all, fields(context.room_id = %context.room_id))]
+    pub async fn explain(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> RulesetExplanation<'_> {
+        let mut rules = Vec::new();
+
+        for rule in self {
+            rules.push(rule.explain(event, context).await);
+        }
+
+        RulesetExplanation { rules }
+    }
+}
+
+impl<'a> AnyPushRuleRef<'a> {
+    /// Explain how the push rule applies to the event.
+    ///
+    /// The conditions of the push rule are evaluated even if the rule is skipped, to show whether
+    /// they would have matched.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the room at the time of the event.
+    pub async fn explain(
+        self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> PushRuleExplanation<'a> {
+        let skip_reason = if event.get_str("sender").is_some_and(|sender| sender == context.user_id)
+        {
+            Some(PushRuleSkipReason::SentByUser)
+        } else {
+            match self {
+                Self::Override(rule) => rule.skip_reason(event, context),
+                Self::Underride(rule) => rule.skip_reason(event, context),
+                Self::Content(rule) => rule.skip_reason(event),
+                #[cfg(feature = "unstable-msc4306")]
+                Self::PostContent(rule) => rule.skip_reason(event, context),
+                Self::Room(rule) => (!rule.enabled).then_some(PushRuleSkipReason::Disabled),
+                Self::Sender(rule) => (!rule.enabled).then_some(PushRuleSkipReason::Disabled),
+            }
+        };
+
+        let mut conditions = Vec::new();
+
+        match self {
+            Self::Override(rule) | Self::Underride(rule) => {
+                for condition in &rule.conditions {
+                    conditions.push(condition.explain(event, context).await);
+                }
+            }
+            #[cfg(feature = "unstable-msc4306")]
+            Self::PostContent(rule) => {
+                for condition in &rule.conditions {
+                    conditions.push(condition.explain(event, context).await);
+                }
+            }
+            // The other kinds of rules have an implicit `event_match` condition.
+            Self::Content(rule) => {
+                let condition = PushCondition::EventMatch {
+                    key: "content.body".to_owned(),
+                    pattern: rule.pattern.to_string(),
+                };
+                conditions.push(PushConditionExplanation::owned(condition, event, context).await);
+            }
+            Self::Room(rule) => {
+                let condition = PushCondition::EventMatch {
+                    key: "room_id".to_owned(),
+                    pattern: rule.rule_id.to_string(),
+                };
+                conditions.push(PushConditionExplanation::owned(condition, event, context).await);
+            }
+            Self::Sender(rule) => {
+                let condition = PushCondition::EventMatch {
+                    key: "sender".to_owned(),
+                    pattern: rule.rule_id.to_string(),
+                };
+                conditions.push(PushConditionExplanation::owned(condition, event, context).await);
+            }
+        }
+
+        PushRuleExplanation { rule: self, skip_reason, conditions }
+    }
+}
+
+/// The explanation of how the push rules in a [`Ruleset`] apply to an event.
+///
+/// This is returned by [`Ruleset::explain()`].
+#[derive(Clone, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct RulesetExplanation<'a> {
+    /// The explanation for each push rule in the ruleset, in priority order.
+    pub rules: Vec<PushRuleExplanation<'a>>,
+}
+
+impl<'a> RulesetExplanation<'a> {
+    /// The explanation of the first push rule that matches the event, if any.
+    ///
+    /// This is the push rule that would be returned by [`Ruleset::get_match()`].
+    pub fn matched_rule(&self) -> Option<&PushRuleExplanation<'a>> {
+        self.rules.iter().find(|rule| rule.matches())
+    }
+
+    /// The push actions that apply to the event.
+    ///
+    /// Returns an empty slice if no push rule matches.
+    pub fn actions(&self) -> &'a [Action] {
+        self.matched_rule().map(|rule| rule.rule.actions()).unwrap_or(&[])
+    }
+}
+
+/// The explanation of how a push rule applies to an event.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct PushRuleExplanation<'a> {
+    /// The push rule.
+    pub rule: AnyPushRuleRef<'a>,
+
+    /// The reason why the push rule was skipped for the event, if any.
+    ///
+    /// If this is set, the push rule doesn't match, regardless of its conditions.
+    pub skip_reason: Option<PushRuleSkipReason>,
+
+    /// The explanation for each condition of the push rule.
+    ///
+    /// For content, room and sender rules, this contains the implicit `event_match` condition of
+    /// the rule.
+    pub conditions: Vec<PushConditionExplanation<'a>>,
+}
+
+impl PushRuleExplanation<'_> {
+    /// Whether the push rule is enabled.
+    pub fn enabled(&self) -> bool {
+        self.rule.enabled()
+    }
+
+    /// Whether the push rule matches the event.
+    pub fn matches(&self) -> bool {
+        self.skip_reason.is_none() && self.conditions.iter().all(|condition| condition.passed)
+    }
+}
+
+/// The reason why a push rule is skipped for an event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum PushRuleSkipReason {
+    /// The event was sent by the user themselves.
+    SentByUser,
+
+    /// The push rule is disabled.
+    Disabled,
+
+    /// The push rule is a legacy mention rule and the event contains an `m.mentions` property.
+    LegacyMentions,
+
+    /// The room supports extensible events and the push rule doesn't have a
+    /// `room_version_supports` condition.
+    #[cfg(feature = "unstable-msc3932")]
+    ExtensibleEvents,
+}
+
+/// The explanation of how a push condition applies to an event.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct PushConditionExplanation<'a> {
+    /// The push condition.
+    pub condition: Cow<'a, PushCondition>,
+
+    /// Whether the push condition passed.
+    pub passed: bool,
+
+    /// The values that were compared to evaluate the push condition.
+    pub compared_values: ComparedValues,
+}
+
+impl PushConditionExplanation<'_> {
+    /// Explain how the given condition, that is not part of a push rule, applies to the event.
+    async fn owned(
+        condition: PushCondition,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> Self {
+        let (passed, compared_values) = condition.evaluate(event, context).await;
+        Self { condition: Cow::Owned(condition), passed, compared_values }
+    }
+}
+
+/// The values of the event or of the room context that were compared to evaluate a push
+/// condition.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum ComparedValues {
+    /// The values compared by an `event_match` condition.
+    EventMatch {
+        /// The string value at the key of the condition, if any.
+        value: Option<String>,
+    },
+
+    /// The values compared by a `contains_display_name` condition.
+    ContainsDisplayName {
+        /// The body of the event, if any.
+        body: Option<String>,
+
+        /// The display name of the user in the room.
+        display_name: String,
+    },
+
+    /// The values compared by a `room_member_count` condition.
+    RoomMemberCount {
+        /// The number of members in the room.
+        member_count: UInt,
+    },
+
+    /// The values compared by a `sender_notification_permission` condition.
+    SenderNotificationPermission {
+        /// The power level of the sender.
+        ///
+        /// This is `None` if the power levels are missing from the context or if the event
+        /// doesn't have a valid sender.
+        sender_power_level: Option<Int>,
+
+        /// The power level required for the key of the condition.
+        ///
+        /// This is `None` if the power levels are missing from the context or if the key is
+        /// unknown.
+        required_power_level: Option<Int>,
+
+        /// Whether the sender is a privileged creator of the room, which always has the
+        /// permission.
+        sender_is_privileged_creator: bool,
+    },
+
+    /// The values compared by a `room_version_supports` condition.
+    #[cfg(feature = "unstable-msc3931")]
+    RoomVersionSupports {
+        /// The features supported by the room.
+        supported_features: Vec<RoomVersionFeature>,
+    },
+
+    /// The values compared by an `event_property_is` or `event_property_contains` condition.
+    EventProperty {
+        /// The value at the key of the condition, if any.
+        value: Option<FlattenedJsonValue>,
+    },
+
+    /// The values compared by a `thread_subscription` condition.
+    #[cfg(feature = "unstable-msc4306")]
+    ThreadSubscription {
+        /// The ID of the thread root, if the event is in a thread.
+        thread_root: Option<OwnedEventId>,
+
+        /// Whether the user is subscribed to the thread.
+        ///
+        /// This is `None` if the event is not in a thread or if the context can't check thread
+        /// subscriptions.
+        is_subscribed: Option<bool>,
+    },
+
+    /// The condition is unknown, so no values were compared.
+    Unknown,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use assert_matches2::assert_matches;
+    use js_int::{int, uint};
+    use macro_rules_attribute::apply;
+    use serde_json::{Value as JsonValue, json};
+    use smol_macros::test;
+
+    use super::{ComparedValues, PushRuleSkipReason};
+    use crate::{
+        owned_room_id, owned_user_id,
+        power_levels::NotificationPowerLevels,
+        push::{
+            FlattenedJson, PredefinedOverrideRuleId, PredefinedUnderrideRuleId,
+            PushConditionPowerLevelsCtx, PushConditionRoomCtx, RuleKind, Ruleset,
+        },
+        room_version_rules::{AuthorizationRules, RoomPowerLevelsRules},
+        serde::Raw,
+        user_id,
+    };
+
+    fn context() -> PushConditionRoomCtx {
+        PushConditionRoomCtx::new(
+            owned_room_id!("!far_west:server.name"),
+            uint!(100),
+            owned_user_id!("@jj:server.name"),
+            "Jolly Jumper".into(),
+        )
+        .with_power_levels(Some(PushConditionPowerLevelsCtx::new(
+            BTreeMap::new(),
+            int!(0),
+            NotificationPowerLevels { room: int!(50) },
+            RoomPowerLevelsRules::new(&AuthorizationRules::V1, None),
+        )))
+    }
+
+    fn raw(json: JsonValue) -> Raw<JsonValue> {
+        Raw::new(&json).unwrap()
+    }
+
+    #[apply(test!)]
+    async fn explain_matches_get_match() {
+        let set = Ruleset::server_default(user_id!("@jj:server.name"));
+        let context = context();
+
+        let events = [
+            json!({ "type": "m.room.message", "sender": "@rantanplan:server.name" }),
+            json!({
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": {
+                    "body": "@room Attention please!",
+                    "m.mentions": { "room": true },
+                },
+            }),
+            json!({
+                "type": "m.room.message",
+                "sender": "@rantanplan:server.name",
+                "content": { "msgtype": "m.notice", "body": "Notice" },
+            }),
+            json!({ "type": "m.room.message", "sender": "@jj:server.name" }),
+            json!({}),
+        ];
+
+        for event in events {
+            let event = raw(event);
+            let explanation = set.explain(&FlattenedJson::from_raw(&event), &context).await;
+
+            assert_eq!(explanation.rules.len(), set.iter().count());
+            assert_eq!(
+                explanation.matched_rule().map(|rule| rule.rule.rule_id()),
+                set.get_match(&event, &context).await.map(|rule| rule.rule_id())
+            );
+            assert_eq!(
+                format!("{:?}", explanation.actions()),
+                format!("{:?}", set.get_actions(&event, &context).await)
+            );
+        }
+    }
+
+    #[apply(test!)]
+    async fn explain_conditions() {
+        let mut set = Ruleset::server_default(user_id!("@jj:server.name"));
+        set.set_enabled(RuleKind::Override, PredefinedOverrideRuleId::SuppressNotices, false)
+            .unwrap();
+        let context = context();
+
+        let notice = FlattenedJson::from_raw(&raw(json!({
+            "type": "m.room.message",
+            "sender": "@rantanplan:server.name",
+            "content": {
+                "msgtype": "m.notice",
+                "body": "Notice",
+                "m.mentions": { "room": true },
+            },
+        })));
+        let explanation = set.explain(&notice, &context).await;
+
+        // The disabled rule would have matched.
+        let suppress_notices = explanation
+            .rules
+            .iter()
+            .find(|rule| rule.rule.rule_id() == PredefinedOverrideRuleId::SuppressNotices.as_ref())
+            .unwrap();
+        assert!(!suppress_notices.enabled());
+        assert_eq!(suppress_notices.skip_reason, Some(PushRuleSkipReason::Disabled));
+        assert_eq!(suppress_notices.conditions.len(), 1);
+        assert!(suppress_notices.conditions[0].passed);
+        assert!(!suppress_notices.matches());
+
+        // The room mention rule fails because of the sender's power level.
+        let room_mention = explanation
+            .rules
+            .iter()
+            .find(|rule| rule.rule.rule_id() == PredefinedOverrideRuleId::IsRoomMention.as_ref())
+            .unwrap();
+        assert_eq!(room_mention.skip_reason, None);
+        assert!(room_mention.conditions[0].passed);
+        assert!(!room_mention.conditions[1].passed);
+        assert_matches!(
+            &room_mention.conditions[1].compared_values,
+            ComparedValues::SenderNotificationPermission {
+                sender_power_level: Some(sender_power_level),
+                required_power_level: Some(required_power_level),
+                sender_is_privileged_creator: false,
+            }
+        );
+        assert_eq!(*sender_power_level, int!(0));
+        assert_eq!(*required_power_level, int!(50));
+
+        // The message rule matches.
+        let matched_rule = explanation.matched_rule().unwrap();
+        assert_eq!(matched_rule.rule.rule_id(), PredefinedUnderrideRuleId::Message.as_ref());
+        assert_matches!(
+            &matched_rule.conditions[0].compared_values,
+            ComparedValues::EventMatch { value: Some(value) }
+        );
+        assert_eq!(value, "m.room.message");
+    }
+}
@@ -0,0 +1,299 @@
+//! Extraction of the parts of the body of an event that trigger push rules.
+
+use std::ops::Range;
+
+use super::{
+    FlattenedJson, FlattenedJsonValue, PredefinedOverrideRuleId, PushCondition,
+    PushConditionRoomCtx, Ruleset, ScalarJsonValue, condition::find_word_matches,
+};
+
+/// The path of the body of an event in a [`FlattenedJson`].
+const BODY_PATH: &str = "content.body";
+
+/// The keyword used to mention the whole room in the body of an event.
+const ROOM_MENTION_KEYWORD: &str = "@room";
+
+impl Ruleset {
+    /// Find the parts of the body of the event that match the push rules of this ruleset.
+    ///
+    /// This allows clients to highlight the parts of the body that triggered a notification. The
+    /// returned spans are sorted by their start and can overlap.
+    ///
+    /// Only the rules that are not skipped during evaluation are taken into account. For example,
+    /// the legacy mention rules are ignored if the event has `m.mentions`.
+    ///
+    /// The spans are found for:
+    ///
+    /// * The patterns of the enabled `content` rules.
+    /// * The display name of the user, if an enabled rule has a
+    ///   [`PushCondition::ContainsDisplayName`] condition.
+    /// * The display name and the ID of the user, if the user is mentioned in the `m.mentions` of
+    ///   the event and the `.m.rule.is_user_mention` rule is enabled.
+    /// * The `@room` keyword, if the room is mentioned in the `m.mentions` of the event and the
+    ///   `.m.rule.is_room_mention` rule is enabled, or if the event doesn't have `m.mentions` and
+    ///   the legacy `.m.rule.roomnotif` rule is enabled.
+    ///
+    /// Returns an empty list if the event doesn't have a body or was sent by the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the room at the time of the event.
+    pub fn highlights(
+        &self,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> Vec<HighlightSpan> {
+        let Some(body) = event.get_str(BODY_PATH) else {
+            return Vec::new();
+        };
+
+        if event.get_str("sender").is_some_and(|sender| sender == context.user_id) {
+            return Vec::new();
+        }
+
+        let mut spans = Vec::new();
+        let mut push_spans = |pattern: &str, kind: HighlightKind| {
+            spans.extend(
+                find_word_matches(body, pattern)
+                    .into_iter()
+                    .map(|range| HighlightSpan { range, kind: kind.clone() }),
+            );
+        };
+
+        for rule in &self.content {
+            if rule.skip_reason(event).is_none() {
+                push_spans(
+                    &rule.pattern,
+                    HighlightKind::Keyword { rule_id: rule.rule_id.to_string() },
+                );
+            }
+        }
+
+        let has_display_name_rule = self.override_.iter().chain(&self.underride).any(|rule| {
+            #[allow(deprecated)]
+            let is_display_name_condition =
+                |condition: &PushCondition| matches!(condition, PushCondition::ContainsDisplayName);
+
+            rule.skip_reason(event, context).is_none()
+                && rule.conditions.iter().any(is_display_name_condition)
+        });
+        if has_display_name_rule {
+            push_spans(&context.user_display_name, HighlightKind::DisplayName);
+        }
+
+        if event.contains_mentions() {
+            let is_user_mention = event
+                .get(r"content.m\.mentions.user_ids")
+                .and_then(FlattenedJsonValue::as_array)
+                .is_some_and(|user_ids| {
+                    user_ids.contains(&ScalarJsonValue::String(context.user_id.to_string()))
+                });
+            if is_user_mention
+                && self.is_active(PredefinedOverrideRuleId::IsUserMention, event, context)
+            {
+                push_spans(&context.user_display_name, HighlightKind::UserMention);
+                push_spans(context.user_id.as_str(), HighlightKind::UserMention);
+            }
+
+            let is_room_mention = event
+                .get(r"content.m\.mentions.room")
+                .and_then(FlattenedJsonValue::as_bool)
+                .unwrap_or(false);
+            if is_room_mention
+                && self.is_active(PredefinedOverrideRuleId::IsRoomMention, event, context)
+            {
+                push_spans(ROOM_MENTION_KEYWORD, HighlightKind::RoomMention);
+            }
+        } else if self.is_active(
+            #[allow(deprecated)]
+            PredefinedOverrideRuleId::RoomNotif,
+            event,
+            context,
+        ) {
+            push_spans(ROOM_MENTION_KEYWORD, HighlightKind::RoomMention);
+        }
+
+        spans.sort_by_key(|span| (span.range.start, span.range.end));
+        spans
+    }
+
+    /// Whether the given predefined override rule is in this ruleset and is not skipped for the
+    /// given event.
+    fn is_active(
+        &self,
+        rule_id: PredefinedOverrideRuleId,
+        event: &FlattenedJson,
+        context: &PushConditionRoomCtx,
+    ) -> bool {
+        self.override_
+            .get(rule_id.as_str())
+            .is_some_and(|rule| rule.skip_reason(event, context).is_none())
+    }
+}
+
+/// A part of the body of an event that matches a push rule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct HighlightSpan {
+    /// The byte range of the span in the body of the event.
+    pub range: Range<usize>,
+
+    /// The kind of match.
+    pub kind: HighlightKind,
+}
+
+/// The kind of a [`HighlightSpan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum HighlightKind {
+    /// The pattern of a `content` rule.
+    Keyword {
+        /// The ID of the rule.
+        rule_id: String,
+    },
+
+    /// The display name of the user, matched by a [`PushCondition::ContainsDisplayName`]
+    /// condition.
+    DisplayName,
+
+    /// The display name or the ID of the user, in an event that mentions the user in its
+    /// `m.mentions`.
+    UserMention,
+
+    /// The `@room` keyword.
+    RoomMention,
+}
+
+#[cfg(test)]
+mod tests {
+    use js_int::uint;
+    use serde_json::json;
+
+    use super::{HighlightKind, HighlightSpan};
+    use crate::{
+        owned_room_id, owned_user_id,
+        push::{
+            ConditionalPushRule, FlattenedJson, NewPatternedPushRule, NewPushRule,
+            PatternedPushRule, PredefinedContentRuleId, PredefinedOverrideRuleId,
+            PushConditionRoomCtx, Ruleset,
+        },
+        user_id,
+    };
+
+    fn context() -> PushConditionRoomCtx {
+        PushConditionRoomCtx::new(
+            owned_room_id!("!room:server.name"),
+            uint!(3),
+            owned_user_id!("@jj:server.name"),
+            "Jolly Jumper".into(),
+        )
+    }
+
+    fn span(range: std::ops::Range<usize>, kind: HighlightKind) -> HighlightSpan {
+        HighlightSpan { range, kind }
+    }
+
+    #[test]
+    fn keywords_and_mentions() {
+        let mut set = Ruleset::server_default(user_id!("@jj:server.name"));
+        set.insert(
+            NewPushRule::Content(NewPatternedPushRule::new(
+                "horse".into(),
+                "h?rses".into(),
+                vec![].into(),
+            )),
+            None,
+            None,
+        )
+        .unwrap();
+
+        let body = "Hey jolly jumper, HORSES and horses, @room!";
+        let event = FlattenedJson::from_value(json!({
+            "sender": "@lucky_luke:server.name",
+            "content": {
+                "body": body,
+                "m.mentions": {
+                    "user_ids": ["@jj:server.name"],
+                    "room": true,
+                },
+            },
+        }));
+
+        let spans = set.highlights(&event, &context());
+        let keyword = HighlightKind::Keyword { rule_id: "horse".to_owned() };
+        assert_eq!(
+            spans,
+            [
+                span(4..16, HighlightKind::UserMention),
+                span(18..24, keyword.clone()),
+                span(29..35, keyword),
+                span(37..42, HighlightKind::RoomMention),
+            ]
+        );
+        assert_eq!(&body[4..16], "jolly jumper");
+
+        // With empty intentional mentions, only the keywords of user-defined rules match, the
+        // legacy mention rules are skipped. The ranges are in bytes.
+        let event = FlattenedJson::from_value(json!({
+            "sender": "@lucky_luke:server.name",
+            "content": {
+                "body": "Ö jolly jumper jj, hörses horses @room",
+                "m.mentions": {},
+            },
+        }));
+        let keyword = HighlightKind::Keyword { rule_id: "horse".to_owned() };
+        let spans = set.highlights(&event, &context());
+        assert_eq!(spans, [span(20..27, keyword.clone()), span(28..34, keyword)]);
+    }
+
+    #[test]
+    fn legacy_mentions() {
+        // The legacy mention rules are not in the server-default rules anymore.
+        let mut set = Ruleset::server_default(user_id!("@jj:server.name"));
+        #[allow(deprecated)]
+        set.override_.insert(ConditionalPushRule::contains_display_name());
+        #[allow(deprecated)]
+        set.override_.insert(ConditionalPushRule {
+            rule_id: PredefinedOverrideRuleId::RoomNotif.as_str().into(),
+            ..ConditionalPushRule::contains_display_name()
+        });
+        set.content.insert(PatternedPushRule {
+            actions: vec![].into(),
+            default: true,
+            enabled: true,
+            rule_id: PredefinedContentRuleId::ContainsUserName.as_str().into(),
+            pattern: "jj".into(),
+        });
+
+        // Without intentional mentions, the legacy mention rules apply.
+        let event = FlattenedJson::from_value(json!({
+            "sender": "@lucky_luke:server.name",
+            "content": {
+                "body": "Hey Jolly Jumper, jj, @room",
+            },
+        }));
+        let spans = set.highlights(&event, &context());
+        assert_eq!(
+            spans,
+            [
+                span(4..16, HighlightKind::DisplayName),
+                span(
+                    18..20,
+                    HighlightKind::Keyword { rule_id: ".m.rule.contains_user_name".to_owned() }
+                ),
+                span(22..27, HighlightKind::RoomMention),
+            ]
+        );
+
+        // With intentional mentions, the legacy mention rules are skipped.
+        let event = FlattenedJson::from_value(json!({
+            "sender": "@lucky_luke:server.name",
+            "content": {
+                "body": "Hey Jolly Jumper, jj, @room",
+                "m.mentions": {},
+            },
+        }));
+        assert_eq!(set.highlights(&event, &context()), []);
+    }
+}
@@ -95,6 +95,19 @@ impl AnyPushRule {
     pub async fn applies(&self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
         self.as_ref().applies(event, context).await
     }
+
+    /// Check synchronously if the push rule applies to the event.
+    ///
+    /// This is the same as [`AnyPushRule::applies()`], except that the conditions are evaluated
+    /// with [`PushCondition::applies_sync()`](super::PushCondition::applies_sync).
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the room at the time of the event.
+    pub fn applies_sync(&self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
+        self.as_ref().applies_sync(event, context)
+    }
 }
 
 /// Iterator type for `Ruleset`
@@ -264,16 +277,41 @@ impl<'a> AnyPushRuleRef<'a> {
     /// * `event` - The flattened JSON representation of a room message event.
     /// * `context` - The context of the room at the time of the event.
     pub async fn applies(self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
+        match self.pre_check(event, context) {
+            PreCheck::Done(applies) => applies,
+            PreCheck::CheckConditions(rule) => rule.applies(event, context).await,
+        }
+    }
+
+    /// Check synchronously if the push rule applies to the event.
+    ///
+    /// This is the same as [`AnyPushRuleRef::applies()`], except that the conditions are
+    /// evaluated with [`PushCondition::applies_sync()`](super::PushCondition::applies_sync).
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The flattened JSON representation of a room message event.
+    /// * `context` - The context of the room at the time of the event.
+    pub fn applies_sync(self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> bool {
+        match self.pre_check(event, context) {
+            PreCheck::Done(applies) => applies,
+            PreCheck::CheckConditions(rule) => rule.applies_sync(event, context),
+        }
+    }
+
+    /// Check the parts of the push rule that don't require evaluating push conditions.
+    fn pre_check(self, event: &FlattenedJson, context: &PushConditionRoomCtx) -> PreCheck<'a> {
         if event.get_str("sender").is_some_and(|sender| sender == context.user_id) {
-            return false;
+            return PreCheck::Done(false);
         }
 
-        match self {
-            Self::Override(rule) => rule.applies(event, context).await,
-            Self::Underride(rule) => rule.applies(event, context).await,
-            Self::Content(rule) => rule.applies_to("content.body", event, context),
+        let applies = match self {
+            Self::Override(rule) | Self::Underride(rule) => {
+                return PreCheck::CheckConditions(rule);
+            }
             #[cfg(feature = "unstable-msc4306")]
-            Self::PostContent(rule) => rule.applies(event, context).await,
+            Self::PostContent(rule) => return PreCheck::CheckConditions(rule),
+            Self::Content(rule) => rule.applies_to("content.body", event, context),
             Self::Room(rule) => {
                 rule.enabled
                     && condition::check_event_match(
@@ -287,10 +325,21 @@ impl<'a> AnyPushRuleRef<'a> {
                 rule.enabled
                     && condition::check_event_match(event, "sender", rule.rule_id.as_ref(), context)
             }
-        }
+        };
+
+        PreCheck::Done(applies)
     }
 }
 
+/// The result of [`AnyPushRuleRef::pre_check()`].
+enum PreCheck<'a> {
+    /// Whether the push rule applies to the event.
+    Done(bool),
+
+    /// The push rule applies to the event if its conditions apply.
+    CheckConditions(&'a ConditionalPushRule),
+}
+
 /// Iterator type for `Ruleset`
 #[derive(Debug)]
 pub struct RulesetIter<'a> {
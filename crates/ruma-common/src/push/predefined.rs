@@ -3,6 +3,7 @@
 //! [predefined push rules]: https://spec.matrix.org/latest/client-server-api/#predefined-rules
 
 use ruma_macros::StringEnum;
+use serde_json::to_value as to_json_value;
 
 use super::{
     Action::*, ConditionalPushRule, PushCondition::*, RoomMemberCountIs, RuleKind, Ruleset, Tweak,
@@ -68,44 +69,109 @@ impl Ruleset {
     ///
     /// The default rules in this ruleset that are not in the new server-default rules are removed.
     ///
+    /// Use [`Ruleset::update_server_default()`] to also update the actions that were not customized
+    /// by the user, and get a report of the changes.
+    ///
     /// # Parameters
     ///
     /// - `server_default`: the new server-default push rules. This ruleset must not contain
     ///   non-default rules.
-    pub fn update_with_server_default(&mut self, mut new_server_default: Ruleset) {
-        // Copy the default rules states from the old rules to the new rules and remove the
-        // server-default rules from the old rules.
+    pub fn update_with_server_default(&mut self, new_server_default: Ruleset) {
+        self.update_server_default_inner(None, new_server_default);
+    }
+
+    /// Update this ruleset with the given server-default push rules, and return the changes that
+    /// were made.
+    ///
+    /// This will replace the server-default rules in this ruleset (with `default` set to `true`)
+    /// with the given ones while keeping the `enabled` field in the same state, since it can be
+    /// changed by the user. The `actions` field is kept in the same state only if it was
+    /// customized by the user, which means that it differs from the actions of the rule in the
+    /// previous server-default rules. New server-default rules are added with their default state.
+    ///
+    /// The default rules in this ruleset that are not in the new server-default rules are removed.
+    ///
+    /// This should be used to migrate the stored push rules of users when the server-default push
+    /// rules change, for example with [`Ruleset::server_default()`].
+    ///
+    /// # Parameters
+    ///
+    /// - `old_server_default`: the previous server-default push rules, that were used to create or
+    ///   update this ruleset.
+    /// - `new_server_default`: the new server-default push rules. This ruleset must not contain
+    ///   non-default rules.
+    pub fn update_server_default(
+        &mut self,
+        old_server_default: &Ruleset,
+        new_server_default: Ruleset,
+    ) -> ServerDefaultRulesChanges {
+        self.update_server_default_inner(Some(old_server_default), new_server_default)
+    }
+
+    /// Update this ruleset with the given server-default push rules, and return the changes that
+    /// were made.
+    ///
+    /// If `old_server_default` is `None`, the actions of all the rules are considered to be
+    /// customized.
+    fn update_server_default_inner(
+        &mut self,
+        old_server_default: Option<&Ruleset>,
+        mut new_server_default: Ruleset,
+    ) -> ServerDefaultRulesChanges {
+        let mut changes = ServerDefaultRulesChanges::default();
+
+        // Copy the default rules states from the old rules to the new rules, remove the
+        // server-default rules from the old rules and record the changes.
         macro_rules! copy_rules_state {
-            ($new_ruleset:ident, $old_ruleset:ident, @fields $($field_name:ident),+) => {
-                $(
-                    $new_ruleset.$field_name = $new_ruleset
-                        .$field_name
-                        .into_iter()
-                        .map(|mut new_rule| {
-                            if let Some(old_rule) =
-                                $old_ruleset.$field_name.shift_take(new_rule.rule_id.as_str())
-                            {
-                                new_rule.enabled = old_rule.enabled;
-                                new_rule.actions = old_rule.actions;
+            ($field_name:ident, $kind:expr) => {
+                new_server_default.$field_name = new_server_default
+                    .$field_name
+                    .into_iter()
+                    .map(|mut new_rule| {
+                        let rule_id = new_rule.rule_id.to_string();
+
+                        if let Some(old_rule) = self.$field_name.shift_take(rule_id.as_str()) {
+                            new_rule.enabled = old_rule.enabled;
+
+                            // Keep the actions only if they were customized by the user.
+                            let has_custom_actions = old_server_default.is_none_or(|ruleset| {
+                                ruleset.$field_name.get(rule_id.as_str()).is_none_or(|rule| {
+                                    to_json_value(&rule.actions).ok()
+                                        != to_json_value(&old_rule.actions).ok()
+                                })
+                            });
+                            if has_custom_actions {
+                                new_rule.actions = old_rule.actions.clone();
                             }
 
-                            new_rule
-                        })
-                        .collect();
-                )+
-            };
-        }
-        copy_rules_state!(new_server_default, self, @fields override_, content, room, sender, underride);
-
-        // Remove the remaining server-default rules from the old rules.
-        macro_rules! remove_remaining_default_rules {
-            ($ruleset:ident, @fields $($field_name:ident),+) => {
-                $(
-                    $ruleset.$field_name.retain(|rule| !rule.default);
-                )+
+                            if to_json_value(&new_rule).ok() != to_json_value(&old_rule).ok() {
+                                changes.updated.push(($kind, rule_id));
+                            }
+                        } else {
+                            changes.added.push(($kind, rule_id));
+                        }
+
+                        new_rule
+                    })
+                    .collect();
+
+                // The remaining server-default rules are not server-default rules anymore.
+                self.$field_name.retain(|rule| {
+                    if rule.default {
+                        changes.removed.push(($kind, rule.rule_id.to_string()));
+                    }
+
+                    !rule.default
+                });
             };
         }
-        remove_remaining_default_rules!(self, @fields override_, content, room, sender, underride);
+        copy_rules_state!(override_, RuleKind::Override);
+        copy_rules_state!(content, RuleKind::Content);
+        #[cfg(feature = "unstable-msc4306")]
+        copy_rules_state!(postcontent, RuleKind::PostContent);
+        copy_rules_state!(room, RuleKind::Room);
+        copy_rules_state!(sender, RuleKind::Sender);
+        copy_rules_state!(underride, RuleKind::Underride);
 
         // `.m.rule.master` comes before all other push rules, while the other server-default push
         // rules come after.
@@ -125,6 +191,38 @@ impl Ruleset {
             };
         }
         merge_rules!(self, new_server_default, @fields override_, content, room, sender, underride);
+        #[cfg(feature = "unstable-msc4306")]
+        merge_rules!(self, new_server_default, @fields postcontent);
+
+        changes
+    }
+}
+
+/// The changes made to the server-default push rules of a [`Ruleset`] by
+/// [`Ruleset::update_server_default()`].
+///
+/// The rules are identified by their kind and ID.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct ServerDefaultRulesChanges {
+    /// The server-default rules that were added.
+    pub added: Vec<(RuleKind, String)>,
+
+    /// The server-default rules whose definition was updated.
+    ///
+    /// Changes of the `enabled` field and of customized actions are not included, since they are
+    /// preserved.
+    pub updated: Vec<(RuleKind, String)>,
+
+    /// The server-default rules that were removed because they are not server-default rules
+    /// anymore.
+    pub removed: Vec<(RuleKind, String)>,
+}
+
+impl ServerDefaultRulesChanges {
+    /// Whether no server-default rules were changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
     }
 }
 
@@ -770,7 +868,7 @@ mod tests {
 
     use super::PredefinedOverrideRuleId;
     use crate::{
-        push::{Action, ConditionalPushRule, ConditionalPushRuleInit, Ruleset},
+        push::{Action, ConditionalPushRule, ConditionalPushRuleInit, RuleKind, Ruleset},
         user_id,
     };
 
@@ -832,4 +930,89 @@ mod tests {
         assert!(member_event_rule.enabled);
         assert_eq!(member_event_rule.actions.len(), 0);
     }
+
+    #[test]
+    fn update_server_default_changes() {
+        let user_id = user_id!("@user:localhost");
+        let mut ruleset = Ruleset::server_default(user_id);
+
+        // Stale server-default rules.
+        ruleset.override_.shift_remove(PredefinedOverrideRuleId::Tombstone.as_str());
+        ruleset.override_.replace(assign!(ConditionalPushRule::suppress_notices(), {
+            conditions: Default::default(),
+            enabled: false,
+        }));
+        ruleset.override_.insert(ConditionalPushRule::contains_display_name());
+
+        let changes = ruleset.update_server_default(
+            &Ruleset::server_default(user_id),
+            Ruleset::server_default(user_id),
+        );
+
+        assert_eq!(
+            changes.added,
+            [(RuleKind::Override, PredefinedOverrideRuleId::Tombstone.as_str().to_owned())]
+        );
+        assert_eq!(
+            changes.updated,
+            [(RuleKind::Override, PredefinedOverrideRuleId::SuppressNotices.as_str().to_owned())]
+        );
+        assert_eq!(
+            changes.removed,
+            [(RuleKind::Override, ".m.rule.contains_display_name".to_owned())]
+        );
+
+        // The conditions were updated but the rule is still disabled.
+        let suppress_notices =
+            ruleset.override_.get(PredefinedOverrideRuleId::SuppressNotices.as_str()).unwrap();
+        assert!(!suppress_notices.enabled);
+        assert!(!suppress_notices.conditions.is_empty());
+
+        // Nothing changes the second time.
+        let changes = ruleset.update_server_default(
+            &Ruleset::server_default(user_id),
+            Ruleset::server_default(user_id),
+        );
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn update_server_default_actions() {
+        let user_id = user_id!("@user:localhost");
+        let old_server_default = Ruleset::server_default(user_id);
+
+        // The user customized the actions of the tombstone rule.
+        let mut ruleset = old_server_default.clone();
+        ruleset.override_.replace(assign!(ConditionalPushRule::tombstone(), {
+            actions: vec![].into(),
+        }));
+
+        // The actions of the tombstone and member event rules changed.
+        let mut new_server_default = old_server_default.clone();
+        new_server_default.override_.replace(assign!(ConditionalPushRule::tombstone(), {
+            actions: vec![Action::Notify].into(),
+        }));
+        new_server_default.override_.replace(assign!(ConditionalPushRule::member_event(), {
+            actions: vec![Action::Notify].into(),
+        }));
+
+        let changes = ruleset.update_server_default(&old_server_default, new_server_default);
+
+        assert!(changes.added.is_empty());
+        assert_eq!(
+            changes.updated,
+            [(RuleKind::Override, PredefinedOverrideRuleId::MemberEvent.as_str().to_owned())]
+        );
+        assert!(changes.removed.is_empty());
+
+        // The new default actions are used if the actions were not customized.
+        let member_event =
+            ruleset.override_.get(PredefinedOverrideRuleId::MemberEvent.as_str()).unwrap();
+        assert_matches!(member_event.actions.as_slice(), [Action::Notify]);
+
+        // The customized actions are kept.
+        let tombstone =
+            ruleset.override_.get(PredefinedOverrideRuleId::Tombstone.as_str()).unwrap();
+        assert!(tombstone.actions.is_empty());
+    }
 }
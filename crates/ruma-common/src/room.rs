@@ -277,6 +277,7 @@ impl From<JoinRuleKind> for JoinRuleSummary {
 
 /// The summary of a room's state.
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(crate::api::openapi::DescribeSchema))]
 #[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
 pub struct RoomSummary {
     /// The ID of the room.
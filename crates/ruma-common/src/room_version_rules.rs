@@ -51,6 +51,13 @@ pub struct RoomVersionRules {
 
     /// The tweaks for verifying the event format.
     pub event_format: EventFormatRules,
+
+    /// Whether the room version supports [extensible events] ([MSC3932]).
+    ///
+    /// [extensible events]: https://github.com/matrix-org/matrix-spec-proposals/pull/1767
+    /// [MSC3932]: https://github.com/matrix-org/matrix-spec-proposals/pull/3932
+    #[cfg(feature = "unstable-msc3932")]
+    pub extensible_events: bool,
 }
 
 impl RoomVersionRules {
@@ -68,6 +75,8 @@ impl RoomVersionRules {
         redaction: RedactionRules::V1,
         signatures: SignaturesRules::V1,
         event_format: EventFormatRules::V1,
+        #[cfg(feature = "unstable-msc3932")]
+        extensible_events: false,
     };
 
     /// Rules for [room version 2].
@@ -156,6 +165,19 @@ impl RoomVersionRules {
         redaction: RedactionRules::MSC2870,
         ..Self::V11
     };
+
+    /// Rules for room version `org.matrix.msc1767.10` ([MSC1767]).
+    ///
+    /// This is room version 10 with support for [extensible events].
+    ///
+    /// [MSC1767]: https://github.com/matrix-org/matrix-spec-proposals/pull/1767
+    /// [extensible events]: https://github.com/matrix-org/matrix-spec-proposals/pull/1767
+    #[cfg(feature = "unstable-msc1767")]
+    pub const MSC1767: Self = Self {
+        disposition: RoomVersionDisposition::Unstable,
+        extensible_events: true,
+        ..Self::V10
+    };
 }
 
 /// The stability of a room version.
@@ -1,8 +1,12 @@
 //! Helpers for tests
+//!
+//! The helpers to load and round-trip JSON examples are hidden from the documentation, they are
+//! only meant to be used by the tests of the ruma crates.
 
-use std::fmt::Debug;
+use std::{collections::BTreeSet, fmt, fmt::Debug, fs, path::Path};
 
 use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value as JsonValue, from_slice as from_json_slice};
 
 /// Assert that serialization of `de` results in `se` and deserialization of `se` results in `de`.
 pub fn serde_json_eq<T>(de: T, se: serde_json::Value)
@@ -12,3 +16,286 @@ where
     assert_eq!(se, serde_json::to_value(de.clone()).unwrap());
     assert_eq!(de, serde_json::from_value(se).unwrap());
 }
+
+/// Load the JSON example at the given path, relative to the `fixtures` directory.
+///
+/// Like in the examples of the Matrix specification, an object can reference another example with
+/// a `$ref` field containing its path, relative to the directory of the current example. The
+/// fields of the referenced example are merged into the object, the fields of the object taking
+/// precedence.
+///
+/// The fixtures directory should be built from the `CARGO_MANIFEST_DIR` environment variable, so
+/// the tests don't depend on the working directory, for example with
+/// `concat!(env!("CARGO_MANIFEST_DIR"), "/tests/it/fixtures")`.
+///
+/// # Panics
+///
+/// Panics if an example can't be read or is not valid JSON.
+#[doc(hidden)]
+pub fn load_json_example(fixtures: impl AsRef<Path>, path: &str) -> JsonValue {
+    load_json_example_file(&fixtures.as_ref().join(path))
+}
+
+fn load_json_example_file(path: &Path) -> JsonValue {
+    let bytes = fs::read(path)
+        .unwrap_or_else(|error| panic!("failed to read example `{}`: {error}", path.display()));
+    let mut example = from_json_slice(&bytes)
+        .unwrap_or_else(|error| panic!("failed to parse example `{}`: {error}", path.display()));
+
+    resolve_json_example_refs(&mut example, path.parent().unwrap_or(Path::new("")));
+    example
+}
+
+/// Replace the `$ref` fields in the given value by the fields of the referenced examples.
+fn resolve_json_example_refs(value: &mut JsonValue, dir: &Path) {
+    match value {
+        JsonValue::Object(object) => {
+            if let Some(JsonValue::String(reference)) = object.remove("$ref") {
+                let JsonValue::Object(referenced) = load_json_example_file(&dir.join(reference))
+                else {
+                    panic!("`$ref` in example must point to a JSON object");
+                };
+
+                for (key, referenced_value) in referenced {
+                    object.entry(key).or_insert(referenced_value);
+                }
+            }
+
+            for value in object.values_mut() {
+                resolve_json_example_refs(value, dir);
+            }
+        }
+        JsonValue::Array(array) => {
+            for value in array {
+                resolve_json_example_refs(value, dir);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A difference between a JSON value and the same value after a round-trip through a Rust type.
+///
+/// Each variant contains the [JSON pointer] of the affected value.
+///
+/// [JSON pointer]: https://datatracker.ietf.org/doc/html/rfc6901
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[allow(clippy::exhaustive_enums)]
+pub enum JsonDifference {
+    /// The value is present in the original JSON but was dropped during the round-trip.
+    ///
+    /// This is usually a field that is not supported by the Rust type.
+    Dropped(String),
+
+    /// The value is not present in the original JSON but was added during the round-trip.
+    Added(String),
+
+    /// The value is present in both JSON values but is different.
+    Changed(String),
+}
+
+impl JsonDifference {
+    /// The JSON pointer of the value affected by this difference.
+    pub fn pointer(&self) -> &str {
+        match self {
+            Self::Dropped(pointer) | Self::Added(pointer) | Self::Changed(pointer) => pointer,
+        }
+    }
+}
+
+impl fmt::Display for JsonDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dropped(pointer) => write!(f, "dropped `{pointer}`"),
+            Self::Added(pointer) => write!(f, "added `{pointer}`"),
+            Self::Changed(pointer) => write!(f, "changed `{pointer}`"),
+        }
+    }
+}
+
+/// Compute the differences between the `original` JSON value and the `round_tripped` JSON value.
+///
+/// Objects are compared field by field, and arrays are compared item by item, so the differences
+/// point to the deepest values that differ.
+#[doc(hidden)]
+pub fn json_differences(original: &JsonValue, round_tripped: &JsonValue) -> Vec<JsonDifference> {
+    let mut differences = Vec::new();
+    collect_json_differences(String::new(), original, round_tripped, &mut differences);
+    differences
+}
+
+fn collect_json_differences(
+    pointer: String,
+    original: &JsonValue,
+    round_tripped: &JsonValue,
+    differences: &mut Vec<JsonDifference>,
+) {
+    match (original, round_tripped) {
+        (JsonValue::Object(original), JsonValue::Object(round_tripped)) => {
+            for (key, original_value) in original {
+                let pointer = format!("{pointer}/{}", escape_json_pointer_token(key));
+
+                match round_tripped.get(key) {
+                    Some(value) => {
+                        collect_json_differences(pointer, original_value, value, differences);
+                    }
+                    None => differences.push(JsonDifference::Dropped(pointer)),
+                }
+            }
+
+            for key in round_tripped.keys().filter(|key| !original.contains_key(*key)) {
+                differences.push(JsonDifference::Added(format!(
+                    "{pointer}/{}",
+                    escape_json_pointer_token(key)
+                )));
+            }
+        }
+        (JsonValue::Array(original), JsonValue::Array(round_tripped)) => {
+            for (index, original_value) in original.iter().enumerate() {
+                let pointer = format!("{pointer}/{index}");
+
+                match round_tripped.get(index) {
+                    Some(value) => {
+                        collect_json_differences(pointer, original_value, value, differences);
+                    }
+                    None => differences.push(JsonDifference::Dropped(pointer)),
+                }
+            }
+
+            for index in original.len()..round_tripped.len() {
+                differences.push(JsonDifference::Added(format!("{pointer}/{index}")));
+            }
+        }
+        (original, round_tripped) => {
+            if original != round_tripped {
+                differences.push(JsonDifference::Changed(pointer));
+            }
+        }
+    }
+}
+
+/// Escape a reference token of a JSON pointer, according to [RFC 6901].
+///
+/// [RFC 6901]: https://datatracker.ietf.org/doc/html/rfc6901#section-3
+fn escape_json_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// Assert that the `round_tripped` JSON value is the same as the `original` JSON value, except for
+/// the `known_differences`.
+///
+/// This is meant to check that a JSON value, like an example from the Matrix specification, is
+/// not altered when it is deserialized to a Rust type and serialized again. The known differences
+/// document the unsupported fields or the differences that are expected. They must all be
+/// observed, so the list is kept up-to-date when support for a field is added.
+///
+/// # Panics
+///
+/// Panics if there is a difference that is not listed in `known_differences`, or if one of the
+/// `known_differences` was not observed. `context` is included in the panic message.
+#[doc(hidden)]
+pub fn assert_json_round_trip(
+    context: &str,
+    original: &JsonValue,
+    round_tripped: &JsonValue,
+    known_differences: &[JsonDifference],
+) {
+    let differences = json_differences(original, round_tripped);
+
+    let unexpected =
+        differences.iter().filter(|d| !known_differences.contains(d)).collect::<BTreeSet<_>>();
+    let missing =
+        known_differences.iter().filter(|d| !differences.contains(d)).collect::<BTreeSet<_>>();
+
+    if unexpected.is_empty() && missing.is_empty() {
+        return;
+    }
+
+    let mut message = format!("JSON round-trip mismatch for {context}");
+
+    for difference in unexpected {
+        message.push_str(&format!("\n  unexpected: {difference}"));
+    }
+    for difference in missing {
+        message.push_str(&format!("\n  known difference not observed: {difference}"));
+    }
+
+    message.push_str(&format!("\noriginal: {original:#}\nround-tripped: {round_tripped:#}"));
+
+    panic!("{message}");
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{JsonDifference, assert_json_round_trip, json_differences};
+
+    #[test]
+    fn json_differences_pointers() {
+        let original = json!({
+            "same": 1,
+            "dropped": true,
+            "a/b~c": "slash and tilde",
+            "nested": {
+                "changed": "before",
+                "list": [1, 2, 3],
+            },
+        });
+        let round_tripped = json!({
+            "same": 1,
+            "added": null,
+            "nested": {
+                "changed": "after",
+                "list": [1, 2],
+            },
+        });
+
+        let mut differences = json_differences(&original, &round_tripped);
+        differences.sort();
+
+        assert_eq!(
+            differences,
+            [
+                JsonDifference::Dropped("/a~1b~0c".to_owned()),
+                JsonDifference::Dropped("/dropped".to_owned()),
+                JsonDifference::Dropped("/nested/list/2".to_owned()),
+                JsonDifference::Added("/added".to_owned()),
+                JsonDifference::Changed("/nested/changed".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_round_trip_known_differences() {
+        let original = json!({ "known": 1, "kept": 2 });
+        let round_tripped = json!({ "kept": 2 });
+
+        assert_json_round_trip(
+            "test",
+            &original,
+            &round_tripped,
+            &[JsonDifference::Dropped("/known".to_owned())],
+        );
+    }
+
+    #[test]
+    #[should_panic = "known difference not observed: dropped `/kept`"]
+    fn json_round_trip_stale_known_difference() {
+        let original = json!({ "kept": 2 });
+
+        assert_json_round_trip(
+            "test",
+            &original,
+            &original,
+            &[JsonDifference::Dropped("/kept".to_owned())],
+        );
+    }
+
+    #[test]
+    #[should_panic = "unexpected: changed `/value`"]
+    fn json_round_trip_unexpected_difference() {
+        assert_json_round_trip("test", &json!({ "value": 1 }), &json!({ "value": 2 }), &[]);
+    }
+}
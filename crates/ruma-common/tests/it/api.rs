@@ -7,7 +7,9 @@ mod default_status;
 mod header_override;
 mod manual_endpoint_impl;
 mod no_fields;
+mod openapi;
 mod optional_headers;
+mod raw_body;
 mod required_headers;
 mod ruma_api;
 mod ruma_api_macros;
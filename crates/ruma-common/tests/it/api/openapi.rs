@@ -0,0 +1,197 @@
+#![cfg(feature = "openapi")]
+
+use http::{Method, header::CONTENT_TYPE};
+use ruma_common::{
+    OwnedRoomId, OwnedUserId,
+    api::{
+        auth_scheme::AccessToken,
+        openapi::{DescribeSchema, OpenApi, ParameterLocation},
+        request, response,
+    },
+    metadata,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, to_value as to_json_value};
+
+metadata! {
+    method: PUT,
+    rate_limited: true,
+    authentication: AccessToken,
+    history: {
+        unstable => "/_matrix/client/unstable/org.bar.msc9000/rooms/{room_id}/thing",
+        1.1 => "/_matrix/client/v3/rooms/{room_id}/thing",
+    }
+}
+
+/// Request type for the `openapi` endpoint.
+#[request]
+pub struct Request {
+    #[ruma_api(path)]
+    pub room_id: OwnedRoomId,
+
+    #[ruma_api(query)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<js_int::UInt>,
+
+    #[ruma_api(header = CONTENT_TYPE)]
+    pub content_type: String,
+
+    #[serde(rename = "m.users")]
+    pub users: Vec<OwnedUserId>,
+
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Response type for the `openapi` endpoint.
+#[response]
+pub struct Response {
+    pub event_id: String,
+}
+
+#[test]
+fn describe_endpoint() {
+    let mut openapi = OpenApi::new("Test API", "1.0");
+    openapi.add_endpoint::<Request>();
+
+    assert_eq!(openapi.paths.len(), 2);
+
+    let unstable = openapi
+        .operation("/_matrix/client/unstable/org.bar.msc9000/rooms/{room_id}/thing", &Method::PUT)
+        .unwrap();
+    assert!(unstable.unstable);
+    assert_eq!(unstable.added_in, None);
+
+    let stable =
+        openapi.operation("/_matrix/client/v3/rooms/{room_id}/thing", &Method::PUT).unwrap();
+    assert!(!stable.unstable);
+    assert_eq!(stable.added_in.as_deref(), Some("v1.1"));
+    assert!(stable.rate_limited);
+    assert_eq!(stable.authentication, "AccessToken");
+
+    let parameters = stable
+        .parameters
+        .iter()
+        .map(|p| (p.name.as_str(), p.location, p.required))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        parameters,
+        [
+            ("room_id", ParameterLocation::Path, true),
+            ("limit", ParameterLocation::Query, false),
+            ("content-type", ParameterLocation::Header, true),
+        ]
+    );
+
+    assert_eq!(
+        to_json_value(stable.request_body.as_ref().unwrap()).unwrap(),
+        json!({
+            "content": {
+                "application/json": {
+                    "schema": {
+                        "type": "object",
+                        "properties": {
+                            "m.users": {
+                                "type": "array",
+                                "items": { "type": "string", "format": "mx-user-id" },
+                            },
+                            "dry_run": { "type": "boolean" },
+                        },
+                        "required": ["m.users"],
+                    },
+                },
+            },
+            "required": true,
+        })
+    );
+
+    assert_eq!(
+        to_json_value(&stable.responses).unwrap(),
+        json!({
+            "200": {
+                "description": "",
+                "content": {
+                    "application/json": {
+                        "schema": {
+                            "type": "object",
+                            "properties": {
+                                "event_id": { "type": "string" },
+                            },
+                            "required": ["event_id"],
+                        },
+                    },
+                },
+            },
+        })
+    );
+}
+
+#[test]
+fn registered_endpoints() {
+    let openapi = OpenApi::for_crate(module_path!(), "Test API", "1.0");
+
+    assert!(openapi.operation("/_matrix/client/v3/rooms/{room_id}/thing", &Method::PUT).is_some());
+    // Endpoints from other modules are not included.
+    assert!(!openapi.paths.keys().any(|path| path == "/_matrix/my/endpoint"));
+}
+
+/// A type used in a body.
+#[derive(Deserialize, Serialize, DescribeSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Thing {
+    pub thing_id: OwnedUserId,
+
+    #[serde(rename = "m.count")]
+    pub count: js_int::UInt,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    #[serde(flatten)]
+    pub extra: Extra,
+
+    #[serde(skip)]
+    #[allow(dead_code)]
+    pub cached: bool,
+
+    #[serde(skip_serializing)]
+    #[allow(dead_code)]
+    pub password: String,
+
+    #[serde(skip_deserializing)]
+    pub generated: bool,
+}
+
+#[derive(Deserialize, Serialize, DescribeSchema)]
+pub struct Extra {
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, DescribeSchema)]
+pub struct ThingName(String);
+
+#[test]
+fn derive_describe_schema() {
+    assert_eq!(
+        to_json_value(Thing::schema()).unwrap(),
+        json!({
+            "type": "object",
+            "properties": {
+                "thingId": { "type": "string", "format": "mx-user-id" },
+                "m.count": { "type": "integer" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "generated": { "type": "boolean" },
+            },
+            "required": ["thingId", "m.count"],
+            "allOf": [
+                {
+                    "type": "object",
+                    "properties": {
+                        "note": { "type": "string" },
+                    },
+                },
+            ],
+        })
+    );
+    assert_eq!(to_json_value(ThingName::schema()).unwrap(), json!({ "type": "string" }));
+}
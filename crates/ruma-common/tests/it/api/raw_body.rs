@@ -0,0 +1,190 @@
+#![allow(clippy::exhaustive_structs)]
+
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use assert_matches2::assert_matches;
+use bytes::Bytes;
+use http::header::CONTENT_TYPE;
+use http_body::Frame;
+use macro_rules_attribute::apply;
+use ruma_common::{
+    api::{
+        IncomingRawBodyRequest as _, IncomingRawBodyResponse as _, OutgoingRawBodyRequest as _,
+        OutgoingRawBodyResponse as _,
+        auth_scheme::{NoAuthentication, SendAccessToken},
+        collect_body,
+        error::FromHttpResponseError,
+        request, response,
+    },
+    metadata,
+};
+use smol_macros::test;
+
+metadata! {
+    method: PUT,
+    rate_limited: false,
+    authentication: NoAuthentication,
+    path: "/_matrix/my/endpoint/{file_name}",
+}
+
+/// Request type for the `raw_body` endpoint.
+#[request]
+pub struct Request {
+    #[ruma_api(path)]
+    pub file_name: String,
+
+    #[ruma_api(header = CONTENT_TYPE)]
+    pub content_type: String,
+
+    #[ruma_api(raw_body)]
+    pub file: Vec<u8>,
+}
+
+/// Response type for the `raw_body` endpoint.
+#[response]
+pub struct Response {
+    #[ruma_api(header = CONTENT_TYPE)]
+    pub content_type: String,
+
+    #[ruma_api(raw_body)]
+    pub file: Vec<u8>,
+}
+
+/// A body that returns the given chunks, one per frame.
+#[derive(Debug)]
+struct ChunkedBody(Vec<&'static [u8]>);
+
+impl http_body::Body for ChunkedBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let chunk = (!self.0.is_empty()).then(|| self.0.remove(0));
+        Poll::Ready(chunk.map(|chunk| Ok(Frame::data(Bytes::from_static(chunk)))))
+    }
+}
+
+#[apply(test!)]
+async fn request_with_body() {
+    let request = Request {
+        file_name: "file.txt".to_owned(),
+        content_type: "text/plain".to_owned(),
+        file: b"ignored".to_vec(),
+    };
+
+    let http_request = request
+        .try_into_http_request_with_body(
+            "https://homeserver.tld",
+            SendAccessToken::None,
+            (),
+            ChunkedBody(vec![b"some ", b"plain text"]),
+        )
+        .unwrap();
+    assert_eq!(http_request.uri(), "https://homeserver.tld/_matrix/my/endpoint/file.txt");
+    assert_eq!(http_request.headers().get(CONTENT_TYPE).unwrap(), "text/plain");
+
+    let (request, body) =
+        Request::try_from_http_request_with_body(http_request, &["file.txt"]).unwrap();
+    assert_eq!(request.file_name, "file.txt");
+    assert_eq!(request.content_type, "text/plain");
+    assert_eq!(request.file, b"");
+    assert_eq!(collect_body(body, usize::MAX).await.unwrap(), b"some plain text");
+}
+
+#[apply(test!)]
+async fn response_with_body() {
+    let response = Response { content_type: "text/plain".to_owned(), file: b"ignored".to_vec() };
+
+    let http_response = response
+        .try_into_http_response_with_body(ChunkedBody(vec![b"some ", b"plain text"]))
+        .unwrap();
+    assert_eq!(http_response.headers().get(CONTENT_TYPE).unwrap(), "text/plain");
+
+    let (response, body) = Response::try_from_http_response_with_body(http_response).await.unwrap();
+    assert_eq!(response.content_type, "text/plain");
+    assert_eq!(response.file, b"");
+    assert_eq!(collect_body(body, usize::MAX).await.unwrap(), b"some plain text");
+}
+
+#[apply(test!)]
+async fn error_response_with_body() {
+    let http_response = http::Response::builder()
+        .status(http::StatusCode::NOT_FOUND)
+        .header(CONTENT_TYPE, "application/json")
+        .body(ChunkedBody(vec![br#"{"errcode": "M_NOT_FOUND", "#, br#""error": "Not found"}"#]))
+        .unwrap();
+
+    let error = Response::try_from_http_response_with_body(http_response).await.unwrap_err();
+    assert_matches!(error, FromHttpResponseError::Server(error));
+    assert_eq!(error.status_code, http::StatusCode::NOT_FOUND);
+}
+
+mod borrowed {
+    use std::borrow::Cow;
+
+    use http::header::CONTENT_TYPE;
+    use ruma_common::{
+        api::{
+            IncomingRawBodyRequest as _, OutgoingRawBodyRequest as _,
+            auth_scheme::{NoAuthentication, SendAccessToken},
+            request,
+        },
+        metadata,
+    };
+
+    pub use super::Response;
+
+    metadata! {
+        @for Request<'_>,
+        method: PUT,
+        rate_limited: false,
+        authentication: NoAuthentication,
+        path: "/_matrix/my/borrowed/{file_name}",
+    }
+
+    /// Request type for the `raw_body` endpoint, with a lifetime.
+    #[request]
+    pub struct Request<'a> {
+        #[ruma_api(path)]
+        pub file_name: Cow<'a, str>,
+
+        #[ruma_api(header = CONTENT_TYPE)]
+        pub content_type: String,
+
+        #[ruma_api(raw_body)]
+        pub file: Vec<u8>,
+    }
+
+    #[test]
+    fn request_with_lifetime_and_body() {
+        let file_name = "file.txt".to_owned();
+        let request = Request {
+            file_name: Cow::Borrowed(&file_name),
+            content_type: "text/plain".to_owned(),
+            file: Vec::new(),
+        };
+
+        let http_request = request
+            .try_into_http_request_with_body(
+                "https://homeserver.tld",
+                SendAccessToken::None,
+                (),
+                b"some plain text".as_slice(),
+            )
+            .unwrap();
+        assert_eq!(http_request.uri(), "https://homeserver.tld/_matrix/my/borrowed/file.txt");
+
+        let (request, body) =
+            Request::try_from_http_request_with_body(http_request, &["file.txt"]).unwrap();
+        assert_eq!(request.file_name, "file.txt");
+        assert_eq!(request.content_type, "text/plain");
+        assert_eq!(body, b"some plain text");
+    }
+}
@@ -0,0 +1,305 @@
+//! Calculation of the display names of rooms and room members.
+//!
+//! This implements the algorithms of the Matrix specification for [calculating the display name
+//! of a room] and [calculating the display name of a user].
+//!
+//! [calculating the display name of a room]: https://spec.matrix.org/latest/client-server-api/#calculating-the-display-name-for-a-room
+//! [calculating the display name of a user]: https://spec.matrix.org/latest/client-server-api/#calculating-the-display-name-for-a-user
+
+use std::fmt;
+
+use ruma_common::{OwnedRoomAliasId, RoomAliasId, UserId};
+
+/// The display name of a room.
+///
+/// Use the `Display` implementation to get the string to present to the user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum RoomDisplayName {
+    /// The name of the room, from the `m.room.name` event.
+    Named(String),
+
+    /// The canonical alias of the room, from the `m.room.canonical_alias` event.
+    Aliased(OwnedRoomAliasId),
+
+    /// A name calculated from the heroes of the room.
+    Calculated(String),
+
+    /// The room is empty, and the name is calculated from the heroes that were in the room.
+    EmptyWas(String),
+
+    /// The room is empty and doesn't have any heroes.
+    Empty,
+}
+
+impl fmt::Display for RoomDisplayName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Named(name) | Self::Calculated(name) => f.write_str(name),
+            Self::Aliased(alias) => f.write_str(alias.as_str()),
+            Self::EmptyWas(name) => write!(f, "Empty Room (was {name})"),
+            Self::Empty => f.write_str("Empty Room"),
+        }
+    }
+}
+
+/// Calculate the display name of a room.
+///
+/// # Arguments
+///
+/// * `name` - The name of the room, from the `m.room.name` event.
+/// * `canonical_alias` - The canonical alias of the room, from the `m.room.canonical_alias` event.
+/// * `heroes` - The display names of the heroes of the room, usually from the room summary of the
+///   sync response. They should not include the current user and should be disambiguated with
+///   [`member_display_name()`].
+/// * `joined_member_count` - The number of joined members in the room, including the current user.
+/// * `invited_member_count` - The number of invited members in the room.
+pub fn room_display_name(
+    name: Option<&str>,
+    canonical_alias: Option<&RoomAliasId>,
+    heroes: &[impl AsRef<str>],
+    joined_member_count: u64,
+    invited_member_count: u64,
+) -> RoomDisplayName {
+    if let Some(name) = name.map(str::trim).filter(|name| !name.is_empty()) {
+        return RoomDisplayName::Named(name.to_owned());
+    }
+
+    if let Some(alias) = canonical_alias {
+        return RoomDisplayName::Aliased(alias.to_owned());
+    }
+
+    let member_count = joined_member_count.saturating_add(invited_member_count);
+
+    if heroes.is_empty() {
+        return RoomDisplayName::Empty;
+    }
+
+    if member_count <= 1 {
+        return RoomDisplayName::EmptyWas(join_hero_names(heroes, 0));
+    }
+
+    // The current user is not part of the heroes.
+    let remaining = (member_count - 1).saturating_sub(heroes.len() as u64);
+    RoomDisplayName::Calculated(join_hero_names(heroes, remaining))
+}
+
+/// Join the given hero names, followed by the number of remaining users.
+fn join_hero_names(heroes: &[impl AsRef<str>], remaining: u64) -> String {
+    let names = heroes.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+
+    match (names.as_slice(), remaining) {
+        ([name], 0) => (*name).to_owned(),
+        ([first, second], 0) => format!("{first} and {second}"),
+        ([init @ .., last], 0) => format!("{}, and {last}", init.join(", ")),
+        (_, 1) => format!("{}, and 1 other", names.join(", ")),
+        (_, remaining) => format!("{}, and {remaining} others", names.join(", ")),
+    }
+}
+
+/// Calculate the display name of a member of a room.
+///
+/// The display name of the member is used if it is set and not empty, otherwise the user ID is
+/// used. The display name is disambiguated by appending the user ID if it could be confused with
+/// the display name of another member, or if it looks like a user ID.
+///
+/// To prevent impersonation, display names are compared after removing invisible and
+/// bidirectional control characters, case folding and replacing common homoglyphs, and
+/// bidirectional control characters are removed from the returned display name.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the member.
+/// * `display_name` - The display name of the member, from its current `m.room.member` event.
+/// * `members` - The user IDs and display names of the joined and invited members of the room,
+///   which can include the member.
+pub fn member_display_name<'a>(
+    user_id: &UserId,
+    display_name: Option<&str>,
+    members: impl IntoIterator<Item = (&'a UserId, Option<&'a str>)>,
+) -> String {
+    let Some(display_name) = display_name.map(sanitize).filter(|name| !name.trim().is_empty())
+    else {
+        return user_id.to_string();
+    };
+
+    let skeleton = skeleton(&display_name);
+    let is_ambiguous = looks_like_user_id(&skeleton)
+        || members.into_iter().any(|(other_user_id, other_display_name)| {
+            other_user_id != user_id
+                && other_display_name.is_some_and(|other| self::skeleton(other) == skeleton)
+        });
+
+    if is_ambiguous { format!("{display_name} ({user_id})") } else { display_name }
+}
+
+/// Whether the given character is an invisible or bidirectional control character.
+fn is_invisible_or_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{061C}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}'
+    )
+}
+
+/// Remove the invisible and bidirectional control characters from the given display name.
+fn sanitize(display_name: &str) -> String {
+    display_name.chars().filter(|c| !is_invisible_or_bidi_control(*c)).collect()
+}
+
+/// Compute the skeleton of the given display name, used to detect display names that look alike.
+fn skeleton(display_name: &str) -> String {
+    display_name
+        .chars()
+        .filter(|c| !is_invisible_or_bidi_control(*c))
+        .flat_map(char::to_lowercase)
+        .map(replace_homoglyph)
+        .collect::<String>()
+        // Letters that look like another letter when they are next to each other.
+        .replace("rn", "m")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Replace the given lowercase character by the ASCII character it looks like, if any.
+fn replace_homoglyph(c: char) -> char {
+    match c {
+        // Fullwidth forms.
+        '\u{FF01}'..='\u{FF5E}' => {
+            char::from_u32(c as u32 - 0xFEE0).map(replace_homoglyph).unwrap_or(c)
+        }
+        // Cyrillic and Greek.
+        'а' => 'a',
+        'в' => 'b',
+        'с' | 'ϲ' => 'c',
+        'ԁ' => 'd',
+        'е' | 'ё' => 'e',
+        'һ' => 'h',
+        'ј' => 'j',
+        'к' | 'κ' => 'k',
+        'м' => 'm',
+        'н' | 'η' => 'n',
+        'о' | 'ο' | 'σ' | '0' => 'o',
+        'р' | 'ρ' => 'p',
+        'ԛ' => 'q',
+        'ѕ' => 's',
+        'т' | 'τ' => 't',
+        'υ' => 'u',
+        'ν' => 'v',
+        'ԝ' | 'ω' => 'w',
+        'х' | 'χ' => 'x',
+        'у' | 'γ' => 'y',
+        'α' => 'a',
+        'β' => 'b',
+        'ε' => 'e',
+        'ζ' => 'z',
+        // Digits and letters that look alike. The uppercase I looks like a lowercase L, so all the
+        // forms of I are replaced.
+        '1' | 'ӏ' | 'l' | '|' | 'i' | 'і' | 'ї' | 'ι' => 'l',
+        _ => c,
+    }
+}
+
+/// Whether the given display name skeleton looks like a user ID.
+fn looks_like_user_id(skeleton: &str) -> bool {
+    skeleton.starts_with('@') && skeleton.contains(':')
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma_common::{room_alias_id, user_id};
+
+    use super::{RoomDisplayName, member_display_name, room_display_name};
+
+    #[test]
+    fn room_names() {
+        let no_heroes: &[&str] = &[];
+
+        let name = room_display_name(Some("Room"), Some(room_alias_id!("#a:b.c")), no_heroes, 3, 0);
+        assert_eq!(name, RoomDisplayName::Named("Room".to_owned()));
+
+        let name = room_display_name(Some(" "), Some(room_alias_id!("#a:b.c")), no_heroes, 3, 0);
+        assert_eq!(name.to_string(), "#a:b.c");
+
+        let name = room_display_name(None, None, &["Alice"], 2, 0);
+        assert_eq!(name, RoomDisplayName::Calculated("Alice".to_owned()));
+
+        let name = room_display_name(None, None, &["Alice", "Bob"], 2, 1);
+        assert_eq!(name.to_string(), "Alice and Bob");
+
+        let name = room_display_name(None, None, &["Alice", "Bob", "Carl"], 4, 0);
+        assert_eq!(name.to_string(), "Alice, Bob, and Carl");
+
+        let name = room_display_name(None, None, &["Alice", "Bob"], 4, 0);
+        assert_eq!(name.to_string(), "Alice, Bob, and 1 other");
+
+        let name = room_display_name(None, None, &["Alice", "Bob"], 1000, 236);
+        assert_eq!(name.to_string(), "Alice, Bob, and 1233 others");
+
+        let name = room_display_name(None, None, &["Alice"], 1, 0);
+        assert_eq!(name, RoomDisplayName::EmptyWas("Alice".to_owned()));
+        assert_eq!(name.to_string(), "Empty Room (was Alice)");
+
+        let name = room_display_name(None, None, no_heroes, 1, 0);
+        assert_eq!(name.to_string(), "Empty Room");
+    }
+
+    #[test]
+    fn member_names() {
+        let alice = user_id!("@alice:localhost");
+        let fake_alice = user_id!("@fake_alice:localhost");
+        let bob = user_id!("@bob:localhost");
+
+        let members = [(alice, Some("Alice")), (bob, Some("Bob")), (fake_alice, None)];
+        assert_eq!(member_display_name(alice, Some("Alice"), members), "Alice");
+        assert_eq!(member_display_name(fake_alice, None, members), "@fake_alice:localhost");
+        assert_eq!(member_display_name(fake_alice, Some(""), members), "@fake_alice:localhost");
+
+        // Same display name.
+        let members = [(alice, Some("Alice")), (fake_alice, Some("Alice"))];
+        assert_eq!(member_display_name(alice, Some("Alice"), members), "Alice (@alice:localhost)");
+
+        // Display names that look alike.
+        for lookalike in [
+            "ALICE",
+            "\u{0410}lice",
+            "A\u{200B}lice",
+            "A1ice",
+            "AIice",
+            "A\u{0406}ice",
+            "\u{FF21}lice",
+        ] {
+            let members = [(alice, Some("Alice")), (fake_alice, Some(lookalike))];
+            assert_eq!(
+                member_display_name(fake_alice, Some(lookalike), members),
+                format!("{} (@fake_alice:localhost)", lookalike.replace('\u{200B}', "")),
+                "{lookalike:?}"
+            );
+        }
+
+        // Letters that look like another letter when they are next to each other.
+        let members = [(bob, Some("Sam")), (fake_alice, Some("Sarn"))];
+        assert_eq!(
+            member_display_name(fake_alice, Some("Sarn"), members),
+            "Sarn (@fake_alice:localhost)"
+        );
+
+        // Bidirectional control characters are removed.
+        let members = [(alice, Some("Alice")), (fake_alice, Some("\u{202E}ecilA"))];
+        assert_eq!(member_display_name(fake_alice, Some("\u{202E}ecilA"), members), "ecilA");
+
+        // Display names that look like user IDs.
+        let members = [(fake_alice, Some("@alice:localhost"))];
+        assert_eq!(
+            member_display_name(fake_alice, Some("@alice:localhost"), members),
+            "@alice:localhost (@fake_alice:localhost)"
+        );
+    }
+}
@@ -0,0 +1,1513 @@
+//! A state machine for the [Short Authentication String (SAS)] key verification method.
+//!
+//! [`SasVerification`] implements the `m.sas.v1` method, using the `curve25519-hkdf-sha256` key
+//! agreement protocol, the `sha256` hash and the `hkdf-hmac-sha256.v2` message authentication
+//! code.
+//!
+//! It doesn't send or receive events itself: it consumes the sender and the content of the
+//! received `m.key.verification.*` events and returns the content of the events that must be sent,
+//! as [`SasContent`]s. They can be converted from and to both the to-device and the in-room
+//! variants of the events. The `m.key.verification.start` messages must be converted from the raw
+//! content as it was received, with [`SasContent::from_raw()`].
+//!
+//! The `m.key.verification.request` and `m.key.verification.ready` events must be handled before
+//! starting the SAS verification.
+//!
+//! [Short Authentication String (SAS)]: https://spec.matrix.org/latest/client-server-api/#short-authentication-string-sas-verification
+
+use std::collections::BTreeMap;
+
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use ruma_common::{
+    CanonicalJsonValue, OwnedDeviceId, OwnedEventId, OwnedTransactionId, OwnedUserId, UserId,
+    canonical_json,
+    serde::{Base64, Raw, base64::Standard},
+};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use super::{
+    HashAlgorithm, KeyAgreementProtocol, MessageAuthenticationCode, ShortAuthenticationString,
+    accept::{
+        self, AcceptMethod, KeyVerificationAcceptEventContent,
+        ToDeviceKeyVerificationAcceptEventContent,
+    },
+    cancel::{
+        CancelCode, KeyVerificationCancelEventContent, ToDeviceKeyVerificationCancelEventContent,
+    },
+    done::{KeyVerificationDoneEventContent, ToDeviceKeyVerificationDoneEventContent},
+    key::{KeyVerificationKeyEventContent, ToDeviceKeyVerificationKeyEventContent},
+    mac::{KeyVerificationMacEventContent, ToDeviceKeyVerificationMacEventContent},
+    start::{
+        self, KeyVerificationStartEventContent, StartMethod,
+        ToDeviceKeyVerificationStartEventContent,
+    },
+};
+use crate::{AnyMessageLikeEventContent, AnyToDeviceEventContent, relation::Reference};
+
+/// HMAC using SHA-256.
+type HmacSha256 = Hmac<Sha256>;
+
+/// The SAS methods supported by [`SasVerification`].
+const SUPPORTED_SAS_METHODS: [ShortAuthenticationString; 2] =
+    [ShortAuthenticationString::Decimal, ShortAuthenticationString::Emoji];
+
+/// The ID of a key verification flow.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum FlowId {
+    /// The transaction ID of a verification using to-device events.
+    ToDevice(OwnedTransactionId),
+
+    /// The ID of the `m.key.verification.request` event of a verification using in-room events.
+    InRoom(OwnedEventId),
+}
+
+impl FlowId {
+    /// The string representation of this ID, used as the transaction ID in the SAS calculations.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::ToDevice(transaction_id) => transaction_id.as_str(),
+            Self::InRoom(event_id) => event_id.as_str(),
+        }
+    }
+}
+
+/// A message of a SAS verification, independent of its transport.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum SasMessage {
+    /// An `m.key.verification.start` message.
+    Start {
+        /// The device ID which is initiating the process.
+        from_device: OwnedDeviceId,
+
+        /// The verification method.
+        method: StartMethod,
+    },
+
+    /// An `m.key.verification.accept` message.
+    Accept(AcceptMethod),
+
+    /// An `m.key.verification.key` message, with the ephemeral public key of the sender.
+    Key(Base64),
+
+    /// An `m.key.verification.mac` message.
+    Mac {
+        /// A map of the key ID to the MAC of the key.
+        mac: BTreeMap<String, Base64>,
+
+        /// The MAC of the comma-separated, sorted, list of key IDs in `mac`.
+        keys: Base64,
+    },
+
+    /// An `m.key.verification.cancel` message.
+    Cancel {
+        /// A human readable description of the `code`.
+        reason: String,
+
+        /// The error code for why the process or request was cancelled.
+        code: CancelCode,
+    },
+
+    /// An `m.key.verification.done` message.
+    Done,
+}
+
+/// The content of an event of a SAS verification, independent of its transport.
+///
+/// It can be converted from the content of the to-device and in-room `m.key.verification.*`
+/// events, and converted to the content of the event to send with [`Self::into_any_content()`].
+///
+/// The received `m.key.verification.start` messages must be converted with [`Self::from_raw()`],
+/// because the commitment of the SAS verification is computed over their content as it was
+/// received.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct SasContent {
+    /// The ID of the verification flow.
+    pub flow_id: FlowId,
+
+    /// The message.
+    pub message: SasMessage,
+
+    /// The canonical JSON of the content of a received `m.key.verification.start` message.
+    start_json: Option<String>,
+}
+
+impl SasContent {
+    /// Creates a new `SasContent` with the given flow ID and message.
+    pub fn new(flow_id: FlowId, message: SasMessage) -> Self {
+        Self { flow_id, message, start_json: None }
+    }
+
+    /// Converts the given raw content of a received to-device or in-room
+    /// `m.key.verification.*` event.
+    ///
+    /// For `m.key.verification.start` messages, this also keeps the canonical JSON of the content
+    /// as it was received, which is necessary to accept them.
+    ///
+    /// Returns an error if the content could not be deserialized.
+    pub fn from_raw<T>(content: &Raw<T>) -> serde_json::Result<Self>
+    where
+        T: DeserializeOwned + Into<SasContent>,
+    {
+        let mut sas_content: Self = content.deserialize()?.into();
+
+        if matches!(sas_content.message, SasMessage::Start { .. }) {
+            let start_json = content.deserialize_as_unchecked::<CanonicalJsonValue>()?;
+            sas_content.start_json = Some(start_json.to_string());
+        }
+
+        Ok(sas_content)
+    }
+
+    /// Creates a new `SasContent` to cancel the given flow with the given code.
+    fn cancel(flow_id: FlowId, code: CancelCode) -> Self {
+        let reason = cancel_reason(&code).to_owned();
+        Self::new(flow_id, SasMessage::Cancel { reason, code })
+    }
+
+    /// Converts this content to the content of the to-device or in-room event to send, according
+    /// to the flow ID.
+    pub fn into_any_content(self) -> AnySasEventContent {
+        match self.flow_id {
+            FlowId::ToDevice(transaction_id) => AnySasEventContent::ToDevice(match self.message {
+                SasMessage::Start { from_device, method } => {
+                    ToDeviceKeyVerificationStartEventContent::new(
+                        from_device,
+                        transaction_id,
+                        method,
+                    )
+                    .into()
+                }
+                SasMessage::Accept(method) => {
+                    ToDeviceKeyVerificationAcceptEventContent::new(transaction_id, method).into()
+                }
+                SasMessage::Key(key) => {
+                    ToDeviceKeyVerificationKeyEventContent::new(transaction_id, key).into()
+                }
+                SasMessage::Mac { mac, keys } => {
+                    ToDeviceKeyVerificationMacEventContent::new(transaction_id, mac, keys).into()
+                }
+                SasMessage::Cancel { reason, code } => {
+                    ToDeviceKeyVerificationCancelEventContent::new(transaction_id, reason, code)
+                        .into()
+                }
+                SasMessage::Done => {
+                    ToDeviceKeyVerificationDoneEventContent::new(transaction_id).into()
+                }
+            }),
+            FlowId::InRoom(event_id) => {
+                let relates_to = Reference::new(event_id);
+
+                AnySasEventContent::MessageLike(match self.message {
+                    SasMessage::Start { from_device, method } => {
+                        KeyVerificationStartEventContent::new(from_device, method, relates_to)
+                            .into()
+                    }
+                    SasMessage::Accept(method) => {
+                        KeyVerificationAcceptEventContent::new(method, relates_to).into()
+                    }
+                    SasMessage::Key(key) => {
+                        KeyVerificationKeyEventContent::new(key, relates_to).into()
+                    }
+                    SasMessage::Mac { mac, keys } => {
+                        KeyVerificationMacEventContent::new(mac, keys, relates_to).into()
+                    }
+                    SasMessage::Cancel { reason, code } => {
+                        KeyVerificationCancelEventContent::new(reason, code, relates_to).into()
+                    }
+                    SasMessage::Done => KeyVerificationDoneEventContent::new(relates_to).into(),
+                })
+            }
+        }
+    }
+}
+
+macro_rules! impl_from_content {
+    ($($to_device:ident, $in_room:ident => |$content:ident| $message:expr;)*) => {
+        $(
+            impl From<$to_device> for SasContent {
+                fn from($content: $to_device) -> Self {
+                    Self::new(FlowId::ToDevice($content.transaction_id), $message)
+                }
+            }
+
+            impl From<$in_room> for SasContent {
+                fn from($content: $in_room) -> Self {
+                    Self::new(FlowId::InRoom($content.relates_to.event_id), $message)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_content! {
+    ToDeviceKeyVerificationStartEventContent, KeyVerificationStartEventContent => |content| {
+        SasMessage::Start { from_device: content.from_device, method: content.method }
+    };
+    ToDeviceKeyVerificationAcceptEventContent, KeyVerificationAcceptEventContent => |content| {
+        SasMessage::Accept(content.method)
+    };
+    ToDeviceKeyVerificationKeyEventContent, KeyVerificationKeyEventContent => |content| {
+        SasMessage::Key(content.key)
+    };
+    ToDeviceKeyVerificationMacEventContent, KeyVerificationMacEventContent => |content| {
+        SasMessage::Mac { mac: content.mac, keys: content.keys }
+    };
+    ToDeviceKeyVerificationCancelEventContent, KeyVerificationCancelEventContent => |content| {
+        SasMessage::Cancel { reason: content.reason, code: content.code }
+    };
+    ToDeviceKeyVerificationDoneEventContent, KeyVerificationDoneEventContent => |_content| {
+        SasMessage::Done
+    };
+}
+
+/// The content of a to-device or in-room event to send.
+#[derive(Clone, Debug)]
+#[allow(clippy::exhaustive_enums, clippy::large_enum_variant)]
+pub enum AnySasEventContent {
+    /// The content of a to-device event.
+    ToDevice(AnyToDeviceEventContent),
+
+    /// The content of an in-room event.
+    MessageLike(AnyMessageLikeEventContent),
+}
+
+/// A device taking part in a SAS verification.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct SasDevice {
+    /// The ID of the user owning the device.
+    pub user_id: OwnedUserId,
+
+    /// The ID of the device.
+    pub device_id: OwnedDeviceId,
+
+    /// The public keys of the user or the device to verify, as a map of key ID to unpadded
+    /// base64-encoded public key.
+    ///
+    /// For the own device, these are the keys for which a MAC is sent. For the other device,
+    /// these are the keys that can be verified with the MAC that is received.
+    pub keys: BTreeMap<String, String>,
+}
+
+impl SasDevice {
+    /// Creates a new `SasDevice` with the given user ID, device ID and keys.
+    pub fn new(
+        user_id: OwnedUserId,
+        device_id: OwnedDeviceId,
+        keys: BTreeMap<String, String>,
+    ) -> Self {
+        Self { user_id, device_id, keys }
+    }
+}
+
+/// The state of a [`SasVerification`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum SasState {
+    /// An `m.key.verification.start` message was sent, waiting for the
+    /// `m.key.verification.accept` message.
+    Started,
+
+    /// An `m.key.verification.accept` message was sent or received, waiting for the
+    /// `m.key.verification.key` message of the other device.
+    Accepted,
+
+    /// The keys were exchanged, waiting for the user to confirm that the SAS match.
+    KeysExchanged,
+
+    /// The user confirmed that the SAS match, waiting for the `m.key.verification.mac` message
+    /// of the other device.
+    Confirmed,
+
+    /// The MAC of the other device was verified, waiting for the `m.key.verification.done`
+    /// message of the other device.
+    WaitingForDone,
+
+    /// The verification is done.
+    Done,
+
+    /// The verification was cancelled.
+    Cancelled {
+        /// The code for why the verification was cancelled.
+        code: CancelCode,
+
+        /// Whether the verification was cancelled by the own device.
+        local: bool,
+    },
+}
+
+/// A SAS verification between the own device and another device.
+///
+/// A verification is created by starting it with [`SasVerification::start()`] or by accepting an
+/// `m.key.verification.start` message with [`SasVerification::accept()`]. The messages of the
+/// other device are then given to [`SasVerification::receive()`], with their sender, and the
+/// messages that are returned must be sent to the other device.
+///
+/// Once the keys are exchanged, the SAS can be shown to the user, who must either
+/// [confirm](Self::confirm) that they match or [report a mismatch](Self::mismatch).
+///
+/// When a protocol violation is detected, the verification is cancelled and the
+/// `m.key.verification.cancel` message with the corresponding [`CancelCode`] is returned.
+pub struct SasVerification {
+    /// The ID of the verification flow.
+    flow_id: FlowId,
+
+    /// The own device.
+    own: SasDevice,
+
+    /// The other device.
+    other: SasDevice,
+
+    /// The current state.
+    state: SasState,
+
+    /// Whether the own device sent the `m.key.verification.start` message.
+    we_started: bool,
+
+    /// The own ephemeral secret key.
+    secret: StaticSecret,
+
+    /// The own ephemeral public key, encoded as unpadded base64.
+    public_key: String,
+
+    /// The canonical JSON of the `m.key.verification.start` message.
+    start_json: String,
+
+    /// The commitment received in the `m.key.verification.accept` message.
+    commitment: Option<Base64>,
+
+    /// The SAS methods that were agreed on.
+    sas_methods: Vec<ShortAuthenticationString>,
+
+    /// The ephemeral public key of the other device, encoded as unpadded base64.
+    their_public_key: Option<String>,
+
+    /// The shared secret computed from the ephemeral keys.
+    shared_secret: Option<Zeroizing<[u8; 32]>>,
+
+    /// The `m.key.verification.mac` message received before the user confirmed the SAS.
+    their_mac: Option<(BTreeMap<String, Base64>, Base64)>,
+
+    /// The IDs of the keys of the other device that were verified.
+    verified_keys: Vec<String>,
+}
+
+impl SasVerification {
+    /// Starts a SAS verification with the other device, in the flow with the given ID.
+    ///
+    /// Returns the verification and the `m.key.verification.start` message to send.
+    pub fn start(own: SasDevice, other: SasDevice, flow_id: FlowId) -> (Self, SasContent) {
+        Self::start_with_secret(own, other, flow_id, random_secret())
+    }
+
+    fn start_with_secret(
+        own: SasDevice,
+        other: SasDevice,
+        flow_id: FlowId,
+        secret: StaticSecret,
+    ) -> (Self, SasContent) {
+        let method = StartMethod::SasV1(
+            start::SasV1ContentInit {
+                key_agreement_protocols: vec![KeyAgreementProtocol::Curve25519HkdfSha256],
+                hashes: vec![HashAlgorithm::Sha256],
+                message_authentication_codes: vec![MessageAuthenticationCode::HkdfHmacSha256V2],
+                short_authentication_string: SUPPORTED_SAS_METHODS.to_vec(),
+            }
+            .into(),
+        );
+        let message = SasMessage::Start { from_device: own.device_id.clone(), method };
+        let content = SasContent::new(flow_id.clone(), message);
+
+        let mut verification = Self::new(own, other, flow_id, secret, true);
+        verification.start_json = start_canonical_json(&content);
+
+        (verification, content)
+    }
+
+    /// Accepts the given `m.key.verification.start` message, sent by the given user.
+    ///
+    /// The start message must have been converted with [`SasContent::from_raw()`].
+    ///
+    /// Returns the verification and the message to send, either an `m.key.verification.accept`
+    /// message or an `m.key.verification.cancel` message if the start message is invalid, was not
+    /// sent by the other device, or its method is not supported.
+    pub fn accept(
+        own: SasDevice,
+        other: SasDevice,
+        sender: &UserId,
+        start: impl Into<SasContent>,
+    ) -> (Self, SasContent) {
+        Self::accept_with_secret(own, other, sender, start.into(), random_secret())
+    }
+
+    fn accept_with_secret(
+        own: SasDevice,
+        other: SasDevice,
+        sender: &UserId,
+        start: SasContent,
+        secret: StaticSecret,
+    ) -> (Self, SasContent) {
+        let mut verification = Self::new(own, other, start.flow_id.clone(), secret, false);
+
+        let content = if sender == verification.other.user_id {
+            verification.accept_start(start)
+        } else {
+            verification.cancel_with(CancelCode::InvalidMessage)
+        };
+
+        (verification, content)
+    }
+
+    fn new(
+        own: SasDevice,
+        other: SasDevice,
+        flow_id: FlowId,
+        secret: StaticSecret,
+        we_started: bool,
+    ) -> Self {
+        let public_key = Base64::<Standard, _>::new(PublicKey::from(&secret).to_bytes()).encode();
+
+        Self {
+            flow_id,
+            own,
+            other,
+            state: SasState::Started,
+            we_started,
+            secret,
+            public_key,
+            start_json: String::new(),
+            commitment: None,
+            sas_methods: Vec::new(),
+            their_public_key: None,
+            shared_secret: None,
+            their_mac: None,
+            verified_keys: Vec::new(),
+        }
+    }
+
+    /// The ID of the verification flow.
+    pub fn flow_id(&self) -> &FlowId {
+        &self.flow_id
+    }
+
+    /// The current state of the verification.
+    pub fn state(&self) -> &SasState {
+        &self.state
+    }
+
+    /// Whether the own device started the verification.
+    pub fn we_started(&self) -> bool {
+        self.we_started
+    }
+
+    /// The SAS methods that were agreed on with the other device.
+    pub fn sas_methods(&self) -> &[ShortAuthenticationString] {
+        &self.sas_methods
+    }
+
+    /// The IDs of the keys of the other device that were verified.
+    ///
+    /// This is only filled once the state is [`SasState::WaitingForDone`] or [`SasState::Done`].
+    pub fn verified_keys(&self) -> &[String] {
+        &self.verified_keys
+    }
+
+    /// The SAS as three 4-digit numbers, if the keys were exchanged.
+    pub fn decimals(&self) -> Option<[u16; 3]> {
+        let bytes = self.sas_bytes()?;
+
+        let first = u16::from(bytes[0]) << 5 | u16::from(bytes[1]) >> 3;
+        let second =
+            u16::from(bytes[1] & 0x07) << 10 | u16::from(bytes[2]) << 2 | u16::from(bytes[3]) >> 6;
+        let third = u16::from(bytes[3] & 0x3F) << 7 | u16::from(bytes[4]) >> 1;
+
+        Some([first + 1000, second + 1000, third + 1000])
+    }
+
+    /// The SAS as seven emojis, if the keys were exchanged and the emoji method was agreed on.
+    pub fn emojis(&self) -> Option<[SasEmoji; 7]> {
+        if !self.sas_methods.contains(&ShortAuthenticationString::Emoji) {
+            return None;
+        }
+
+        let bytes = self.sas_bytes()?;
+        let bits = bytes.iter().fold(0_u64, |bits, byte| bits << 8 | u64::from(*byte));
+
+        Some(std::array::from_fn(|i| SAS_EMOJIS[(bits >> (42 - 6 * i)) as usize & 0x3F]))
+    }
+
+    /// Handles the given message, sent by the given user.
+    ///
+    /// Messages that were not sent by the user of the other device are ignored.
+    /// `m.key.verification.start` messages must have been converted with
+    /// [`SasContent::from_raw()`].
+    ///
+    /// Returns the messages to send to the other device.
+    pub fn receive(&mut self, sender: &UserId, content: impl Into<SasContent>) -> Vec<SasContent> {
+        let content = content.into();
+
+        if sender != self.other.user_id
+            || matches!(self.state, SasState::Done | SasState::Cancelled { .. })
+        {
+            return Vec::new();
+        }
+
+        if content.flow_id != self.flow_id {
+            return vec![SasContent::cancel(content.flow_id, CancelCode::UnknownTransaction)];
+        }
+
+        match (content.message, self.state.clone()) {
+            (SasMessage::Cancel { code, .. }, _) => {
+                self.state = SasState::Cancelled { code, local: false };
+                Vec::new()
+            }
+            (message @ SasMessage::Start { .. }, SasState::Started) => {
+                // Both devices started the verification, the start message of the device with the
+                // lexicographically smallest user ID and device ID is used.
+                let own = (&self.own.user_id, &self.own.device_id);
+                let other = (&self.other.user_id, &self.other.device_id);
+
+                if own < other {
+                    Vec::new()
+                } else {
+                    self.we_started = false;
+                    vec![self.accept_start(SasContent { message, ..content })]
+                }
+            }
+            (SasMessage::Accept(method), SasState::Started) if self.we_started => {
+                self.receive_accept(method)
+            }
+            (SasMessage::Key(key), SasState::Accepted) => self.receive_key(key),
+            (SasMessage::Mac { mac, keys }, SasState::KeysExchanged)
+                if self.their_mac.is_none() =>
+            {
+                // The MAC is only checked once the user confirmed that the SAS match.
+                self.their_mac = Some((mac, keys));
+                Vec::new()
+            }
+            (SasMessage::Mac { mac, keys }, SasState::Confirmed) => {
+                self.verify_mac(&mac, &keys).into_iter().collect()
+            }
+            (SasMessage::Done, SasState::WaitingForDone) => {
+                self.state = SasState::Done;
+                Vec::new()
+            }
+            _ => vec![self.cancel_with(CancelCode::UnexpectedMessage)],
+        }
+    }
+
+    /// Confirms that the SAS shown to the user match the SAS shown on the other device.
+    ///
+    /// Returns the messages to send to the other device. If the keys were not exchanged yet, this
+    /// does nothing.
+    pub fn confirm(&mut self) -> Vec<SasContent> {
+        if self.state != SasState::KeysExchanged {
+            return Vec::new();
+        }
+
+        let mac = self
+            .own
+            .keys
+            .iter()
+            .map(|(key_id, key)| (key_id.clone(), self.own_mac(key, key_id)))
+            .collect::<BTreeMap<_, _>>();
+        let keys = self.own_mac(&key_ids(&mac), "KEY_IDS");
+
+        let mut contents =
+            vec![SasContent::new(self.flow_id.clone(), SasMessage::Mac { mac, keys })];
+        self.state = SasState::Confirmed;
+
+        if let Some((mac, keys)) = self.their_mac.take() {
+            contents.extend(self.verify_mac(&mac, &keys));
+        }
+
+        contents
+    }
+
+    /// Reports that the SAS shown to the user don't match the SAS shown on the other device.
+    ///
+    /// Returns the `m.key.verification.cancel` message to send, if the verification is not done
+    /// or cancelled yet.
+    pub fn mismatch(&mut self) -> Option<SasContent> {
+        self.cancel_with_code(CancelCode::MismatchedSas)
+    }
+
+    /// Cancels the verification at the request of the user.
+    ///
+    /// Returns the `m.key.verification.cancel` message to send, if the verification is not done
+    /// or cancelled yet.
+    pub fn cancel(&mut self) -> Option<SasContent> {
+        self.cancel_with_code(CancelCode::User)
+    }
+
+    fn cancel_with_code(&mut self, code: CancelCode) -> Option<SasContent> {
+        if matches!(self.state, SasState::Done | SasState::Cancelled { .. }) {
+            return None;
+        }
+
+        Some(self.cancel_with(code))
+    }
+
+    /// Cancels the verification with the given code and returns the message to send.
+    fn cancel_with(&mut self, code: CancelCode) -> SasContent {
+        self.state = SasState::Cancelled { code: code.clone(), local: true };
+        SasContent::cancel(self.flow_id.clone(), code)
+    }
+
+    /// Accepts the given start message, and returns the message to send.
+    fn accept_start(&mut self, start: SasContent) -> SasContent {
+        let SasMessage::Start { from_device, method } = start.message else {
+            return self.cancel_with(CancelCode::UnexpectedMessage);
+        };
+
+        // The commitment must be computed over the content as it was received.
+        let Some(start_json) = start.start_json else {
+            return self.cancel_with(CancelCode::InvalidMessage);
+        };
+
+        if from_device != self.other.device_id {
+            return self.cancel_with(CancelCode::InvalidMessage);
+        }
+
+        let StartMethod::SasV1(method) = method else {
+            return self.cancel_with(CancelCode::UnknownMethod);
+        };
+
+        let sas_methods = SUPPORTED_SAS_METHODS
+            .into_iter()
+            .filter(|sas| method.short_authentication_string.contains(sas))
+            .collect::<Vec<_>>();
+
+        if !method.key_agreement_protocols.contains(&KeyAgreementProtocol::Curve25519HkdfSha256)
+            || !method.hashes.contains(&HashAlgorithm::Sha256)
+            || !method
+                .message_authentication_codes
+                .contains(&MessageAuthenticationCode::HkdfHmacSha256V2)
+            || sas_methods.is_empty()
+        {
+            return self.cancel_with(CancelCode::UnknownMethod);
+        }
+
+        self.start_json = start_json;
+        let commitment = sha256(&[self.public_key.as_bytes(), self.start_json.as_bytes()]);
+
+        let method = AcceptMethod::SasV1(
+            accept::SasV1ContentInit {
+                key_agreement_protocol: KeyAgreementProtocol::Curve25519HkdfSha256,
+                hash: HashAlgorithm::Sha256,
+                message_authentication_code: MessageAuthenticationCode::HkdfHmacSha256V2,
+                short_authentication_string: sas_methods.clone(),
+                commitment: Base64::new(commitment.to_vec()),
+            }
+            .into(),
+        );
+
+        self.sas_methods = sas_methods;
+        self.state = SasState::Accepted;
+
+        SasContent::new(self.flow_id.clone(), SasMessage::Accept(method))
+    }
+
+    /// Handles the accept message of the other device.
+    fn receive_accept(&mut self, method: AcceptMethod) -> Vec<SasContent> {
+        let AcceptMethod::SasV1(method) = method else {
+            return vec![self.cancel_with(CancelCode::UnknownMethod)];
+        };
+
+        if method.key_agreement_protocol != KeyAgreementProtocol::Curve25519HkdfSha256
+            || method.hash != HashAlgorithm::Sha256
+            || method.message_authentication_code != MessageAuthenticationCode::HkdfHmacSha256V2
+            || method.short_authentication_string.is_empty()
+            || !method
+                .short_authentication_string
+                .iter()
+                .all(|sas| SUPPORTED_SAS_METHODS.contains(sas))
+        {
+            return vec![self.cancel_with(CancelCode::UnknownMethod)];
+        }
+
+        self.commitment = Some(method.commitment);
+        self.sas_methods = method.short_authentication_string;
+        self.state = SasState::Accepted;
+
+        vec![self.key_content()]
+    }
+
+    /// Handles the key message of the other device.
+    fn receive_key(&mut self, key: Base64) -> Vec<SasContent> {
+        let Ok(key_bytes) = <[u8; 32]>::try_from(key.as_bytes()) else {
+            return vec![self.cancel_with(CancelCode::InvalidMessage)];
+        };
+        let their_public_key = key.encode();
+
+        if let Some(commitment) = &self.commitment {
+            let expected = sha256(&[their_public_key.as_bytes(), self.start_json.as_bytes()]);
+
+            if commitment.as_bytes() != expected {
+                return vec![self.cancel_with(CancelCode::MismatchedCommitment)];
+            }
+        }
+
+        let shared_secret = self.secret.diffie_hellman(&PublicKey::from(key_bytes));
+        if !shared_secret.was_contributory() {
+            return vec![self.cancel_with(CancelCode::KeyMismatch)];
+        }
+
+        self.shared_secret = Some(Zeroizing::new(shared_secret.to_bytes()));
+        self.their_public_key = Some(their_public_key);
+        self.state = SasState::KeysExchanged;
+
+        // The device that sent the start message sent its key first.
+        if self.we_started { Vec::new() } else { vec![self.key_content()] }
+    }
+
+    /// Verifies the MAC message of the other device, and returns the message to send.
+    fn verify_mac(&mut self, mac: &BTreeMap<String, Base64>, keys: &Base64) -> Option<SasContent> {
+        if self.their_mac_key(&key_ids(mac), "KEY_IDS").verify_slice(keys.as_bytes()).is_err() {
+            return Some(self.cancel_with(CancelCode::KeyMismatch));
+        }
+
+        let mut verified_keys = Vec::new();
+
+        // Ignore the keys that are unknown.
+        for (key_id, key_mac) in mac {
+            let Some(key) = self.other.keys.get(key_id) else {
+                continue;
+            };
+
+            if self.their_mac_key(key, key_id).verify_slice(key_mac.as_bytes()).is_err() {
+                return Some(self.cancel_with(CancelCode::KeyMismatch));
+            }
+
+            verified_keys.push(key_id.clone());
+        }
+
+        if verified_keys.is_empty() {
+            return Some(self.cancel_with(CancelCode::KeyMismatch));
+        }
+
+        self.verified_keys = verified_keys;
+        self.state = SasState::WaitingForDone;
+
+        Some(SasContent::new(self.flow_id.clone(), SasMessage::Done))
+    }
+
+    /// The key message to send.
+    fn key_content(&self) -> SasContent {
+        let key = Base64::parse(&self.public_key).expect("public key should be valid base64");
+        SasContent::new(self.flow_id.clone(), SasMessage::Key(key))
+    }
+
+    /// The 6 bytes used to compute the SAS, if the keys were exchanged.
+    fn sas_bytes(&self) -> Option<[u8; 6]> {
+        let shared_secret = self.shared_secret.as_ref()?;
+        let their_public_key = self.their_public_key.as_ref()?;
+
+        let ((start_device, start_key), (accept_device, accept_key)) = if self.we_started {
+            ((&self.own, &self.public_key), (&self.other, their_public_key))
+        } else {
+            ((&self.other, their_public_key), (&self.own, &self.public_key))
+        };
+
+        let info = format!(
+            "MATRIX_KEY_VERIFICATION_SAS|{}|{}|{start_key}|{}|{}|{accept_key}|{}",
+            start_device.user_id,
+            start_device.device_id,
+            accept_device.user_id,
+            accept_device.device_id,
+            self.flow_id.as_str(),
+        );
+
+        let mut bytes = [0; 6];
+        Hkdf::<Sha256>::new(None, shared_secret.as_slice())
+            .expand(info.as_bytes(), &mut bytes)
+            .expect("6 bytes should be a valid length for HKDF-SHA-256");
+
+        Some(bytes)
+    }
+
+    /// Computes the MAC of the given input, for the key with the given ID of the own device.
+    fn own_mac(&self, input: &str, key_id: &str) -> Base64 {
+        let mut hmac = self.mac_key(&self.own, &self.other, key_id);
+        hmac.update(input.as_bytes());
+        Base64::new(hmac.finalize().into_bytes().to_vec())
+    }
+
+    /// Constructs the HMAC to verify the MAC of the given input, for the key with the given ID of
+    /// the other device.
+    fn their_mac_key(&self, input: &str, key_id: &str) -> HmacSha256 {
+        let mut hmac = self.mac_key(&self.other, &self.own, key_id);
+        hmac.update(input.as_bytes());
+        hmac
+    }
+
+    /// Constructs the HMAC for the key with the given ID, sent by `sender` to `receiver`.
+    fn mac_key(&self, sender: &SasDevice, receiver: &SasDevice, key_id: &str) -> HmacSha256 {
+        let shared_secret =
+            self.shared_secret.as_ref().expect("MACs should only be computed after key exchange");
+        let info = format!(
+            "MATRIX_KEY_VERIFICATION_MAC{}{}{}{}{}{key_id}",
+            sender.user_id,
+            sender.device_id,
+            receiver.user_id,
+            receiver.device_id,
+            self.flow_id.as_str(),
+        );
+
+        let mut key = Zeroizing::new([0; 32]);
+        Hkdf::<Sha256>::new(None, shared_secret.as_slice())
+            .expand(info.as_bytes(), &mut *key)
+            .expect("32 bytes should be a valid length for HKDF-SHA-256");
+
+        HmacSha256::new_from_slice(&*key).expect("HMAC should accept a key of any size")
+    }
+}
+
+impl std::fmt::Debug for SasVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SasVerification")
+            .field("flow_id", &self.flow_id)
+            .field("own", &self.own)
+            .field("other", &self.other)
+            .field("state", &self.state)
+            .field("we_started", &self.we_started)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An emoji of the SAS, with its English description.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct SasEmoji {
+    /// The emoji.
+    pub symbol: &'static str,
+
+    /// The English description of the emoji.
+    pub description: &'static str,
+}
+
+/// The table of [emojis] used for the SAS.
+///
+/// [emojis]: https://spec.matrix.org/latest/client-server-api/#sas-method-emoji
+const SAS_EMOJIS: [SasEmoji; 64] = [
+    SasEmoji { symbol: "🐶", description: "Dog" },
+    SasEmoji { symbol: "🐱", description: "Cat" },
+    SasEmoji { symbol: "🦁", description: "Lion" },
+    SasEmoji { symbol: "🐎", description: "Horse" },
+    SasEmoji { symbol: "🦄", description: "Unicorn" },
+    SasEmoji { symbol: "🐷", description: "Pig" },
+    SasEmoji { symbol: "🐘", description: "Elephant" },
+    SasEmoji { symbol: "🐰", description: "Rabbit" },
+    SasEmoji { symbol: "🐼", description: "Panda" },
+    SasEmoji { symbol: "🐓", description: "Rooster" },
+    SasEmoji { symbol: "🐧", description: "Penguin" },
+    SasEmoji { symbol: "🐢", description: "Turtle" },
+    SasEmoji { symbol: "🐟", description: "Fish" },
+    SasEmoji { symbol: "🐙", description: "Octopus" },
+    SasEmoji { symbol: "🦋", description: "Butterfly" },
+    SasEmoji { symbol: "🌷", description: "Flower" },
+    SasEmoji { symbol: "🌳", description: "Tree" },
+    SasEmoji { symbol: "🌵", description: "Cactus" },
+    SasEmoji { symbol: "🍄", description: "Mushroom" },
+    SasEmoji { symbol: "🌏", description: "Globe" },
+    SasEmoji { symbol: "🌙", description: "Moon" },
+    SasEmoji { symbol: "☁️", description: "Cloud" },
+    SasEmoji { symbol: "🔥", description: "Fire" },
+    SasEmoji { symbol: "🍌", description: "Banana" },
+    SasEmoji { symbol: "🍎", description: "Apple" },
+    SasEmoji { symbol: "🍓", description: "Strawberry" },
+    SasEmoji { symbol: "🌽", description: "Corn" },
+    SasEmoji { symbol: "🍕", description: "Pizza" },
+    SasEmoji { symbol: "🎂", description: "Cake" },
+    SasEmoji { symbol: "❤️", description: "Heart" },
+    SasEmoji { symbol: "😀", description: "Smiley" },
+    SasEmoji { symbol: "🤖", description: "Robot" },
+    SasEmoji { symbol: "🎩", description: "Hat" },
+    SasEmoji { symbol: "👓", description: "Glasses" },
+    SasEmoji { symbol: "🔧", description: "Spanner" },
+    SasEmoji { symbol: "🎅", description: "Santa" },
+    SasEmoji { symbol: "👍", description: "Thumbs Up" },
+    SasEmoji { symbol: "☂️", description: "Umbrella" },
+    SasEmoji { symbol: "⌛", description: "Hourglass" },
+    SasEmoji { symbol: "⏰", description: "Clock" },
+    SasEmoji { symbol: "🎁", description: "Gift" },
+    SasEmoji { symbol: "💡", description: "Light Bulb" },
+    SasEmoji { symbol: "📕", description: "Book" },
+    SasEmoji { symbol: "✏️", description: "Pencil" },
+    SasEmoji { symbol: "📎", description: "Paperclip" },
+    SasEmoji { symbol: "✂️", description: "Scissors" },
+    SasEmoji { symbol: "🔒", description: "Lock" },
+    SasEmoji { symbol: "🔑", description: "Key" },
+    SasEmoji { symbol: "🔨", description: "Hammer" },
+    SasEmoji { symbol: "☎️", description: "Telephone" },
+    SasEmoji { symbol: "🏁", description: "Flag" },
+    SasEmoji { symbol: "🚂", description: "Train" },
+    SasEmoji { symbol: "🚲", description: "Bicycle" },
+    SasEmoji { symbol: "✈️", description: "Aeroplane" },
+    SasEmoji { symbol: "🚀", description: "Rocket" },
+    SasEmoji { symbol: "🏆", description: "Trophy" },
+    SasEmoji { symbol: "⚽", description: "Ball" },
+    SasEmoji { symbol: "🎸", description: "Guitar" },
+    SasEmoji { symbol: "🎺", description: "Trumpet" },
+    SasEmoji { symbol: "🔔", description: "Bell" },
+    SasEmoji { symbol: "⚓", description: "Anchor" },
+    SasEmoji { symbol: "🎧", description: "Headphones" },
+    SasEmoji { symbol: "📁", description: "Folder" },
+    SasEmoji { symbol: "📌", description: "Pin" },
+];
+
+/// Generates a random ephemeral secret key.
+fn random_secret() -> StaticSecret {
+    StaticSecret::random_from_rng(rand::thread_rng())
+}
+
+/// Computes the canonical JSON of the event content of the given start message to send.
+fn start_canonical_json(start: &SasContent) -> String {
+    let value = match start.clone().into_any_content() {
+        AnySasEventContent::ToDevice(content) => canonical_json::to_canonical_value(content),
+        AnySasEventContent::MessageLike(content) => canonical_json::to_canonical_value(content),
+    };
+
+    value.expect("start content should be valid canonical JSON").to_string()
+}
+
+/// Computes the SHA-256 hash of the concatenation of the given inputs.
+fn sha256(inputs: &[&[u8]]) -> [u8; 32] {
+    inputs.iter().fold(Sha256::new(), |hasher, input| hasher.chain_update(input)).finalize().into()
+}
+
+/// The comma-separated list of the sorted key IDs of the given MAC map.
+fn key_ids(mac: &BTreeMap<String, Base64>) -> String {
+    mac.keys().map(String::as_str).collect::<Vec<_>>().join(",")
+}
+
+/// The reason to send with the given cancel code.
+fn cancel_reason(code: &CancelCode) -> &'static str {
+    match code {
+        CancelCode::User => "The user cancelled the verification.",
+        CancelCode::UnknownTransaction => "Unknown transaction.",
+        CancelCode::UnknownMethod => "The verification method is not supported.",
+        CancelCode::UnexpectedMessage => "Unexpected message.",
+        CancelCode::KeyMismatch => "The keys could not be verified.",
+        CancelCode::InvalidMessage => "Invalid message.",
+        CancelCode::MismatchedCommitment => "The commitment did not match.",
+        CancelCode::MismatchedSas => "The short authentication strings did not match.",
+        _ => "The verification was cancelled.",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use assert_matches2::assert_matches;
+    use ruma_common::{
+        device_id, owned_event_id,
+        serde::{Base64, Raw},
+        user_id,
+    };
+    use serde::{Serialize, de::DeserializeOwned};
+    use serde_json::{from_value as from_json_value, json};
+    use x25519_dalek::StaticSecret;
+
+    use super::{
+        AnySasEventContent, FlowId, SasContent, SasDevice, SasMessage, SasState, SasVerification,
+    };
+    use crate::{
+        AnyMessageLikeEventContent, AnyToDeviceEventContent,
+        key::verification::{
+            KeyAgreementProtocol,
+            accept::AcceptMethod,
+            cancel::CancelCode,
+            start::{StartMethod, ToDeviceKeyVerificationStartEventContent},
+        },
+    };
+
+    fn alice_device() -> SasDevice {
+        SasDevice::new(
+            user_id!("@alice:example.org").to_owned(),
+            device_id!("ALICEDEVICE").to_owned(),
+            BTreeMap::from([("ed25519:ALICEDEVICE".to_owned(), "QWxpY2VEZXZpY2VLZXk".to_owned())]),
+        )
+    }
+
+    fn bob_device() -> SasDevice {
+        SasDevice::new(
+            user_id!("@bob:example.org").to_owned(),
+            device_id!("BOBDEVICE").to_owned(),
+            BTreeMap::from([
+                ("ed25519:BOBDEVICE".to_owned(), "Qm9iRGV2aWNlS2V5".to_owned()),
+                ("ed25519:BobMasterKey".to_owned(), "Qm9iTWFzdGVyS2V5".to_owned()),
+            ]),
+        )
+    }
+
+    fn alice_secret() -> StaticSecret {
+        StaticSecret::from(std::array::from_fn(|i| i as u8 + 1))
+    }
+
+    fn bob_secret() -> StaticSecret {
+        StaticSecret::from(std::array::from_fn(|i| i as u8 + 33))
+    }
+
+    /// Converts the given content to the raw event content to send, and back.
+    fn transport(content: SasContent) -> SasContent {
+        fn through_raw<T: Serialize + DeserializeOwned + Into<SasContent>>(
+            content: T,
+        ) -> SasContent {
+            SasContent::from_raw(&Raw::new(&content).unwrap()).unwrap()
+        }
+
+        match content.into_any_content() {
+            AnySasEventContent::ToDevice(content) => match content {
+                AnyToDeviceEventContent::KeyVerificationStart(c) => through_raw(c),
+                AnyToDeviceEventContent::KeyVerificationAccept(c) => through_raw(c),
+                AnyToDeviceEventContent::KeyVerificationKey(c) => through_raw(c),
+                AnyToDeviceEventContent::KeyVerificationMac(c) => through_raw(c),
+                AnyToDeviceEventContent::KeyVerificationCancel(c) => through_raw(c),
+                AnyToDeviceEventContent::KeyVerificationDone(c) => through_raw(c),
+                _ => panic!("unexpected to-device event content"),
+            },
+            AnySasEventContent::MessageLike(content) => match content {
+                AnyMessageLikeEventContent::KeyVerificationStart(c) => through_raw(c),
+                AnyMessageLikeEventContent::KeyVerificationAccept(c) => through_raw(c),
+                AnyMessageLikeEventContent::KeyVerificationKey(c) => through_raw(c),
+                AnyMessageLikeEventContent::KeyVerificationMac(c) => through_raw(c),
+                AnyMessageLikeEventContent::KeyVerificationCancel(c) => through_raw(c),
+                AnyMessageLikeEventContent::KeyVerificationDone(c) => through_raw(c),
+                _ => panic!("unexpected message-like event content"),
+            },
+        }
+    }
+
+    /// Sends the given contents to the given verification, from the other device, and returns
+    /// its response.
+    fn send(to: &mut SasVerification, contents: Vec<SasContent>) -> Vec<SasContent> {
+        let sender = to.other.user_id.clone();
+        contents.into_iter().flat_map(|content| to.receive(&sender, transport(content))).collect()
+    }
+
+    /// Starts a verification between Alice and Bob and exchanges their keys.
+    fn exchange_keys(flow_id: FlowId) -> (SasVerification, SasVerification) {
+        let (mut alice, start) = SasVerification::start_with_secret(
+            alice_device(),
+            bob_device(),
+            flow_id,
+            alice_secret(),
+        );
+        let (mut bob, accept) = SasVerification::accept_with_secret(
+            bob_device(),
+            alice_device(),
+            user_id!("@alice:example.org"),
+            transport(start),
+            bob_secret(),
+        );
+
+        let alice_key = send(&mut alice, vec![accept]);
+        let bob_key = send(&mut bob, alice_key);
+        assert!(send(&mut alice, bob_key).is_empty());
+
+        (alice, bob)
+    }
+
+    /// The keys, SAS and MACs of this test were checked against an independent computation, like
+    /// in `independent_vectors`.
+    #[test]
+    fn to_device_verification() {
+        let flow_id = FlowId::ToDevice("txn1".into());
+        let (mut alice, start) = SasVerification::start_with_secret(
+            alice_device(),
+            bob_device(),
+            flow_id,
+            alice_secret(),
+        );
+        assert_eq!(
+            alice.start_json,
+            r#"{"from_device":"ALICEDEVICE","hashes":["sha256"],"key_agreement_protocols":["curve25519-hkdf-sha256"],"message_authentication_codes":["hkdf-hmac-sha256.v2"],"method":"m.sas.v1","short_authentication_string":["decimal","emoji"],"transaction_id":"txn1"}"#
+        );
+
+        // Bob accepts, with a commitment to his key.
+        let (mut bob, accept) = SasVerification::accept_with_secret(
+            bob_device(),
+            alice_device(),
+            user_id!("@alice:example.org"),
+            transport(start),
+            bob_secret(),
+        );
+        assert_eq!(*bob.state(), SasState::Accepted);
+        assert_matches!(&accept.message, SasMessage::Accept(AcceptMethod::SasV1(method)));
+        assert_eq!(method.commitment.encode(), "66tsg2B/yMTrXFTNQxe1F5SqM4k+OZRyNgfftAWyAvw");
+
+        // The keys are exchanged.
+        let alice_key = send(&mut alice, vec![accept]);
+        assert_eq!(*alice.state(), SasState::Accepted);
+        assert_matches!(alice_key.as_slice(), [SasContent { message: SasMessage::Key(key), .. }]);
+        assert_eq!(key.encode(), "B6N8vBQgk8i3VdwbEOhstCY3StFqqFPtC9/AsrhtHHw");
+
+        let bob_key = send(&mut bob, alice_key);
+        assert_eq!(*bob.state(), SasState::KeysExchanged);
+        assert_matches!(bob_key.as_slice(), [SasContent { message: SasMessage::Key(key), .. }]);
+        assert_eq!(key.encode(), "WGmv9FBUlzLLqu1eXfmzCm2jHLDldCutWtShp2jxpns");
+
+        assert!(send(&mut alice, bob_key).is_empty());
+        assert_eq!(*alice.state(), SasState::KeysExchanged);
+
+        // Both devices show the same SAS.
+        assert_eq!(alice.decimals(), Some([3486, 7557, 2949]));
+        assert_eq!(bob.decimals(), Some([3486, 7557, 2949]));
+        let emojis = alice.emojis().unwrap().map(|emoji| emoji.description);
+        assert_eq!(emojis, ["Globe", "Pizza", "Strawberry", "Clock", "Globe", "Train", "Lock"]);
+        assert_eq!(bob.emojis(), alice.emojis());
+
+        // Alice confirms first, Bob checks her MAC after confirming.
+        let alice_mac = alice.confirm();
+        assert_eq!(*alice.state(), SasState::Confirmed);
+        assert_matches!(
+            alice_mac.as_slice(),
+            [SasContent { message: SasMessage::Mac { mac, keys }, .. }]
+        );
+        assert_eq!(mac.len(), 1);
+        assert_eq!(
+            mac["ed25519:ALICEDEVICE"].encode(),
+            "cN535E0jhs0rjY0e7qlM2Q5rOoW/PXCMPGFLTRWQqWc"
+        );
+        assert_eq!(keys.encode(), "pjXlNtjjPXLl356vOw4byu4jHG/fuemSygp0TxC0R6I");
+
+        assert!(send(&mut bob, alice_mac).is_empty());
+        assert_eq!(*bob.state(), SasState::KeysExchanged);
+
+        let bob_mac = bob.confirm();
+        assert_eq!(*bob.state(), SasState::WaitingForDone);
+        assert_eq!(bob.verified_keys(), ["ed25519:ALICEDEVICE"]);
+        assert_matches!(
+            bob_mac.as_slice(),
+            [
+                SasContent { message: SasMessage::Mac { mac, keys }, .. },
+                SasContent { message: SasMessage::Done, .. }
+            ]
+        );
+        assert_eq!(
+            mac["ed25519:BOBDEVICE"].encode(),
+            "9Nz7Y6LmiYfSo/KpB3qVRKMCeysWWJ5GYb8bqf6Hq/0"
+        );
+        assert_eq!(
+            mac["ed25519:BobMasterKey"].encode(),
+            "Hy9yX7IQE0czVIAsIxPjW1efcjTldzP0g/ritXfaz0Q"
+        );
+        assert_eq!(keys.encode(), "MSnnYdD1Tn374BMpcQ3FDaX1/MANOSs39PBTLYE+CNM");
+
+        // Both devices are done.
+        let alice_done = send(&mut alice, bob_mac);
+        assert_eq!(*alice.state(), SasState::Done);
+        assert_eq!(alice.verified_keys(), ["ed25519:BOBDEVICE", "ed25519:BobMasterKey"]);
+
+        assert!(send(&mut bob, alice_done).is_empty());
+        assert_eq!(*bob.state(), SasState::Done);
+    }
+
+    /// The values of this test were computed independently from this implementation, with the
+    /// X25519, HKDF-SHA-256 and HMAC-SHA-256 implementations of Python's `cryptography` and
+    /// `hmac` modules, following the formulas of the specification.
+    #[test]
+    fn independent_vectors() {
+        let flow_id = FlowId::InRoom(owned_event_id!("$request:example.org"));
+        let (mut alice, start) = SasVerification::start_with_secret(
+            alice_device(),
+            bob_device(),
+            flow_id,
+            StaticSecret::from(std::array::from_fn(|i| (i as u8).wrapping_mul(7) + 3)),
+        );
+        let (mut bob, accept) = SasVerification::accept_with_secret(
+            bob_device(),
+            alice_device(),
+            user_id!("@alice:example.org"),
+            transport(start),
+            StaticSecret::from(std::array::from_fn(|i| 255 - i as u8)),
+        );
+        assert_eq!(alice.public_key, "u1D/noKldM+/gg6X9g+5wUPsdBXPUU+M/Zjv9Z4FlhQ");
+        assert_eq!(bob.public_key, "Pry2khSTRNxU5YFgz5C+2e6h3RToHI6R3lV699ev2RU");
+
+        let alice_key = send(&mut alice, vec![accept]);
+        let bob_key = send(&mut bob, alice_key);
+        assert!(send(&mut alice, bob_key).is_empty());
+
+        // SAS bytes of `curve25519-hkdf-sha256`.
+        assert_eq!(alice.sas_bytes(), Some([0xa5, 0x57, 0xdb, 0x2a, 0xf1, 0x53]));
+        assert_eq!(bob.sas_bytes(), alice.sas_bytes());
+        assert_eq!(alice.decimals(), Some([6290, 9044, 6496]));
+        let emojis = alice.emojis().unwrap().map(|emoji| emoji.description);
+        assert_eq!(emojis, ["Light Bulb", "Cloud", "Robot", "Pizza", "Penguin", "Key", "Pig"]);
+
+        // MACs of `hkdf-hmac-sha256.v2`.
+        let alice_mac = alice.confirm();
+        assert_matches!(
+            alice_mac.as_slice(),
+            [SasContent { message: SasMessage::Mac { mac, keys }, .. }]
+        );
+        assert_eq!(
+            mac["ed25519:ALICEDEVICE"].encode(),
+            "pg0+u8Va4tBrMeqrnnHffKppwkcGMS61zX367Nt2JAw"
+        );
+        assert_eq!(keys.encode(), "VS0mrx+s6B1sb9XNpH9kEQj//rGkgWtk4/iy3Krhuvc");
+
+        let bob_mac = bob.confirm();
+        assert_matches!(
+            bob_mac.as_slice(),
+            [SasContent { message: SasMessage::Mac { mac, keys }, .. }]
+        );
+        assert_eq!(
+            mac["ed25519:BOBDEVICE"].encode(),
+            "mYwWdenDg4zlR0ab+Rrljvkbbnkwr+hpYIvFBsSkkwY"
+        );
+        assert_eq!(
+            mac["ed25519:BobMasterKey"].encode(),
+            "RNnPwXMTe8hlKPlewc22hkWAAG3HmW5QgeYY2kA2fPQ"
+        );
+        assert_eq!(keys.encode(), "cza5rXbZrtmYQquyPPw007L8SdpN1xBWbK8jQnBJ3IY");
+    }
+
+    #[test]
+    fn in_room_verification() {
+        let flow_id = FlowId::InRoom(owned_event_id!("$request"));
+        let (mut alice, start) =
+            SasVerification::start(alice_device(), bob_device(), flow_id.clone());
+        let (mut bob, accept) = SasVerification::accept(
+            bob_device(),
+            alice_device(),
+            user_id!("@alice:example.org"),
+            transport(start),
+        );
+        assert_eq!(*bob.flow_id(), flow_id);
+
+        let alice_key = send(&mut alice, vec![accept]);
+        let bob_key = send(&mut bob, alice_key);
+        assert!(send(&mut alice, bob_key).is_empty());
+
+        assert!(alice.decimals().is_some());
+        assert_eq!(alice.decimals(), bob.decimals());
+        assert_eq!(alice.emojis(), bob.emojis());
+
+        let bob_mac = bob.confirm();
+        assert!(send(&mut alice, bob_mac).is_empty());
+        let alice_mac_and_done = alice.confirm();
+        let bob_done = send(&mut bob, alice_mac_and_done);
+        assert_eq!(*bob.state(), SasState::Done);
+        assert!(send(&mut alice, bob_done).is_empty());
+        assert_eq!(*alice.state(), SasState::Done);
+    }
+
+    #[test]
+    fn start_collision() {
+        let flow_id = FlowId::ToDevice("txn1".into());
+        let (mut alice, alice_start) =
+            SasVerification::start(alice_device(), bob_device(), flow_id.clone());
+        let (mut bob, bob_start) = SasVerification::start(bob_device(), alice_device(), flow_id);
+
+        // Alice has the smallest user ID, so her start message is used.
+        assert!(send(&mut alice, vec![bob_start]).is_empty());
+        assert!(alice.we_started());
+
+        let accept = send(&mut bob, vec![alice_start]);
+        assert!(!bob.we_started());
+        assert_matches!(accept.as_slice(), [SasContent { message: SasMessage::Accept(_), .. }]);
+        assert_eq!(send(&mut alice, accept).len(), 1);
+    }
+
+    #[test]
+    fn protocol_violations() {
+        let flow_id = FlowId::ToDevice("txn1".into());
+
+        // Unknown transaction, the verification continues.
+        let (mut alice, _) = SasVerification::start(alice_device(), bob_device(), flow_id.clone());
+        let unknown = SasContent::new(
+            FlowId::ToDevice("txn2".into()),
+            SasMessage::Key(Base64::new(vec![0; 32])),
+        );
+        let contents = alice.receive(user_id!("@bob:example.org"), unknown);
+        assert_matches!(contents.as_slice(), [cancel]);
+        assert_eq!(cancel.flow_id, FlowId::ToDevice("txn2".into()));
+        assert_matches!(
+            &cancel.message,
+            SasMessage::Cancel { code: CancelCode::UnknownTransaction, .. }
+        );
+        assert_eq!(*alice.state(), SasState::Started);
+
+        // Unexpected message.
+        let key = SasContent::new(flow_id.clone(), SasMessage::Key(Base64::new(vec![0; 32])));
+        let contents = alice.receive(user_id!("@bob:example.org"), key);
+        assert_matches!(
+            contents.as_slice(),
+            [SasContent {
+                message: SasMessage::Cancel { code: CancelCode::UnexpectedMessage, .. },
+                ..
+            }]
+        );
+        assert_eq!(
+            *alice.state(),
+            SasState::Cancelled { code: CancelCode::UnexpectedMessage, local: true }
+        );
+
+        // Unsupported method.
+        let (_, mut start) = SasVerification::start(alice_device(), bob_device(), flow_id.clone());
+        assert_matches!(
+            &mut start.message,
+            SasMessage::Start { method: StartMethod::SasV1(method), .. }
+        );
+        method.key_agreement_protocols = vec![KeyAgreementProtocol::Curve25519];
+        let (bob, cancel) = SasVerification::accept(
+            bob_device(),
+            alice_device(),
+            user_id!("@alice:example.org"),
+            transport(start),
+        );
+        assert_matches!(cancel.message, SasMessage::Cancel { code: CancelCode::UnknownMethod, .. });
+        assert_matches!(
+            bob.state(),
+            SasState::Cancelled { code: CancelCode::UnknownMethod, local: true }
+        );
+
+        // Mismatched commitment.
+        let (mut alice, start) = SasVerification::start_with_secret(
+            alice_device(),
+            bob_device(),
+            flow_id.clone(),
+            alice_secret(),
+        );
+        let (mut bob, mut accept) = SasVerification::accept_with_secret(
+            bob_device(),
+            alice_device(),
+            user_id!("@alice:example.org"),
+            transport(start),
+            bob_secret(),
+        );
+        assert_matches!(&mut accept.message, SasMessage::Accept(AcceptMethod::SasV1(method)));
+        method.commitment = Base64::new(vec![0; 32]);
+        let alice_key = send(&mut alice, vec![accept]);
+        let bob_key = send(&mut bob, alice_key);
+        let contents = send(&mut alice, bob_key);
+        assert_matches!(
+            contents.as_slice(),
+            [SasContent {
+                message: SasMessage::Cancel { code: CancelCode::MismatchedCommitment, .. },
+                ..
+            }]
+        );
+
+        // Invalid MAC.
+        let (mut alice, mut bob) = exchange_keys(flow_id.clone());
+        let mut alice_mac = alice.confirm();
+        assert_matches!(&mut alice_mac[0].message, SasMessage::Mac { mac, .. });
+        mac.insert("ed25519:ALICEDEVICE".to_owned(), Base64::new(vec![0; 32]));
+        assert!(send(&mut bob, alice_mac).is_empty());
+        let contents = bob.confirm();
+        assert_matches!(
+            contents.as_slice(),
+            [
+                _,
+                SasContent {
+                    message: SasMessage::Cancel { code: CancelCode::KeyMismatch, .. },
+                    ..
+                }
+            ]
+        );
+
+        // The SAS don't match.
+        let (mut alice, mut bob) = exchange_keys(flow_id);
+        let cancel = alice.mismatch().unwrap();
+        assert_matches!(
+            &cancel.message,
+            SasMessage::Cancel { code: CancelCode::MismatchedSas, .. }
+        );
+        assert!(send(&mut bob, vec![cancel]).is_empty());
+        assert_eq!(
+            *bob.state(),
+            SasState::Cancelled { code: CancelCode::MismatchedSas, local: false }
+        );
+        assert!(bob.cancel().is_none());
+        assert!(bob.confirm().is_empty());
+    }
+
+    #[test]
+    fn commitment_uses_received_start_content() {
+        // The start message has a field that is unknown to the typed content.
+        let start = json!({
+            "from_device": "ALICEDEVICE",
+            "hashes": ["sha256"],
+            "key_agreement_protocols": ["curve25519-hkdf-sha256"],
+            "message_authentication_codes": ["hkdf-hmac-sha256.v2"],
+            "method": "m.sas.v1",
+            "org.example.unknown": true,
+            "short_authentication_string": ["decimal", "emoji"],
+            "transaction_id": "txn1",
+        });
+        let raw_start =
+            from_json_value::<Raw<ToDeviceKeyVerificationStartEventContent>>(start.clone())
+                .unwrap();
+
+        let (bob, _) = SasVerification::accept_with_secret(
+            bob_device(),
+            alice_device(),
+            user_id!("@alice:example.org"),
+            SasContent::from_raw(&raw_start).unwrap(),
+            bob_secret(),
+        );
+        assert_eq!(*bob.state(), SasState::Accepted);
+        assert_eq!(bob.start_json, start.to_string());
+
+        // A start message that was not converted from the raw content can't be accepted.
+        let typed_start = raw_start.deserialize().unwrap();
+        let (bob, cancel) = SasVerification::accept(
+            bob_device(),
+            alice_device(),
+            user_id!("@alice:example.org"),
+            typed_start,
+        );
+        assert_matches!(
+            cancel.message,
+            SasMessage::Cancel { code: CancelCode::InvalidMessage, .. }
+        );
+        assert_matches!(
+            bob.state(),
+            SasState::Cancelled { code: CancelCode::InvalidMessage, local: true }
+        );
+    }
+
+    #[test]
+    fn unexpected_sender() {
+        let flow_id = FlowId::ToDevice("txn1".into());
+        let mallory = user_id!("@mallory:example.org");
+
+        // The start message is not sent by the other user.
+        let (_, start) = SasVerification::start(alice_device(), bob_device(), flow_id.clone());
+        let (bob, cancel) =
+            SasVerification::accept(bob_device(), alice_device(), mallory, transport(start));
+        assert_matches!(
+            cancel.message,
+            SasMessage::Cancel { code: CancelCode::InvalidMessage, .. }
+        );
+        assert_matches!(bob.state(), SasState::Cancelled { .. });
+
+        // Messages that are not sent by the other user are ignored.
+        let (mut alice, mut bob) = exchange_keys(flow_id.clone());
+        let cancel = SasContent::new(
+            flow_id,
+            SasMessage::Cancel { reason: "Nope".to_owned(), code: CancelCode::User },
+        );
+        assert!(alice.receive(mallory, transport(cancel)).is_empty());
+        assert_eq!(*alice.state(), SasState::KeysExchanged);
+
+        let bob_mac = bob.confirm();
+        assert!(alice.receive(mallory, transport(bob_mac[0].clone())).is_empty());
+        assert!(send(&mut alice, bob_mac).is_empty());
+        let alice_mac_and_done = alice.confirm();
+        assert_eq!(*alice.state(), SasState::WaitingForDone);
+        assert_eq!(send(&mut bob, alice_mac_and_done).len(), 1);
+    }
+}
@@ -145,6 +145,7 @@ pub mod beacon;
 pub mod beacon_info;
 pub mod call;
 pub mod direct;
+pub mod display_name;
 #[cfg(feature = "unstable-msc4359")]
 pub mod do_not_disturb;
 pub mod dummy;
@@ -184,6 +185,7 @@ pub mod room_key;
 #[cfg(feature = "unstable-msc4268")]
 pub mod room_key_bundle;
 pub mod room_key_request;
+pub mod room_state;
 #[cfg(feature = "unstable-msc4310")]
 pub mod rtc;
 pub mod secret;
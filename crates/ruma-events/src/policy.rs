@@ -1,3 +1,6 @@
 //! Modules for events in the `m.policy` namespace.
 
+mod list;
 pub mod rule;
+
+pub use self::list::{PolicyList, PolicyRuleKind, PolicyRuleMatch};
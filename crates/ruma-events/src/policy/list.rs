@@ -0,0 +1,361 @@
+//! An evaluator of moderation policy lists.
+
+use std::collections::BTreeMap;
+
+use ruma_common::{
+    EventId, OwnedEventId, OwnedRoomId, RoomId, RoomOrAliasId, ServerName, UserId, serde::Raw,
+};
+use serde::Deserialize;
+use wildmatch::WildMatch;
+
+use super::rule::{PolicyRuleEventContent, Recommendation};
+use crate::{AnySyncStateEvent, StateEventType};
+
+/// The moderation policy rules of one or more [policy lists].
+///
+/// The rules are ingested from the `m.policy.rule.*` state events of the policy list rooms with
+/// [`PolicyList::handle_event()`]. A rule replaces the previous rule of the same type with the same
+/// state key, and is removed when its event is redacted or when an event with empty content is sent
+/// with the same state key.
+///
+/// The rules can then be used to check whether a user, a room or a server is banned. The lists
+/// are checked in the order in which their first event was handled, the first list having the
+/// highest priority. In a list, the rules with a literal entity are checked before the rules with
+/// a glob.
+///
+/// [policy lists]: https://spec.matrix.org/latest/client-server-api/#moderation-policy-lists
+#[derive(Clone, Debug, Default)]
+pub struct PolicyList {
+    /// The rules of each list, in priority order.
+    lists: Vec<(OwnedRoomId, PolicyRules)>,
+}
+
+impl PolicyList {
+    /// Creates an empty `PolicyList`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The IDs of the policy list rooms, in priority order.
+    pub fn room_ids(&self) -> impl Iterator<Item = &RoomId> {
+        self.lists.iter().map(|(room_id, _)| &**room_id)
+    }
+
+    /// Handles the given state event of the policy list room with the given ID.
+    ///
+    /// If the event is an `m.policy.rule.*` event, its rule replaces the rule of the same type with
+    /// the same state key. If the content of the event is empty or invalid, for example because the
+    /// event was redacted, the rule of the same type with the same state key is removed instead.
+    ///
+    /// Returns `true` if the event is an `m.policy.rule.*` event.
+    pub fn handle_event(&mut self, room_id: &RoomId, event: &Raw<AnySyncStateEvent>) -> bool {
+        #[derive(Deserialize)]
+        struct PolicyRuleEventDeHelper {
+            #[serde(rename = "type")]
+            event_type: StateEventType,
+            state_key: String,
+            event_id: OwnedEventId,
+            content: Raw<PolicyRuleEventContent>,
+        }
+
+        let Ok(event) = event.deserialize_as_unchecked::<PolicyRuleEventDeHelper>() else {
+            return false;
+        };
+
+        let kind = match event.event_type {
+            StateEventType::PolicyRuleUser => PolicyRuleKind::User,
+            StateEventType::PolicyRuleRoom => PolicyRuleKind::Room,
+            StateEventType::PolicyRuleServer => PolicyRuleKind::Server,
+            _ => return false,
+        };
+
+        let key = (kind, event.state_key);
+
+        match event.content.deserialize() {
+            Ok(content) => {
+                let rule = PolicyRule::new(event.event_id, content);
+                self.rules_mut(room_id).rules.insert(key, rule);
+            }
+            Err(_) => {
+                if let Some(rules) = self.rules_mut_if_exists(room_id) {
+                    rules.rules.remove(&key);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Handles the redaction of the event with the given ID in the policy list room with the given
+    /// ID.
+    ///
+    /// Returns `true` if the redacted event is the event of a rule, that was removed.
+    pub fn handle_redaction(&mut self, room_id: &RoomId, redacted_event_id: &EventId) -> bool {
+        let Some(rules) = self.rules_mut_if_exists(room_id) else {
+            return false;
+        };
+
+        let len = rules.rules.len();
+        rules.rules.retain(|_, rule| rule.event_id != redacted_event_id);
+
+        rules.rules.len() != len
+    }
+
+    /// Removes the rules of the policy list room with the given ID.
+    ///
+    /// Returns `true` if the list was known.
+    pub fn remove_list(&mut self, room_id: &RoomId) -> bool {
+        let len = self.lists.len();
+        self.lists.retain(|(id, _)| id != room_id);
+
+        self.lists.len() != len
+    }
+
+    /// The rule of the given type with the given state key in the policy list room with the given
+    /// ID, if any.
+    pub fn rule(
+        &self,
+        room_id: &RoomId,
+        kind: PolicyRuleKind,
+        state_key: &str,
+    ) -> Option<&PolicyRuleEventContent> {
+        let rules = self.rules(room_id)?;
+        rules.rules.get(&(kind, state_key.to_owned())).map(|rule| &rule.content)
+    }
+
+    /// Returns the rule that bans the given user, if any.
+    ///
+    /// The user is banned if a user rule matches their user ID or if a server rule matches their
+    /// server name. In a list, the user rules are checked before the server rules.
+    pub fn user_ban(&self, user_id: &UserId) -> Option<PolicyRuleMatch<'_>> {
+        let user_entity = Entity::new(user_id.as_str());
+        let server_entity = Entity::new(user_id.server_name().as_str());
+
+        self.lists.iter().find_map(|(room_id, rules)| {
+            rules
+                .find_ban(PolicyRuleKind::User, &user_entity)
+                .or_else(|| rules.find_ban(PolicyRuleKind::Server, &server_entity))
+                .map(|(key, rule)| PolicyRuleMatch::new(room_id, key, rule))
+        })
+    }
+
+    /// Returns the rule that bans the given room ID or alias, if any.
+    pub fn room_ban(&self, room: &RoomOrAliasId) -> Option<PolicyRuleMatch<'_>> {
+        self.find_ban(PolicyRuleKind::Room, room.as_str())
+    }
+
+    /// Returns the rule that bans the given server, if any.
+    pub fn server_ban(&self, server_name: &ServerName) -> Option<PolicyRuleMatch<'_>> {
+        self.find_ban(PolicyRuleKind::Server, server_name.as_str())
+    }
+
+    /// Returns the first rule of the given type that bans the given entity, if any.
+    fn find_ban(&self, kind: PolicyRuleKind, entity: &str) -> Option<PolicyRuleMatch<'_>> {
+        let entity = Entity::new(entity);
+
+        self.lists.iter().find_map(|(room_id, rules)| {
+            rules
+                .find_ban(kind, &entity)
+                .map(|(key, rule)| PolicyRuleMatch::new(room_id, key, rule))
+        })
+    }
+
+    /// The rules of the policy list room with the given ID, if any.
+    fn rules(&self, room_id: &RoomId) -> Option<&PolicyRules> {
+        self.lists.iter().find(|(id, _)| id == room_id).map(|(_, rules)| rules)
+    }
+
+    /// The rules of the policy list room with the given ID, if any.
+    fn rules_mut_if_exists(&mut self, room_id: &RoomId) -> Option<&mut PolicyRules> {
+        self.lists.iter_mut().find(|(id, _)| id == room_id).map(|(_, rules)| rules)
+    }
+
+    /// The rules of the policy list room with the given ID, inserting it with the lowest priority
+    /// if it is unknown.
+    fn rules_mut(&mut self, room_id: &RoomId) -> &mut PolicyRules {
+        let index = match self.lists.iter().position(|(id, _)| id == room_id) {
+            Some(index) => index,
+            None => {
+                self.lists.push((room_id.to_owned(), PolicyRules::default()));
+                self.lists.len() - 1
+            }
+        };
+
+        &mut self.lists[index].1
+    }
+}
+
+/// The type of a policy rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum PolicyRuleKind {
+    /// An `m.policy.rule.user` rule.
+    User,
+
+    /// An `m.policy.rule.room` rule.
+    Room,
+
+    /// An `m.policy.rule.server` rule.
+    Server,
+}
+
+/// A policy rule that matched an entity.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct PolicyRuleMatch<'a> {
+    /// The ID of the policy list room of the rule.
+    pub room_id: &'a RoomId,
+
+    /// The type of the rule.
+    ///
+    /// This is [`PolicyRuleKind::Server`] if a user was banned by a server rule.
+    pub kind: PolicyRuleKind,
+
+    /// The state key of the rule.
+    pub state_key: &'a str,
+
+    /// The ID of the event of the rule.
+    pub event_id: &'a EventId,
+
+    /// The content of the rule.
+    pub rule: &'a PolicyRuleEventContent,
+}
+
+impl<'a> PolicyRuleMatch<'a> {
+    fn new(
+        room_id: &'a RoomId,
+        (kind, state_key): &'a (PolicyRuleKind, String),
+        rule: &'a PolicyRule,
+    ) -> Self {
+        Self { room_id, kind: *kind, state_key, event_id: &rule.event_id, rule: &rule.content }
+    }
+
+    /// The human-readable reason of the rule.
+    pub fn reason(&self) -> &'a str {
+        &self.rule.reason
+    }
+}
+
+/// The rules of a policy list.
+#[derive(Clone, Debug, Default)]
+struct PolicyRules {
+    /// The rules, by type and state key.
+    rules: BTreeMap<(PolicyRuleKind, String), PolicyRule>,
+}
+
+impl PolicyRules {
+    /// Returns the first rule of the given type that bans the given entity, if any.
+    ///
+    /// The rules with a literal entity are checked before the rules with a glob.
+    fn find_ban(
+        &self,
+        kind: PolicyRuleKind,
+        entity: &Entity<'_>,
+    ) -> Option<(&(PolicyRuleKind, String), &PolicyRule)> {
+        let mut bans = self.rules.iter().filter(|((rule_kind, _), rule)| {
+            *rule_kind == kind && rule.content.recommendation == Recommendation::Ban
+        });
+
+        bans.clone()
+            .find(|(_, rule)| rule.matcher.matches_exactly(entity))
+            .or_else(|| bans.find(|(_, rule)| rule.matcher.matches_glob(entity)))
+    }
+}
+
+/// A policy rule.
+#[derive(Clone, Debug)]
+struct PolicyRule {
+    /// The ID of the event of the rule.
+    event_id: OwnedEventId,
+
+    /// The content of the rule.
+    content: PolicyRuleEventContent,
+
+    /// The compiled entity of the rule.
+    matcher: EntityMatcher,
+}
+
+impl PolicyRule {
+    fn new(event_id: OwnedEventId, content: PolicyRuleEventContent) -> Self {
+        let matcher = EntityMatcher::new(&content);
+        Self { event_id, content, matcher }
+    }
+}
+
+/// The compiled entity of a policy rule.
+#[derive(Clone, Debug)]
+enum EntityMatcher {
+    /// The entity doesn't contain glob characters.
+    Literal(String),
+
+    /// The entity contains glob characters.
+    Glob(WildMatch),
+
+    /// The entity is given as its SHA-256 hash.
+    #[cfg(feature = "unstable-msc4205")]
+    Sha256(Vec<u8>),
+
+    /// The rule doesn't have an entity, it doesn't match anything.
+    None,
+}
+
+impl EntityMatcher {
+    fn new(content: &PolicyRuleEventContent) -> Self {
+        #[cfg(feature = "unstable-msc4205")]
+        if content.entity.is_empty() {
+            if let Some(sha256) = content.hashes.as_ref().and_then(|hashes| hashes.sha256.as_ref())
+            {
+                return Self::Sha256(sha256.as_bytes().to_vec());
+            }
+        }
+
+        if content.entity.is_empty() {
+            Self::None
+        } else if content.entity.contains(['*', '?']) {
+            Self::Glob(WildMatch::new(&content.entity))
+        } else {
+            Self::Literal(content.entity.clone())
+        }
+    }
+
+    /// Whether this matcher matches the given entity without a glob.
+    fn matches_exactly(&self, entity: &Entity<'_>) -> bool {
+        match self {
+            Self::Literal(literal) => literal == entity.value,
+            #[cfg(feature = "unstable-msc4205")]
+            Self::Sha256(sha256) => *sha256 == entity.sha256,
+            _ => false,
+        }
+    }
+
+    /// Whether this matcher matches the given entity with a glob.
+    fn matches_glob(&self, entity: &Entity<'_>) -> bool {
+        match self {
+            Self::Glob(glob) => glob.matches(entity.value),
+            _ => false,
+        }
+    }
+}
+
+/// An entity to match against the policy rules.
+struct Entity<'a> {
+    /// The entity.
+    value: &'a str,
+
+    /// The SHA-256 hash of the entity.
+    #[cfg(feature = "unstable-msc4205")]
+    sha256: [u8; 32],
+}
+
+impl<'a> Entity<'a> {
+    fn new(value: &'a str) -> Self {
+        Self {
+            value,
+            #[cfg(feature = "unstable-msc4205")]
+            sha256: {
+                use sha2::Digest;
+                sha2::Sha256::digest(value.as_bytes()).into()
+            },
+        }
+    }
+}
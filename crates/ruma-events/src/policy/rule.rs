@@ -1,5 +1,7 @@
 //! Modules and types for events in the `m.policy.rule` namespace.
 
+#[cfg(feature = "unstable-msc4205")]
+use ruma_common::serde::Base64;
 use ruma_common::serde::StringEnum;
 use serde::{Deserialize, Serialize};
 
@@ -17,8 +19,23 @@ pub struct PolicyRuleEventContent {
     ///
     /// Glob characters `*` and `?` can be used to match zero or more characters or exactly one
     /// character respectively.
+    ///
+    /// With the `unstable-msc4205` cargo feature, this is empty if the rule uses `hashes` instead.
+    #[cfg_attr(
+        feature = "unstable-msc4205",
+        serde(default, skip_serializing_if = "String::is_empty")
+    )]
     pub entity: String,
 
+    /// The hashes of the entity affected by this rule, if it is not given in plain text.
+    ///
+    /// This uses the unstable format defined in [MSC4205].
+    ///
+    /// [MSC4205]: https://github.com/matrix-org/matrix-spec-proposals/pull/4205
+    #[cfg(feature = "unstable-msc4205")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<PolicyRuleEntityHashes>,
+
     /// The suggested action to take.
     pub recommendation: Recommendation,
 
@@ -29,7 +46,46 @@ pub struct PolicyRuleEventContent {
 impl PolicyRuleEventContent {
     /// Creates a new `PolicyRuleEventContent` with the given entity, recommendation and reason.
     pub fn new(entity: String, recommendation: Recommendation, reason: String) -> Self {
-        Self { entity, recommendation, reason }
+        Self {
+            entity,
+            #[cfg(feature = "unstable-msc4205")]
+            hashes: None,
+            recommendation,
+            reason,
+        }
+    }
+
+    /// Creates a new `PolicyRuleEventContent` with the given entity hashes, recommendation and
+    /// reason.
+    #[cfg(feature = "unstable-msc4205")]
+    pub fn with_hashes(
+        hashes: PolicyRuleEntityHashes,
+        recommendation: Recommendation,
+        reason: String,
+    ) -> Self {
+        Self { entity: String::new(), hashes: Some(hashes), recommendation, reason }
+    }
+}
+
+/// The hashes of the entity affected by a policy rule.
+///
+/// This uses the unstable format defined in [MSC4205].
+///
+/// [MSC4205]: https://github.com/matrix-org/matrix-spec-proposals/pull/4205
+#[cfg(feature = "unstable-msc4205")]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct PolicyRuleEntityHashes {
+    /// The SHA-256 hash of the entity.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<Base64>,
+}
+
+#[cfg(feature = "unstable-msc4205")]
+impl PolicyRuleEntityHashes {
+    /// Creates a new `PolicyRuleEntityHashes` with the given SHA-256 hash.
+    pub fn with_sha256(sha256: Base64) -> Self {
+        Self { sha256: Some(sha256) }
     }
 }
 
@@ -46,6 +102,15 @@ pub struct PossiblyRedactedPolicyRuleEventContent {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub entity: Option<String>,
 
+    /// The hashes of the entity affected by this rule, if it is not given in plain text.
+    ///
+    /// This uses the unstable format defined in [MSC4205].
+    ///
+    /// [MSC4205]: https://github.com/matrix-org/matrix-spec-proposals/pull/4205
+    #[cfg(feature = "unstable-msc4205")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<PolicyRuleEntityHashes>,
+
     /// The suggested action to take.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub recommendation: Option<Recommendation>,
@@ -48,6 +48,8 @@ mod tests {
     fn serialization() {
         let content = PolicyRuleRoomEventContent(PolicyRuleEventContent {
             entity: "#*:example.org".into(),
+            #[cfg(feature = "unstable-msc4205")]
+            hashes: None,
             reason: "undesirable content".into(),
             recommendation: Recommendation::Ban,
         });
@@ -61,6 +63,38 @@ mod tests {
         assert_eq!(to_json_value(content).unwrap(), json);
     }
 
+    #[test]
+    #[cfg(feature = "unstable-msc4205")]
+    fn hashes_serialization() {
+        use ruma_common::serde::Base64;
+
+        use crate::policy::rule::PolicyRuleEntityHashes;
+
+        let content = PolicyRuleRoomEventContent(PolicyRuleEventContent {
+            entity: String::new(),
+            hashes: Some(PolicyRuleEntityHashes {
+                sha256: Some(Base64::parse("9iEHLuf8cZ2Wg0w+Hep9JVsr06dJ5rXxbRBctyj+dds").unwrap()),
+            }),
+            reason: "undesirable content".into(),
+            recommendation: Recommendation::Ban,
+        });
+
+        let json = json!({
+            "hashes": { "sha256": "9iEHLuf8cZ2Wg0w+Hep9JVsr06dJ5rXxbRBctyj+dds" },
+            "reason": "undesirable content",
+            "recommendation": "m.ban"
+        });
+
+        assert_eq!(to_json_value(&content).unwrap(), json);
+
+        let content = from_json_value::<PolicyRuleRoomEventContent>(json).unwrap();
+        assert_eq!(content.0.entity, "");
+        assert_eq!(
+            content.0.hashes.unwrap().sha256.unwrap().encode(),
+            "9iEHLuf8cZ2Wg0w+Hep9JVsr06dJ5rXxbRBctyj+dds"
+        );
+    }
+
     #[test]
     fn deserialization() {
         let json = json!({
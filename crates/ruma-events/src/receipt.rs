@@ -2,6 +2,7 @@
 //!
 //! [`m.receipt`]: https://spec.matrix.org/latest/client-server-api/#mreceipt
 
+mod notification_counts;
 mod receipt_thread_serde;
 
 use std::{
@@ -15,6 +16,9 @@ use ruma_common::{
 use ruma_macros::{EventContent, StringEnum};
 use serde::{Deserialize, Serialize};
 
+pub use self::notification_counts::{
+    NotificationCounts, NotificationCountsCalculator, UnreadNotificationCounts,
+};
 use crate::PrivOwnedStr;
 
 /// The content of an `m.receipt` event.
@@ -0,0 +1,424 @@
+//! Computation of the unread notification counts of a room from its read receipts.
+
+use std::collections::BTreeMap;
+
+use js_int::{UInt, uint};
+use ruma_common::{EventId, OwnedEventId, OwnedUserId, UserId, push::Action, serde::Raw};
+use serde::Deserialize;
+
+use super::{ReceiptEventContent, ReceiptThread, ReceiptType};
+
+/// The number of unread notifications and highlights.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct NotificationCounts {
+    /// The number of unread events that trigger a notification.
+    pub notification_count: UInt,
+
+    /// The number of unread events that trigger a highlight.
+    pub highlight_count: UInt,
+}
+
+impl NotificationCounts {
+    /// Creates an empty `NotificationCounts`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether there are no unread notifications nor highlights.
+    pub fn is_empty(&self) -> bool {
+        self.notification_count == uint!(0) && self.highlight_count == uint!(0)
+    }
+
+    /// Add the given push actions of an unread event to these counts.
+    fn add(&mut self, actions: &[Action]) {
+        if actions.iter().any(Action::should_notify) {
+            self.notification_count += uint!(1);
+        }
+
+        if actions.iter().any(Action::is_highlight) {
+            self.highlight_count += uint!(1);
+        }
+    }
+}
+
+/// The unread notification counts of a room, split between the main timeline and the threads.
+///
+/// This is computed by a [`NotificationCountsCalculator`].
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct UnreadNotificationCounts {
+    /// The counts of the main timeline.
+    ///
+    /// This includes the events that are not in a thread, including thread roots.
+    pub main: NotificationCounts,
+
+    /// The counts of each thread, by thread root ID.
+    ///
+    /// Threads without unread notifications nor highlights are omitted.
+    pub threads: BTreeMap<OwnedEventId, NotificationCounts>,
+}
+
+impl UnreadNotificationCounts {
+    /// The counts of the whole room, regardless of threads.
+    ///
+    /// These are the counts to return to clients that didn't opt in to thread notifications.
+    pub fn total(&self) -> NotificationCounts {
+        self.threads.values().fold(self.main, |mut total, counts| {
+            total.notification_count += counts.notification_count;
+            total.highlight_count += counts.highlight_count;
+            total
+        })
+    }
+}
+
+/// Helper to compute the unread notification counts of a room for a user, according to the
+/// [threaded read receipts] rules.
+///
+/// The read receipts of the user must be added with [`add_receipts()`](Self::add_receipts) or
+/// [`add_receipt()`](Self::add_receipt) before the events of the room are pushed, in the order
+/// of the timeline, with [`push()`](Self::push) or [`push_raw()`](Self::push_raw).
+///
+/// The receipts of both the `m.read` and `m.read.private` types are used, and the most recent of
+/// them applies. An unthreaded receipt applies to the events of the main timeline and of all the
+/// threads, while a threaded receipt only applies to the events of its thread. Receipts for
+/// events that are not pushed are assumed to be for events older than the pushed events.
+///
+/// [threaded read receipts]: https://spec.matrix.org/latest/client-server-api/#threaded-read-receipts
+#[derive(Clone, Debug)]
+pub struct NotificationCountsCalculator {
+    /// The ID of the user.
+    user_id: OwnedUserId,
+
+    /// The threads of the read receipts of the user, by event ID.
+    receipts: BTreeMap<OwnedEventId, Vec<ReceiptThread>>,
+
+    /// The thread roots of the pushed events that are in a thread, by event ID.
+    thread_roots: BTreeMap<OwnedEventId, OwnedEventId>,
+
+    /// The counts computed so far.
+    counts: UnreadNotificationCounts,
+}
+
+impl NotificationCountsCalculator {
+    /// Creates a new `NotificationCountsCalculator` for the given user.
+    pub fn new(user_id: OwnedUserId) -> Self {
+        Self {
+            user_id,
+            receipts: BTreeMap::new(),
+            thread_roots: BTreeMap::new(),
+            counts: UnreadNotificationCounts::default(),
+        }
+    }
+
+    /// The ID of the user.
+    pub fn user_id(&self) -> &UserId {
+        &self.user_id
+    }
+
+    /// Add the read receipts of the user that are in the given content of an `m.receipt` event.
+    pub fn add_receipts(&mut self, content: &ReceiptEventContent) {
+        for (event_id, receipts) in content.iter() {
+            for receipt_type in [ReceiptType::Read, ReceiptType::ReadPrivate] {
+                let Some(receipt) =
+                    receipts.get(&receipt_type).and_then(|receipts| receipts.get(&self.user_id))
+                else {
+                    continue;
+                };
+
+                self.add_receipt(event_id.clone(), receipt.thread.clone());
+            }
+        }
+    }
+
+    /// Add a read receipt of the user for the given event in the given thread.
+    ///
+    /// Receipts with a custom thread are ignored.
+    pub fn add_receipt(&mut self, event_id: OwnedEventId, thread: ReceiptThread) {
+        if matches!(thread, ReceiptThread::_Custom(_)) {
+            return;
+        }
+
+        self.receipts.entry(event_id).or_default().push(thread);
+    }
+
+    /// Push the next event of the timeline of the room.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_id` - The ID of the event.
+    /// * `thread_root` - The ID of the root of the thread that the event belongs to, if any. It
+    ///   should be `None` for events in the main timeline, including thread roots. Events that are
+    ///   related to an event in a thread, like reactions or edits, belong to the same thread.
+    /// * `actions` - The push actions that apply to the event for the user.
+    pub fn push(&mut self, event_id: &EventId, thread_root: Option<&EventId>, actions: &[Action]) {
+        match thread_root {
+            Some(thread_root) => {
+                self.thread_roots.insert(event_id.to_owned(), thread_root.to_owned());
+                self.counts.threads.entry(thread_root.to_owned()).or_default().add(actions);
+            }
+            None => self.counts.main.add(actions),
+        }
+
+        let Some(threads) = self.receipts.get(event_id) else {
+            return;
+        };
+
+        // The event was read, so all the previous events it applies to were read too.
+        for thread in threads {
+            match thread {
+                ReceiptThread::Unthreaded => {
+                    self.counts = UnreadNotificationCounts::default();
+                }
+                ReceiptThread::Main => {
+                    self.counts.main = NotificationCounts::default();
+                }
+                ReceiptThread::Thread(thread_root) => {
+                    self.counts.threads.remove(thread_root);
+                }
+                ReceiptThread::_Custom(_) => {}
+            }
+        }
+    }
+
+    /// Push the next event of the timeline of the room, as raw JSON.
+    ///
+    /// The ID of the event and the thread that it belongs to are read from the JSON. An event
+    /// belongs to a thread if it has an `m.thread` relation, or if it is related to an event in a
+    /// thread that was pushed before, for example with a reaction, an edit or a reply. If the
+    /// event doesn't have a valid ID, it is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The raw JSON of the event.
+    /// * `actions` - The push actions that apply to the event for the user.
+    pub fn push_raw<T>(&mut self, event: &Raw<T>, actions: &[Action]) {
+        let Ok(event) = event.deserialize_as_unchecked::<EventThreadDeHelper>() else {
+            return;
+        };
+
+        let thread_root =
+            event.content.and_then(|content| content.relates_to).and_then(|relates_to| {
+                if relates_to.rel_type.as_deref() == Some("m.thread") {
+                    return relates_to.event_id;
+                }
+
+                // The event is in the same thread as the event it relates to.
+                let related_event_id = relates_to
+                    .event_id
+                    .or_else(|| relates_to.in_reply_to.map(|in_reply_to| in_reply_to.event_id))?;
+                self.thread_roots.get(&related_event_id).cloned()
+            });
+
+        self.push(&event.event_id, thread_root.as_deref(), actions);
+    }
+
+    /// The counts computed from the events pushed so far.
+    pub fn counts(&self) -> &UnreadNotificationCounts {
+        &self.counts
+    }
+
+    /// Consume this calculator and return the computed counts.
+    pub fn finish(self) -> UnreadNotificationCounts {
+        self.counts
+    }
+}
+
+/// Helper type to deserialize the thread of an event.
+#[derive(Deserialize)]
+struct EventThreadDeHelper {
+    event_id: OwnedEventId,
+    content: Option<EventContentThreadDeHelper>,
+}
+
+/// Helper type to deserialize the thread of the content of an event.
+#[derive(Deserialize)]
+struct EventContentThreadDeHelper {
+    #[serde(rename = "m.relates_to")]
+    relates_to: Option<RelatesToThreadDeHelper>,
+}
+
+/// Helper type to deserialize the thread relation of an event.
+#[derive(Deserialize)]
+struct RelatesToThreadDeHelper {
+    rel_type: Option<String>,
+    event_id: Option<OwnedEventId>,
+    #[serde(rename = "m.in_reply_to")]
+    in_reply_to: Option<InReplyToThreadDeHelper>,
+}
+
+/// Helper type to deserialize the replied-to event of an event.
+#[derive(Deserialize)]
+struct InReplyToThreadDeHelper {
+    event_id: OwnedEventId,
+}
+
+#[cfg(test)]
+mod tests {
+    use js_int::uint;
+    use ruma_common::{
+        event_id, owned_event_id, owned_user_id,
+        push::{Action, Tweak},
+        serde::Raw,
+    };
+    use serde_json::{from_value as from_json_value, json};
+
+    use super::{NotificationCounts, NotificationCountsCalculator};
+    use crate::receipt::{ReceiptEventContent, ReceiptThread};
+
+    fn counts(notification_count: u32, highlight_count: u32) -> NotificationCounts {
+        NotificationCounts {
+            notification_count: notification_count.into(),
+            highlight_count: highlight_count.into(),
+        }
+    }
+
+    #[test]
+    fn unthreaded_receipts() {
+        let notify = [Action::Notify];
+        let highlight = [Action::Notify, Action::SetTweak(Tweak::Highlight(true))];
+
+        let receipts = from_json_value::<ReceiptEventContent>(json!({
+            "$2": { "m.read": { "@alice:localhost": {} } },
+            "$3": { "m.read.private": { "@alice:localhost": {} } },
+            "$5": { "m.read": { "@bob:localhost": {} } },
+        }))
+        .unwrap();
+
+        let mut calculator = NotificationCountsCalculator::new(owned_user_id!("@alice:localhost"));
+        calculator.add_receipts(&receipts);
+
+        calculator.push(event_id!("$1"), None, &notify);
+        calculator.push(event_id!("$2"), None, &highlight);
+        calculator.push(event_id!("$3"), Some(event_id!("$1")), &notify);
+        calculator.push(event_id!("$4"), Some(event_id!("$1")), &highlight);
+        calculator.push(event_id!("$5"), None, &notify);
+        calculator.push(event_id!("$6"), None, &[]);
+
+        let counts_ = calculator.finish();
+        assert_eq!(counts_.main, counts(1, 0));
+        assert_eq!(counts_.threads.len(), 1);
+        assert_eq!(counts_.threads[event_id!("$1")], counts(1, 1));
+        assert_eq!(counts_.total(), counts(2, 1));
+    }
+
+    #[test]
+    fn threaded_receipts() {
+        let notify = [Action::Notify];
+
+        let mut calculator = NotificationCountsCalculator::new(owned_user_id!("@alice:localhost"));
+        calculator.add_receipt(owned_event_id!("$root"), ReceiptThread::Main);
+        calculator.add_receipt(
+            owned_event_id!("$thread_2"),
+            ReceiptThread::Thread(owned_event_id!("$root")),
+        );
+
+        let events = [
+            json!({ "event_id": "$root", "content": { "body": "Root" } }),
+            json!({
+                "event_id": "$thread_1",
+                "content": {
+                    "m.relates_to": { "rel_type": "m.thread", "event_id": "$root" },
+                },
+            }),
+            json!({ "event_id": "$main", "content": {} }),
+            json!({
+                "event_id": "$thread_2",
+                "content": {
+                    "m.relates_to": { "rel_type": "m.thread", "event_id": "$root" },
+                },
+            }),
+            json!({
+                "event_id": "$reply",
+                "content": {
+                    "m.relates_to": { "m.in_reply_to": { "event_id": "$main" } },
+                },
+            }),
+            json!({
+                "event_id": "$thread_3",
+                "content": {
+                    "m.relates_to": { "rel_type": "m.thread", "event_id": "$root" },
+                },
+            }),
+        ];
+
+        for event in events {
+            calculator.push_raw(&Raw::new(&event).unwrap(), &notify);
+        }
+
+        let counts_ = calculator.counts();
+        assert_eq!(counts_.main, counts(2, 0));
+        assert_eq!(counts_.threads[event_id!("$root")], counts(1, 0));
+
+        // An unthreaded receipt resets all the counts.
+        calculator.add_receipt(owned_event_id!("$last"), ReceiptThread::Unthreaded);
+        calculator.push(event_id!("$last"), None, &notify);
+
+        let counts_ = calculator.finish();
+        assert!(counts_.main.is_empty());
+        assert!(counts_.threads.is_empty());
+        assert_eq!(counts_.total().notification_count, uint!(0));
+    }
+
+    #[test]
+    fn relations_to_threaded_events() {
+        let notify = [Action::Notify];
+
+        let mut calculator = NotificationCountsCalculator::new(owned_user_id!("@alice:localhost"));
+        calculator.add_receipt(owned_event_id!("$root"), ReceiptThread::Main);
+
+        let events = [
+            json!({ "event_id": "$root", "content": { "body": "Root" } }),
+            json!({
+                "event_id": "$thread",
+                "content": {
+                    "m.relates_to": { "rel_type": "m.thread", "event_id": "$root" },
+                },
+            }),
+            // A reaction to an event in the thread is in the thread.
+            json!({
+                "event_id": "$reaction",
+                "content": {
+                    "m.relates_to": {
+                        "rel_type": "m.annotation",
+                        "event_id": "$thread",
+                        "key": "👍",
+                    },
+                },
+            }),
+            // An edit of an event in the thread is in the thread.
+            json!({
+                "event_id": "$edit",
+                "content": {
+                    "m.relates_to": { "rel_type": "m.replace", "event_id": "$thread" },
+                },
+            }),
+            // A reply to a reaction in the thread is in the thread too.
+            json!({
+                "event_id": "$reply",
+                "content": {
+                    "m.relates_to": { "m.in_reply_to": { "event_id": "$reaction" } },
+                },
+            }),
+            // A reaction to the thread root is in the main timeline.
+            json!({
+                "event_id": "$root_reaction",
+                "content": {
+                    "m.relates_to": {
+                        "rel_type": "m.annotation",
+                        "event_id": "$root",
+                        "key": "👍",
+                    },
+                },
+            }),
+        ];
+
+        for event in events {
+            calculator.push_raw(&Raw::new(&event).unwrap(), &notify);
+        }
+
+        let counts_ = calculator.finish();
+        assert_eq!(counts_.main, counts(1, 0));
+        assert_eq!(counts_.threads[event_id!("$root")], counts(4, 0));
+    }
+}
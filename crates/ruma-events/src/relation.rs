@@ -13,7 +13,14 @@ use serde::{Deserialize, Serialize};
 
 use crate::{AnySyncMessageLikeEvent, PrivOwnedStr};
 
+mod aggregation;
 mod rel_serde;
+mod replacement;
+
+pub use self::{
+    aggregation::BundledRelationsAggregator,
+    replacement::{ReplaceableEventContent, ReplacementError, validate_replacement},
+};
 
 /// Information about the event a [rich reply] is replying to.
 ///
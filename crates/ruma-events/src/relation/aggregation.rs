@@ -0,0 +1,312 @@
+//! Server-side aggregation of the related events of an event into bundled relations.
+
+use js_int::{UInt, uint};
+use ruma_common::{OwnedUserId, UserId, serde::Raw};
+
+use super::{
+    BundledMessageLikeRelations, BundledReference, BundledStateRelations, BundledThread,
+    ReferenceChunk, RelationType,
+    replacement::{EventFields, check_replacement},
+};
+use crate::{AnySyncMessageLikeEvent, AnySyncTimelineEvent};
+
+/// An aggregator of the events related to a parent event, to compute the [bundled aggregations]
+/// of the parent event, as a homeserver would put them in its `unsigned.m.relations` field.
+///
+/// The related events should be added in topological order, because the latest event of a thread
+/// is the last one that was added. Only the events that are valid according to the spec are taken
+/// into account:
+///
+/// * The selected replacement is the latest replacement, by `origin_server_ts` then by event ID,
+///   that is valid according to [`validate_replacement()`]. Replacements are ignored if the parent
+///   is redacted.
+/// * The thread summary includes the events with a thread relation to the parent.
+/// * The references are listed in the order in which they were added.
+///
+/// [bundled aggregations]: https://spec.matrix.org/latest/client-server-api/#aggregations-of-child-events
+/// [`validate_replacement()`]: super::validate_replacement
+#[derive(Clone, Debug)]
+pub struct BundledRelationsAggregator {
+    /// The fields of the parent event.
+    parent: EventFields,
+
+    /// The ID of the user that the relations are computed for.
+    current_user_id: OwnedUserId,
+
+    /// The latest valid replacement, with its fields.
+    replace: Option<(EventFields, Raw<AnySyncMessageLikeEvent>)>,
+
+    /// The latest event in the thread.
+    thread_latest_event: Option<Raw<AnySyncMessageLikeEvent>>,
+
+    /// The number of events in the thread.
+    thread_count: UInt,
+
+    /// Whether the current user sent an event in the thread.
+    thread_current_user_participated: bool,
+
+    /// The references to the parent event.
+    references: Vec<BundledReference>,
+}
+
+impl BundledRelationsAggregator {
+    /// Creates a new `BundledRelationsAggregator` for the given parent event, as seen by the given
+    /// user.
+    ///
+    /// Returns an error if the parent event doesn't have the fields required of a timeline event.
+    pub fn new(
+        parent: &Raw<AnySyncTimelineEvent>,
+        current_user_id: &UserId,
+    ) -> serde_json::Result<Self> {
+        Ok(Self {
+            parent: parent.deserialize_as_unchecked()?,
+            current_user_id: current_user_id.to_owned(),
+            replace: None,
+            thread_latest_event: None,
+            thread_count: uint!(0),
+            thread_current_user_participated: false,
+            references: Vec::new(),
+        })
+    }
+
+    /// Add the given event.
+    ///
+    /// Returns `Ok(true)` if the event was aggregated, `Ok(false)` if it is not a valid relation
+    /// to the parent event, and an error if it doesn't have the fields required of a timeline
+    /// event.
+    pub fn add_event(&mut self, event: &Raw<AnySyncTimelineEvent>) -> serde_json::Result<bool> {
+        let fields = event.deserialize_as_unchecked::<EventFields>()?;
+
+        let Some(relates_to) = &fields.content.relates_to else {
+            return Ok(false);
+        };
+
+        if relates_to.event_id.as_ref() != Some(&self.parent.event_id) || fields.is_redacted() {
+            return Ok(false);
+        }
+
+        match &relates_to.rel_type {
+            Some(RelationType::Replacement) => {
+                if !self.is_valid_replacement(&fields) {
+                    return Ok(false);
+                }
+
+                let is_latest = self.replace.as_ref().is_none_or(|(latest, _)| {
+                    (fields.origin_server_ts, &fields.event_id)
+                        > (latest.origin_server_ts, &latest.event_id)
+                });
+
+                if is_latest {
+                    self.replace = Some((fields, event.clone().cast_unchecked()));
+                }
+            }
+            Some(RelationType::Thread) => {
+                if fields.state_key.is_some() {
+                    return Ok(false);
+                }
+
+                self.thread_current_user_participated |= fields.sender == self.current_user_id;
+                self.thread_count = self.thread_count.saturating_add(uint!(1));
+                self.thread_latest_event = Some(event.clone().cast_unchecked());
+            }
+            Some(RelationType::Reference) => {
+                self.references.push(BundledReference::new(fields.event_id));
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Whether the event with the given fields is a valid replacement of the parent event.
+    fn is_valid_replacement(&self, replacement: &EventFields) -> bool {
+        !self.parent.is_redacted() && check_replacement(&self.parent, replacement).is_ok()
+    }
+
+    /// The thread summary, if the thread is not empty.
+    fn bundled_thread(&self) -> Option<Box<BundledThread>> {
+        let latest_event = self.thread_latest_event.clone()?;
+        let current_user_participated =
+            self.thread_current_user_participated || self.parent.sender == self.current_user_id;
+
+        Some(Box::new(BundledThread::new(
+            latest_event,
+            self.thread_count,
+            current_user_participated,
+        )))
+    }
+
+    /// The chunk of references, if there are references.
+    fn reference_chunk(&self) -> Option<Box<ReferenceChunk>> {
+        (!self.references.is_empty())
+            .then(|| Box::new(ReferenceChunk::new(self.references.clone())))
+    }
+
+    /// The bundled relations, for a message-like parent event.
+    pub fn message_like_relations(
+        &self,
+    ) -> BundledMessageLikeRelations<Raw<AnySyncMessageLikeEvent>> {
+        let mut relations = BundledMessageLikeRelations::new();
+        relations.replace = self.replace.as_ref().map(|(_, event)| Box::new(event.clone()));
+        relations.thread = self.bundled_thread();
+        relations.reference = self.reference_chunk();
+        relations
+    }
+
+    /// The bundled relations, for a state parent event.
+    pub fn state_relations(&self) -> BundledStateRelations {
+        let mut relations = BundledStateRelations::new();
+        relations.thread = self.bundled_thread();
+        relations.reference = self.reference_chunk();
+        relations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use js_int::uint;
+    use ruma_common::{serde::Raw, user_id};
+    use serde_json::{
+        Value as JsonValue, from_value as from_json_value, json, to_value as to_json_value,
+    };
+
+    use super::BundledRelationsAggregator;
+    use crate::AnySyncTimelineEvent;
+
+    fn event(
+        event_id: &str,
+        sender: &str,
+        ts: u64,
+        content: JsonValue,
+    ) -> Raw<AnySyncTimelineEvent> {
+        from_json_value(json!({
+            "type": "m.room.message",
+            "event_id": event_id,
+            "sender": sender,
+            "origin_server_ts": ts,
+            "content": content,
+        }))
+        .unwrap()
+    }
+
+    fn edit(event_id: &str, sender: &str, ts: u64, body: &str) -> Raw<AnySyncTimelineEvent> {
+        event(
+            event_id,
+            sender,
+            ts,
+            json!({
+                "msgtype": "m.text",
+                "body": format!("* {body}"),
+                "m.new_content": { "msgtype": "m.text", "body": body },
+                "m.relates_to": { "rel_type": "m.replace", "event_id": "$parent" },
+            }),
+        )
+    }
+
+    #[test]
+    fn replacements() {
+        let parent =
+            event("$parent", "@alice:localhost", 1, json!({ "msgtype": "m.text", "body": "hi" }));
+        let mut aggregator =
+            BundledRelationsAggregator::new(&parent, user_id!("@bob:localhost")).unwrap();
+
+        assert!(aggregator.add_event(&edit("$edit2", "@alice:localhost", 3, "second")).unwrap());
+        assert!(aggregator.add_event(&edit("$edit1", "@alice:localhost", 2, "first")).unwrap());
+        // Same timestamp, the event ID is used to break the tie.
+        assert!(aggregator.add_event(&edit("$edit3", "@alice:localhost", 3, "third")).unwrap());
+
+        // Invalid replacements.
+        assert!(!aggregator.add_event(&edit("$edit4", "@bob:localhost", 4, "bob")).unwrap());
+        let no_new_content = event(
+            "$edit5",
+            "@alice:localhost",
+            5,
+            json!({
+                "msgtype": "m.text",
+                "body": "* new",
+                "m.relates_to": { "rel_type": "m.replace", "event_id": "$parent" },
+            }),
+        );
+        assert!(!aggregator.add_event(&no_new_content).unwrap());
+
+        let relations = aggregator.message_like_relations();
+        let replace = relations.replace.unwrap();
+        assert_eq!(replace.get_field::<String>("event_id").unwrap().unwrap(), "$edit3");
+        assert!(relations.thread.is_none());
+        assert!(relations.reference.is_none());
+
+        // Replacements of redacted events are ignored.
+        let mut redacted_parent = parent.deserialize_as_unchecked::<JsonValue>().unwrap();
+        redacted_parent["unsigned"] = json!({ "redacted_because": {} });
+        let redacted_parent = from_json_value(redacted_parent).unwrap();
+        let mut aggregator =
+            BundledRelationsAggregator::new(&redacted_parent, user_id!("@bob:localhost")).unwrap();
+        assert!(!aggregator.add_event(&edit("$edit1", "@alice:localhost", 2, "first")).unwrap());
+    }
+
+    #[test]
+    fn threads_and_references() {
+        let thread_event = |event_id, sender| {
+            event(
+                event_id,
+                sender,
+                2,
+                json!({
+                    "msgtype": "m.text",
+                    "body": "in thread",
+                    "m.relates_to": { "rel_type": "m.thread", "event_id": "$parent" },
+                }),
+            )
+        };
+        let reference = |event_id| {
+            event(
+                event_id,
+                "@carl:localhost",
+                3,
+                json!({
+                    "msgtype": "m.text",
+                    "body": "reference",
+                    "m.relates_to": { "rel_type": "m.reference", "event_id": "$parent" },
+                }),
+            )
+        };
+
+        let parent =
+            event("$parent", "@alice:localhost", 1, json!({ "msgtype": "m.text", "body": "hi" }));
+        let mut aggregator =
+            BundledRelationsAggregator::new(&parent, user_id!("@bob:localhost")).unwrap();
+
+        assert!(aggregator.add_event(&thread_event("$thread1", "@carl:localhost")).unwrap());
+        assert!(aggregator.add_event(&thread_event("$thread2", "@dan:localhost")).unwrap());
+        assert!(aggregator.add_event(&reference("$ref1")).unwrap());
+        assert!(aggregator.add_event(&reference("$ref2")).unwrap());
+        // Unrelated event.
+        assert!(!aggregator.add_event(&parent).unwrap());
+
+        let relations = aggregator.message_like_relations();
+        let thread = relations.thread.unwrap();
+        assert_eq!(thread.count, uint!(2));
+        assert_eq!(
+            thread.latest_event.get_field::<String>("event_id").unwrap().unwrap(),
+            "$thread2"
+        );
+        assert!(!thread.current_user_participated);
+        assert_eq!(
+            to_json_value(relations.reference.unwrap()).unwrap(),
+            json!({ "chunk": [{ "event_id": "$ref1" }, { "event_id": "$ref2" }] })
+        );
+
+        // The current user participated in the thread.
+        assert!(aggregator.add_event(&thread_event("$thread3", "@bob:localhost")).unwrap());
+        assert!(aggregator.state_relations().thread.unwrap().current_user_participated);
+
+        // The sender of the root participates in the thread.
+        let aggregator =
+            BundledRelationsAggregator::new(&parent, user_id!("@alice:localhost")).unwrap();
+        assert!(aggregator.message_like_relations().is_empty());
+        let mut aggregator =
+            BundledRelationsAggregator::new(&parent, user_id!("@alice:localhost")).unwrap();
+        aggregator.add_event(&thread_event("$thread1", "@carl:localhost")).unwrap();
+        assert!(aggregator.message_like_relations().thread.unwrap().current_user_participated);
+    }
+}
@@ -0,0 +1,500 @@
+//! Validation and application of [event replacements].
+//!
+//! [event replacements]: https://spec.matrix.org/latest/client-server-api/#event-replacements
+
+use ruma_common::{
+    EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedUserId, UserId,
+    serde::{JsonObject, Raw},
+};
+use serde::{Deserialize, de::IgnoredAny};
+
+use super::{RelationType, Replacement};
+#[cfg(feature = "unstable-msc3381")]
+use crate::poll::unstable_start::{
+    NewUnstablePollStartEventContent, UnstablePollStartEventContent,
+};
+use crate::{
+    MessageLikeEventContent, OriginalMessageLikeEvent, OriginalSyncMessageLikeEvent,
+    room::message::Relation,
+};
+
+/// An event content that can be replaced by an [event replacement].
+///
+/// This is implemented for all the event contents that support a [`Replacement`] relation.
+///
+/// It is not implemented for [`RoomEncryptedEventContent`], because the `m.new_content` of an
+/// encrypted replacement is only available after decryption. Encrypted replacements must be
+/// decrypted before they are applied, although their relation can still be checked with
+/// [`validate_replacement()`].
+///
+/// [event replacement]: https://spec.matrix.org/latest/client-server-api/#event-replacements
+/// [`RoomEncryptedEventContent`]: crate::room::encrypted::RoomEncryptedEventContent
+pub trait ReplaceableEventContent {
+    /// The type of the new content of a replacement, in its `m.new_content` field.
+    type NewContent;
+
+    /// The replacement relation of this content, if it replaces another event.
+    fn replacement(&self) -> Option<&Replacement<Self::NewContent>>;
+
+    /// Apply the given new content from a replacement to this content.
+    ///
+    /// The relation of this content, if any, is preserved.
+    ///
+    /// This doesn't check that the replacement is valid, use [`validate_replacement()`] or the
+    /// `apply_replacement()` methods of the original event types for that.
+    fn apply_replacement(&mut self, new_content: Self::NewContent);
+}
+
+macro_rules! impl_replaceable_event_content {
+    ($( $(#[$attr:meta])* $content:ty => $new_content:ty, )*) => {
+        $(
+            $(#[$attr])*
+            impl ReplaceableEventContent for $content {
+                type NewContent = $new_content;
+
+                fn replacement(&self) -> Option<&Replacement<Self::NewContent>> {
+                    match &self.relates_to {
+                        Some(Relation::Replacement(replacement)) => Some(replacement),
+                        _ => None,
+                    }
+                }
+
+                fn apply_replacement(&mut self, new_content: Self::NewContent) {
+                    let relates_to = self.relates_to.take();
+                    *self = new_content.with_relation(relates_to);
+                }
+            }
+        )*
+    };
+}
+
+impl_replaceable_event_content! {
+    crate::room::message::RoomMessageEventContent
+        => crate::room::message::RoomMessageEventContentWithoutRelation,
+    crate::sticker::StickerEventContent => crate::sticker::StickerEventContentWithoutRelation,
+    #[cfg(feature = "unstable-msc1767")]
+    crate::message::MessageEventContent => crate::message::MessageEventContentWithoutRelation,
+    #[cfg(feature = "unstable-msc3954")]
+    crate::emote::EmoteEventContent => crate::emote::EmoteEventContentWithoutRelation,
+    #[cfg(feature = "unstable-msc3551")]
+    crate::file::FileEventContent => crate::file::FileEventContentWithoutRelation,
+    #[cfg(feature = "unstable-msc3552")]
+    crate::image::ImageEventContent => crate::image::ImageEventContentWithoutRelation,
+    #[cfg(feature = "unstable-msc3927")]
+    crate::audio::AudioEventContent => crate::audio::AudioEventContentWithoutRelation,
+    #[cfg(feature = "unstable-msc3553")]
+    crate::video::VideoEventContent => crate::video::VideoEventContentWithoutRelation,
+    #[cfg(feature = "unstable-msc3245")]
+    crate::voice::VoiceEventContent => crate::voice::VoiceEventContentWithoutRelation,
+    #[cfg(feature = "unstable-msc3488")]
+    crate::location::LocationEventContent => crate::location::LocationEventContentWithoutRelation,
+    #[cfg(feature = "unstable-msc3381")]
+    crate::poll::start::PollStartEventContent
+        => crate::poll::start::PollStartEventContentWithoutRelation,
+}
+
+#[cfg(feature = "unstable-msc3381")]
+impl ReplaceableEventContent for UnstablePollStartEventContent {
+    type NewContent = crate::poll::unstable_start::NewUnstablePollStartEventContentWithoutRelation;
+
+    fn replacement(&self) -> Option<&Replacement<Self::NewContent>> {
+        match self {
+            Self::New(_) => None,
+            Self::Replacement(content) => Some(&content.relates_to),
+        }
+    }
+
+    fn apply_replacement(&mut self, new_content: Self::NewContent) {
+        let relates_to = match self {
+            Self::New(content) => content.relates_to.take(),
+            Self::Replacement(_) => None,
+        };
+
+        let mut content = NewUnstablePollStartEventContent::new(new_content.poll_start);
+        content.text = new_content.text;
+        content.relates_to = relates_to;
+        *self = Self::New(content);
+    }
+}
+
+impl<C> OriginalMessageLikeEvent<C>
+where
+    C: MessageLikeEventContent + ReplaceableEventContent,
+    C::NewContent: Clone,
+{
+    /// Apply the given replacement to this event, if it is valid.
+    ///
+    /// Returns an error if the replacement is not a valid replacement of this event.
+    pub fn apply_replacement(&mut self, replacement: &Self) -> Result<(), ReplacementError> {
+        if self.room_id != replacement.room_id {
+            return Err(ReplacementError::RoomIdMismatch);
+        }
+
+        let new_content = check_typed_replacement(
+            (&self.event_id, &self.sender, &self.content),
+            (&replacement.sender, &replacement.content),
+        )?;
+        self.content.apply_replacement(new_content.clone());
+
+        Ok(())
+    }
+}
+
+impl<C> OriginalSyncMessageLikeEvent<C>
+where
+    C: MessageLikeEventContent + ReplaceableEventContent,
+    C::NewContent: Clone,
+{
+    /// Apply the given replacement to this event, if it is valid.
+    ///
+    /// The events are assumed to be in the same room.
+    ///
+    /// Returns an error if the replacement is not a valid replacement of this event.
+    pub fn apply_replacement(&mut self, replacement: &Self) -> Result<(), ReplacementError> {
+        let new_content = check_typed_replacement(
+            (&self.event_id, &self.sender, &self.content),
+            (&replacement.sender, &replacement.content),
+        )?;
+        self.content.apply_replacement(new_content.clone());
+
+        Ok(())
+    }
+}
+
+/// Check that the replacement with the given sender and content is a valid replacement of the
+/// event with the given ID, sender and content.
+///
+/// Returns the new content of the replacement.
+fn check_typed_replacement<'a, C: ReplaceableEventContent>(
+    (original_event_id, original_sender, original_content): (&EventId, &UserId, &C),
+    (replacement_sender, replacement_content): (&UserId, &'a C),
+) -> Result<&'a C::NewContent, ReplacementError> {
+    let replacement = replacement_content.replacement().ok_or(ReplacementError::NotAReplacement)?;
+
+    if replacement.event_id != original_event_id {
+        return Err(ReplacementError::EventIdMismatch);
+    }
+
+    if original_sender != replacement_sender {
+        return Err(ReplacementError::SenderMismatch);
+    }
+
+    if original_content.replacement().is_some() {
+        return Err(ReplacementError::OriginalIsReplacement);
+    }
+
+    Ok(&replacement.new_content)
+}
+
+/// Check that the given event is a valid replacement of the given original event, according to
+/// the [validity rules] of the spec.
+///
+/// The events can be of any kind, including sync events that don't have a `room_id` field, in
+/// which case they are assumed to be in the same room. Encrypted events should be checked after
+/// decryption, if the original event was decrypted the replacement must be decrypted too. If both
+/// events are encrypted, the `m.new_content` field of the replacement can't be checked.
+///
+/// Returns an error if the replacement is invalid or if the events don't have the fields required
+/// of an event.
+///
+/// [validity rules]: https://spec.matrix.org/latest/client-server-api/#validity-of-replacement-events
+pub fn validate_replacement<T, U>(
+    original: &Raw<T>,
+    replacement: &Raw<U>,
+) -> Result<(), ReplacementError> {
+    let original = original
+        .deserialize_as_unchecked::<EventFields>()
+        .map_err(|_| ReplacementError::InvalidEvent)?;
+    let replacement = replacement
+        .deserialize_as_unchecked::<EventFields>()
+        .map_err(|_| ReplacementError::InvalidEvent)?;
+
+    check_replacement(&original, &replacement)
+}
+
+/// Check that the event with the given replacement fields is a valid replacement of the event
+/// with the given original fields.
+pub(super) fn check_replacement(
+    original: &EventFields,
+    replacement: &EventFields,
+) -> Result<(), ReplacementError> {
+    let relates_to = replacement
+        .content
+        .relates_to
+        .as_ref()
+        .filter(|relates_to| relates_to.rel_type == Some(RelationType::Replacement))
+        .ok_or(ReplacementError::NotAReplacement)?;
+
+    if relates_to.event_id.as_ref() != Some(&original.event_id) {
+        return Err(ReplacementError::EventIdMismatch);
+    }
+
+    if original.room_id.is_some()
+        && replacement.room_id.is_some()
+        && original.room_id != replacement.room_id
+    {
+        return Err(ReplacementError::RoomIdMismatch);
+    }
+
+    if original.sender != replacement.sender {
+        return Err(ReplacementError::SenderMismatch);
+    }
+
+    if original.event_type != replacement.event_type {
+        return Err(ReplacementError::EventTypeMismatch);
+    }
+
+    if original.state_key.is_some() || replacement.state_key.is_some() {
+        return Err(ReplacementError::StateEvent);
+    }
+
+    if original
+        .content
+        .relates_to
+        .as_ref()
+        .is_some_and(|relates_to| relates_to.rel_type == Some(RelationType::Replacement))
+    {
+        return Err(ReplacementError::OriginalIsReplacement);
+    }
+
+    if replacement.event_type != "m.room.encrypted" {
+        let new_content =
+            replacement.content.new_content.as_ref().ok_or(ReplacementError::MissingNewContent)?;
+
+        if new_content.contains_key("m.relates_to") {
+            return Err(ReplacementError::NewContentHasRelation);
+        }
+    }
+
+    Ok(())
+}
+
+/// The reason why an event is not a valid replacement of another event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ReplacementError {
+    /// One of the events doesn't have the fields required of an event.
+    #[error("invalid event")]
+    InvalidEvent,
+
+    /// The replacement doesn't have an `m.replace` relation.
+    #[error("the event doesn't have an `m.replace` relation")]
+    NotAReplacement,
+
+    /// The replacement replaces another event.
+    #[error("the replacement doesn't relate to the original event")]
+    EventIdMismatch,
+
+    /// The events are not in the same room.
+    #[error("the replacement is not in the same room as the original event")]
+    RoomIdMismatch,
+
+    /// The events were not sent by the same user.
+    #[error("the replacement was not sent by the sender of the original event")]
+    SenderMismatch,
+
+    /// The events don't have the same type.
+    #[error("the replacement doesn't have the same type as the original event")]
+    EventTypeMismatch,
+
+    /// One of the events is a state event.
+    #[error("state events can't be replaced")]
+    StateEvent,
+
+    /// The original event is itself a replacement.
+    #[error("the original event is itself a replacement")]
+    OriginalIsReplacement,
+
+    /// The replacement doesn't have an `m.new_content` field.
+    #[error("the replacement doesn't have an `m.new_content` field")]
+    MissingNewContent,
+
+    /// The `m.new_content` of the replacement has an `m.relates_to` field.
+    #[error("the `m.new_content` of the replacement has an `m.relates_to` field")]
+    NewContentHasRelation,
+}
+
+/// The fields of an event that are necessary to validate and aggregate relations.
+#[derive(Clone, Debug, Deserialize)]
+pub(super) struct EventFields {
+    pub(super) event_id: OwnedEventId,
+    pub(super) room_id: Option<OwnedRoomId>,
+    pub(super) sender: OwnedUserId,
+    #[serde(rename = "type")]
+    pub(super) event_type: String,
+    pub(super) state_key: Option<IgnoredAny>,
+    pub(super) origin_server_ts: MilliSecondsSinceUnixEpoch,
+    #[serde(default)]
+    pub(super) content: ContentFields,
+    #[serde(default)]
+    pub(super) unsigned: UnsignedFields,
+}
+
+impl EventFields {
+    /// Whether the event is redacted.
+    pub(super) fn is_redacted(&self) -> bool {
+        self.unsigned.redacted_because.is_some()
+    }
+}
+
+/// The fields of the content of an event that are necessary to validate and aggregate relations.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(super) struct ContentFields {
+    #[serde(rename = "m.relates_to")]
+    pub(super) relates_to: Option<RelatesToFields>,
+    #[serde(rename = "m.new_content")]
+    pub(super) new_content: Option<JsonObject>,
+}
+
+/// The fields of the `m.relates_to` object that are necessary to validate and aggregate relations.
+#[derive(Clone, Debug, Deserialize)]
+pub(super) struct RelatesToFields {
+    pub(super) rel_type: Option<RelationType>,
+    pub(super) event_id: Option<OwnedEventId>,
+}
+
+/// The fields of the `unsigned` object that are necessary to aggregate relations.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(super) struct UnsignedFields {
+    pub(super) redacted_because: Option<IgnoredAny>,
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_matches;
+    use ruma_common::{owned_event_id, owned_user_id, serde::Raw};
+    use serde_json::{Value as JsonValue, from_value as from_json_value, json};
+
+    use super::{ReplacementError, validate_replacement};
+    use crate::{
+        AnySyncTimelineEvent, OriginalSyncMessageLikeEvent,
+        room::message::{Relation, RoomMessageEventContent},
+    };
+
+    fn original() -> JsonValue {
+        json!({
+            "type": "m.room.message",
+            "event_id": "$original",
+            "sender": "@alice:localhost",
+            "origin_server_ts": 1,
+            "content": {
+                "msgtype": "m.text",
+                "body": "Hello",
+                "m.relates_to": { "m.in_reply_to": { "event_id": "$replied" } },
+            },
+        })
+    }
+
+    fn replacement() -> JsonValue {
+        json!({
+            "type": "m.room.message",
+            "event_id": "$replacement",
+            "sender": "@alice:localhost",
+            "origin_server_ts": 2,
+            "content": {
+                "msgtype": "m.text",
+                "body": "* Hello world",
+                "m.new_content": { "msgtype": "m.text", "body": "Hello world" },
+                "m.relates_to": { "rel_type": "m.replace", "event_id": "$original" },
+            },
+        })
+    }
+
+    fn validate(original: JsonValue, replacement: JsonValue) -> Result<(), ReplacementError> {
+        let original = from_json_value::<Raw<AnySyncTimelineEvent>>(original).unwrap();
+        let replacement = from_json_value::<Raw<AnySyncTimelineEvent>>(replacement).unwrap();
+        validate_replacement(&original, &replacement)
+    }
+
+    #[test]
+    fn validate_replacements() {
+        validate(original(), replacement()).unwrap();
+
+        let mut invalid = replacement();
+        invalid["sender"] = "@bob:localhost".into();
+        assert_matches!(validate(original(), invalid), Err(ReplacementError::SenderMismatch));
+
+        let mut invalid = replacement();
+        invalid["type"] = "m.sticker".into();
+        assert_matches!(validate(original(), invalid), Err(ReplacementError::EventTypeMismatch));
+
+        let (mut original_in_room, mut invalid) = (original(), replacement());
+        original_in_room["room_id"] = "!a:localhost".into();
+        invalid["room_id"] = "!b:localhost".into();
+        assert_matches!(validate(original_in_room, invalid), Err(ReplacementError::RoomIdMismatch));
+
+        let mut invalid = replacement();
+        invalid["content"]["m.relates_to"]["event_id"] = "$other".into();
+        assert_matches!(validate(original(), invalid), Err(ReplacementError::EventIdMismatch));
+
+        let mut invalid = replacement();
+        invalid["content"]["m.new_content"]["m.relates_to"] =
+            json!({ "rel_type": "m.thread", "event_id": "$root" });
+        assert_matches!(
+            validate(original(), invalid),
+            Err(ReplacementError::NewContentHasRelation)
+        );
+
+        let mut invalid = replacement();
+        invalid["content"].as_object_mut().unwrap().remove("m.new_content");
+        assert_matches!(validate(original(), invalid), Err(ReplacementError::MissingNewContent));
+
+        assert_matches!(validate(original(), original()), Err(ReplacementError::NotAReplacement));
+
+        let mut edit_of_edit = replacement();
+        edit_of_edit["event_id"] = "$edit_of_edit".into();
+        edit_of_edit["content"]["m.relates_to"]["event_id"] = "$replacement".into();
+        assert_matches!(
+            validate(replacement(), edit_of_edit),
+            Err(ReplacementError::OriginalIsReplacement)
+        );
+
+        // The new content of encrypted replacements can't be checked.
+        let (mut encrypted_original, mut encrypted) = (original(), replacement());
+        encrypted_original["type"] = "m.room.encrypted".into();
+        encrypted["type"] = "m.room.encrypted".into();
+        encrypted["content"] = json!({
+            "algorithm": "m.megolm.v1.aes-sha2",
+            "ciphertext": "AwgAEnAC",
+            "m.relates_to": { "rel_type": "m.replace", "event_id": "$original" },
+        });
+        validate(encrypted_original, encrypted).unwrap();
+    }
+
+    #[test]
+    fn apply_replacement() {
+        let mut original =
+            from_json_value::<OriginalSyncMessageLikeEvent<RoomMessageEventContent>>(original())
+                .unwrap();
+        let replacement =
+            from_json_value::<OriginalSyncMessageLikeEvent<RoomMessageEventContent>>(replacement())
+                .unwrap();
+
+        let mut invalid = replacement.clone();
+        invalid.sender = owned_user_id!("@bob:localhost");
+        assert_matches!(
+            original.apply_replacement(&invalid),
+            Err(ReplacementError::SenderMismatch)
+        );
+        assert_eq!(original.content.body(), "Hello");
+
+        original.apply_replacement(&replacement).unwrap();
+        assert_eq!(original.content.body(), "Hello world");
+        // The relation of the original event is kept.
+        assert_matches!(original.content.relates_to, Some(Relation::Reply { in_reply_to }));
+        assert_eq!(in_reply_to.event_id, "$replied");
+
+        // Edits of edits are not allowed.
+        let mut edit = replacement.clone();
+        let edit_of_edit = RoomMessageEventContent::text_plain("Edit").make_replacement(&edit);
+        let edit_of_edit = OriginalSyncMessageLikeEvent {
+            content: edit_of_edit,
+            event_id: owned_event_id!("$edit_of_edit"),
+            ..replacement
+        };
+        assert_matches!(
+            edit.apply_replacement(&edit_of_edit),
+            Err(ReplacementError::OriginalIsReplacement)
+        );
+    }
+}
@@ -13,6 +13,8 @@ use serde::{Deserialize, Serialize, de};
 use zeroize::Zeroize;
 
 pub mod aliases;
+#[cfg(feature = "encrypted-attachments")]
+mod attachment;
 pub mod avatar;
 pub mod canonical_alias;
 pub mod create;
@@ -36,6 +38,9 @@ mod thumbnail_source_serde;
 pub mod tombstone;
 pub mod topic;
 
+#[cfg(feature = "encrypted-attachments")]
+pub use self::attachment::{AttachmentDecryptor, AttachmentEncryptor, AttachmentError};
+
 /// The source of a media file.
 #[derive(Clone, Debug, Serialize)]
 #[allow(clippy::exhaustive_enums)]
@@ -0,0 +1,475 @@
+//! Encryption and decryption of [encrypted attachments].
+//!
+//! [encrypted attachments]: https://spec.matrix.org/latest/client-server-api/#sending-encrypted-attachments
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Read, Write},
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use aes::Aes256;
+use ctr::{
+    Ctr128BE,
+    cipher::{KeyIvInit, StreamCipher},
+};
+use futures_io::AsyncRead;
+use rand::RngCore;
+use ruma_common::{OwnedMxcUri, serde::Base64};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use super::{EncryptedFile, EncryptedFileInit, JsonWebKeyInit};
+
+/// AES-256 in CTR mode, with a 128-bit big-endian counter.
+type Aes256Ctr = Ctr128BE<Aes256>;
+
+/// The size of an AES-256 key, in bytes.
+const KEY_SIZE: usize = 32;
+
+/// The size of the initialization vector, in bytes.
+const IV_SIZE: usize = 16;
+
+/// An encryptor of attachments.
+///
+/// It encrypts the data with a fresh key and initialization vector, as required by the version 2
+/// of the [encrypted attachments] protocol, and computes the SHA-256 hash of the ciphertext.
+///
+/// The data can be encrypted:
+///
+/// * With [`Read`] or [`AsyncRead`], if `T` implements it: the plaintext is read from `T` and the
+///   ciphertext is returned.
+/// * With [`Write`], if `T` implements it: the plaintext is encrypted and the ciphertext is written
+///   to `T`.
+///
+/// Once all the data was encrypted and uploaded, [`AttachmentEncryptor::finish()`] returns the
+/// [`EncryptedFile`] to use in the event.
+///
+/// [encrypted attachments]: https://spec.matrix.org/latest/client-server-api/#sending-encrypted-attachments
+pub struct AttachmentEncryptor<T> {
+    /// The inner reader or writer.
+    inner: T,
+
+    /// The key.
+    key: Zeroizing<[u8; KEY_SIZE]>,
+
+    /// The initialization vector.
+    iv: [u8; IV_SIZE],
+
+    /// The cipher.
+    cipher: Aes256Ctr,
+
+    /// The hash of the ciphertext.
+    sha256: Sha256,
+}
+
+impl<T> AttachmentEncryptor<T> {
+    /// Creates a new `AttachmentEncryptor` wrapping the given reader or writer, with a random key
+    /// and initialization vector.
+    pub fn new(inner: T) -> Self {
+        let mut rng = rand::thread_rng();
+
+        let mut key = Zeroizing::new([0; KEY_SIZE]);
+        rng.fill_bytes(&mut *key);
+
+        // Only the first 64 bits are random, the counter is in the last 64 bits and starts at
+        // zero, to avoid overflowing it.
+        let mut iv = [0; IV_SIZE];
+        rng.fill_bytes(&mut iv[..IV_SIZE / 2]);
+
+        let cipher = Aes256Ctr::new(&(*key).into(), &iv.into());
+
+        Self { inner, key, iv, cipher, sha256: Sha256::new() }
+    }
+
+    /// Gets a reference to the inner reader or writer.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Encrypt the given data in place and update the hash of the ciphertext.
+    fn encrypt(&mut self, data: &mut [u8]) {
+        self.cipher.apply_keystream(data);
+        self.sha256.update(data);
+    }
+
+    /// Consumes this `AttachmentEncryptor` and returns the [`EncryptedFile`] for the data
+    /// encrypted so far, with the given URL of the uploaded ciphertext, and the inner reader or
+    /// writer.
+    pub fn finish(self, url: OwnedMxcUri) -> (EncryptedFile, T) {
+        let key = JsonWebKeyInit {
+            kty: "oct".to_owned(),
+            key_ops: vec!["encrypt".to_owned(), "decrypt".to_owned()],
+            alg: "A256CTR".to_owned(),
+            k: Base64::new(self.key.to_vec()),
+            ext: true,
+        }
+        .into();
+        let hashes =
+            BTreeMap::from([("sha256".to_owned(), Base64::new(self.sha256.finalize().to_vec()))]);
+
+        let file = EncryptedFileInit {
+            url,
+            key,
+            iv: Base64::new(self.iv.to_vec()),
+            hashes,
+            v: "v2".to_owned(),
+        }
+        .into();
+
+        (file, self.inner)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AttachmentEncryptor<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AttachmentEncryptor").field("inner", &self.inner).finish_non_exhaustive()
+    }
+}
+
+impl<R: Read> Read for AttachmentEncryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.encrypt(&mut buf[..read]);
+        Ok(read)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AttachmentEncryptor<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let read = ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.encrypt(&mut buf[..read]);
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl<W: Write> Write for AttachmentEncryptor<W> {
+    /// Encrypt the given data and write it to the inner writer.
+    ///
+    /// All the data is always written to the inner writer. If an error occurs, the state of the
+    /// encryptor is inconsistent and it should not be used anymore.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = buf.to_vec();
+        self.encrypt(&mut data);
+        self.inner.write_all(&data)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A decryptor of attachments.
+///
+/// It decrypts the data with the key and initialization vector of an [`EncryptedFile`], and
+/// checks that the SHA-256 hash of the ciphertext matches the one of the `EncryptedFile`.
+///
+/// The data can be decrypted:
+///
+/// * With [`Read`] or [`AsyncRead`], if `T` implements it: the ciphertext is read from `T` and the
+///   plaintext is returned. When the end of the ciphertext is reached, an error of kind
+///   [`io::ErrorKind::InvalidData`] is returned if the hash doesn't match.
+/// * With [`Write`], if `T` implements it: the ciphertext is decrypted and the plaintext is written
+///   to `T`. [`AttachmentDecryptor::finish()`] must be called at the end of the ciphertext to check
+///   the hash.
+///
+/// Only the version 2 of the [encrypted attachments] protocol is supported.
+///
+/// [encrypted attachments]: https://spec.matrix.org/latest/client-server-api/#sending-encrypted-attachments
+pub struct AttachmentDecryptor<T> {
+    /// The inner reader or writer.
+    inner: T,
+
+    /// The cipher.
+    cipher: Aes256Ctr,
+
+    /// The hash of the ciphertext.
+    sha256: Sha256,
+
+    /// The expected hash of the ciphertext.
+    expected_sha256: Vec<u8>,
+}
+
+impl<T> AttachmentDecryptor<T> {
+    /// Creates a new `AttachmentDecryptor` wrapping the given reader or writer, to decrypt the
+    /// given file.
+    ///
+    /// Returns an error if the file uses an unsupported version or algorithm, if its key or
+    /// initialization vector is invalid, or if it doesn't have a SHA-256 hash.
+    pub fn new(inner: T, file: &EncryptedFile) -> Result<Self, AttachmentError> {
+        if file.v != "v2" {
+            return Err(AttachmentError::UnsupportedVersion(file.v.clone()));
+        }
+
+        if file.key.kty != "oct" || file.key.alg != "A256CTR" {
+            return Err(AttachmentError::UnsupportedAlgorithm(file.key.alg.clone()));
+        }
+
+        let key = <&[u8; KEY_SIZE]>::try_from(file.key.k.as_bytes())
+            .map_err(|_| AttachmentError::InvalidKeyLength)?;
+        let iv = <&[u8; IV_SIZE]>::try_from(file.iv.as_bytes())
+            .map_err(|_| AttachmentError::InvalidIvLength)?;
+        let expected_sha256 =
+            file.hashes.get("sha256").ok_or(AttachmentError::MissingHash)?.as_bytes().to_vec();
+
+        Ok(Self {
+            inner,
+            cipher: Aes256Ctr::new(key.into(), iv.into()),
+            sha256: Sha256::new(),
+            expected_sha256,
+        })
+    }
+
+    /// Gets a reference to the inner reader or writer.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Update the hash of the ciphertext and decrypt the given data in place.
+    fn decrypt(&mut self, data: &mut [u8]) {
+        self.sha256.update(&*data);
+        self.cipher.apply_keystream(data);
+    }
+
+    /// Check that the hash of the ciphertext decrypted so far matches the expected hash.
+    fn verify_hash(&self) -> Result<(), AttachmentError> {
+        if self.sha256.clone().finalize().as_slice() == self.expected_sha256 {
+            Ok(())
+        } else {
+            Err(AttachmentError::HashMismatch)
+        }
+    }
+
+    /// Consumes this `AttachmentDecryptor` and returns the inner reader or writer, after checking
+    /// the hash of the ciphertext decrypted so far.
+    pub fn finish(self) -> Result<T, AttachmentError> {
+        self.verify_hash()?;
+        Ok(self.inner)
+    }
+
+    /// Decrypt the given data that was just read, or check the hash if the end was reached.
+    fn after_read(&mut self, buf: &mut [u8], read: usize) -> io::Result<usize> {
+        if read == 0 && !buf.is_empty() {
+            self.verify_hash()
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        } else {
+            self.decrypt(&mut buf[..read]);
+        }
+
+        Ok(read)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AttachmentDecryptor<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AttachmentDecryptor").field("inner", &self.inner).finish_non_exhaustive()
+    }
+}
+
+impl<R: Read> Read for AttachmentDecryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.after_read(buf, read)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AttachmentDecryptor<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let read = ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        Poll::Ready(this.after_read(buf, read))
+    }
+}
+
+impl<W: Write> Write for AttachmentDecryptor<W> {
+    /// Decrypt the given data and write it to the inner writer.
+    ///
+    /// All the data is always written to the inner writer. If an error occurs, the state of the
+    /// decryptor is inconsistent and it should not be used anymore.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = Zeroizing::new(buf.to_vec());
+        self.decrypt(&mut data);
+        self.inner.write_all(&data)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// An error encountered when decrypting an attachment.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum AttachmentError {
+    /// The version of the encrypted attachments protocol is not supported.
+    #[error("unsupported encrypted attachment version `{0}`")]
+    UnsupportedVersion(String),
+
+    /// The key type or algorithm is not supported.
+    #[error("unsupported key algorithm `{0}`")]
+    UnsupportedAlgorithm(String),
+
+    /// The key doesn't have the length of an AES-256 key.
+    #[error("invalid key length, expected {KEY_SIZE} bytes")]
+    InvalidKeyLength,
+
+    /// The initialization vector doesn't have the length of an AES block.
+    #[error("invalid initialization vector length, expected {IV_SIZE} bytes")]
+    InvalidIvLength,
+
+    /// The file doesn't have a SHA-256 hash.
+    #[error("missing SHA-256 hash")]
+    MissingHash,
+
+    /// The SHA-256 hash of the ciphertext doesn't match the hash of the file.
+    #[error("the SHA-256 hash of the ciphertext doesn't match")]
+    HashMismatch,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Read, Write},
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use assert_matches2::assert_matches;
+    use futures_io::AsyncRead;
+    use ruma_common::{mxc_uri, serde::Base64};
+    use serde_json::{json, to_value as to_json_value};
+
+    use super::{AttachmentDecryptor, AttachmentEncryptor, AttachmentError};
+
+    const PLAINTEXT: &[u8] = b"It's a secret to everybody.";
+
+    #[test]
+    fn read_roundtrip() {
+        let mut encryptor = AttachmentEncryptor::new(PLAINTEXT);
+        let mut ciphertext = Vec::new();
+        encryptor.read_to_end(&mut ciphertext).unwrap();
+        assert_ne!(ciphertext, PLAINTEXT);
+
+        let (file, _) = encryptor.finish(mxc_uri!("mxc://localhost/encrypted").to_owned());
+
+        assert_eq!(file.iv.as_bytes().len(), 16);
+        assert_eq!(&file.iv.as_bytes()[8..], [0; 8]);
+        assert_eq!(file.key.k.as_bytes().len(), 32);
+        let mut json = to_json_value(&file).unwrap();
+        let object = json.as_object_mut().unwrap();
+        object.remove("iv");
+        object.remove("hashes");
+        object["key"].as_object_mut().unwrap().remove("k");
+        assert_eq!(
+            json,
+            json!({
+                "url": "mxc://localhost/encrypted",
+                "key": {
+                    "kty": "oct",
+                    "key_ops": ["encrypt", "decrypt"],
+                    "alg": "A256CTR",
+                    "ext": true,
+                },
+                "v": "v2",
+            })
+        );
+
+        let mut decryptor = AttachmentDecryptor::new(ciphertext.as_slice(), &file).unwrap();
+        let mut plaintext = Vec::new();
+        decryptor.read_to_end(&mut plaintext).unwrap();
+        assert_eq!(plaintext, PLAINTEXT);
+
+        // Tampered ciphertext.
+        ciphertext[0] ^= 1;
+        let mut decryptor = AttachmentDecryptor::new(ciphertext.as_slice(), &file).unwrap();
+        let error = decryptor.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn write_roundtrip() {
+        let mut encryptor = AttachmentEncryptor::new(Vec::new());
+        for chunk in PLAINTEXT.chunks(5) {
+            encryptor.write_all(chunk).unwrap();
+        }
+        let (file, ciphertext) = encryptor.finish(mxc_uri!("mxc://localhost/encrypted").to_owned());
+
+        let mut decryptor = AttachmentDecryptor::new(Vec::new(), &file).unwrap();
+        for chunk in ciphertext.chunks(7) {
+            decryptor.write_all(chunk).unwrap();
+        }
+        assert_eq!(decryptor.finish().unwrap(), PLAINTEXT);
+
+        // Truncated ciphertext.
+        let mut decryptor = AttachmentDecryptor::new(Vec::new(), &file).unwrap();
+        decryptor.write_all(&ciphertext[1..]).unwrap();
+        assert_matches!(decryptor.finish(), Err(AttachmentError::HashMismatch));
+    }
+
+    #[test]
+    fn async_read_roundtrip() {
+        fn read_to_end(mut reader: impl AsyncRead + Unpin) -> io::Result<Vec<u8>> {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut data = Vec::new();
+            let mut buf = [0; 8];
+
+            loop {
+                match Pin::new(&mut reader).poll_read(&mut cx, &mut buf) {
+                    Poll::Ready(Ok(0)) => return Ok(data),
+                    Poll::Ready(Ok(read)) => data.extend_from_slice(&buf[..read]),
+                    Poll::Ready(Err(error)) => return Err(error),
+                    Poll::Pending => unreachable!(),
+                }
+            }
+        }
+
+        let mut encryptor = AttachmentEncryptor::new(PLAINTEXT);
+        let ciphertext = read_to_end(&mut encryptor).unwrap();
+        let (file, _) = encryptor.finish(mxc_uri!("mxc://localhost/encrypted").to_owned());
+
+        let decryptor = AttachmentDecryptor::new(ciphertext.as_slice(), &file).unwrap();
+        assert_eq!(read_to_end(decryptor).unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn invalid_file() {
+        let mut encryptor = AttachmentEncryptor::new(PLAINTEXT);
+        encryptor.read_to_end(&mut Vec::new()).unwrap();
+        let (file, _) = encryptor.finish(mxc_uri!("mxc://localhost/encrypted").to_owned());
+
+        let mut v1_file = file.clone();
+        v1_file.v = "v1".to_owned();
+        assert_matches!(
+            AttachmentDecryptor::new(PLAINTEXT, &v1_file),
+            Err(AttachmentError::UnsupportedVersion(version))
+        );
+        assert_eq!(version, "v1");
+
+        let mut invalid_iv_file = file.clone();
+        invalid_iv_file.iv = Base64::new(vec![0; 8]);
+        assert_matches!(
+            AttachmentDecryptor::new(PLAINTEXT, &invalid_iv_file),
+            Err(AttachmentError::InvalidIvLength)
+        );
+
+        let mut no_hash_file = file;
+        no_hash_file.hashes.clear();
+        assert_matches!(
+            AttachmentDecryptor::new(PLAINTEXT, &no_hash_file),
+            Err(AttachmentError::MissingHash)
+        );
+    }
+}
@@ -10,7 +10,7 @@ use ruma_common::{
     serde::{JsonObject, StringEnum},
 };
 #[cfg(feature = "html")]
-use ruma_html::{HtmlSanitizerMode, RemoveReplyFallback, sanitize_html};
+use ruma_html::{Html, HtmlSanitizerMode, RemoveReplyFallback, sanitize_html};
 use ruma_macros::EventContent;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::Value as JsonValue;
@@ -32,6 +32,8 @@ mod gallery;
 mod image;
 mod key_verification_request;
 mod location;
+#[cfg(feature = "markdown")]
+mod markdown;
 mod media_caption;
 mod notice;
 mod relation;
@@ -50,6 +52,8 @@ pub use self::audio::{
 };
 #[cfg(feature = "unstable-msc4274")]
 pub use self::gallery::{GalleryItemType, GalleryMessageEventContent};
+#[cfg(feature = "markdown")]
+pub use self::markdown::{MarkdownWithMentions, MentionLinkFormat};
 #[cfg(feature = "unstable-msc4095")]
 pub use self::url_preview::{PreviewImage, PreviewImageSource, UrlPreview};
 pub use self::{
@@ -118,12 +122,32 @@ impl RoomMessageEventContent {
         Self::new(MessageType::text_html(body, html_body))
     }
 
+    /// A constructor to create an html message with a plain text body generated from the HTML.
+    ///
+    /// See [`Html::to_plain_text()`] for more details about the conversion.
+    #[cfg(feature = "html")]
+    pub fn text_html_only(html_body: impl Into<String>) -> Self {
+        let html_body = html_body.into();
+        let body = Html::parse(&html_body).to_plain_text();
+        Self::text_html(body, html_body)
+    }
+
     /// A constructor to create a markdown message.
     #[cfg(feature = "markdown")]
     pub fn text_markdown(body: impl AsRef<str> + Into<String>) -> Self {
         Self::new(MessageType::text_markdown(body))
     }
 
+    /// A constructor to create a markdown message with mentions.
+    ///
+    /// The user IDs and room aliases in the text are converted to `matrix.to` links, and the
+    /// mentioned users and `@room` are added to the [`Mentions`]. See
+    /// [`MarkdownWithMentions::parse()`] for more details.
+    #[cfg(feature = "markdown")]
+    pub fn text_markdown_with_mentions(body: impl AsRef<str> + Into<String>) -> Self {
+        Self::markdown_with_mentions(body, MessageType::text_plain, MessageType::text_html)
+    }
+
     /// A constructor to create a plain text notice.
     pub fn notice_plain(body: impl Into<String>) -> Self {
         Self::new(MessageType::notice_plain(body))
@@ -134,12 +158,32 @@ impl RoomMessageEventContent {
         Self::new(MessageType::notice_html(body, html_body))
     }
 
+    /// A constructor to create an html notice with a plain text body generated from the HTML.
+    ///
+    /// See [`Html::to_plain_text()`] for more details about the conversion.
+    #[cfg(feature = "html")]
+    pub fn notice_html_only(html_body: impl Into<String>) -> Self {
+        let html_body = html_body.into();
+        let body = Html::parse(&html_body).to_plain_text();
+        Self::notice_html(body, html_body)
+    }
+
     /// A constructor to create a markdown notice.
     #[cfg(feature = "markdown")]
     pub fn notice_markdown(body: impl AsRef<str> + Into<String>) -> Self {
         Self::new(MessageType::notice_markdown(body))
     }
 
+    /// A constructor to create a markdown notice with mentions.
+    ///
+    /// The user IDs and room aliases in the text are converted to `matrix.to` links, and the
+    /// mentioned users and `@room` are added to the [`Mentions`]. See
+    /// [`MarkdownWithMentions::parse()`] for more details.
+    #[cfg(feature = "markdown")]
+    pub fn notice_markdown_with_mentions(body: impl AsRef<str> + Into<String>) -> Self {
+        Self::markdown_with_mentions(body, MessageType::notice_plain, MessageType::notice_html)
+    }
+
     /// A constructor to create a plain text emote.
     pub fn emote_plain(body: impl Into<String>) -> Self {
         Self::new(MessageType::emote_plain(body))
@@ -150,12 +194,52 @@ impl RoomMessageEventContent {
         Self::new(MessageType::emote_html(body, html_body))
     }
 
+    /// A constructor to create an html emote with a plain text body generated from the HTML.
+    ///
+    /// See [`Html::to_plain_text()`] for more details about the conversion.
+    #[cfg(feature = "html")]
+    pub fn emote_html_only(html_body: impl Into<String>) -> Self {
+        let html_body = html_body.into();
+        let body = Html::parse(&html_body).to_plain_text();
+        Self::emote_html(body, html_body)
+    }
+
     /// A constructor to create a markdown emote.
     #[cfg(feature = "markdown")]
     pub fn emote_markdown(body: impl AsRef<str> + Into<String>) -> Self {
         Self::new(MessageType::emote_markdown(body))
     }
 
+    /// A constructor to create a markdown emote with mentions.
+    ///
+    /// The user IDs and room aliases in the text are converted to `matrix.to` links, and the
+    /// mentioned users and `@room` are added to the [`Mentions`]. See
+    /// [`MarkdownWithMentions::parse()`] for more details.
+    #[cfg(feature = "markdown")]
+    pub fn emote_markdown_with_mentions(body: impl AsRef<str> + Into<String>) -> Self {
+        Self::markdown_with_mentions(body, MessageType::emote_plain, MessageType::emote_html)
+    }
+
+    /// Create a message by parsing the Markdown in `body` with mentions.
+    ///
+    /// The `plain` and `html` constructors are used to create the message type, depending on
+    /// whether formatting or mentions were found.
+    #[cfg(feature = "markdown")]
+    fn markdown_with_mentions(
+        body: impl AsRef<str> + Into<String>,
+        plain: fn(String) -> MessageType,
+        html: fn(String, String) -> MessageType,
+    ) -> Self {
+        let MarkdownWithMentions { formatted, mentions } =
+            MarkdownWithMentions::parse(body.as_ref(), MentionLinkFormat::default());
+        let msgtype = match formatted {
+            Some(formatted) => html(body.into(), formatted.body),
+            None => plain(body.into()),
+        };
+
+        Self::new(msgtype).add_mentions(mentions)
+    }
+
     /// Turns `self` into a [rich reply] to the message using the given metadata.
     ///
     /// Sets the `in_reply_to` field inside `relates_to`, and optionally the `rel_type` to
@@ -757,6 +841,28 @@ impl FormattedBody {
         parse_markdown(body.as_ref()).map(Self::html)
     }
 
+    /// Convert this `FormattedBody` to Markdown if its format is `MessageFormat::Html`.
+    ///
+    /// This can be used to edit a message. The mention links are converted back to the plain user
+    /// IDs and room aliases. See [`Html::to_markdown()`] for more details.
+    ///
+    /// Returns `None` if the format is not `MessageFormat::Html`.
+    #[cfg(feature = "html")]
+    pub fn to_markdown(&self) -> Option<String> {
+        (self.format == MessageFormat::Html).then(|| Html::parse(&self.body).to_markdown())
+    }
+
+    /// Convert this `FormattedBody` to plain text if its format is `MessageFormat::Html`.
+    ///
+    /// This can be used to generate the plain text `body` of a message. See
+    /// [`Html::to_plain_text()`] for more details.
+    ///
+    /// Returns `None` if the format is not `MessageFormat::Html`.
+    #[cfg(feature = "html")]
+    pub fn to_plain_text(&self) -> Option<String> {
+        (self.format == MessageFormat::Html).then(|| Html::parse(&self.body).to_plain_text())
+    }
+
     /// Sanitize this `FormattedBody` if its format is `MessageFormat::Html`.
     ///
     /// This removes any [tags and attributes] that are not listed in the Matrix specification.
@@ -796,94 +902,122 @@ pub struct CustomEventContent {
 
 #[cfg(feature = "markdown")]
 pub(crate) fn parse_markdown(text: &str) -> Option<String> {
-    use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+    let markdown = MarkdownEvents::parse(text);
+
+    // If the string does not contain markdown, don't generate HTML.
+    markdown.has_markdown.then(|| markdown.into_html())
+}
 
-    const OPTIONS: Options = Options::ENABLE_TABLES.union(Options::ENABLE_STRIKETHROUGH);
+/// The events of parsed Markdown.
+#[cfg(feature = "markdown")]
+pub(crate) struct MarkdownEvents<'a> {
+    /// The parsed events.
+    pub(crate) events: Vec<pulldown_cmark::Event<'a>>,
 
-    let parser_events: Vec<_> = Parser::new_ext(text, OPTIONS)
-        .map(|event| match event {
-            Event::SoftBreak => Event::HardBreak,
-            _ => event,
-        })
-        .collect();
-
-    // Text that does not contain markdown syntax is always inline because when we encounter several
-    // blocks we convert them to HTML. Inline text is always wrapped by a single paragraph.
-    let first_event_is_paragraph_start =
-        parser_events.first().is_some_and(|event| matches!(event, Event::Start(Tag::Paragraph)));
-    let last_event_is_paragraph_end =
-        parser_events.last().is_some_and(|event| matches!(event, Event::End(TagEnd::Paragraph)));
-    let mut is_inline = first_event_is_paragraph_start && last_event_is_paragraph_end;
-    let mut has_markdown = !is_inline;
-
-    if !has_markdown {
-        // Check whether the events contain other blocks and whether they contain inline markdown
-        // syntax.
-        let mut pos = 0;
-
-        for event in parser_events.iter().skip(1) {
-            match event {
-                Event::Text(s) => {
-                    // If the string does not contain markdown, the only modification that should
-                    // happen is that newlines are converted to hardbreaks. It means that we should
-                    // find all the other characters from the original string in the text events.
-                    // Let's check that by walking the original string.
-                    if text[pos..].starts_with(s.as_ref()) {
-                        pos += s.len();
-                        continue;
+    /// Whether the content is inline, i.e. it is a single paragraph.
+    pub(crate) is_inline: bool,
+
+    /// Whether the text contains Markdown syntax.
+    pub(crate) has_markdown: bool,
+}
+
+#[cfg(feature = "markdown")]
+impl<'a> MarkdownEvents<'a> {
+    /// Parse the given Markdown text.
+    pub(crate) fn parse(text: &'a str) -> Self {
+        use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+        const OPTIONS: Options = Options::ENABLE_TABLES.union(Options::ENABLE_STRIKETHROUGH);
+
+        let parser_events: Vec<_> = Parser::new_ext(text, OPTIONS)
+            .map(|event| match event {
+                Event::SoftBreak => Event::HardBreak,
+                _ => event,
+            })
+            .collect();
+
+        // Text that does not contain markdown syntax is always inline because when we encounter
+        // several blocks we convert them to HTML. Inline text is always wrapped by a single
+        // paragraph.
+        let first_event_is_paragraph_start = parser_events
+            .first()
+            .is_some_and(|event| matches!(event, Event::Start(Tag::Paragraph)));
+        let last_event_is_paragraph_end = parser_events
+            .last()
+            .is_some_and(|event| matches!(event, Event::End(TagEnd::Paragraph)));
+        let mut is_inline = first_event_is_paragraph_start && last_event_is_paragraph_end;
+        let mut has_markdown = !is_inline;
+
+        if !has_markdown {
+            // Check whether the events contain other blocks and whether they contain inline
+            // markdown syntax.
+            let mut pos = 0;
+
+            for event in parser_events.iter().skip(1) {
+                match event {
+                    Event::Text(s) => {
+                        // If the string does not contain markdown, the only modification that
+                        // should happen is that newlines are converted to hardbreaks. It means
+                        // that we should find all the other characters from the original string
+                        // in the text events. Let's check that by walking the original string.
+                        if text[pos..].starts_with(s.as_ref()) {
+                            pos += s.len();
+                            continue;
+                        }
                     }
-                }
-                Event::HardBreak => {
-                    // A hard break happens when a newline is encountered, which is not necessarily
-                    // markdown syntax. Skip the newline in the original string for the walking
-                    // above to work.
-                    if text[pos..].starts_with("\r\n") {
-                        pos += 2;
-                        continue;
-                    } else if text[pos..].starts_with(['\r', '\n']) {
-                        pos += 1;
-                        continue;
+                    Event::HardBreak => {
+                        // A hard break happens when a newline is encountered, which is not
+                        // necessarily markdown syntax. Skip the newline in the original string
+                        // for the walking above to work.
+                        if text[pos..].starts_with("\r\n") {
+                            pos += 2;
+                            continue;
+                        } else if text[pos..].starts_with(['\r', '\n']) {
+                            pos += 1;
+                            continue;
+                        }
                     }
+                    // A paragraph end is fine because we would detect markdown from the paragraph
+                    // start.
+                    Event::End(TagEnd::Paragraph) => continue,
+                    // Any other event means there is markdown syntax.
+                    Event::Start(tag) => {
+                        is_inline &= !is_block_tag(tag);
+                    }
+                    _ => {}
                 }
-                // A paragraph end is fine because we would detect markdown from the paragraph
-                // start.
-                Event::End(TagEnd::Paragraph) => continue,
-                // Any other event means there is markdown syntax.
-                Event::Start(tag) => {
-                    is_inline &= !is_block_tag(tag);
-                }
-                _ => {}
-            }
 
-            has_markdown = true;
+                has_markdown = true;
 
-            // Stop when we also know that there are several blocks.
-            if !is_inline {
-                break;
+                // Stop when we also know that there are several blocks.
+                if !is_inline {
+                    break;
+                }
             }
-        }
 
-        // If we are not at the end of the string, some characters were removed.
-        has_markdown |= pos != text.len();
-    }
+            // If we are not at the end of the string, some characters were removed.
+            has_markdown |= pos != text.len();
+        }
 
-    // If the string does not contain markdown, don't generate HTML.
-    if !has_markdown {
-        return None;
+        Self { events: parser_events, is_inline, has_markdown }
     }
 
-    let mut events_iter = parser_events.into_iter();
+    /// Convert the events to HTML.
+    pub(crate) fn into_html(self) -> String {
+        let mut events_iter = self.events.into_iter();
 
-    // If the content is inline, remove the wrapping paragraph, as instructed by the Matrix spec.
-    if is_inline {
-        events_iter.next();
-        events_iter.next_back();
-    }
+        // If the content is inline, remove the wrapping paragraph, as instructed by the Matrix
+        // spec.
+        if self.is_inline {
+            events_iter.next();
+            events_iter.next_back();
+        }
 
-    let mut html_body = String::new();
-    pulldown_cmark::html::push_html(&mut html_body, events_iter);
+        let mut html_body = String::new();
+        pulldown_cmark::html::push_html(&mut html_body, events_iter);
 
-    Some(html_body)
+        html_body
+    }
 }
 
 /// Whether the given tag is a block HTML element.
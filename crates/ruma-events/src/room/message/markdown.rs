@@ -0,0 +1,283 @@
+//! Markdown parsing with support for Matrix mentions.
+
+use pulldown_cmark::{CowStr, Event, LinkType, Tag, TagEnd};
+use ruma_common::{MatrixToUri, MatrixUri, OwnedRoomAliasId, OwnedUserId, matrix_uri::MatrixId};
+
+use super::{FormattedBody, MarkdownEvents};
+use crate::Mentions;
+
+/// The format of the links generated for mentions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum MentionLinkFormat {
+    /// A `https://matrix.to` URI.
+    #[default]
+    MatrixTo,
+
+    /// A `matrix:` URI.
+    MatrixUri,
+}
+
+/// The formatted body and the mentions of a message, parsed from Markdown.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct MarkdownWithMentions {
+    /// The HTML-formatted body, if the Markdown contains formatting or mentions.
+    pub formatted: Option<FormattedBody>,
+
+    /// The mentions found in the Markdown.
+    pub mentions: Mentions,
+}
+
+impl MarkdownWithMentions {
+    /// Parse the Markdown in `body`, detecting Matrix mentions.
+    ///
+    /// The following mentions are detected in the text:
+    ///
+    /// * User IDs, like `@user:example.org`, are converted to links with the given format and are
+    ///   added to the user mentions.
+    /// * Room aliases, like `#room:example.org`, are converted to links with the given format.
+    /// * `@room` is a mention of the whole room.
+    ///
+    /// The text in code and in links is ignored, but links to users in the Markdown are added to
+    /// the user mentions.
+    pub fn parse(body: &str, link_format: MentionLinkFormat) -> Self {
+        let mut markdown = MarkdownEvents::parse(body);
+        let mut mentions = Mentions::new();
+        let mut has_links = false;
+
+        let mut events = Vec::with_capacity(markdown.events.len());
+        // The number of elements whose text should not be scanned that we are in.
+        let mut ignore_depth = 0_usize;
+        let mut events_iter = std::mem::take(&mut markdown.events).into_iter().peekable();
+
+        while let Some(event) = events_iter.next() {
+            match event {
+                Event::Start(Tag::Link { ref dest_url, .. }) => {
+                    mentions.user_ids.extend(linked_user_id(dest_url));
+                    ignore_depth += 1;
+                    events.push(event);
+                }
+                Event::Start(Tag::CodeBlock(_) | Tag::Image { .. }) => {
+                    ignore_depth += 1;
+                    events.push(event);
+                }
+                Event::End(TagEnd::Link | TagEnd::CodeBlock | TagEnd::Image) => {
+                    ignore_depth = ignore_depth.saturating_sub(1);
+                    events.push(event);
+                }
+                Event::Text(text) if ignore_depth == 0 => {
+                    // Merge consecutive text events, because a mention could be split between
+                    // them.
+                    let mut text = text.into_string();
+                    while let Some(Event::Text(next)) =
+                        events_iter.next_if(|event| matches!(event, Event::Text(_)))
+                    {
+                        text.push_str(&next);
+                    }
+
+                    has_links |=
+                        push_text_with_mentions(&text, link_format, &mut events, &mut mentions);
+                }
+                _ => events.push(event),
+            }
+        }
+
+        markdown.events = events;
+
+        // If the string does not contain markdown or mentions, don't generate HTML.
+        let formatted =
+            (markdown.has_markdown || has_links).then(|| FormattedBody::html(markdown.into_html()));
+
+        Self { formatted, mentions }
+    }
+}
+
+/// A mention found in text.
+enum TextMention {
+    /// A mention of the whole room.
+    Room,
+
+    /// A mention of a user.
+    User(OwnedUserId),
+
+    /// A mention of a room alias.
+    RoomAlias(OwnedRoomAliasId),
+}
+
+/// Push the given text to the given events, replacing the user IDs and room aliases with links.
+///
+/// The mentions are added to `mentions`. Returns whether links were added.
+fn push_text_with_mentions(
+    text: &str,
+    link_format: MentionLinkFormat,
+    events: &mut Vec<Event<'_>>,
+    mentions: &mut Mentions,
+) -> bool {
+    let mut has_links = false;
+    let mut pos = 0;
+    let mut last_end = 0;
+
+    while let Some(offset) = text[pos..].find(['@', '#']) {
+        let start = pos + offset;
+        pos = start + 1;
+
+        // A mention must be at the start of a word.
+        if text[..start].chars().next_back().is_some_and(is_mention_char) {
+            continue;
+        }
+
+        let end = text[pos..].find(|c| !is_mention_char(c)).map_or(text.len(), |i| pos + i);
+        // Punctuation at the end of the mention is likely not part of it.
+        let candidate = text[start..end].trim_end_matches(['.', ':']);
+
+        let Some(mention) = parse_mention(candidate) else {
+            continue;
+        };
+
+        let uri = match mention {
+            TextMention::Room => {
+                mentions.room = true;
+                continue;
+            }
+            TextMention::User(user_id) => {
+                let uri = match link_format {
+                    MentionLinkFormat::MatrixTo => user_id.matrix_to_uri().to_string(),
+                    MentionLinkFormat::MatrixUri => user_id.matrix_uri(false).to_string(),
+                };
+                mentions.user_ids.insert(user_id);
+                uri
+            }
+            TextMention::RoomAlias(alias) => match link_format {
+                MentionLinkFormat::MatrixTo => alias.matrix_to_uri().to_string(),
+                MentionLinkFormat::MatrixUri => alias.matrix_uri(false).to_string(),
+            },
+        };
+
+        if start > last_end {
+            events.push(Event::Text(text[last_end..start].to_owned().into()));
+        }
+
+        events.push(Event::Start(Tag::Link {
+            link_type: LinkType::Inline,
+            dest_url: uri.into(),
+            title: CowStr::Borrowed(""),
+            id: CowStr::Borrowed(""),
+        }));
+        events.push(Event::Text(candidate.to_owned().into()));
+        events.push(Event::End(TagEnd::Link));
+
+        has_links = true;
+        last_end = start + candidate.len();
+        pos = last_end;
+    }
+
+    if last_end < text.len() {
+        events.push(Event::Text(text[last_end..].to_owned().into()));
+    }
+
+    has_links
+}
+
+/// Whether the given character can be part of a mention.
+fn is_mention_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '_' | '=' | '-' | '/' | '+' | ':' | '[' | ']')
+}
+
+/// Parse the given mention candidate.
+fn parse_mention(candidate: &str) -> Option<TextMention> {
+    if candidate == "@room" {
+        Some(TextMention::Room)
+    } else if candidate.starts_with('@') {
+        OwnedUserId::try_from(candidate).ok().map(TextMention::User)
+    } else {
+        OwnedRoomAliasId::try_from(candidate).ok().map(TextMention::RoomAlias)
+    }
+}
+
+/// The ID of the user that the given link points to, if any.
+fn linked_user_id(url: &str) -> Option<OwnedUserId> {
+    let id = match MatrixToUri::parse(url) {
+        Ok(uri) => uri.id().clone(),
+        Err(_) => MatrixUri::parse(url).ok()?.id().clone(),
+    };
+
+    match id {
+        MatrixId::User(user_id) => Some(user_id),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma_common::owned_user_id;
+
+    use super::{MarkdownWithMentions, MentionLinkFormat};
+
+    #[test]
+    fn mentions_in_text() {
+        let parsed = MarkdownWithMentions::parse(
+            "Hey @alice:example.org: have you seen #room:example.org? cc @room",
+            MentionLinkFormat::MatrixTo,
+        );
+        assert_eq!(
+            parsed.formatted.unwrap().body,
+            "Hey <a href=\"https://matrix.to/#/@alice:example.org\">@alice:example.org</a>: \
+             have you seen <a href=\"https://matrix.to/#/%23room:example.org\">#room:example.org</a>? \
+             cc @room"
+        );
+        assert_eq!(
+            parsed.mentions.user_ids.into_iter().collect::<Vec<_>>(),
+            [owned_user_id!("@alice:example.org")]
+        );
+        assert!(parsed.mentions.room);
+
+        let parsed =
+            MarkdownWithMentions::parse("**Hi** @bob:example.org.", MentionLinkFormat::MatrixUri);
+        assert_eq!(
+            parsed.formatted.unwrap().body,
+            "<strong>Hi</strong> <a href=\"matrix:u/bob:example.org\">@bob:example.org</a>."
+        );
+        assert_eq!(
+            parsed.mentions.user_ids.into_iter().collect::<Vec<_>>(),
+            [owned_user_id!("@bob:example.org")]
+        );
+    }
+
+    #[test]
+    fn ignored_text() {
+        // Plain text without mentions.
+        let parsed = MarkdownWithMentions::parse(
+            "Send an email to alice@example.org, not @alice",
+            MentionLinkFormat::MatrixTo,
+        );
+        assert!(parsed.formatted.is_none());
+        assert!(parsed.mentions.user_ids.is_empty());
+        assert!(!parsed.mentions.room);
+
+        // Code and links.
+        let parsed = MarkdownWithMentions::parse(
+            "`@alice:example.org` [@bob:example.org](https://example.org)\n\n\
+             ```\n@room\n```",
+            MentionLinkFormat::MatrixTo,
+        );
+        assert_eq!(
+            parsed.formatted.unwrap().body,
+            "<p><code>@alice:example.org</code> \
+             <a href=\"https://example.org\">@bob:example.org</a></p>\n\
+             <pre><code>@room\n</code></pre>\n"
+        );
+        assert!(parsed.mentions.user_ids.is_empty());
+        assert!(!parsed.mentions.room);
+
+        // Links to users are mentions.
+        let parsed = MarkdownWithMentions::parse(
+            "[Alice](https://matrix.to/#/@alice:example.org)",
+            MentionLinkFormat::MatrixTo,
+        );
+        assert_eq!(
+            parsed.mentions.user_ids.into_iter().collect::<Vec<_>>(),
+            [owned_user_id!("@alice:example.org")]
+        );
+    }
+}
@@ -0,0 +1,602 @@
+//! A typed aggregate of the current state of a room.
+//!
+//! [`RoomState`] collects the state events of a room, as received in the timeline or in the
+//! `state` of a sync response, or as stripped state for invites and knocks, and provides typed
+//! accessors for the most common state.
+
+use std::collections::{BTreeMap, btree_map};
+
+use ruma_common::{
+    EventEncryptionAlgorithm, EventId, MxcUri, OwnedRoomAliasId, OwnedUserId, RoomAliasId,
+    RoomVersionId, UserId,
+    room_version_rules::RoomVersionRules,
+    serde::{JsonObject, Raw},
+};
+#[cfg(feature = "canonical-json")]
+use ruma_common::{
+    canonical_json::{CanonicalJsonObject, RedactedBecause, RedactionError, redact},
+    room_version_rules::RedactionRules,
+};
+
+#[cfg(feature = "canonical-json")]
+use crate::room::redaction::OriginalSyncRoomRedactionEvent;
+use crate::{
+    AnyStrippedStateEvent, AnySyncStateEvent, StateEventType, SyncStateEvent,
+    display_name::{RoomDisplayName, member_display_name, room_display_name},
+    room::{
+        history_visibility::HistoryVisibility,
+        join_rules::JoinRule,
+        member::MembershipState,
+        power_levels::{RoomPowerLevels, RoomPowerLevelsEventContent, RoomPowerLevelsSource},
+        server_acl::RoomServerAclEventContent,
+    },
+};
+
+/// The current state of a room, built from its state events.
+///
+/// Events are keyed by their `(type, state_key)` tuple, so adding an event replaces the previous
+/// event with the same key. Both full and stripped state events are supported, which allows to
+/// use this type for joined and left rooms as well as for invited and knocked rooms.
+///
+/// The typed accessors handle redacted events and stripped events with possibly redacted content.
+#[derive(Clone, Debug, Default)]
+pub struct RoomState {
+    /// The state events, by `(type, state_key)`.
+    events: BTreeMap<(StateEventType, String), RoomStateEntry>,
+}
+
+impl RoomState {
+    /// Creates an empty `RoomState`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the given state event to this state.
+    ///
+    /// Replaces the event with the same type and state key, if any.
+    ///
+    /// Returns an error if the event fails to deserialize.
+    pub fn add_event(&mut self, raw: Raw<AnySyncStateEvent>) -> serde_json::Result<()> {
+        let event = raw.deserialize()?;
+        self.insert(RoomStateEvent::Sync(event), raw.cast());
+        Ok(())
+    }
+
+    /// Add the given stripped state event to this state.
+    ///
+    /// Replaces the event with the same type and state key, if any.
+    ///
+    /// Returns an error if the event fails to deserialize.
+    pub fn add_stripped_event(
+        &mut self,
+        raw: Raw<AnyStrippedStateEvent>,
+    ) -> serde_json::Result<()> {
+        let event = raw.deserialize()?;
+        self.insert(RoomStateEvent::Stripped(event), raw.cast());
+        Ok(())
+    }
+
+    /// Insert the given event in the map.
+    fn insert(&mut self, event: RoomStateEvent, raw: Raw<JsonObject>) {
+        let key = (event.event_type(), event.state_key().to_owned());
+        self.events.insert(key, RoomStateEntry { event, raw });
+    }
+
+    /// Apply the given redaction event to this state.
+    ///
+    /// If the redacted event is part of this state, it is replaced by its redacted form, according
+    /// to the given redaction rules.
+    ///
+    /// Returns `Ok(true)` if an event was redacted, `Ok(false)` if the redacted event is not part
+    /// of this state, and an error if the redaction event or the redacted event are invalid.
+    #[cfg(feature = "canonical-json")]
+    pub fn redact(
+        &mut self,
+        redaction: &Raw<OriginalSyncRoomRedactionEvent>,
+        rules: &RedactionRules,
+    ) -> Result<bool, RoomStateRedactionError> {
+        let redaction_event = redaction.deserialize()?;
+        let redacts = redaction_event.redacts(rules);
+
+        let Some(entry) =
+            self.events.values_mut().find(|entry| entry.event.event_id() == Some(redacts))
+        else {
+            return Ok(false);
+        };
+
+        let object = entry.raw.deserialize_as_unchecked::<CanonicalJsonObject>()?;
+        let redacted_object =
+            redact(object, rules, Some(RedactedBecause::from_raw_event(redaction)?))?;
+        let raw =
+            Raw::<AnySyncStateEvent>::from_json(serde_json::value::to_raw_value(&redacted_object)?);
+
+        *entry =
+            RoomStateEntry { event: RoomStateEvent::Sync(raw.deserialize()?), raw: raw.cast() };
+
+        Ok(true)
+    }
+
+    /// All the events of this state, by `(type, state_key)`.
+    pub fn events(&self) -> &BTreeMap<(StateEventType, String), RoomStateEntry> {
+        &self.events
+    }
+
+    /// The event with the given type and state key, if any.
+    pub fn get(&self, event_type: &StateEventType, state_key: &str) -> Option<&RoomStateEntry> {
+        self.events.get(&(event_type.clone(), state_key.to_owned()))
+    }
+
+    /// The event with the given type and an empty state key, if any.
+    fn get_event(&self, event_type: StateEventType) -> Option<&RoomStateEvent> {
+        self.events.get(&(event_type, String::new())).map(|entry| &entry.event)
+    }
+
+    /// The version of the room, from the `m.room.create` event.
+    pub fn room_version(&self) -> Option<&RoomVersionId> {
+        match self.get_event(StateEventType::RoomCreate)? {
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomCreate(SyncStateEvent::Original(ev))) => {
+                Some(&ev.content.room_version)
+            }
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomCreate(SyncStateEvent::Redacted(ev))) => {
+                Some(&ev.content.room_version)
+            }
+            RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomCreate(ev)) => {
+                Some(&ev.content.room_version)
+            }
+            _ => None,
+        }
+    }
+
+    /// The rules of the version of the room, if the `m.room.create` event is present and the room
+    /// version is known.
+    pub fn room_version_rules(&self) -> Option<RoomVersionRules> {
+        self.room_version()?.rules()
+    }
+
+    /// The creators of the room, as defined by the given room version rules.
+    fn creators(&self, rules: &RoomVersionRules) -> Vec<OwnedUserId> {
+        let (sender, content) = match self.get_event(StateEventType::RoomCreate) {
+            Some(RoomStateEvent::Sync(AnySyncStateEvent::RoomCreate(
+                SyncStateEvent::Original(ev),
+            ))) => (&ev.sender, &ev.content),
+            Some(RoomStateEvent::Sync(AnySyncStateEvent::RoomCreate(
+                SyncStateEvent::Redacted(ev),
+            ))) => (&ev.sender, &ev.content),
+            Some(RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomCreate(ev))) => {
+                (&ev.sender, &ev.content)
+            }
+            _ => return Vec::new(),
+        };
+
+        let mut creators = if rules.authorization.use_room_create_sender {
+            vec![sender.clone()]
+        } else {
+            #[allow(deprecated)]
+            let creator = content.creator.clone();
+            vec![creator.unwrap_or_else(|| sender.clone())]
+        };
+
+        if rules.authorization.explicitly_privilege_room_creators {
+            creators.extend(content.additional_creators.iter().cloned());
+        }
+
+        creators
+    }
+
+    /// The name of the room, from the `m.room.name` event.
+    pub fn name(&self) -> Option<&str> {
+        match self.get_event(StateEventType::RoomName)? {
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomName(SyncStateEvent::Original(ev))) => {
+                Some(ev.content.name.as_str())
+            }
+            RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomName(ev)) => {
+                ev.content.name.as_deref()
+            }
+            _ => None,
+        }
+        .filter(|name| !name.is_empty())
+    }
+
+    /// The topic of the room, from the `m.room.topic` event.
+    pub fn topic(&self) -> Option<&str> {
+        match self.get_event(StateEventType::RoomTopic)? {
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomTopic(SyncStateEvent::Original(ev))) => {
+                Some(ev.content.topic.as_str())
+            }
+            RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomTopic(ev)) => {
+                ev.content.topic.as_deref()
+            }
+            _ => None,
+        }
+        .filter(|topic| !topic.is_empty())
+    }
+
+    /// The URL of the avatar of the room, from the `m.room.avatar` event.
+    pub fn avatar_url(&self) -> Option<&MxcUri> {
+        match self.get_event(StateEventType::RoomAvatar)? {
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomAvatar(SyncStateEvent::Original(ev))) => {
+                ev.content.url.as_deref()
+            }
+            RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomAvatar(ev)) => {
+                ev.content.url.as_deref()
+            }
+            _ => None,
+        }
+    }
+
+    /// The canonical alias of the room, from the `m.room.canonical_alias` event.
+    pub fn canonical_alias(&self) -> Option<&RoomAliasId> {
+        match self.get_event(StateEventType::RoomCanonicalAlias)? {
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomCanonicalAlias(
+                SyncStateEvent::Original(ev),
+            )) => ev.content.alias.as_deref(),
+            RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomCanonicalAlias(ev)) => {
+                ev.content.alias.as_deref()
+            }
+            _ => None,
+        }
+    }
+
+    /// The alternative aliases of the room, from the `m.room.canonical_alias` event.
+    pub fn alt_aliases(&self) -> &[OwnedRoomAliasId] {
+        match self.get_event(StateEventType::RoomCanonicalAlias) {
+            Some(RoomStateEvent::Sync(AnySyncStateEvent::RoomCanonicalAlias(
+                SyncStateEvent::Original(ev),
+            ))) => &ev.content.alt_aliases,
+            Some(RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomCanonicalAlias(ev))) => {
+                &ev.content.alt_aliases
+            }
+            _ => &[],
+        }
+    }
+
+    /// The join rule of the room, from the `m.room.join_rules` event.
+    pub fn join_rule(&self) -> Option<&JoinRule> {
+        match self.get_event(StateEventType::RoomJoinRules)? {
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomJoinRules(SyncStateEvent::Original(
+                ev,
+            ))) => Some(&ev.content.join_rule),
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomJoinRules(SyncStateEvent::Redacted(
+                ev,
+            ))) => Some(&ev.content.join_rule),
+            RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomJoinRules(ev)) => {
+                Some(&ev.content.join_rule)
+            }
+            _ => None,
+        }
+    }
+
+    /// The history visibility of the room, from the `m.room.history_visibility` event.
+    pub fn history_visibility(&self) -> Option<&HistoryVisibility> {
+        match self.get_event(StateEventType::RoomHistoryVisibility)? {
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomHistoryVisibility(
+                SyncStateEvent::Original(ev),
+            )) => Some(&ev.content.history_visibility),
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomHistoryVisibility(
+                SyncStateEvent::Redacted(ev),
+            )) => Some(&ev.content.history_visibility),
+            RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomHistoryVisibility(ev)) => {
+                Some(&ev.content.history_visibility)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the room is encrypted, i.e. whether it has an `m.room.encryption` event.
+    ///
+    /// A redacted `m.room.encryption` event still means that the room is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.get_event(StateEventType::RoomEncryption).is_some()
+    }
+
+    /// The encryption algorithm of the room, from the `m.room.encryption` event.
+    pub fn encryption_algorithm(&self) -> Option<&EventEncryptionAlgorithm> {
+        match self.get_event(StateEventType::RoomEncryption)? {
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomEncryption(SyncStateEvent::Original(
+                ev,
+            ))) => Some(&ev.content.algorithm),
+            RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomEncryption(ev)) => {
+                ev.content.algorithm.as_ref()
+            }
+            _ => None,
+        }
+    }
+
+    /// The power levels of the room, from the `m.room.power_levels` and `m.room.create` events.
+    ///
+    /// Uses the default power levels if the room doesn't have an `m.room.power_levels` event.
+    ///
+    /// Returns `None` if the `m.room.create` event is missing or the room version is unknown.
+    pub fn power_levels(&self) -> Option<RoomPowerLevels> {
+        let rules = self.room_version_rules()?;
+
+        let source = match self.events.get(&(StateEventType::RoomPowerLevels, String::new())) {
+            Some(RoomStateEntry {
+                event:
+                    RoomStateEvent::Sync(AnySyncStateEvent::RoomPowerLevels(SyncStateEvent::Original(
+                        ev,
+                    ))),
+                ..
+            }) => RoomPowerLevelsSource::Original(ev.content.clone()),
+            Some(RoomStateEntry {
+                event:
+                    RoomStateEvent::Sync(AnySyncStateEvent::RoomPowerLevels(SyncStateEvent::Redacted(
+                        ev,
+                    ))),
+                ..
+            }) => RoomPowerLevelsSource::Redacted(ev.content.clone()),
+            // All the fields of the content have a default value, so the possibly redacted content
+            // can be deserialized as the original content.
+            Some(RoomStateEntry { event: RoomStateEvent::Stripped(_), raw }) => raw
+                .get_field::<RoomPowerLevelsEventContent>("content")
+                .ok()
+                .flatten()
+                .map(RoomPowerLevelsSource::Original)
+                .unwrap_or_default(),
+            _ => RoomPowerLevelsSource::None,
+        };
+
+        Some(RoomPowerLevels::new(source, &rules.authorization, self.creators(&rules)))
+    }
+
+    /// The server ACL of the room, from the `m.room.server_acl` event.
+    ///
+    /// Returns `None` if the room doesn't have an `m.room.server_acl` event or if it was redacted.
+    pub fn server_acl(&self) -> Option<RoomServerAclEventContent> {
+        match self.get_event(StateEventType::RoomServerAcl)? {
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomServerAcl(SyncStateEvent::Original(
+                ev,
+            ))) => Some(ev.content.clone()),
+            RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomServerAcl(ev)) => {
+                Some(RoomServerAclEventContent::new(
+                    ev.content.allow_ip_literals,
+                    ev.content.allow.clone(),
+                    ev.content.deny.clone(),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// The member of the room with the given user ID, from its `m.room.member` event.
+    pub fn member(&self, user_id: &UserId) -> Option<RoomMember<'_>> {
+        self.events
+            .get(&(StateEventType::RoomMember, user_id.as_str().to_owned()))
+            .and_then(|entry| RoomMember::from_event(&entry.event))
+    }
+
+    /// The members of the room, from the `m.room.member` events.
+    ///
+    /// This includes all the users with an `m.room.member` event, regardless of their membership.
+    pub fn members(&self) -> RoomMembers<'_> {
+        RoomMembers { inner: self.events.range((StateEventType::RoomMember, String::new())..) }
+    }
+
+    /// The display name of the member of the room with the given user ID, disambiguated with the
+    /// display names of the joined and invited members.
+    ///
+    /// See [`member_display_name()`] for more details.
+    pub fn member_display_name(&self, user_id: &UserId) -> Option<String> {
+        let member = self.member(user_id)?;
+        let members = self
+            .members()
+            .filter(|member| is_joined_or_invited(member.membership))
+            .map(|member| (member.user_id, member.display_name));
+
+        Some(member_display_name(user_id, member.display_name, members))
+    }
+
+    /// The display name of the room, as seen by the given user.
+    ///
+    /// The heroes are the first 5 joined or invited members other than the given user, ordered by
+    /// user ID, or the first 5 members that left the room if it is otherwise empty.
+    ///
+    /// See [`room_display_name()`] for more details.
+    pub fn display_name(&self, own_user_id: &UserId) -> RoomDisplayName {
+        const MAX_HEROES: usize = 5;
+
+        let joined_member_count =
+            self.members().filter(|member| *member.membership == MembershipState::Join).count()
+                as u64;
+        let invited_member_count =
+            self.members().filter(|member| *member.membership == MembershipState::Invite).count()
+                as u64;
+
+        let mut heroes = self
+            .members()
+            .filter(|member| {
+                member.user_id != own_user_id && is_joined_or_invited(member.membership)
+            })
+            .take(MAX_HEROES)
+            .map(|member| member.user_id)
+            .collect::<Vec<_>>();
+
+        if heroes.is_empty() {
+            heroes = self
+                .members()
+                .filter(|member| {
+                    member.user_id != own_user_id
+                        && matches!(
+                            member.membership,
+                            MembershipState::Leave | MembershipState::Ban
+                        )
+                })
+                .take(MAX_HEROES)
+                .map(|member| member.user_id)
+                .collect();
+        }
+
+        let heroes = heroes
+            .into_iter()
+            .filter_map(|user_id| self.member_display_name(user_id))
+            .collect::<Vec<_>>();
+
+        room_display_name(
+            self.name(),
+            self.canonical_alias(),
+            &heroes,
+            joined_member_count,
+            invited_member_count,
+        )
+    }
+}
+
+/// Whether the given membership state is `join` or `invite`.
+fn is_joined_or_invited(membership: &MembershipState) -> bool {
+    matches!(membership, MembershipState::Join | MembershipState::Invite)
+}
+
+/// An event in a [`RoomState`], with its raw JSON.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct RoomStateEntry {
+    /// The deserialized event.
+    pub event: RoomStateEvent,
+
+    /// The raw JSON of the event.
+    pub raw: Raw<JsonObject>,
+}
+
+/// A state event in a [`RoomState`].
+#[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub enum RoomStateEvent {
+    /// A full state event.
+    Sync(AnySyncStateEvent),
+
+    /// A stripped state event.
+    Stripped(AnyStrippedStateEvent),
+}
+
+impl RoomStateEvent {
+    /// The type of the event.
+    pub fn event_type(&self) -> StateEventType {
+        match self {
+            Self::Sync(ev) => ev.event_type(),
+            Self::Stripped(ev) => ev.event_type(),
+        }
+    }
+
+    /// The state key of the event.
+    pub fn state_key(&self) -> &str {
+        match self {
+            Self::Sync(ev) => ev.state_key(),
+            Self::Stripped(ev) => ev.state_key(),
+        }
+    }
+
+    /// The sender of the event.
+    pub fn sender(&self) -> &UserId {
+        match self {
+            Self::Sync(ev) => ev.sender(),
+            Self::Stripped(ev) => ev.sender(),
+        }
+    }
+
+    /// The ID of the event, if this is not a stripped event.
+    pub fn event_id(&self) -> Option<&EventId> {
+        match self {
+            Self::Sync(ev) => Some(ev.event_id()),
+            Self::Stripped(_) => None,
+        }
+    }
+
+    /// Whether the event is redacted.
+    ///
+    /// Stripped events are never considered to be redacted.
+    pub fn is_redacted(&self) -> bool {
+        match self {
+            Self::Sync(ev) => ev.is_redacted(),
+            Self::Stripped(_) => false,
+        }
+    }
+}
+
+/// A member of a room, from its `m.room.member` event in a [`RoomState`].
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(not(ruma_unstable_exhaustive_types), non_exhaustive)]
+pub struct RoomMember<'a> {
+    /// The ID of the user.
+    pub user_id: &'a UserId,
+
+    /// The membership state of the user.
+    pub membership: &'a MembershipState,
+
+    /// The display name of the user in the room, if any.
+    pub display_name: Option<&'a str>,
+
+    /// The avatar URL of the user in the room, if any.
+    pub avatar_url: Option<&'a MxcUri>,
+}
+
+impl<'a> RoomMember<'a> {
+    /// Construct a `RoomMember` from the given event, if it is an `m.room.member` event.
+    fn from_event(event: &'a RoomStateEvent) -> Option<Self> {
+        match event {
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomMember(SyncStateEvent::Original(ev))) => {
+                Some(Self {
+                    user_id: &ev.state_key,
+                    membership: &ev.content.membership,
+                    display_name: ev.content.displayname.as_deref(),
+                    avatar_url: ev.content.avatar_url.as_deref(),
+                })
+            }
+            RoomStateEvent::Sync(AnySyncStateEvent::RoomMember(SyncStateEvent::Redacted(ev))) => {
+                Some(Self {
+                    user_id: &ev.state_key,
+                    membership: &ev.content.membership,
+                    display_name: None,
+                    avatar_url: None,
+                })
+            }
+            RoomStateEvent::Stripped(AnyStrippedStateEvent::RoomMember(ev)) => Some(Self {
+                user_id: &ev.state_key,
+                membership: &ev.content.membership,
+                display_name: ev.content.displayname.as_deref(),
+                avatar_url: ev.content.avatar_url.as_deref(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// An iterator over the members of a [`RoomState`].
+///
+/// This is created by [`RoomState::members()`].
+#[derive(Debug)]
+pub struct RoomMembers<'a> {
+    inner: btree_map::Range<'a, (StateEventType, String), RoomStateEntry>,
+}
+
+impl<'a> Iterator for RoomMembers<'a> {
+    type Item = RoomMember<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ((event_type, _), entry) = self.inner.next()?;
+
+            if *event_type != StateEventType::RoomMember {
+                return None;
+            }
+
+            if let Some(member) = RoomMember::from_event(&entry.event) {
+                return Some(member);
+            }
+        }
+    }
+}
+
+/// An error encountered when applying a redaction to a [`RoomState`].
+#[cfg(feature = "canonical-json")]
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RoomStateRedactionError {
+    /// An event is not valid JSON or failed to deserialize.
+    #[error("invalid event: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The redacted event could not be redacted.
+    #[error("failed to redact event: {0}")]
+    Redaction(#[from] RedactionError),
+}
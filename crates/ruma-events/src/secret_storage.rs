@@ -1,5 +1,10 @@
 //! Module for events in the `m.secret_storage` namespace.
 
+#[cfg(feature = "secret-storage-encryption")]
+mod aes_hmac_sha2;
 pub mod default_key;
 pub mod key;
 pub mod secret;
+
+#[cfg(feature = "secret-storage-encryption")]
+pub use self::aes_hmac_sha2::{SecretStorageError, SecretStorageKey};
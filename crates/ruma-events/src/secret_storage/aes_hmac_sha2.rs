@@ -0,0 +1,526 @@
+//! Implementation of the [`m.secret_storage.v1.aes-hmac-sha2`] algorithm.
+//!
+//! [`m.secret_storage.v1.aes-hmac-sha2`]: https://spec.matrix.org/latest/client-server-api/#msecret_storagev1aes-hmac-sha2
+
+use std::fmt;
+
+use aes::Aes256;
+use ctr::{
+    Ctr128BE,
+    cipher::{KeyIvInit, StreamCipher},
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use js_int::uint;
+use rand::RngCore;
+use ruma_common::{KeyDerivationAlgorithm, serde::Base64};
+use sha2::{Sha256, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use super::{
+    key::{
+        PassPhrase, SecretStorageEncryptionAlgorithm, SecretStorageKeyEventContent,
+        SecretStorageV1AesHmacSha2Properties,
+    },
+    secret::SecretEncryptedData,
+};
+
+/// AES-256 in CTR mode, with a 128-bit big-endian counter.
+type Aes256Ctr = Ctr128BE<Aes256>;
+
+/// HMAC using SHA-256.
+type HmacSha256 = Hmac<Sha256>;
+
+/// The size of a secret storage key, in bytes.
+const KEY_SIZE: usize = 32;
+
+/// The size of the initialization vector, in bytes.
+const IV_SIZE: usize = 16;
+
+/// The bytes at the start of a decoded recovery key.
+const RECOVERY_KEY_PREFIX: [u8; 2] = [0x8B, 0x01];
+
+/// The length of a decoded recovery key: the prefix, the key and the parity byte.
+const RECOVERY_KEY_LEN: usize = RECOVERY_KEY_PREFIX.len() + KEY_SIZE + 1;
+
+/// A key to encrypt and decrypt secrets with the `m.secret_storage.v1.aes-hmac-sha2` algorithm.
+///
+/// The key can be [generated randomly](Self::new), [derived from a
+/// passphrase](Self::from_passphrase) or [decoded from a recovery key](Self::from_recovery_key).
+///
+/// The key is zeroized when it is dropped.
+#[derive(Clone)]
+pub struct SecretStorageKey {
+    /// The bytes of the key.
+    bytes: Zeroizing<[u8; KEY_SIZE]>,
+}
+
+impl SecretStorageKey {
+    /// Generates a new random `SecretStorageKey`.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let mut bytes = Zeroizing::new([0; KEY_SIZE]);
+        rand::thread_rng().fill_bytes(&mut *bytes);
+        Self { bytes }
+    }
+
+    /// Creates a `SecretStorageKey` from the given bytes.
+    pub fn from_bytes(bytes: [u8; KEY_SIZE]) -> Self {
+        Self { bytes: Zeroizing::new(bytes) }
+    }
+
+    /// The bytes of this key.
+    pub fn as_bytes(&self) -> &[u8; KEY_SIZE] {
+        &self.bytes
+    }
+
+    /// Derives a `SecretStorageKey` from the given passphrase, with the given PBKDF2 parameters.
+    ///
+    /// Returns an error if the key derivation algorithm is not `m.pbkdf2` or if the number of bits
+    /// to generate is not 256.
+    pub fn from_passphrase(
+        passphrase: &str,
+        params: &PassPhrase,
+    ) -> Result<Self, SecretStorageError> {
+        if params.algorithm != KeyDerivationAlgorithm::Pbkfd2 {
+            return Err(SecretStorageError::UnsupportedKeyDerivation);
+        }
+
+        if params.bits != uint!(256) {
+            return Err(SecretStorageError::UnsupportedKeyLength);
+        }
+
+        let iterations = u32::try_from(params.iterations)
+            .map_err(|_| SecretStorageError::UnsupportedIterations)?;
+
+        let mut bytes = Zeroizing::new([0; KEY_SIZE]);
+        pbkdf2::pbkdf2_hmac::<Sha512>(
+            passphrase.as_bytes(),
+            params.salt.as_bytes(),
+            iterations,
+            &mut *bytes,
+        );
+
+        Ok(Self { bytes })
+    }
+
+    /// Decodes a `SecretStorageKey` from the given [recovery key].
+    ///
+    /// The whitespace in the recovery key is ignored.
+    ///
+    /// [recovery key]: https://spec.matrix.org/latest/client-server-api/#recovery-key
+    pub fn from_recovery_key(recovery_key: &str) -> Result<Self, SecretStorageError> {
+        let encoded =
+            Zeroizing::new(recovery_key.chars().filter(|c| !c.is_whitespace()).collect::<String>());
+        let decoded = Zeroizing::new(
+            bs58::decode(encoded.as_bytes())
+                .into_vec()
+                .map_err(|_| SecretStorageError::InvalidRecoveryKey)?,
+        );
+
+        if decoded.len() != RECOVERY_KEY_LEN
+            || decoded[..RECOVERY_KEY_PREFIX.len()] != RECOVERY_KEY_PREFIX
+            || parity(&decoded) != 0
+        {
+            return Err(SecretStorageError::InvalidRecoveryKey);
+        }
+
+        let mut bytes = Zeroizing::new([0; KEY_SIZE]);
+        bytes.copy_from_slice(&decoded[RECOVERY_KEY_PREFIX.len()..RECOVERY_KEY_LEN - 1]);
+
+        Ok(Self { bytes })
+    }
+
+    /// Encodes this key as a [recovery key], with a space every 4 characters.
+    ///
+    /// [recovery key]: https://spec.matrix.org/latest/client-server-api/#recovery-key
+    pub fn to_recovery_key(&self) -> String {
+        let mut decoded = Zeroizing::new(Vec::with_capacity(RECOVERY_KEY_LEN));
+        decoded.extend_from_slice(&RECOVERY_KEY_PREFIX);
+        decoded.extend_from_slice(&*self.bytes);
+        let parity = parity(&decoded);
+        decoded.push(parity);
+
+        let encoded = Zeroizing::new(bs58::encode(&*decoded).into_vec());
+        encoded
+            .chunks(4)
+            .map(|chunk| std::str::from_utf8(chunk).expect("base58 should be valid ASCII"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Creates the description of this key, to be stored in the `m.secret_storage.key.*` account
+    /// data event with the given key ID.
+    ///
+    /// The description contains a random initialization vector and the corresponding MAC that
+    /// allow to [check the key](Self::check).
+    pub fn key_description(&self, key_id: String) -> SecretStorageKeyEventContent {
+        let iv = random_iv();
+        let (_, mac) = self.encrypt_with_iv(&[0; KEY_SIZE], "", &iv);
+
+        SecretStorageKeyEventContent::new(
+            key_id,
+            SecretStorageEncryptionAlgorithm::V1AesHmacSha2(
+                SecretStorageV1AesHmacSha2Properties::new(
+                    Some(Base64::new(iv.to_vec())),
+                    Some(Base64::new(mac.to_vec())),
+                ),
+            ),
+        )
+    }
+
+    /// Checks that this key matches the given key description.
+    ///
+    /// Returns an error if the algorithm of the key description is not
+    /// `m.secret_storage.v1.aes-hmac-sha2`, if it doesn't have an initialization vector and a MAC,
+    /// or if the MAC doesn't match.
+    pub fn check(
+        &self,
+        description: &SecretStorageKeyEventContent,
+    ) -> Result<(), SecretStorageError> {
+        let SecretStorageEncryptionAlgorithm::V1AesHmacSha2(properties) = &description.algorithm
+        else {
+            return Err(SecretStorageError::UnsupportedAlgorithm);
+        };
+        let (Some(iv), Some(mac)) = (&properties.iv, &properties.mac) else {
+            return Err(SecretStorageError::MissingKeyCheck);
+        };
+
+        let iv = to_iv(iv)?;
+        let (ciphertext, _) = self.encrypt_with_iv(&[0; KEY_SIZE], "", &iv);
+        let (_, mac_key) = self.derive_keys("");
+
+        verify_mac(&mac_key, &ciphertext, mac)
+    }
+
+    /// Encrypts the given secret, with the given name.
+    ///
+    /// The name of the secret is the type of the account data event where it will be stored.
+    pub fn encrypt_secret(&self, secret: &str, name: &str) -> SecretEncryptedData {
+        let iv = random_iv();
+        let (ciphertext, mac) = self.encrypt_with_iv(secret.as_bytes(), name, &iv);
+
+        SecretEncryptedData::AesHmacSha2EncryptedData {
+            iv: Base64::new(iv.to_vec()),
+            ciphertext: Base64::new(ciphertext),
+            mac: Base64::new(mac.to_vec()),
+        }
+    }
+
+    /// Decrypts the given secret data, with the given name.
+    ///
+    /// The name of the secret is the type of the account data event where it is stored.
+    ///
+    /// Returns an error if the MAC doesn't match or if the decrypted secret is not valid UTF-8.
+    pub fn decrypt_secret(
+        &self,
+        data: &SecretEncryptedData,
+        name: &str,
+    ) -> Result<String, SecretStorageError> {
+        let SecretEncryptedData::AesHmacSha2EncryptedData { iv, ciphertext, mac } = data;
+
+        let iv = to_iv(iv)?;
+        let (aes_key, mac_key) = self.derive_keys(name);
+        verify_mac(&mac_key, ciphertext.as_bytes(), mac)?;
+
+        let mut plaintext = ciphertext.as_bytes().to_vec();
+        Aes256Ctr::new(&(*aes_key).into(), &iv.into()).apply_keystream(&mut plaintext);
+
+        String::from_utf8(plaintext).map_err(|error| {
+            error.into_bytes().zeroize();
+            SecretStorageError::InvalidSecret
+        })
+    }
+
+    /// Derives the AES and MAC keys for the secret with the given name.
+    fn derive_keys(&self, name: &str) -> (Zeroizing<[u8; 32]>, Zeroizing<[u8; 32]>) {
+        let hkdf = Hkdf::<Sha256>::new(Some(&[0; 32]), &*self.bytes);
+
+        let mut keys = Zeroizing::new([0; 64]);
+        hkdf.expand(name.as_bytes(), &mut *keys)
+            .expect("64 bytes should be a valid length for HKDF-SHA-256");
+
+        let mut aes_key = Zeroizing::new([0; 32]);
+        let mut mac_key = Zeroizing::new([0; 32]);
+        aes_key.copy_from_slice(&keys[..32]);
+        mac_key.copy_from_slice(&keys[32..]);
+
+        (aes_key, mac_key)
+    }
+
+    /// Encrypts the given plaintext, with the given name and initialization vector.
+    ///
+    /// Returns the ciphertext and its MAC.
+    fn encrypt_with_iv(
+        &self,
+        plaintext: &[u8],
+        name: &str,
+        iv: &[u8; IV_SIZE],
+    ) -> (Vec<u8>, [u8; 32]) {
+        let (aes_key, mac_key) = self.derive_keys(name);
+
+        let mut ciphertext = plaintext.to_vec();
+        Aes256Ctr::new(&(*aes_key).into(), iv.into()).apply_keystream(&mut ciphertext);
+
+        let mut hmac =
+            HmacSha256::new_from_slice(&*mac_key).expect("HMAC should accept a key of any size");
+        hmac.update(&ciphertext);
+
+        (ciphertext, hmac.finalize().into_bytes().into())
+    }
+}
+
+impl fmt::Debug for SecretStorageKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretStorageKey").finish_non_exhaustive()
+    }
+}
+
+/// Generates a random initialization vector.
+fn random_iv() -> [u8; IV_SIZE] {
+    let mut iv = [0; IV_SIZE];
+    rand::thread_rng().fill_bytes(&mut iv);
+
+    // Clear bit 63 to work around differences in the AES-CTR implementations.
+    iv[8] &= 0x7F;
+
+    iv
+}
+
+/// Converts the given base64-encoded initialization vector.
+fn to_iv(iv: &Base64) -> Result<[u8; IV_SIZE], SecretStorageError> {
+    iv.as_bytes().try_into().map_err(|_| SecretStorageError::InvalidIvLength)
+}
+
+/// Verifies that the given MAC matches the given ciphertext, in constant time.
+fn verify_mac(
+    mac_key: &[u8; 32],
+    ciphertext: &[u8],
+    mac: &Base64,
+) -> Result<(), SecretStorageError> {
+    let mut hmac =
+        HmacSha256::new_from_slice(mac_key).expect("HMAC should accept a key of any size");
+    hmac.update(ciphertext);
+    hmac.verify_slice(mac.as_bytes()).map_err(|_| SecretStorageError::MacMismatch)
+}
+
+/// Computes the parity byte of the given bytes.
+fn parity(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |parity, byte| parity ^ byte)
+}
+
+/// An error encountered when using a secret storage key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum SecretStorageError {
+    /// The key derivation algorithm of the passphrase is not supported.
+    #[error("unsupported key derivation algorithm")]
+    UnsupportedKeyDerivation,
+
+    /// The number of bits to generate from the passphrase is not supported.
+    #[error("unsupported key length, expected 256 bits")]
+    UnsupportedKeyLength,
+
+    /// The number of iterations of PBKDF2 is too big.
+    #[error("unsupported number of PBKDF2 iterations")]
+    UnsupportedIterations,
+
+    /// The recovery key is invalid.
+    #[error("invalid recovery key")]
+    InvalidRecoveryKey,
+
+    /// The encryption algorithm of the key description is not supported.
+    #[error("unsupported secret storage encryption algorithm")]
+    UnsupportedAlgorithm,
+
+    /// The key description doesn't have an initialization vector and a MAC to check the key.
+    #[error("the key description doesn't allow to check the key")]
+    MissingKeyCheck,
+
+    /// The initialization vector doesn't have the length of an AES block.
+    #[error("invalid initialization vector length, expected {IV_SIZE} bytes")]
+    InvalidIvLength,
+
+    /// The MAC doesn't match.
+    #[error("the MAC doesn't match")]
+    MacMismatch,
+
+    /// The decrypted secret is not valid UTF-8.
+    #[error("the decrypted secret is not valid UTF-8")]
+    InvalidSecret,
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_matches;
+    use js_int::uint;
+    use ruma_common::serde::{Base64, base64::Standard};
+
+    use super::{SecretStorageError, SecretStorageKey};
+    use crate::secret_storage::{
+        key::{
+            PassPhrase, SecretStorageEncryptionAlgorithm, SecretStorageKeyEventContent,
+            SecretStorageV1AesHmacSha2Properties,
+        },
+        secret::SecretEncryptedData,
+    };
+
+    // The expected values in these tests were computed independently, with Python's `hashlib`
+    // and `hmac` modules and the `cryptography` package.
+
+    fn test_key() -> SecretStorageKey {
+        SecretStorageKey::from_bytes(std::array::from_fn(|i| i as u8))
+    }
+
+    fn test_iv() -> [u8; 16] {
+        let mut iv = std::array::from_fn(|i| i as u8 + 16);
+        iv[8] &= 0x7F;
+        iv
+    }
+
+    #[test]
+    fn recovery_key() {
+        let recovery_key = "EsSz ykH7 LCZx 7Cae cmKD wcmY JRXi Ybtu 8iQ3 t8Ez nRwK pUY1";
+
+        assert_eq!(test_key().to_recovery_key(), recovery_key);
+
+        let key = SecretStorageKey::from_recovery_key(recovery_key).unwrap();
+        assert_eq!(key.as_bytes(), test_key().as_bytes());
+        let key = SecretStorageKey::from_recovery_key(&recovery_key.replace(' ', "")).unwrap();
+        assert_eq!(key.as_bytes(), test_key().as_bytes());
+
+        // Wrong parity.
+        assert_matches!(
+            SecretStorageKey::from_recovery_key(
+                "EsSz ykH7 LCZx 7Cae cmKD wcmY JRXi Ybtu 8iQ3 t8Ez nRwK pUY2"
+            ),
+            Err(SecretStorageError::InvalidRecoveryKey)
+        );
+        // Invalid base58 character.
+        assert_matches!(
+            SecretStorageKey::from_recovery_key(
+                "EsSz ykH7 LCZx 7Cae cmKD wcmY JRXi Ybtu 8iQ3 t8Ez nRwK pUY0"
+            ),
+            Err(SecretStorageError::InvalidRecoveryKey)
+        );
+        // Too short.
+        assert_matches!(
+            SecretStorageKey::from_recovery_key("EsSz ykH7"),
+            Err(SecretStorageError::InvalidRecoveryKey)
+        );
+    }
+
+    #[test]
+    fn passphrase() {
+        let params = PassPhrase::new("MmMsAlty".to_owned(), uint!(1000));
+        let key =
+            SecretStorageKey::from_passphrase("correct horse battery staple", &params).unwrap();
+        assert_eq!(
+            key.as_bytes(),
+            &[
+                0x23, 0x21, 0x99, 0x83, 0x32, 0xa0, 0x04, 0x10, 0x3e, 0x52, 0x65, 0x92, 0xd9, 0x3c,
+                0x08, 0xad, 0xce, 0x8e, 0xc2, 0xb6, 0xa0, 0xf9, 0xff, 0xe0, 0x0a, 0xb4, 0xf9, 0xb7,
+                0x57, 0xd3, 0x20, 0xaf
+            ]
+        );
+
+        // The usual PBKDF2-HMAC-SHA512 test vector, truncated to 256 bits.
+        let params = PassPhrase::new("salt".to_owned(), uint!(1));
+        let key = SecretStorageKey::from_passphrase("password", &params).unwrap();
+        assert_eq!(
+            key.as_bytes(),
+            &[
+                0x86, 0x7f, 0x70, 0xcf, 0x1a, 0xde, 0x02, 0xcf, 0xf3, 0x75, 0x25, 0x99, 0xa3, 0xa5,
+                0x3d, 0xc4, 0xaf, 0x34, 0xc7, 0xa6, 0x69, 0x81, 0x5a, 0xe5, 0xd5, 0x13, 0x55, 0x4e,
+                0x1c, 0x8c, 0xf2, 0x52
+            ]
+        );
+
+        let mut params = params;
+        params.bits = uint!(128);
+        assert_matches!(
+            SecretStorageKey::from_passphrase("correct horse battery staple", &params),
+            Err(SecretStorageError::UnsupportedKeyLength)
+        );
+    }
+
+    #[test]
+    fn key_check() {
+        let key = test_key();
+        let (_, mac) = key.encrypt_with_iv(&[0; 32], "", &test_iv());
+        assert_eq!(
+            Base64::<Standard>::new(mac.to_vec()).encode(),
+            "nrMWSgMXBpO1lS9nLEL08Saa+XuAn1pdQ0B7gSgsrQQ"
+        );
+
+        let description = SecretStorageKeyEventContent::new(
+            "key".to_owned(),
+            SecretStorageEncryptionAlgorithm::V1AesHmacSha2(
+                SecretStorageV1AesHmacSha2Properties::new(
+                    Some(Base64::parse("EBESExQVFhcYGRobHB0eHw").unwrap()),
+                    Some(Base64::parse("nrMWSgMXBpO1lS9nLEL08Saa+XuAn1pdQ0B7gSgsrQQ").unwrap()),
+                ),
+            ),
+        );
+        key.check(&description).unwrap();
+        assert_matches!(
+            SecretStorageKey::new().check(&description),
+            Err(SecretStorageError::MacMismatch)
+        );
+
+        // Generated key description.
+        let key = SecretStorageKey::new();
+        let description = key.key_description("key".to_owned());
+        key.check(&description).unwrap();
+        assert_matches!(test_key().check(&description), Err(SecretStorageError::MacMismatch));
+
+        // Without key check.
+        let description = SecretStorageKeyEventContent::new(
+            "key".to_owned(),
+            SecretStorageEncryptionAlgorithm::V1AesHmacSha2(
+                SecretStorageV1AesHmacSha2Properties::new(None, None),
+            ),
+        );
+        assert_matches!(key.check(&description), Err(SecretStorageError::MissingKeyCheck));
+    }
+
+    #[test]
+    fn secret_encryption() {
+        let key = test_key();
+        let name = "m.cross_signing.master";
+
+        let data = SecretEncryptedData::AesHmacSha2EncryptedData {
+            iv: Base64::parse("EBESExQVFhcYGRobHB0eHw").unwrap(),
+            ciphertext: Base64::parse("MehZZPG4F4Vl").unwrap(),
+            mac: Base64::parse("7268JNemaot8MJupsMxyfuHUFYN+XKZa488sTMS87EM").unwrap(),
+        };
+        assert_eq!(key.decrypt_secret(&data, name).unwrap(), "my secret");
+        assert_matches!(
+            key.decrypt_secret(&data, "m.cross_signing.self_signing"),
+            Err(SecretStorageError::MacMismatch)
+        );
+
+        // A secret spanning several AES blocks, with a random key.
+        let key = SecretStorageKey::from_recovery_key(
+            "EsU7 5PNw Kxmj N9XV mb6w Fg8C gu2d UkbR njpk Xarq ozwa 437w",
+        )
+        .unwrap();
+        let data = SecretEncryptedData::AesHmacSha2EncryptedData {
+            iv: Base64::parse("soBf+OElL1dHYWOx/5EktA").unwrap(),
+            ciphertext: Base64::parse(
+                "YFnvDhfd6rD7DZ7EWOJhYDsblgKNCdTbBqlVcxN7w2ZYkwgJ4QfWh2JlwQjO0DPMbLChcz54Ir0+BogOa7UPXA",
+            )
+            .unwrap(),
+            mac: Base64::parse("Rid6NY2Kw/3evNOyVORDSU8YKcQEpOFwmW2UJJ+5xgs").unwrap(),
+        };
+        assert_eq!(
+            key.decrypt_secret(&data, "m.megolm_backup.v1").unwrap(),
+            "A secret that is longer than a single AES block of sixteen bytes"
+        );
+
+        let data = key.encrypt_secret("my other secret", name);
+        assert_matches!(&data, SecretEncryptedData::AesHmacSha2EncryptedData { iv, .. });
+        assert_eq!(iv.as_bytes()[8] & 0x80, 0);
+        assert_eq!(key.decrypt_secret(&data, name).unwrap(), "my other secret");
+    }
+}
@@ -13,11 +13,14 @@ mod image;
 mod initial_state;
 mod location;
 mod message;
+mod policy_list;
 mod poll;
 mod redacted;
 mod redaction;
 mod relations;
 mod room_message;
+mod room_state;
+mod spec_examples;
 mod state_event;
 mod sticker;
 mod stripped;
@@ -0,0 +1,236 @@
+use assert_matches2::assert_matches;
+use ruma_common::{event_id, room_alias_id, room_id, serde::Raw, server_name, user_id};
+use ruma_events::{
+    AnySyncStateEvent,
+    policy::{PolicyList, PolicyRuleKind},
+};
+use serde_json::{Value as JsonValue, from_value as from_json_value, json};
+
+fn rule_event(kind: &str, state_key: &str, event_id: &str, entity: &str) -> Raw<AnySyncStateEvent> {
+    rule_event_with_content(
+        kind,
+        state_key,
+        event_id,
+        json!({
+            "entity": entity,
+            "recommendation": "m.ban",
+            "reason": format!("{entity} is spam"),
+        }),
+    )
+}
+
+fn rule_event_with_content(
+    kind: &str,
+    state_key: &str,
+    event_id: &str,
+    content: JsonValue,
+) -> Raw<AnySyncStateEvent> {
+    from_json_value(json!({
+        "type": format!("m.policy.rule.{kind}"),
+        "state_key": state_key,
+        "sender": "@moderator:example.org",
+        "content": content,
+        "event_id": event_id,
+        "origin_server_ts": 1,
+    }))
+    .unwrap()
+}
+
+fn redacted_rule_event(kind: &str, state_key: &str, event_id: &str) -> Raw<AnySyncStateEvent> {
+    from_json_value(json!({
+        "type": format!("m.policy.rule.{kind}"),
+        "state_key": state_key,
+        "sender": "@moderator:example.org",
+        "content": {},
+        "event_id": event_id,
+        "origin_server_ts": 1,
+        "unsigned": {
+            "redacted_because": {
+                "type": "m.room.redaction",
+                "content": { "redacts": event_id },
+                "redacts": event_id,
+                "event_id": "$redaction",
+                "origin_server_ts": 2,
+                "sender": "@moderator:example.org",
+            },
+        },
+    }))
+    .unwrap()
+}
+
+#[test]
+fn user_rules() {
+    let list_id = room_id!("!list:example.org");
+    let mut list = PolicyList::new();
+
+    assert!(list.handle_event(list_id, &rule_event("user", "rule:1", "$1", "@spam*:example.org")));
+    assert!(
+        list.handle_event(list_id, &rule_event("user", "rule:2", "$2", "@spammer:example.org"))
+    );
+    assert!(list.handle_event(list_id, &rule_event("server", "rule:3", "$3", "evil.*")));
+
+    // Literal rules are checked before globs.
+    let ban = list.user_ban(user_id!("@spammer:example.org")).unwrap();
+    assert_eq!(ban.room_id, list_id);
+    assert_eq!(ban.kind, PolicyRuleKind::User);
+    assert_eq!(ban.state_key, "rule:2");
+    assert_eq!(ban.event_id, event_id!("$2"));
+    assert_eq!(ban.reason(), "@spammer:example.org is spam");
+
+    let ban = list.user_ban(user_id!("@spambot:example.org")).unwrap();
+    assert_eq!(ban.state_key, "rule:1");
+
+    // Users are banned by server rules.
+    let ban = list.user_ban(user_id!("@alice:evil.org")).unwrap();
+    assert_eq!(ban.kind, PolicyRuleKind::Server);
+    assert_eq!(ban.state_key, "rule:3");
+
+    assert!(list.user_ban(user_id!("@alice:example.org")).is_none());
+    // `*` matches zero or more characters.
+    assert!(list.user_ban(user_id!("@spam:example.org")).is_some());
+    assert!(list.server_ban(server_name!("evil.org")).is_some());
+    assert!(list.server_ban(server_name!("evil")).is_none());
+}
+
+#[test]
+fn room_rules() {
+    let list_id = room_id!("!list:example.org");
+    let mut list = PolicyList::new();
+
+    list.handle_event(list_id, &rule_event("room", "rule:1", "$1", "#*:evil.org"));
+    list.handle_event(list_id, &rule_event("room", "rule:2", "$2", "!bad:example.org"));
+
+    let ban = list.room_ban(room_alias_id!("#spam:evil.org").into()).unwrap();
+    assert_eq!(ban.kind, PolicyRuleKind::Room);
+    assert_eq!(ban.state_key, "rule:1");
+    assert!(list.room_ban(room_id!("!bad:example.org").into()).is_some());
+    assert!(list.room_ban(room_id!("!good:example.org").into()).is_none());
+    assert!(list.user_ban(user_id!("@alice:evil.org")).is_none());
+}
+
+#[test]
+fn rule_replacement_and_removal() {
+    let list_id = room_id!("!list:example.org");
+    let mut list = PolicyList::new();
+    let alice = user_id!("@alice:example.org");
+    let bob = user_id!("@bob:example.org");
+
+    list.handle_event(list_id, &rule_event("user", "rule:1", "$1", "@alice:example.org"));
+    assert!(list.user_ban(alice).is_some());
+
+    // A new rule with the same state key replaces the previous one.
+    list.handle_event(list_id, &rule_event("user", "rule:1", "$2", "@bob:example.org"));
+    assert!(list.user_ban(alice).is_none());
+    assert_eq!(list.user_ban(bob).unwrap().event_id, event_id!("$2"));
+    assert_eq!(
+        list.rule(list_id, PolicyRuleKind::User, "rule:1").unwrap().entity,
+        "@bob:example.org"
+    );
+
+    // A rule with the same state key but another type doesn't replace it.
+    list.handle_event(list_id, &rule_event("server", "rule:1", "$3", "evil.org"));
+    assert!(list.user_ban(bob).is_some());
+
+    // Rules with another recommendation are not bans.
+    list.handle_event(
+        list_id,
+        &rule_event_with_content(
+            "user",
+            "rule:1",
+            "$4",
+            json!({ "entity": "@bob:example.org", "recommendation": "org.example.mute", "reason": "" }),
+        ),
+    );
+    assert!(list.user_ban(bob).is_none());
+
+    // A redacted state event removes the rule.
+    list.handle_event(list_id, &rule_event("user", "rule:1", "$5", "@bob:example.org"));
+    assert!(list.user_ban(bob).is_some());
+    list.handle_event(list_id, &redacted_rule_event("user", "rule:1", "$5"));
+    assert!(list.user_ban(bob).is_none());
+    assert!(list.rule(list_id, PolicyRuleKind::User, "rule:1").is_none());
+
+    // A state event with empty content removes the rule.
+    list.handle_event(list_id, &rule_event("user", "rule:1", "$6", "@bob:example.org"));
+    assert!(list.user_ban(bob).is_some());
+    assert!(
+        list.handle_event(list_id, &rule_event_with_content("user", "rule:1", "$7", json!({})))
+    );
+    assert!(list.user_ban(bob).is_none());
+    assert!(list.rule(list_id, PolicyRuleKind::User, "rule:1").is_none());
+
+    // A state event with invalid content removes the rule.
+    list.handle_event(list_id, &rule_event("user", "rule:1", "$8", "@bob:example.org"));
+    assert!(list.user_ban(bob).is_some());
+    assert!(list.handle_event(
+        list_id,
+        &rule_event_with_content("user", "rule:1", "$9", json!({ "entity": "@bob:example.org" })),
+    ));
+    assert!(list.user_ban(bob).is_none());
+
+    // A redaction removes the rule.
+    list.handle_event(list_id, &rule_event("user", "rule:2", "$10", "@bob:example.org"));
+    assert!(!list.handle_redaction(list_id, event_id!("$unknown")));
+    assert!(list.handle_redaction(list_id, event_id!("$10")));
+    assert!(list.user_ban(bob).is_none());
+
+    // Other state events are ignored.
+    let event = from_json_value(json!({
+        "type": "m.room.name",
+        "state_key": "",
+        "sender": "@moderator:example.org",
+        "content": { "name": "Ban list" },
+        "event_id": "$11",
+        "origin_server_ts": 1,
+    }))
+    .unwrap();
+    assert!(!list.handle_event(list_id, &event));
+}
+
+#[test]
+fn multiple_lists() {
+    let first_id = room_id!("!first:example.org");
+    let second_id = room_id!("!second:example.org");
+    let mut list = PolicyList::new();
+
+    list.handle_event(first_id, &rule_event("server", "rule:1", "$1", "*.evil.org"));
+    list.handle_event(second_id, &rule_event("server", "rule:1", "$2", "spam.evil.org"));
+
+    // The first list has priority.
+    let ban = list.server_ban(server_name!("spam.evil.org")).unwrap();
+    assert_eq!(ban.room_id, first_id);
+    assert_eq!(list.room_ids().collect::<Vec<_>>(), [first_id, second_id]);
+
+    assert!(list.remove_list(first_id));
+    assert!(!list.remove_list(first_id));
+    let ban = list.server_ban(server_name!("spam.evil.org")).unwrap();
+    assert_eq!(ban.room_id, second_id);
+    assert_matches!(list.server_ban(server_name!("other.evil.org")), None);
+}
+
+#[test]
+#[cfg(feature = "unstable-msc4205")]
+fn hashed_rules() {
+    let list_id = room_id!("!list:example.org");
+    let mut list = PolicyList::new();
+
+    // SHA-256 of `@spammer:example.org`.
+    list.handle_event(
+        list_id,
+        &rule_event_with_content(
+            "user",
+            "rule:1",
+            "$1",
+            json!({
+                "hashes": { "sha256": "9iEHLuf8cZ2Wg0w+Hep9JVsr06dJ5rXxbRBctyj+dds" },
+                "recommendation": "m.ban",
+                "reason": "spam",
+            }),
+        ),
+    );
+
+    let ban = list.user_ban(user_id!("@spammer:example.org")).unwrap();
+    assert_eq!(ban.state_key, "rule:1");
+    assert_eq!(ban.rule.entity, "");
+    assert!(list.user_ban(user_id!("@alice:example.org")).is_none());
+}
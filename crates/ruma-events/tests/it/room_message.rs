@@ -240,6 +240,76 @@ fn markdown_options() {
     assert_eq!(formatted_body.unwrap().body, "A message with a <del>strike</del>");
 }
 
+#[test]
+#[cfg(feature = "markdown")]
+fn markdown_with_mentions() {
+    let text = "Hello @alice:localhost, welcome to **#room:localhost** @room";
+    let content = RoomMessageEventContent::text_markdown_with_mentions(text);
+    assert_eq!(
+        to_json_value(&content).unwrap(),
+        json!({
+            "body": text,
+            "formatted_body": "Hello <a href=\"https://matrix.to/#/@alice:localhost\">@alice:localhost</a>, \
+                welcome to <strong><a href=\"https://matrix.to/#/%23room:localhost\">#room:localhost</a></strong> @room",
+            "format": "org.matrix.custom.html",
+            "msgtype": "m.text",
+            "m.mentions": {
+                "user_ids": ["@alice:localhost"],
+                "room": true,
+            },
+        })
+    );
+
+    // Without mentions, an empty `m.mentions` is set.
+    let content = RoomMessageEventContent::notice_markdown_with_mentions("Hello!");
+    assert_eq!(
+        to_json_value(&content).unwrap(),
+        json!({
+            "body": "Hello!",
+            "msgtype": "m.notice",
+            "m.mentions": {},
+        })
+    );
+}
+
+#[test]
+#[cfg(all(feature = "markdown", feature = "html"))]
+fn formatted_body_to_markdown() {
+    let text = "Hello @alice:localhost, see `code` and **#room:localhost**";
+    let content = RoomMessageEventContent::text_markdown_with_mentions(text);
+    assert_matches!(content.msgtype, MessageType::Text(TextMessageEventContent { formatted, .. }));
+    assert_eq!(formatted.unwrap().to_markdown().unwrap(), text);
+}
+
+#[test]
+#[cfg(all(feature = "markdown", feature = "html"))]
+fn formatted_body_to_markdown_block_markers() {
+    let html_body =
+        "<p># not a heading</p>\n<p>&gt; not a quote<br />\n- not a list<br />\n1. nor this</p>\n";
+    let markdown = FormattedBody::html(html_body).to_markdown().unwrap();
+    assert_eq!(markdown, "\\# not a heading\n\n\\> not a quote\n\\- not a list\n1\\. nor this");
+
+    let formatted = FormattedBody::markdown(&markdown).unwrap();
+    assert_eq!(formatted.body, html_body);
+}
+
+#[test]
+#[cfg(feature = "html")]
+fn html_only_content() {
+    let html_body = "<mx-reply><blockquote>In reply to</blockquote></mx-reply>\
+        <p>Hello <a href=\"https://matrix.to/#/@alice:localhost\">Alice</a>!</p>\
+        <ul><li>See <a href=\"https://ruma.dev\">this</a></li><li><code>code</code></li></ul>";
+    let content = RoomMessageEventContent::text_html_only(html_body);
+
+    assert_matches!(
+        content.msgtype,
+        MessageType::Text(TextMessageEventContent { body, formatted: Some(formatted), .. })
+    );
+    assert_eq!(body, "Hello Alice!\n\n- See this <https://ruma.dev>\n- code");
+    assert_eq!(formatted.body, html_body);
+    assert_eq!(formatted.to_plain_text().unwrap(), body);
+}
+
 #[test]
 fn verification_request_msgtype_deserialization() {
     let user_id = user_id!("@example2:localhost");
@@ -0,0 +1,312 @@
+use js_int::int;
+use ruma_common::{EventEncryptionAlgorithm, mxc_uri, room_alias_id, serde::Raw, user_id};
+use ruma_events::{
+    StateEventType,
+    room::{
+        history_visibility::HistoryVisibility, join_rules::JoinRule, member::MembershipState,
+        power_levels::UserPowerLevel,
+    },
+    room_state::RoomState,
+};
+use serde_json::{Value as JsonValue, from_value as from_json_value, json};
+
+fn state_event(event_type: &str, state_key: &str, sender: &str, content: JsonValue) -> JsonValue {
+    json!({
+        "type": event_type,
+        "state_key": state_key,
+        "sender": sender,
+        "content": content,
+        "event_id": format!("${}{state_key}", event_type.replace('.', "_")),
+        "origin_server_ts": 1,
+    })
+}
+
+fn room_state() -> RoomState {
+    let events = [
+        state_event(
+            "m.room.create",
+            "",
+            "@creator:localhost",
+            json!({ "room_version": "12", "additional_creators": ["@other_creator:localhost"] }),
+        ),
+        state_event("m.room.name", "", "@creator:localhost", json!({ "name": "Room" })),
+        state_event("m.room.topic", "", "@creator:localhost", json!({ "topic": "A room" })),
+        state_event(
+            "m.room.avatar",
+            "",
+            "@creator:localhost",
+            json!({ "url": "mxc://localhost/avatar" }),
+        ),
+        state_event(
+            "m.room.canonical_alias",
+            "",
+            "@creator:localhost",
+            json!({ "alias": "#room:localhost", "alt_aliases": ["#alt:localhost"] }),
+        ),
+        state_event(
+            "m.room.join_rules",
+            "",
+            "@creator:localhost",
+            json!({ "join_rule": "invite" }),
+        ),
+        state_event(
+            "m.room.history_visibility",
+            "",
+            "@creator:localhost",
+            json!({ "history_visibility": "joined" }),
+        ),
+        state_event(
+            "m.room.encryption",
+            "",
+            "@creator:localhost",
+            json!({ "algorithm": "m.megolm.v1.aes-sha2" }),
+        ),
+        state_event(
+            "m.room.power_levels",
+            "",
+            "@creator:localhost",
+            json!({ "users": { "@alice:localhost": 50 } }),
+        ),
+        state_event(
+            "m.room.server_acl",
+            "",
+            "@creator:localhost",
+            json!({ "allow": ["*"], "deny": ["evil.example.org"] }),
+        ),
+        state_event(
+            "m.room.member",
+            "@alice:localhost",
+            "@alice:localhost",
+            json!({ "membership": "join", "displayname": "Alice" }),
+        ),
+        state_event(
+            "m.room.member",
+            "@bob:localhost",
+            "@creator:localhost",
+            json!({ "membership": "invite" }),
+        ),
+        state_event(
+            "m.space.child",
+            "!child:localhost",
+            "@creator:localhost",
+            json!({ "via": ["localhost"] }),
+        ),
+    ];
+
+    let mut state = RoomState::new();
+    for event in events {
+        state.add_event(from_json_value(event).unwrap()).unwrap();
+    }
+    state
+}
+
+#[test]
+fn typed_accessors() {
+    let state = room_state();
+
+    assert_eq!(state.events().len(), 13);
+    assert_eq!(state.room_version().unwrap().as_str(), "12");
+    assert_eq!(state.name(), Some("Room"));
+    assert_eq!(state.topic(), Some("A room"));
+    assert_eq!(state.avatar_url(), Some(mxc_uri!("mxc://localhost/avatar")));
+    assert_eq!(state.canonical_alias(), Some(room_alias_id!("#room:localhost")));
+    assert_eq!(state.alt_aliases(), [room_alias_id!("#alt:localhost")]);
+    assert_eq!(state.join_rule(), Some(&JoinRule::Invite));
+    assert_eq!(state.history_visibility(), Some(&HistoryVisibility::Joined));
+    assert!(state.is_encrypted());
+    assert_eq!(state.encryption_algorithm(), Some(&EventEncryptionAlgorithm::MegolmV1AesSha2));
+
+    let acl = state.server_acl().unwrap();
+    assert!(acl.is_allowed(ruma_common::server_name!("matrix.org")));
+    assert!(!acl.is_allowed(ruma_common::server_name!("evil.example.org")));
+
+    let power_levels = state.power_levels().unwrap();
+    assert_eq!(power_levels.for_user(user_id!("@alice:localhost")), UserPowerLevel::Int(int!(50)));
+    assert_eq!(power_levels.for_user(user_id!("@creator:localhost")), UserPowerLevel::Infinite);
+    assert_eq!(
+        power_levels.for_user(user_id!("@other_creator:localhost")),
+        UserPowerLevel::Infinite
+    );
+
+    let alice = state.member(user_id!("@alice:localhost")).unwrap();
+    assert_eq!(alice.membership, &MembershipState::Join);
+    assert_eq!(alice.display_name, Some("Alice"));
+    assert!(state.member(user_id!("@carl:localhost")).is_none());
+
+    let members = state.members().map(|member| member.user_id).collect::<Vec<_>>();
+    assert_eq!(members, [user_id!("@alice:localhost"), user_id!("@bob:localhost")]);
+
+    let child = state.get(&StateEventType::SpaceChild, "!child:localhost").unwrap();
+    assert_eq!(child.event.sender(), user_id!("@creator:localhost"));
+}
+
+#[test]
+fn replace_events() {
+    let mut state = room_state();
+
+    let event = state_event("m.room.name", "", "@alice:localhost", json!({ "name": "New name" }));
+    state.add_event(from_json_value(event).unwrap()).unwrap();
+    assert_eq!(state.name(), Some("New name"));
+
+    let event = state_event(
+        "m.room.member",
+        "@alice:localhost",
+        "@alice:localhost",
+        json!({ "membership": "leave" }),
+    );
+    state.add_event(from_json_value(event).unwrap()).unwrap();
+    let alice = state.member(user_id!("@alice:localhost")).unwrap();
+    assert_eq!(alice.membership, &MembershipState::Leave);
+    assert_eq!(alice.display_name, None);
+}
+
+#[test]
+fn stripped_state() {
+    let events = [
+        json!({
+            "type": "m.room.create",
+            "state_key": "",
+            "sender": "@creator:localhost",
+            "content": { "room_version": "11" },
+        }),
+        json!({
+            "type": "m.room.name",
+            "state_key": "",
+            "sender": "@creator:localhost",
+            "content": { "name": "Invited room" },
+        }),
+        json!({
+            "type": "m.room.encryption",
+            "state_key": "",
+            "sender": "@creator:localhost",
+            "content": {},
+        }),
+        json!({
+            "type": "m.room.join_rules",
+            "state_key": "",
+            "sender": "@creator:localhost",
+            "content": { "join_rule": "knock" },
+        }),
+        json!({
+            "type": "m.room.member",
+            "state_key": "@bob:localhost",
+            "sender": "@creator:localhost",
+            "content": { "membership": "invite", "displayname": "Bob" },
+        }),
+    ];
+
+    let mut state = RoomState::new();
+    for event in events {
+        state.add_stripped_event(from_json_value(event).unwrap()).unwrap();
+    }
+
+    assert_eq!(state.name(), Some("Invited room"));
+    assert_eq!(state.topic(), None);
+    assert_eq!(state.join_rule(), Some(&JoinRule::Knock));
+    assert!(state.is_encrypted());
+    assert_eq!(state.encryption_algorithm(), None);
+
+    let bob = state.member(user_id!("@bob:localhost")).unwrap();
+    assert_eq!(bob.membership, &MembershipState::Invite);
+    assert_eq!(bob.display_name, Some("Bob"));
+
+    // Without power levels, the creator has the default power level of creators.
+    let power_levels = state.power_levels().unwrap();
+    assert_eq!(
+        power_levels.for_user(user_id!("@creator:localhost")),
+        UserPowerLevel::Int(int!(100))
+    );
+
+    let entry = state.get(&StateEventType::RoomName, "").unwrap();
+    assert_eq!(entry.event.event_id(), None);
+    assert!(!entry.event.is_redacted());
+}
+
+#[test]
+fn display_names() {
+    let own_user_id = user_id!("@alice:localhost");
+    let mut state = room_state();
+    assert_eq!(state.display_name(own_user_id).to_string(), "Room");
+
+    let add_event = |state: &mut RoomState, event_type, state_key: &str, content| {
+        let sender = if state_key.is_empty() { "@creator:localhost" } else { state_key };
+        let event = state_event(event_type, state_key, sender, content);
+        state.add_event(from_json_value(event).unwrap()).unwrap();
+    };
+
+    add_event(&mut state, "m.room.name", "", json!({ "name": "" }));
+    assert_eq!(state.display_name(own_user_id).to_string(), "#room:localhost");
+
+    add_event(&mut state, "m.room.canonical_alias", "", json!({}));
+    assert_eq!(state.display_name(own_user_id).to_string(), "@bob:localhost");
+
+    add_event(
+        &mut state,
+        "m.room.member",
+        "@carl:localhost",
+        json!({ "membership": "join", "displayname": "Carl" }),
+    );
+    assert_eq!(state.display_name(own_user_id).to_string(), "@bob:localhost and Carl");
+
+    // Display name changes are taken into account for disambiguation.
+    add_event(
+        &mut state,
+        "m.room.member",
+        "@bob:localhost",
+        json!({ "membership": "join", "displayname": "Carl" }),
+    );
+    assert_eq!(
+        state.member_display_name(user_id!("@carl:localhost")).unwrap(),
+        "Carl (@carl:localhost)"
+    );
+    assert_eq!(
+        state.display_name(own_user_id).to_string(),
+        "Carl (@bob:localhost) and Carl (@carl:localhost)"
+    );
+    assert_eq!(state.member_display_name(own_user_id).unwrap(), "Alice");
+    assert_eq!(state.member_display_name(user_id!("@dan:localhost")), None);
+
+    add_event(&mut state, "m.room.member", "@bob:localhost", json!({ "membership": "leave" }));
+    add_event(&mut state, "m.room.member", "@carl:localhost", json!({ "membership": "leave" }));
+    assert_eq!(
+        state.display_name(own_user_id).to_string(),
+        "Empty Room (was @bob:localhost and @carl:localhost)"
+    );
+}
+
+#[test]
+#[cfg(feature = "canonical-json")]
+fn redactions() {
+    use ruma_common::room_version_rules::RedactionRules;
+
+    let mut state = room_state();
+
+    let redaction = |redacts: &str| {
+        from_json_value::<Raw<_>>(json!({
+            "type": "m.room.redaction",
+            "sender": "@creator:localhost",
+            "event_id": format!("$redaction_{redacts}"),
+            "origin_server_ts": 2,
+            "content": { "redacts": redacts },
+        }))
+        .unwrap()
+    };
+
+    assert!(state.redact(&redaction("$m_room_name"), &RedactionRules::V11).unwrap());
+    assert!(state.redact(&redaction("$m_room_join_rules"), &RedactionRules::V11).unwrap());
+    assert!(
+        state.redact(&redaction("$m_room_member@alice:localhost"), &RedactionRules::V11).unwrap()
+    );
+    assert!(!state.redact(&redaction("$unknown"), &RedactionRules::V11).unwrap());
+
+    let name = state.get(&StateEventType::RoomName, "").unwrap();
+    assert!(name.event.is_redacted());
+    assert_eq!(state.name(), None);
+
+    // The join rule is preserved by the redaction algorithm.
+    assert_eq!(state.join_rule(), Some(&JoinRule::Invite));
+
+    let alice = state.member(user_id!("@alice:localhost")).unwrap();
+    assert_eq!(alice.membership, &MembershipState::Join);
+    assert_eq!(alice.display_name, None);
+}
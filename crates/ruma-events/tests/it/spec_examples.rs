@@ -0,0 +1,139 @@
+//! Conformance tests using the event examples of the [Matrix specification].
+//!
+//! The examples are vendored in `tests/it/fixtures/spec`, see the README there. Each example is
+//! deserialized into the corresponding event enum, then its content is serialized again, and the
+//! result must match the content of the example, except for the known differences listed by the
+//! test.
+//!
+//! [Matrix specification]: https://spec.matrix.org/latest/client-server-api/#events
+
+use ruma_common::serde::test::{JsonDifference, assert_json_round_trip, load_json_example};
+use ruma_events::{AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnyTimelineEvent};
+use serde_json::{
+    Value as JsonValue, from_value as from_json_value, json, to_value as to_json_value,
+};
+
+const FIXTURES_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/it/fixtures/spec");
+
+/// Load the example at the given path, relative to `tests/it/fixtures/spec`.
+fn load_example(path: &str) -> JsonValue {
+    load_json_example(FIXTURES_PATH, path)
+}
+
+/// Check that the content of the example event round-trips, with the differences relative to the
+/// event.
+fn assert_content_round_trip(
+    path: &str,
+    example: &JsonValue,
+    content: JsonValue,
+    known_differences: &[JsonDifference],
+) {
+    assert_json_round_trip(
+        path,
+        &json!({ "content": example["content"] }),
+        &json!({ "content": content }),
+        known_differences,
+    );
+}
+
+/// Check that the timeline event example at the given path round-trips through
+/// [`AnyTimelineEvent`].
+fn check_timeline_event(path: &str, known_differences: &[JsonDifference]) {
+    let example = load_example(path);
+    let event = from_json_value::<AnyTimelineEvent>(example.clone())
+        .unwrap_or_else(|error| panic!("failed to deserialize event `{path}`: {error}"));
+
+    assert_eq!(event.event_type().to_string(), example["type"]);
+    assert_eq!(event.event_id().as_str(), example["event_id"]);
+    assert_eq!(event.sender().as_str(), example["sender"]);
+    assert_eq!(event.room_id().as_str(), example["room_id"]);
+    assert_eq!(to_json_value(event.origin_server_ts()).unwrap(), example["origin_server_ts"]);
+
+    let content = match &event {
+        AnyTimelineEvent::MessageLike(event) => to_json_value(event.original_content().unwrap()),
+        AnyTimelineEvent::State(event) => {
+            assert_eq!(event.state_key(), example["state_key"]);
+            to_json_value(event.original_content().unwrap())
+        }
+    }
+    .unwrap_or_else(|error| panic!("failed to serialize content of event `{path}`: {error}"));
+
+    assert_content_round_trip(path, &example, content, known_differences);
+}
+
+/// Check that the global account data event example at the given path round-trips through
+/// [`AnyGlobalAccountDataEvent`].
+fn check_global_account_data_event(path: &str, known_differences: &[JsonDifference]) {
+    let example = load_example(path);
+    let event = from_json_value::<AnyGlobalAccountDataEvent>(example.clone())
+        .unwrap_or_else(|error| panic!("failed to deserialize event `{path}`: {error}"));
+
+    assert_eq!(event.event_type().to_string(), example["type"]);
+
+    let content = to_json_value(event.content())
+        .unwrap_or_else(|error| panic!("failed to serialize content of event `{path}`: {error}"));
+    assert_content_round_trip(path, &example, content, known_differences);
+}
+
+/// Check that the room account data event example at the given path round-trips through
+/// [`AnyRoomAccountDataEvent`].
+fn check_room_account_data_event(path: &str, known_differences: &[JsonDifference]) {
+    let example = load_example(path);
+    let event = from_json_value::<AnyRoomAccountDataEvent>(example.clone())
+        .unwrap_or_else(|error| panic!("failed to deserialize event `{path}`: {error}"));
+
+    assert_eq!(event.event_type().to_string(), example["type"]);
+
+    let content = to_json_value(event.content())
+        .unwrap_or_else(|error| panic!("failed to serialize content of event `{path}`: {error}"));
+    assert_content_round_trip(path, &example, content, known_differences);
+}
+
+#[test]
+fn room_message() {
+    check_timeline_event("m.room.message$m.text.yaml", &[]);
+}
+
+#[test]
+fn reaction() {
+    check_timeline_event("m.reaction.yaml", &[]);
+}
+
+#[test]
+fn room_member() {
+    check_timeline_event("m.room.member.yaml", &[]);
+}
+
+#[test]
+fn room_create() {
+    check_timeline_event(
+        "m.room.create.yaml",
+        // `m.federate` is not serialized when it is the default value.
+        &[JsonDifference::Dropped("/content/m.federate".to_owned())],
+    );
+}
+
+#[test]
+fn room_name() {
+    check_timeline_event("m.room.name.yaml", &[]);
+}
+
+#[test]
+fn room_topic() {
+    check_timeline_event("m.room.topic.yaml", &[]);
+}
+
+#[test]
+fn room_power_levels() {
+    check_timeline_event("m.room.power_levels.yaml", &[]);
+}
+
+#[test]
+fn direct() {
+    check_global_account_data_event("m.direct.yaml", &[]);
+}
+
+#[test]
+fn tag() {
+    check_room_account_data_event("m.tag.yaml", &[]);
+}
@@ -10,12 +10,9 @@ use serde::{Deserialize, Serialize};
 pub mod get_content;
 pub mod get_content_thumbnail;
 
-/// The `multipart/mixed` mime "essence".
-const MULTIPART_MIXED: &str = "multipart/mixed";
-/// The maximum number of headers to parse in a body part.
-const MAX_HEADERS_COUNT: usize = 32;
-/// The length of the generated boundary.
-const GENERATED_BOUNDARY_LENGTH: usize = 30;
+/// The maximum length of the serialized metadata in a streamed `multipart/mixed` body.
+#[cfg(feature = "client")]
+const MAX_STREAMED_METADATA_LENGTH: usize = 64 * 1024;
 
 /// The metadata of a file from the content repository.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -70,75 +67,200 @@ impl Content {
     }
 }
 
-/// Serialize the given metadata and content into a `http::Response` `multipart/mixed` body.
+/// The description of the fields of a `multipart/mixed` response.
 ///
-/// Returns a tuple containing the boundary used
+/// The body is described as raw bytes, since OpenAPI cannot describe the parts of the body.
+#[cfg(feature = "openapi")]
+fn multipart_mixed_response_fields() -> Vec<ruma_common::api::openapi::FieldDescription> {
+    use ruma_common::api::openapi::{FieldDescription, FieldLocation, Schema};
+
+    vec![FieldDescription::new("body", FieldLocation::RawBody, true, Schema::binary())]
+}
+
+/// Serialize the given metadata and content into a `http::Response` `multipart/mixed` body.
 #[cfg(feature = "server")]
 fn try_into_multipart_mixed_response<T: Default + bytes::BufMut>(
     metadata: &ContentMetadata,
     content: &FileOrLocation,
 ) -> Result<http::Response<T>, ruma_common::api::error::IntoHttpError> {
-    use std::io::Write as _;
+    use ruma_common::api::multipart::MultipartMixedBuilder;
+
+    let builder = MultipartMixedBuilder::with_generated_boundary();
 
-    use rand::Rng as _;
+    let mut body = T::default();
+    write_multipart_mixed_head(&builder, metadata, content, &mut body)?;
 
-    let boundary = rand::thread_rng()
-        .sample_iter(&rand::distributions::Alphanumeric)
-        .map(char::from)
-        .take(GENERATED_BOUNDARY_LENGTH)
-        .collect::<String>();
+    if let FileOrLocation::File(content) = content {
+        body.put_slice(&content.file);
+    }
 
-    let mut body_writer = T::default().writer();
+    builder.write_end(&mut body);
+
+    Ok(http::Response::builder()
+        .header(http::header::CONTENT_TYPE, builder.content_type())
+        .body(body)?)
+}
 
-    // Add first boundary separator and header for the metadata.
-    let _ = write!(
-        body_writer,
-        "\r\n--{boundary}\r\n{}: {}\r\n\r\n",
+/// Serialize the given metadata and content into a `http::Response` with a `multipart/mixed` body
+/// that streams the given body as the content of the file.
+///
+/// The bytes of the file in `content` are ignored. If `content` is a location, `body` is ignored.
+#[cfg(feature = "server")]
+fn try_into_multipart_mixed_response_with_body<B>(
+    metadata: &ContentMetadata,
+    content: &FileOrLocation,
+    body: B,
+) -> Result<http::Response<MultipartMixedBody<B>>, ruma_common::api::error::IntoHttpError> {
+    use ruma_common::api::multipart::MultipartMixedBuilder;
+
+    let builder = MultipartMixedBuilder::with_generated_boundary();
+
+    let mut head = Vec::new();
+    write_multipart_mixed_head(&builder, metadata, content, &mut head)?;
+
+    let mut tail = Vec::new();
+    builder.write_end(&mut tail);
+
+    let body = MultipartMixedBody {
+        head: Some(head.into()),
+        file: matches!(content, FileOrLocation::File(_)).then_some(body),
+        tail: Some(tail.into()),
+    };
+
+    Ok(http::Response::builder()
+        .header(http::header::CONTENT_TYPE, builder.content_type())
+        .body(body)?)
+}
+
+/// Write the part of a `multipart/mixed` body that comes before the bytes of the file.
+#[cfg(feature = "server")]
+fn write_multipart_mixed_head<T: bytes::BufMut>(
+    builder: &ruma_common::api::multipart::MultipartMixedBuilder,
+    metadata: &ContentMetadata,
+    content: &FileOrLocation,
+    dst: &mut T,
+) -> Result<(), ruma_common::api::error::IntoHttpError> {
+    use http::{HeaderMap, HeaderValue};
+
+    // Add the metadata part.
+    let mut metadata_headers = HeaderMap::new();
+    metadata_headers.insert(
         http::header::CONTENT_TYPE,
-        mime::APPLICATION_JSON
+        HeaderValue::from_static(mime::APPLICATION_JSON.as_ref()),
     );
+    builder.write_part_head(&metadata_headers, dst);
+    serde_json::to_writer(bytes::BufMut::writer(&mut *dst), metadata)?;
 
-    // Add serialized metadata.
-    serde_json::to_writer(&mut body_writer, metadata)?;
+    // Add the headers of the content part.
+    let mut content_headers = HeaderMap::new();
 
-    // Add second boundary separator.
-    let _ = write!(body_writer, "\r\n--{boundary}\r\n");
-
-    // Add content.
     match content {
         FileOrLocation::File(content) => {
-            // Add headers.
             let content_type =
                 content.content_type.as_deref().unwrap_or(mime::APPLICATION_OCTET_STREAM.as_ref());
-            let _ = write!(body_writer, "{}: {content_type}\r\n", http::header::CONTENT_TYPE);
+            content_headers.insert(http::header::CONTENT_TYPE, content_type.try_into()?);
 
             if let Some(content_disposition) = &content.content_disposition {
-                let _ = write!(
-                    body_writer,
-                    "{}: {content_disposition}\r\n",
-                    http::header::CONTENT_DISPOSITION
-                );
+                content_headers
+                    .insert(http::header::CONTENT_DISPOSITION, content_disposition.try_into()?);
             }
+        }
+        FileOrLocation::Location(location) => {
+            // Only add location header.
+            content_headers.insert(http::header::LOCATION, location.as_str().try_into()?);
+        }
+    }
 
-            // Add empty line separator after headers.
-            let _ = body_writer.write_all(b"\r\n");
+    builder.write_part_head(&content_headers, dst);
+
+    Ok(())
+}
 
-            // Add bytes.
-            let _ = body_writer.write_all(&content.file);
+/// A `multipart/mixed` HTTP body that streams the content of a file after its metadata.
+///
+/// This is the body of the HTTP responses of the `get_content` and `get_content_thumbnail`
+/// endpoints created with [`OutgoingRawBodyResponse`].
+///
+/// The trailers of the body of the file are dropped.
+///
+/// [`OutgoingRawBodyResponse`]: ruma_common::api::OutgoingRawBodyResponse
+#[cfg(feature = "server")]
+#[derive(Debug)]
+pub struct MultipartMixedBody<B> {
+    /// The part of the body before the bytes of the file.
+    head: Option<bytes::Bytes>,
+
+    /// The body of the file, if it was not consumed yet.
+    file: Option<B>,
+
+    /// The part of the body after the bytes of the file.
+    tail: Option<bytes::Bytes>,
+}
+
+#[cfg(feature = "server")]
+impl<B> http_body::Body for MultipartMixedBody<B>
+where
+    B: http_body::Body + Unpin,
+{
+    type Data = bytes::Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        use std::{pin::Pin, task::Poll};
+
+        use bytes::Buf as _;
+        use http_body::Frame;
+
+        let this = self.get_mut();
+
+        if let Some(head) = this.head.take() {
+            return Poll::Ready(Some(Ok(Frame::data(head))));
         }
-        FileOrLocation::Location(location) => {
-            // Only add location header and empty line separator.
-            let _ = write!(body_writer, "{}: {location}\r\n\r\n", http::header::LOCATION);
+
+        while let Some(file) = &mut this.file {
+            match std::task::ready!(Pin::new(file).poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    // Trailers cannot be sent in the middle of the body, drop them.
+                    if let Ok(mut data) = frame.into_data() {
+                        let data = data.copy_to_bytes(data.remaining());
+                        return Poll::Ready(Some(Ok(Frame::data(data))));
+                    }
+                }
+                Some(Err(error)) => return Poll::Ready(Some(Err(error))),
+                None => this.file = None,
+            }
         }
+
+        Poll::Ready(this.tail.take().map(|tail| Ok(Frame::data(tail))))
     }
 
-    // Add final boundary.
-    let _ = write!(body_writer, "\r\n--{boundary}--");
+    fn is_end_stream(&self) -> bool {
+        self.head.is_none() && self.file.is_none() && self.tail.is_none()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        let parts_len = self.head.as_ref().map_or(0, |head| head.len())
+            + self.tail.as_ref().map_or(0, |tail| tail.len());
+        let parts_len = parts_len as u64;
+
+        let mut size_hint = http_body::SizeHint::new();
 
-    let content_type = format!("{MULTIPART_MIXED}; boundary={boundary}");
-    let body = body_writer.into_inner();
+        if let Some(file) = &self.file {
+            let file_size_hint = file.size_hint();
+            size_hint.set_lower(file_size_hint.lower() + parts_len);
 
-    Ok(http::Response::builder().header(http::header::CONTENT_TYPE, content_type).body(body)?)
+            if let Some(upper) = file_size_hint.upper() {
+                size_hint.set_upper(upper + parts_len);
+            }
+        } else {
+            size_hint.set_exact(parts_len);
+        }
+
+        size_hint
+    }
 }
 
 /// Deserialize the given metadata and content from a `http::Response` with a `multipart/mixed`
@@ -150,167 +272,233 @@ fn try_from_multipart_mixed_response<T: AsRef<[u8]>>(
     (ContentMetadata, FileOrLocation),
     ruma_common::api::error::FromHttpResponseError<ruma_common::api::error::MatrixError>,
 > {
-    use ruma_common::api::error::{HeaderDeserializationError, MultipartMixedDeserializationError};
-
-    // First, get the boundary from the content type header.
-    let body_content_type = http_response
-        .headers()
-        .get(http::header::CONTENT_TYPE)
-        .ok_or_else(|| HeaderDeserializationError::MissingHeader("Content-Type".to_owned()))?
-        .to_str()?
-        .parse::<mime::Mime>()
-        .map_err(|e| HeaderDeserializationError::InvalidHeader(e.into()))?;
-
-    if !body_content_type.essence_str().eq_ignore_ascii_case(MULTIPART_MIXED) {
-        return Err(HeaderDeserializationError::InvalidHeaderValue {
-            header: "Content-Type".to_owned(),
-            expected: MULTIPART_MIXED.to_owned(),
-            unexpected: body_content_type.essence_str().to_owned(),
-        }
-        .into());
-    }
-
-    let boundary = body_content_type
-        .get_param("boundary")
-        .ok_or(HeaderDeserializationError::MissingMultipartBoundary)?
-        .as_str()
-        .as_bytes();
-
-    // Split the body with the boundary.
-    let body = http_response.body().as_ref();
-
-    let mut full_boundary = Vec::with_capacity(boundary.len() + 4);
-    full_boundary.extend_from_slice(b"\r\n--");
-    full_boundary.extend_from_slice(boundary);
-    let full_boundary_no_crlf = full_boundary.strip_prefix(b"\r\n").unwrap();
-
-    let mut boundaries = memchr::memmem::find_iter(body, &full_boundary);
-
-    let metadata_start = if body.starts_with(full_boundary_no_crlf) {
-        // If there is no preamble before the first boundary, it may omit the
-        // preceding CRLF.
-        full_boundary_no_crlf.len()
-    } else {
-        boundaries.next().ok_or_else(|| MultipartMixedDeserializationError::MissingBodyParts {
-            expected: 2,
-            found: 0,
-        })? + full_boundary.len()
+    use ruma_common::api::{
+        error::MultipartMixedDeserializationError,
+        multipart::{Boundary, parse_body},
     };
-    let metadata_end = boundaries.next().ok_or_else(|| {
-        MultipartMixedDeserializationError::MissingBodyParts { expected: 2, found: 0 }
-    })?;
 
-    let (_raw_metadata_headers, serialized_metadata) =
-        parse_multipart_body_part(body, metadata_start, metadata_end)?;
+    let boundary = Boundary::from_headers(http_response.headers())?;
+    let mut parts = parse_body(&boundary, http_response.body().as_ref())?.into_iter();
+
+    let metadata_part = parts
+        .next()
+        .ok_or(MultipartMixedDeserializationError::MissingBodyParts { expected: 2, found: 0 })?;
+    let content_part = parts
+        .next()
+        .ok_or(MultipartMixedDeserializationError::MissingBodyParts { expected: 2, found: 1 })?;
 
     // Don't search for anything in the headers, just deserialize the content that should be JSON.
-    let metadata = serde_json::from_slice(serialized_metadata)?;
-
-    // Look at the part containing the media content now.
-    let content_start = metadata_end + full_boundary.len();
-    let content_end = boundaries.next().ok_or_else(|| {
-        MultipartMixedDeserializationError::MissingBodyParts { expected: 2, found: 1 }
-    })?;
-
-    let (raw_content_headers, file) = parse_multipart_body_part(body, content_start, content_end)?;
-
-    // Parse the headers to retrieve the content type and content disposition.
-    let mut content_headers = [httparse::EMPTY_HEADER; MAX_HEADERS_COUNT];
-    httparse::parse_headers(raw_content_headers, &mut content_headers)
-        .map_err(|e| MultipartMixedDeserializationError::InvalidHeader(e.into()))?;
-
-    let mut location = None;
-    let mut content_type = None;
-    let mut content_disposition = None;
-    for header in content_headers {
-        if header.name.is_empty() {
-            // This is a empty header, we have reached the end of the parsed headers.
-            break;
-        }
+    let metadata = serde_json::from_slice(&metadata_part.content)?;
+    let content =
+        content_from_multipart_headers(&content_part.headers, content_part.content.into())?;
+
+    Ok((metadata, content))
+}
+
+/// Deserialize the given metadata and content from a `http::Response` with a `multipart/mixed`
+/// body, without reading the bytes of the file.
+///
+/// The bytes of the file can be read from the returned body.
+#[cfg(feature = "client")]
+async fn try_from_multipart_mixed_response_with_body<B>(
+    http_response: http::Response<B>,
+) -> Result<
+    (ContentMetadata, FileOrLocation, MultipartMixedFileBody<B>),
+    ruma_common::api::error::FromHttpResponseError<ruma_common::api::error::MatrixError>,
+>
+where
+    B: http_body::Body + Unpin,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    use ruma_common::api::{
+        error::MultipartMixedDeserializationError,
+        multipart::{Boundary, MultipartMixedEvent, MultipartMixedParser},
+    };
+
+    let boundary = Boundary::from_headers(http_response.headers())?;
+    let mut parser = MultipartMixedParser::new(&boundary);
+    let mut body = http_response.into_body();
 
-        if header.name == http::header::LOCATION {
-            location = Some(
-                String::from_utf8(header.value.to_vec())
-                    .map_err(|e| MultipartMixedDeserializationError::InvalidHeader(e.into()))?,
-            );
-
-            // This is the only header we need, stop parsing.
-            break;
-        } else if header.name == http::header::CONTENT_TYPE {
-            content_type = Some(
-                String::from_utf8(header.value.to_vec())
-                    .map_err(|e| MultipartMixedDeserializationError::InvalidHeader(e.into()))?,
-            );
-        } else if header.name == http::header::CONTENT_DISPOSITION {
-            content_disposition = Some(
-                ContentDisposition::try_from(header.value)
-                    .map_err(|e| MultipartMixedDeserializationError::InvalidHeader(e.into()))?,
-            );
+    // Read the metadata part.
+    let mut serialized_metadata = Vec::new();
+
+    loop {
+        match parser.next_event_from_body(&mut body).await? {
+            MultipartMixedEvent::PartStart(_) => {}
+            MultipartMixedEvent::PartData(data) => {
+                if serialized_metadata.len() + data.len() > MAX_STREAMED_METADATA_LENGTH {
+                    return Err(MultipartMixedDeserializationError::BodyPartTooLarge.into());
+                }
+
+                serialized_metadata.extend_from_slice(&data);
+            }
+            MultipartMixedEvent::PartEnd => break,
+            MultipartMixedEvent::End => {
+                return Err(MultipartMixedDeserializationError::MissingBodyParts {
+                    expected: 2,
+                    found: 0,
+                }
+                .into());
+            }
         }
     }
 
-    let content = if let Some(location) = location {
-        FileOrLocation::Location(location)
-    } else {
-        FileOrLocation::File(Content {
-            file: file.to_owned(),
-            content_type: content_type.map(Into::into),
-            content_disposition,
-        })
+    let metadata = serde_json::from_slice(&serialized_metadata)?;
+
+    // Read the headers of the part containing the media content.
+    let MultipartMixedEvent::PartStart(headers) = parser.next_event_from_body(&mut body).await?
+    else {
+        return Err(
+            MultipartMixedDeserializationError::MissingBodyParts { expected: 2, found: 1 }.into()
+        );
     };
 
-    Ok((metadata, content))
+    let content = content_from_multipart_headers(&headers, Vec::new())?;
+    let file = MultipartMixedFileBody { body, parser, file_finished: false, finished: false };
+
+    Ok((metadata, content, file))
 }
 
-/// Parse the multipart body part in the given bytes, starting and ending at the given positions.
+/// A body that streams the bytes of a file in a `multipart/mixed` body.
 ///
-/// Returns a `(headers_bytes, content_bytes)` tuple. Returns an error if the separation between the
-/// headers and the content could not be found.
+/// This is the raw body returned alongside the responses of the `get_content` and
+/// `get_content_thumbnail` endpoints by [`IncomingRawBodyResponse`]. It only returns the content
+/// of the body part of the file, but reads the body until the final delimiter, and returns an error
+/// if the body ends before it.
+///
+/// [`IncomingRawBodyResponse`]: ruma_common::api::IncomingRawBodyResponse
 #[cfg(feature = "client")]
-fn parse_multipart_body_part(
-    bytes: &[u8],
-    start: usize,
-    end: usize,
-) -> Result<(&[u8], &[u8]), ruma_common::api::error::MultipartMixedDeserializationError> {
-    use ruma_common::api::error::MultipartMixedDeserializationError;
-
-    // The part should start with a newline after the boundary. We need to ignore characters before
-    // it in case of extra whitespaces, and for compatibility it might not have a CR.
-    let headers_start = memchr::memchr(b'\n', &bytes[start..end])
-        .expect("the end boundary contains a newline")
-        + start
-        + 1;
-
-    // Let's find an empty line now.
-    let mut line_start = headers_start;
-    let mut line_end;
+#[derive(Debug)]
+pub struct MultipartMixedFileBody<B> {
+    /// The `multipart/mixed` body.
+    body: B,
 
-    loop {
-        line_end = memchr::memchr(b'\n', &bytes[line_start..end])
-            .ok_or(MultipartMixedDeserializationError::MissingBodyPartInnerSeparator)?
-            + line_start
-            + 1;
+    /// The parser of the `multipart/mixed` body.
+    parser: ruma_common::api::multipart::MultipartMixedParser,
 
-        if matches!(&bytes[line_start..line_end], b"\r\n" | b"\n") {
-            break;
+    /// Whether the end of the body part of the file was reached.
+    file_finished: bool,
+
+    /// Whether the end of the `multipart/mixed` body was reached.
+    finished: bool,
+}
+
+#[cfg(feature = "client")]
+impl<B> http_body::Body for MultipartMixedFileBody<B>
+where
+    B: http_body::Body + Unpin,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Data = bytes::Bytes;
+    type Error = ruma_common::api::error::DeserializationError;
+
+    fn poll_frame(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        use std::{pin::Pin, task::Poll};
+
+        use ruma_common::api::multipart::MultipartMixedEvent;
+
+        let this = self.get_mut();
+
+        if this.finished {
+            return Poll::Ready(None);
         }
 
-        line_start = line_end;
+        loop {
+            match std::task::ready!(this.parser.poll_next_event(Pin::new(&mut this.body), cx)) {
+                Ok(MultipartMixedEvent::PartData(data)) if !this.file_finished => {
+                    return Poll::Ready(Some(Ok(http_body::Frame::data(data))));
+                }
+                Ok(MultipartMixedEvent::PartEnd) => {
+                    this.file_finished = true;
+                }
+                Ok(MultipartMixedEvent::End) => {
+                    this.finished = true;
+                    return Poll::Ready(None);
+                }
+                // Ignore the body parts after the file.
+                Ok(MultipartMixedEvent::PartStart(_) | MultipartMixedEvent::PartData(_)) => {}
+                Err(error) => {
+                    this.finished = true;
+                    return Poll::Ready(Some(Err(error)));
+                }
+            }
+        }
     }
 
-    Ok((&bytes[headers_start..line_start], &bytes[line_end..end]))
+    fn is_end_stream(&self) -> bool {
+        self.finished
+    }
+}
+
+/// Construct the content of the file with the given headers and bytes.
+#[cfg(feature = "client")]
+fn content_from_multipart_headers(
+    headers: &ruma_common::api::multipart::BodyPartHeaders,
+    file: Vec<u8>,
+) -> Result<FileOrLocation, ruma_common::api::error::MultipartMixedDeserializationError> {
+    if let Some(location) = headers.get_str(http::header::LOCATION)? {
+        // This is the only header we need.
+        return Ok(FileOrLocation::Location(location.to_owned()));
+    }
+
+    Ok(FileOrLocation::File(Content {
+        file,
+        content_type: headers.content_type()?.map(|content_type| content_type.to_owned().into()),
+        content_disposition: headers.content_disposition()?,
+    }))
 }
 
 #[cfg(all(test, feature = "client", feature = "server"))]
 mod tests {
+    use std::{
+        convert::Infallible,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
     use assert_matches2::assert_matches;
-    use ruma_common::http_headers::{ContentDisposition, ContentDispositionType};
+    use bytes::Bytes;
+    use http_body::Frame;
+    use macro_rules_attribute::apply;
+    use ruma_common::{
+        api::collect_body,
+        http_headers::{ContentDisposition, ContentDispositionType},
+    };
+    use smol_macros::test;
 
     use super::{
         Content, ContentMetadata, FileOrLocation, try_from_multipart_mixed_response,
-        try_into_multipart_mixed_response,
+        try_from_multipart_mixed_response_with_body, try_into_multipart_mixed_response,
+        try_into_multipart_mixed_response_with_body,
     };
 
+    /// A body that returns the given chunks, one per frame.
+    #[derive(Debug)]
+    struct ChunkedBody(Vec<Bytes>);
+
+    impl ChunkedBody {
+        /// Split the given bytes into chunks of the given size.
+        fn split(bytes: &[u8], chunk_size: usize) -> Self {
+            Self(bytes.chunks(chunk_size).map(Bytes::copy_from_slice).collect())
+        }
+    }
+
+    impl http_body::Body for ChunkedBody {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+            let chunk = (!self.0.is_empty()).then(|| self.0.remove(0));
+            Poll::Ready(chunk.map(|chunk| Ok(Frame::data(chunk))))
+        }
+    }
+
     #[test]
     fn multipart_mixed_content_ascii_filename_conversions() {
         let file = "s⌽me UTF-8 Ťext".as_bytes();
@@ -561,4 +749,144 @@ mod tests {
         assert_eq!(content_disposition.disposition_type, ContentDispositionType::Inline);
         assert_eq!(content_disposition.filename.unwrap(), "ȵ⌾Ⱦԩ💈Ňɠ");
     }
+
+    #[apply(test!)]
+    async fn multipart_mixed_streaming_round_trip() {
+        let file = "s⌽me UTF-8 Ťext".as_bytes();
+        let content_disposition = ContentDisposition::new(ContentDispositionType::Attachment)
+            .with_filename(Some("filename.txt".to_owned()));
+        let outgoing_content = FileOrLocation::File(Content {
+            file: Vec::new(),
+            content_type: Some("text/plain".into()),
+            content_disposition: Some(content_disposition.clone()),
+        });
+
+        let response = try_into_multipart_mixed_response_with_body(
+            &ContentMetadata::new(),
+            &outgoing_content,
+            ChunkedBody::split(file, 4),
+        )
+        .unwrap();
+        let (parts, body) = response.into_parts();
+        let body = collect_body(body, usize::MAX).await.unwrap();
+
+        // The streamed body is the same as the buffered body.
+        let buffered_response = http::Response::from_parts(parts.clone(), body.as_slice());
+        let (_metadata, content) = try_from_multipart_mixed_response(buffered_response).unwrap();
+        assert_matches!(content, FileOrLocation::File(file_content));
+        assert_eq!(file_content.file, file);
+
+        // Split the body at every position, to cut the boundaries and the headers.
+        for chunk_size in 1..=body.len() {
+            let response =
+                http::Response::from_parts(parts.clone(), ChunkedBody::split(&body, chunk_size));
+            let (_metadata, content, file_body) =
+                try_from_multipart_mixed_response_with_body(response).await.unwrap();
+
+            assert_matches!(content, FileOrLocation::File(file_content));
+            assert_eq!(file_content.file, b"");
+            assert_eq!(file_content.content_type.unwrap(), "text/plain");
+            assert_eq!(file_content.content_disposition.as_ref(), Some(&content_disposition));
+            assert_eq!(collect_body(file_body, usize::MAX).await.unwrap(), file);
+        }
+    }
+
+    #[apply(test!)]
+    async fn multipart_mixed_streaming_location() {
+        let outgoing_content =
+            FileOrLocation::Location("https://server.local/media/filename.txt".to_owned());
+
+        let response = try_into_multipart_mixed_response_with_body(
+            &ContentMetadata::new(),
+            &outgoing_content,
+            ChunkedBody::split(b"ignored", 1),
+        )
+        .unwrap();
+        let (parts, body) = response.into_parts();
+        let body = collect_body(body, usize::MAX).await.unwrap();
+
+        let response = http::Response::from_parts(parts, ChunkedBody::split(&body, 5));
+        let (_metadata, content, file_body) =
+            try_from_multipart_mixed_response_with_body(response).await.unwrap();
+
+        assert_matches!(content, FileOrLocation::Location(file_location));
+        assert_eq!(file_location, "https://server.local/media/filename.txt");
+        assert_eq!(collect_body(file_body, usize::MAX).await.unwrap(), b"");
+    }
+
+    #[apply(test!)]
+    async fn multipart_mixed_streaming_boundary_prefix_in_file() {
+        // The file contains the start of the boundary.
+        let body = "\r\n--abcdef\r\n\r\n{}\r\n--abcdef\r\n\r\nsome\r\n--abc text\r\n--abcdef--";
+
+        for chunk_size in 1..=body.len() {
+            let response = http::Response::builder()
+                .header(http::header::CONTENT_TYPE, "multipart/mixed; boundary=abcdef")
+                .body(ChunkedBody::split(body.as_bytes(), chunk_size))
+                .unwrap();
+
+            let (_metadata, _content, file_body) =
+                try_from_multipart_mixed_response_with_body(response).await.unwrap();
+            assert_eq!(collect_body(file_body, usize::MAX).await.unwrap(), b"some\r\n--abc text");
+        }
+    }
+
+    #[apply(test!)]
+    async fn multipart_mixed_streaming_extra_body_part() {
+        // The body parts after the file are ignored.
+        let body = "\r\n--abcdef\r\n\r\n{}\r\n--abcdef\r\n\r\nsome plain text\r\n--abcdef\r\n\r\nextra\r\n--abcdef--";
+        let response = http::Response::builder()
+            .header(http::header::CONTENT_TYPE, "multipart/mixed; boundary=abcdef")
+            .body(ChunkedBody::split(body.as_bytes(), 7))
+            .unwrap();
+
+        let (_metadata, _content, file_body) =
+            try_from_multipart_mixed_response_with_body(response).await.unwrap();
+        assert_eq!(collect_body(file_body, usize::MAX).await.unwrap(), b"some plain text");
+    }
+
+    #[apply(test!)]
+    async fn multipart_mixed_streaming_invalid() {
+        // Missing final boundary.
+        let body = "\r\n--abcdef\r\n\r\n{}\r\n--abcdef\r\n\r\nsome plain text";
+        let response = http::Response::builder()
+            .header(http::header::CONTENT_TYPE, "multipart/mixed; boundary=abcdef")
+            .body(ChunkedBody::split(body.as_bytes(), 7))
+            .unwrap();
+
+        let (_metadata, _content, file_body) =
+            try_from_multipart_mixed_response_with_body(response).await.unwrap();
+        collect_body(file_body, usize::MAX).await.unwrap_err();
+
+        // Truncated final delimiter.
+        let body = "\r\n--abcdef\r\n\r\n{}\r\n--abcdef\r\n\r\nsome plain text\r\n--abcdef";
+        for chunk_size in 1..=body.len() {
+            let response = http::Response::builder()
+                .header(http::header::CONTENT_TYPE, "multipart/mixed; boundary=abcdef")
+                .body(ChunkedBody::split(body.as_bytes(), chunk_size))
+                .unwrap();
+
+            let (_metadata, _content, file_body) =
+                try_from_multipart_mixed_response_with_body(response).await.unwrap();
+            collect_body(file_body, usize::MAX).await.unwrap_err();
+        }
+
+        // Missing second body part.
+        let body = "\r\n--abcdef\r\n\r\n{}\r\n";
+        let response = http::Response::builder()
+            .header(http::header::CONTENT_TYPE, "multipart/mixed; boundary=abcdef")
+            .body(ChunkedBody::split(body.as_bytes(), 7))
+            .unwrap();
+
+        try_from_multipart_mixed_response_with_body(response).await.unwrap_err();
+
+        // Missing header and content empty line separator in file body part.
+        let body = "\r\n--abcdef\r\n\r\n{}\r\n--abcdef\r\nContent-Type: text/plain\r\nsome plain text\r\n--abcdef--";
+        let response = http::Response::builder()
+            .header(http::header::CONTENT_TYPE, "multipart/mixed; boundary=abcdef")
+            .body(ChunkedBody::split(body.as_bytes(), 7))
+            .unwrap();
+
+        try_from_multipart_mixed_response_with_body(response).await.unwrap_err();
+    }
 }
@@ -99,6 +99,55 @@ impl ruma_common::api::OutgoingResponse for Response {
     }
 }
 
+#[cfg(feature = "client")]
+impl ruma_common::api::IncomingRawBodyResponse for Response {
+    type RawBody<B> =
+        <super::v1::Response as ruma_common::api::IncomingRawBodyResponse>::RawBody<B>;
+
+    async fn try_from_http_response_with_body<B>(
+        http_response: http::Response<B>,
+    ) -> Result<
+        (Self, Self::RawBody<B>),
+        ruma_common::api::error::FromHttpResponseError<Self::EndpointError>,
+    >
+    where
+        B: http_body::Body + Send + Unpin,
+        B::Data: Send,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        // Reuse the custom deserialization.
+        let (response, file) =
+            super::v1::Response::try_from_http_response_with_body(http_response).await?;
+        Ok((response.into(), file))
+    }
+}
+
+#[cfg(feature = "server")]
+impl ruma_common::api::OutgoingRawBodyResponse for Response {
+    type HttpBody<B> =
+        <super::v1::Response as ruma_common::api::OutgoingRawBodyResponse>::HttpBody<B>;
+
+    fn try_into_http_response_with_body<B>(
+        self,
+        body: B,
+    ) -> Result<http::Response<Self::HttpBody<B>>, ruma_common::api::error::IntoHttpError> {
+        // Reuse the custom serialization.
+        super::v1::Response::from(self).try_into_http_response_with_body(body)
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl ruma_common::api::openapi::DescribeResponse for Response {
+    fn response_status() -> http::StatusCode {
+        http::StatusCode::OK
+    }
+
+    fn response_fields() -> Vec<ruma_common::api::openapi::FieldDescription> {
+        // Reuse the description of the stable endpoint.
+        <super::v1::Response as ruma_common::api::openapi::DescribeResponse>::response_fields()
+    }
+}
+
 impl From<super::v1::Response> for Response {
     fn from(value: super::v1::Response) -> Self {
         let super::v1::Response { metadata, content } = value;
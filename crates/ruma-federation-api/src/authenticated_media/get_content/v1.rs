@@ -92,3 +92,58 @@ impl ruma_common::api::OutgoingResponse for Response {
         crate::authenticated_media::try_into_multipart_mixed_response(&self.metadata, &self.content)
     }
 }
+
+#[cfg(feature = "client")]
+impl ruma_common::api::IncomingRawBodyResponse for Response {
+    type RawBody<B> = crate::authenticated_media::MultipartMixedFileBody<B>;
+
+    async fn try_from_http_response_with_body<B>(
+        http_response: http::Response<B>,
+    ) -> Result<
+        (Self, Self::RawBody<B>),
+        ruma_common::api::error::FromHttpResponseError<Self::EndpointError>,
+    >
+    where
+        B: http_body::Body + Send + Unpin,
+        B::Data: Send,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        if http_response.status().as_u16() < 400 {
+            let (metadata, content, file) =
+                crate::authenticated_media::try_from_multipart_mixed_response_with_body(
+                    http_response,
+                )
+                .await?;
+            Ok((Self { metadata, content }, file))
+        } else {
+            Err(ruma_common::api::error_from_http_response_with_body(http_response).await)
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl ruma_common::api::OutgoingRawBodyResponse for Response {
+    type HttpBody<B> = crate::authenticated_media::MultipartMixedBody<B>;
+
+    fn try_into_http_response_with_body<B>(
+        self,
+        body: B,
+    ) -> Result<http::Response<Self::HttpBody<B>>, ruma_common::api::error::IntoHttpError> {
+        crate::authenticated_media::try_into_multipart_mixed_response_with_body(
+            &self.metadata,
+            &self.content,
+            body,
+        )
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl ruma_common::api::openapi::DescribeResponse for Response {
+    fn response_status() -> http::StatusCode {
+        http::StatusCode::OK
+    }
+
+    fn response_fields() -> Vec<ruma_common::api::openapi::FieldDescription> {
+        crate::authenticated_media::multipart_mixed_response_fields()
+    }
+}
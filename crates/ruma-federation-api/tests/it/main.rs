@@ -1,2 +1,3 @@
 mod authentication;
 mod membership;
+mod spec_examples;
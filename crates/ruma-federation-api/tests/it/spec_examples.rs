@@ -0,0 +1,167 @@
+//! Conformance tests using the examples of the [Matrix Server-Server API specification].
+//!
+//! The examples are vendored in `tests/it/fixtures/spec`, see the README there. Each example is
+//! deserialized into the type of the corresponding endpoint, then serialized again, and the result
+//! must match the example, except for the known differences listed by the test.
+//!
+//! [Matrix Server-Server API specification]: https://spec.matrix.org/latest/server-server-api/
+#![cfg(all(feature = "client", feature = "server"))]
+
+use ruma_common::{
+    api::{
+        IncomingRequest, IncomingResponse, OutgoingRequest, OutgoingResponse,
+        path_builder::SinglePath,
+    },
+    owned_server_name,
+    serde::test::{JsonDifference, assert_json_round_trip, load_json_example},
+};
+use ruma_federation_api::{
+    authentication::{ServerSignatures, ServerSignaturesInput},
+    directory::get_public_rooms,
+    discovery::{get_server_keys, get_server_version},
+    keys::claim_keys,
+    membership::prepare_join_event,
+    query::{get_profile_information, get_room_information},
+    transactions::send_transaction_message,
+};
+use ruma_signatures::Ed25519KeyPair;
+use serde_json::{Value as JsonValue, from_slice as from_json_slice, to_vec as to_json_vec};
+
+const FIXTURES_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/it/fixtures/spec");
+
+static PKCS8_ED25519_DER: &[u8] =
+    include_bytes!("../../../ruma-signatures/tests/it/keys/ed25519.der");
+
+/// Load the example at the given path, relative to `tests/it/fixtures/spec`.
+fn load_example(path: &str) -> JsonValue {
+    load_json_example(FIXTURES_PATH, path)
+}
+
+/// Check that the request body example at the given path round-trips through the request type
+/// `R`.
+fn check_request<R>(path: &str, path_args: &[&str], known_differences: &[JsonDifference])
+where
+    R: IncomingRequest
+        + OutgoingRequest<Authentication = ServerSignatures, PathBuilder = SinglePath>,
+{
+    let example = load_example(path);
+    let body = to_json_vec(&example).unwrap();
+    let http_request = http::Request::builder()
+        .method(R::METHOD)
+        .uri("https://destination.local/")
+        .body(body.as_slice())
+        .unwrap();
+
+    let request = R::try_from_http_request(http_request, path_args)
+        .unwrap_or_else(|error| panic!("failed to deserialize request `{path}`: {error}"));
+
+    let key_pair = Ed25519KeyPair::from_der(PKCS8_ED25519_DER, "1".to_owned()).unwrap();
+    let http_request = request
+        .try_into_http_request::<Vec<u8>>(
+            "https://destination.local",
+            ServerSignaturesInput::new(
+                owned_server_name!("origin.local"),
+                owned_server_name!("destination.local"),
+                &key_pair,
+            ),
+            (),
+        )
+        .unwrap_or_else(|error| panic!("failed to serialize request `{path}`: {error}"));
+
+    assert_json_round_trip(
+        path,
+        &example,
+        &from_json_slice::<JsonValue>(http_request.body()).unwrap(),
+        known_differences,
+    );
+}
+
+/// Check that the response body example at the given path round-trips through the response type
+/// `R`.
+fn check_response<R>(path: &str, known_differences: &[JsonDifference])
+where
+    R: IncomingResponse + OutgoingResponse,
+{
+    let example = load_example(path);
+    let body = to_json_vec(&example).unwrap();
+    let http_response = http::Response::builder().body(body.as_slice()).unwrap();
+
+    let response = R::try_from_http_response(http_response)
+        .unwrap_or_else(|error| panic!("failed to deserialize response `{path}`: {error}"));
+    let http_response = response
+        .try_into_http_response::<Vec<u8>>()
+        .unwrap_or_else(|error| panic!("failed to serialize response `{path}`: {error}"));
+
+    assert_json_round_trip(
+        path,
+        &example,
+        &from_json_slice::<JsonValue>(http_response.body()).unwrap(),
+        known_differences,
+    );
+}
+
+#[test]
+fn get_server_version() {
+    check_response::<get_server_version::v1::Response>("version/getVersion.response.json", &[]);
+}
+
+#[test]
+fn get_server_keys() {
+    check_response::<get_server_keys::v2::Response>("keys_server/getServerKey.response.json", &[]);
+}
+
+#[test]
+fn get_room_information() {
+    check_response::<get_room_information::v1::Response>(
+        "query/queryRoomDirectory.response.json",
+        &[],
+    );
+}
+
+#[test]
+fn get_profile_information() {
+    check_response::<get_profile_information::v1::Response>(
+        "query/queryProfile.response.json",
+        &[],
+    );
+}
+
+#[test]
+fn send_transaction_message() {
+    check_request::<send_transaction_message::v1::Request>(
+        "transactions/sendTransaction.request.json",
+        &["S6RzgvX2"],
+        &[],
+    );
+    check_response::<send_transaction_message::v1::Response>(
+        "transactions/sendTransaction.response.json",
+        &[],
+    );
+}
+
+#[test]
+fn get_public_rooms() {
+    check_response::<get_public_rooms::v1::Response>(
+        "public_rooms/getPublicRooms.response.json",
+        // The join rule is not serialized when it is the default value.
+        &[JsonDifference::Dropped("/chunk/0/join_rule".to_owned())],
+    );
+}
+
+#[test]
+fn claim_keys() {
+    check_request::<claim_keys::v1::Request>(
+        "user_keys/claimUserEncryptionKeys.request.json",
+        &[],
+        &[],
+    );
+    check_response::<claim_keys::v1::Response>(
+        "user_keys/claimUserEncryptionKeys.response.json",
+        &[],
+    );
+}
+
+#[test]
+fn prepare_join_event() {
+    check_response::<prepare_join_event::v1::Response>("joins-v1/makeJoin.response.json", &[]);
+}
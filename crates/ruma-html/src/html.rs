@@ -15,9 +15,12 @@ use html5ever::{
 };
 use tracing::debug;
 
+mod markdown;
 #[cfg(feature = "matrix")]
 pub mod matrix;
+mod plain_text;
 
+pub use self::plain_text::{LinkStyle, PlainTextConfig};
 use crate::SanitizerConfig;
 
 /// An HTML fragment.
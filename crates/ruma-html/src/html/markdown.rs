@@ -0,0 +1,465 @@
+//! Conversion of HTML to Markdown.
+
+use super::{Html, NodeData, NodeRef};
+
+impl Html {
+    /// Convert this HTML to Markdown.
+    ///
+    /// This is meant to allow users to edit a message that was sent with an HTML formatted body.
+    /// The elements suggested by the Matrix specification are converted to their Markdown syntax,
+    /// the other elements are replaced by their content, and the rich reply fallback is removed.
+    ///
+    /// Links to users and room aliases, as generated for mentions, are converted back to the plain
+    /// user ID or room alias. With the `matrix` cargo feature, the URIs of the links are fully
+    /// validated.
+    pub fn to_markdown(&self) -> String {
+        blocks_to_markdown(self.children())
+    }
+}
+
+/// Convert the given sibling nodes, that are the content of a block, to Markdown.
+///
+/// The whitespace at the start and the end of the block is removed.
+fn blocks_to_markdown(nodes: impl Iterator<Item = NodeRef>) -> String {
+    nodes_to_markdown(nodes).trim().to_owned()
+}
+
+/// Convert the given sibling nodes to Markdown.
+///
+/// Block elements are separated by blank lines.
+fn nodes_to_markdown(nodes: impl Iterator<Item = NodeRef>) -> String {
+    let mut markdown = String::new();
+    let mut after_block = false;
+
+    for node in nodes {
+        if is_block(&node) {
+            let block = block_to_markdown(&node);
+
+            if block.is_empty() {
+                continue;
+            }
+
+            push_block_separator(&mut markdown);
+            markdown.push_str(&block);
+            after_block = true;
+        } else {
+            let inline = inline_to_markdown(&node);
+
+            if after_block {
+                // Ignore the whitespace between blocks.
+                if inline.trim().is_empty() {
+                    continue;
+                }
+
+                push_block_separator(&mut markdown);
+                markdown.push_str(&escape_block_start(inline.trim_start()));
+                after_block = false;
+            } else if markdown.ends_with('\n') {
+                // Ignore the whitespace at the start of a line.
+                markdown.push_str(&escape_block_start(inline.trim_start()));
+            } else if markdown.is_empty() {
+                markdown.push_str(&escape_block_start(&inline));
+            } else {
+                markdown.push_str(&inline);
+            }
+        }
+    }
+
+    markdown
+}
+
+/// Push a blank line at the end of the given Markdown, if it is not empty.
+pub(super) fn push_block_separator(markdown: &mut String) {
+    let len = markdown.trim_end().len();
+    markdown.truncate(len);
+
+    if !markdown.is_empty() {
+        markdown.push_str("\n\n");
+    }
+}
+
+/// Whether the given node is a block element.
+pub(super) fn is_block(node: &NodeRef) -> bool {
+    element_name(node).is_some_and(|name| {
+        matches!(
+            name,
+            "p" | "div"
+                | "h1"
+                | "h2"
+                | "h3"
+                | "h4"
+                | "h5"
+                | "h6"
+                | "blockquote"
+                | "ul"
+                | "ol"
+                | "li"
+                | "pre"
+                | "hr"
+                | "table"
+                | "details"
+                | "mx-reply"
+        )
+    })
+}
+
+/// The local name of the given node, if it is an element.
+pub(super) fn element_name(node: &NodeRef) -> Option<&str> {
+    node.as_element().map(|element| &*element.name.local)
+}
+
+/// The value of the attribute with the given name of the given node, if any.
+pub(super) fn attribute(node: &NodeRef, name: &str) -> Option<String> {
+    let element = node.as_element()?;
+    let attrs = element.attrs.borrow();
+    let attr = attrs.iter().find(|attr| &*attr.name.local == name)?;
+    Some(attr.value.to_string())
+}
+
+/// Convert the given block element to Markdown.
+fn block_to_markdown(node: &NodeRef) -> String {
+    match element_name(node).unwrap_or_default() {
+        name @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+            let level = usize::from(name.as_bytes()[1] - b'0');
+            let content = blocks_to_markdown(node.children()).replace('\n', " ");
+            format!("{} {content}", "#".repeat(level))
+        }
+        "blockquote" => prefix_lines(&blocks_to_markdown(node.children()), "> ", "> "),
+        "ul" => list_to_markdown(node, None),
+        "ol" => {
+            let start = attribute(node, "start").and_then(|start| start.parse().ok()).unwrap_or(1);
+            list_to_markdown(node, Some(start))
+        }
+        "pre" => code_block_to_markdown(node),
+        "hr" => "---".to_owned(),
+        "table" => table_to_markdown(node),
+        "mx-reply" => String::new(),
+        _ => blocks_to_markdown(node.children()),
+    }
+}
+
+/// Prefix the lines of the given text with the given prefixes.
+///
+/// The first line is prefixed with `first` and the other lines with `others`.
+pub(super) fn prefix_lines(text: &str, first: &str, others: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { first } else { others };
+
+            if line.is_empty() { prefix.trim_end().to_owned() } else { format!("{prefix}{line}") }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Convert the given list element to Markdown.
+///
+/// `start` is the number of the first item if the list is ordered.
+fn list_to_markdown(node: &NodeRef, start: Option<i64>) -> String {
+    node.children()
+        .filter(|child| element_name(child) == Some("li"))
+        .enumerate()
+        .map(|(i, item)| {
+            let marker = match start {
+                Some(start) => format!("{}. ", start.saturating_add(i as i64)),
+                None => "- ".to_owned(),
+            };
+            let indent = " ".repeat(marker.len());
+
+            prefix_lines(&blocks_to_markdown(item.children()), &marker, &indent)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Convert the given `<pre>` element to a Markdown code block.
+fn code_block_to_markdown(node: &NodeRef) -> String {
+    let language = node
+        .children()
+        .find(|child| element_name(child) == Some("code"))
+        .and_then(|code| attribute(&code, "class"))
+        .and_then(|class| {
+            class
+                .split_whitespace()
+                .find_map(|class| class.strip_prefix("language-").map(ToOwned::to_owned))
+        })
+        .unwrap_or_default();
+
+    let code = text_content(node);
+    let fence = code_fence(&code, '`', 3);
+
+    format!("{fence}{language}\n{}\n{fence}", code.trim_end_matches('\n'))
+}
+
+/// Convert the given `<table>` element to a Markdown table.
+fn table_to_markdown(node: &NodeRef) -> String {
+    let mut rows = Vec::new();
+    collect_table_rows(node, &mut rows);
+
+    let Some(columns) = rows.iter().map(Vec::len).max().filter(|columns| *columns > 0) else {
+        return String::new();
+    };
+
+    let format_row = |cells: &[String]| {
+        let cells = (0..columns).map(|i| cells.get(i).map(String::as_str).unwrap_or_default());
+        format!("| {} |", cells.collect::<Vec<_>>().join(" | "))
+    };
+
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    lines.push(format_row(&rows[0]));
+    lines.push(format!("|{}", " --- |".repeat(columns)));
+    lines.extend(rows[1..].iter().map(|row| format_row(row)));
+
+    lines.join("\n")
+}
+
+/// Collect the cells of the rows in the given table element.
+fn collect_table_rows(node: &NodeRef, rows: &mut Vec<Vec<String>>) {
+    for child in node.children() {
+        match element_name(&child) {
+            Some("thead" | "tbody" | "tfoot") => collect_table_rows(&child, rows),
+            Some("tr") => {
+                let cells = child
+                    .children()
+                    .filter(|cell| matches!(element_name(cell), Some("td" | "th")))
+                    .map(|cell| {
+                        blocks_to_markdown(cell.children()).replace('\n', " ").replace('|', "\\|")
+                    })
+                    .collect();
+                rows.push(cells);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Convert the given inline node to Markdown.
+fn inline_to_markdown(node: &NodeRef) -> String {
+    match node.data() {
+        NodeData::Text(text) => escape_markdown(&collapse_whitespace(&text.borrow())),
+        NodeData::Element(_) => element_to_markdown(node),
+        _ => String::new(),
+    }
+}
+
+/// Convert the given inline element to Markdown.
+fn element_to_markdown(node: &NodeRef) -> String {
+    match element_name(node).unwrap_or_default() {
+        "strong" | "b" => wrap_inline(&nodes_to_markdown(node.children()), "**"),
+        "em" | "i" => wrap_inline(&nodes_to_markdown(node.children()), "*"),
+        "del" | "s" | "strike" => wrap_inline(&nodes_to_markdown(node.children()), "~~"),
+        "code" => {
+            let code = text_content(node);
+            let fence = code_fence(&code, '`', 1);
+
+            // Add spaces if the code starts or ends with a backtick.
+            if code.starts_with('`') || code.ends_with('`') {
+                format!("{fence} {code} {fence}")
+            } else {
+                format!("{fence}{code}{fence}")
+            }
+        }
+        "br" => "\n".to_owned(),
+        "a" => {
+            let content = nodes_to_markdown(node.children());
+            let Some(href) = attribute(node, "href") else {
+                return content;
+            };
+
+            if let Some(id) = mention_id(&href) {
+                return id;
+            }
+
+            format!("[{content}]({})", href.replace(' ', "%20").replace(')', "%29"))
+        }
+        "img" => {
+            let alt = attribute(node, "alt").unwrap_or_default();
+            let src = attribute(node, "src").unwrap_or_default();
+            format!("![{}]({src})", escape_markdown(&alt))
+        }
+        _ => nodes_to_markdown(node.children()),
+    }
+}
+
+/// Wrap the given inline Markdown with the given delimiter.
+///
+/// The whitespace at the start and the end of the content is moved outside of the delimiters.
+fn wrap_inline(content: &str, delimiter: &str) -> String {
+    let trimmed = content.trim();
+
+    if trimmed.is_empty() {
+        return content.to_owned();
+    }
+
+    let leading = &content[..content.len() - content.trim_start().len()];
+    let trailing = &content[content.trim_end().len()..];
+
+    format!("{leading}{delimiter}{trimmed}{delimiter}{trailing}")
+}
+
+/// Get the text content of the given node and its descendants.
+pub(super) fn text_content(node: &NodeRef) -> String {
+    match node.data() {
+        NodeData::Text(text) => text.borrow().to_string(),
+        NodeData::Element(_) if element_name(node) == Some("br") => "\n".to_owned(),
+        _ => node.children().map(|child| text_content(&child)).collect(),
+    }
+}
+
+/// Get a code fence made of the given character that is longer than any sequence of that
+/// character in the given code, with the given minimum length.
+fn code_fence(code: &str, c: char, min_len: usize) -> String {
+    let longest_sequence = code
+        .split(|other| other != c)
+        .map(|sequence| sequence.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    c.to_string().repeat(min_len.max(longest_sequence + 1))
+}
+
+/// Collapse the sequences of whitespace in the given text into single spaces, like in HTML.
+pub(super) fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut previous_is_whitespace = false;
+
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            if !previous_is_whitespace {
+                collapsed.push(' ');
+            }
+            previous_is_whitespace = true;
+        } else {
+            collapsed.push(c);
+            previous_is_whitespace = false;
+        }
+    }
+
+    collapsed
+}
+
+/// Escape the characters of the given text that have a meaning in Markdown.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    let chars = text.chars().collect::<Vec<_>>();
+
+    for (i, &c) in chars.iter().enumerate() {
+        let needs_escape = match c {
+            '\\' | '`' | '*' | '[' | ']' | '~' | '<' => true,
+            // Underscores inside words can't be used for emphasis.
+            '_' => {
+                let is_alphanumeric = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric());
+                !(i > 0 && is_alphanumeric(chars.get(i - 1)) && is_alphanumeric(chars.get(i + 1)))
+            }
+            _ => false,
+        };
+
+        if needs_escape {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Escape the start of the given inline Markdown, that is at the start of a line, if it would be
+/// parsed as the start of a block.
+///
+/// This handles the markers of headings, blockquotes, lists, thematic breaks and setext headings
+/// underlines. The other characters that can start a block are escaped by [`escape_markdown()`].
+fn escape_block_start(inline: &str) -> String {
+    let content = inline.trim_start_matches(' ');
+    let leading = &inline[..inline.len() - content.len()];
+    let bytes = content.as_bytes();
+
+    // Whether the byte at the given index is the end of a marker.
+    let is_marker_end = |i: usize| bytes.get(i).is_none_or(|b| b.is_ascii_whitespace());
+    // The length of the run of the given byte at the start of the content.
+    let run_len = |byte: u8| bytes.iter().take_while(|b| **b == byte).count();
+
+    let escape_at = match bytes.first() {
+        Some(b'>') => Some(0),
+        Some(b'#') => {
+            let len = run_len(b'#');
+            (len <= 6 && is_marker_end(len)).then_some(0)
+        }
+        Some(&b @ (b'-' | b'+' | b'=')) => is_marker_end(run_len(b)).then_some(0),
+        Some(b'0'..=b'9') => {
+            let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+            (digits <= 9
+                && matches!(bytes.get(digits), Some(b'.' | b')'))
+                && is_marker_end(digits + 1))
+            .then_some(digits)
+        }
+        _ => None,
+    };
+
+    match escape_at {
+        Some(i) => format!("{leading}{}\\{}", &content[..i], &content[i..]),
+        None => inline.to_owned(),
+    }
+}
+
+/// The user ID or room alias that the given link points to, if any.
+#[cfg(feature = "matrix")]
+pub(super) fn mention_id(href: &str) -> Option<String> {
+    use ruma_common::{MatrixToUri, MatrixUri, matrix_uri::MatrixId};
+
+    let id = match MatrixToUri::parse(href) {
+        Ok(uri) => uri.id().clone(),
+        Err(_) => MatrixUri::parse(href).ok()?.id().clone(),
+    };
+
+    match id {
+        MatrixId::User(user_id) => Some(user_id.into()),
+        MatrixId::RoomAlias(alias) => Some(alias.into()),
+        _ => None,
+    }
+}
+
+/// The user ID or room alias that the given link points to, if any.
+///
+/// Without the `matrix` cargo feature, the URI is not fully validated: only its scheme and the
+/// sigil of the identifier are checked.
+#[cfg(not(feature = "matrix"))]
+pub(super) fn mention_id(href: &str) -> Option<String> {
+    let id = if let Some(fragment) = href.strip_prefix("https://matrix.to/#/") {
+        let id = fragment.split('?').next()?;
+        percent_decode(id)?
+    } else {
+        let path = href.strip_prefix("matrix:")?.split('?').next()?;
+        let (kind, id) = path.split_once('/')?;
+        let sigil = match kind {
+            "u" => '@',
+            "r" => '#',
+            _ => return None,
+        };
+        format!("{sigil}{}", percent_decode(id)?)
+    };
+
+    // Links to events have more path segments.
+    let is_mention =
+        (id.starts_with('@') || id.starts_with('#')) && id.contains(':') && !id.contains('/');
+    is_mention.then_some(id)
+}
+
+/// Decode the percent-encoded characters of the given string.
+#[cfg(not(feature = "matrix"))]
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
@@ -0,0 +1,323 @@
+//! Conversion of HTML to plain text.
+
+use super::{
+    Html, NodeData, NodeRef,
+    markdown::{
+        attribute, collapse_whitespace, element_name, is_block, mention_id, prefix_lines,
+        push_block_separator, text_content,
+    },
+};
+
+/// How links are rendered in plain text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LinkStyle {
+    /// Only the text of the link is kept.
+    TextOnly,
+
+    /// The URL of the link is added after its text, like `text <https://example.org>`.
+    #[default]
+    Inline,
+
+    /// A numbered reference is added after the text of the link, like `text[1]`, and the list of
+    /// URLs is added at the end of the text.
+    Footnotes,
+}
+
+/// Configuration to convert HTML to plain text.
+#[derive(Debug, Default, Clone)]
+pub struct PlainTextConfig {
+    /// The maximum width of the lines, if they should be wrapped.
+    line_width: Option<usize>,
+
+    /// How links are rendered.
+    link_style: LinkStyle,
+}
+
+impl PlainTextConfig {
+    /// Constructs a `PlainTextConfig` that doesn't wrap lines and uses [`LinkStyle::Inline`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap the lines at the given width, in characters.
+    ///
+    /// Code blocks and words that are longer than the width are not wrapped.
+    pub fn line_width(mut self, width: usize) -> Self {
+        self.line_width = Some(width);
+        self
+    }
+
+    /// Render the links with the given style.
+    pub fn link_style(mut self, style: LinkStyle) -> Self {
+        self.link_style = style;
+        self
+    }
+}
+
+impl Html {
+    /// Convert this HTML to plain text.
+    ///
+    /// This is equivalent to calling [`Self::to_plain_text_with()`] with a `config` value of
+    /// `PlainTextConfig::new()`.
+    pub fn to_plain_text(&self) -> String {
+        self.to_plain_text_with(&PlainTextConfig::new())
+    }
+
+    /// Convert this HTML to plain text according to the given configuration.
+    ///
+    /// This is meant to generate the plain text `body` of a message from its HTML formatted body:
+    ///
+    /// * Blocks are separated by blank lines, list items are prefixed by their marker and
+    ///   blockquotes are prefixed by `> `.
+    /// * The rich reply fallback is removed.
+    /// * The content of spoilers is replaced by `[Spoiler]`, followed by the reason if any.
+    /// * Images are replaced by their alternative text.
+    /// * Links to users and room aliases, like mention pills, are replaced by their text. With the
+    ///   `matrix` cargo feature, the URIs of the links are fully validated.
+    pub fn to_plain_text_with(&self, config: &PlainTextConfig) -> String {
+        let mut renderer =
+            PlainTextRenderer { link_style: config.link_style, footnotes: Vec::new() };
+        let mut text = renderer.blocks(self.children(), config.line_width);
+
+        if !renderer.footnotes.is_empty() {
+            push_block_separator(&mut text);
+
+            let footnotes = renderer
+                .footnotes
+                .iter()
+                .enumerate()
+                .map(|(i, url)| format!("[{}]: {url}", i + 1))
+                .collect::<Vec<_>>();
+            text.push_str(&footnotes.join("\n"));
+        }
+
+        text
+    }
+}
+
+/// A renderer of HTML to plain text.
+struct PlainTextRenderer {
+    /// How links are rendered.
+    link_style: LinkStyle,
+
+    /// The URLs of the links, if they are rendered as footnotes.
+    footnotes: Vec<String>,
+}
+
+impl PlainTextRenderer {
+    /// Convert the given sibling nodes, that are the content of a block, to plain text.
+    ///
+    /// The empty lines at the start and the end of the block are removed.
+    fn blocks(&mut self, nodes: impl Iterator<Item = NodeRef>, width: Option<usize>) -> String {
+        self.nodes(nodes, width).trim_matches('\n').to_owned()
+    }
+
+    /// Convert the given sibling nodes to plain text.
+    ///
+    /// Block elements are separated by blank lines.
+    fn nodes(&mut self, nodes: impl Iterator<Item = NodeRef>, width: Option<usize>) -> String {
+        let mut text = String::new();
+        let mut inline = String::new();
+
+        for node in nodes {
+            if is_block(&node) || element_name(&node) == Some("summary") {
+                push_paragraph(&mut text, &inline, width);
+                inline.clear();
+
+                let block = self.block(&node, width);
+
+                if !block.is_empty() {
+                    push_block_separator(&mut text);
+                    text.push_str(&block);
+                }
+            } else {
+                inline.push_str(&self.inline(&node));
+            }
+        }
+
+        push_paragraph(&mut text, &inline, width);
+
+        text
+    }
+
+    /// Convert the given block element to plain text.
+    fn block(&mut self, node: &NodeRef, width: Option<usize>) -> String {
+        match element_name(node).unwrap_or_default() {
+            "blockquote" => {
+                let content = self.blocks(node.children(), width.map(|w| w.saturating_sub(2)));
+                prefix_lines(&content, "> ", "> ")
+            }
+            "ul" => self.list(node, None, width),
+            "ol" => {
+                let start =
+                    attribute(node, "start").and_then(|start| start.parse().ok()).unwrap_or(1);
+                self.list(node, Some(start), width)
+            }
+            "pre" => text_content(node).trim_end_matches('\n').to_owned(),
+            "hr" => "---".to_owned(),
+            "table" => self.table(node),
+            "mx-reply" => String::new(),
+            _ => self.blocks(node.children(), width),
+        }
+    }
+
+    /// Convert the given list element to plain text.
+    ///
+    /// `start` is the number of the first item if the list is ordered.
+    fn list(&mut self, node: &NodeRef, start: Option<i64>, width: Option<usize>) -> String {
+        node.children()
+            .filter(|child| element_name(child) == Some("li"))
+            .enumerate()
+            .map(|(i, item)| {
+                let marker = match start {
+                    Some(start) => format!("{}. ", start.saturating_add(i as i64)),
+                    None => "- ".to_owned(),
+                };
+                let indent = " ".repeat(marker.len());
+                let content =
+                    self.blocks(item.children(), width.map(|w| w.saturating_sub(marker.len())));
+
+                prefix_lines(&content, &marker, &indent)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Convert the given `<table>` element to plain text, with one line per row.
+    fn table(&mut self, node: &NodeRef) -> String {
+        let mut lines = Vec::new();
+        self.table_rows(node, &mut lines);
+        lines.join("\n")
+    }
+
+    /// Convert the rows in the given table element to lines of plain text.
+    fn table_rows(&mut self, node: &NodeRef, lines: &mut Vec<String>) {
+        for child in node.children() {
+            match element_name(&child) {
+                Some("thead" | "tbody" | "tfoot") => self.table_rows(&child, lines),
+                Some("tr") => {
+                    let cells = child
+                        .children()
+                        .filter(|cell| matches!(element_name(cell), Some("td" | "th")))
+                        .map(|cell| self.blocks(cell.children(), None).replace('\n', " "))
+                        .collect::<Vec<_>>();
+                    lines.push(cells.join(" | "));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Convert the given inline node to plain text.
+    fn inline(&mut self, node: &NodeRef) -> String {
+        match node.data() {
+            NodeData::Text(text) => collapse_whitespace(&text.borrow()),
+            NodeData::Element(_) => self.element(node),
+            _ => String::new(),
+        }
+    }
+
+    /// Convert the given inline element to plain text.
+    fn element(&mut self, node: &NodeRef) -> String {
+        if let Some(reason) = attribute(node, "data-mx-spoiler") {
+            return if reason.is_empty() {
+                "[Spoiler]".to_owned()
+            } else {
+                format!("[Spoiler: {reason}]")
+            };
+        }
+
+        match element_name(node).unwrap_or_default() {
+            "br" => "\n".to_owned(),
+            "code" => text_content(node),
+            "img" => attribute(node, "alt").unwrap_or_default(),
+            "a" => self.link(node),
+            _ => self.children(node),
+        }
+    }
+
+    /// Convert the children of the given inline element to plain text.
+    fn children(&mut self, node: &NodeRef) -> String {
+        node.children().map(|child| self.inline(&child)).collect()
+    }
+
+    /// Convert the given `<a>` element to plain text.
+    fn link(&mut self, node: &NodeRef) -> String {
+        let text = self.children(node);
+        let Some(href) = attribute(node, "href") else {
+            return text;
+        };
+
+        if text.trim().is_empty() {
+            return href;
+        }
+
+        // Don't repeat the URL if it is the text of the link, and don't add the URL of mentions.
+        let is_autolink = text.trim() == href || href.strip_prefix("mailto:") == Some(text.trim());
+        if is_autolink || mention_id(&href).is_some() {
+            return text;
+        }
+
+        match self.link_style {
+            LinkStyle::TextOnly => text,
+            LinkStyle::Inline => format!("{text} <{href}>"),
+            LinkStyle::Footnotes => {
+                let index = match self.footnotes.iter().position(|url| *url == href) {
+                    Some(index) => index,
+                    None => {
+                        self.footnotes.push(href);
+                        self.footnotes.len() - 1
+                    }
+                };
+
+                format!("{text}[{}]", index + 1)
+            }
+        }
+    }
+}
+
+/// Push the given inline text as a paragraph at the end of the given plain text.
+///
+/// The whitespace around the lines is removed and the lines are wrapped at the given width.
+fn push_paragraph(text: &mut String, inline: &str, width: Option<usize>) {
+    let paragraph =
+        inline.split('\n').map(|line| wrap_line(line.trim(), width)).collect::<Vec<_>>().join("\n");
+    let paragraph = paragraph.trim_matches('\n');
+
+    if paragraph.is_empty() {
+        return;
+    }
+
+    push_block_separator(text);
+    text.push_str(paragraph);
+}
+
+/// Wrap the given line at the given width.
+fn wrap_line(line: &str, width: Option<usize>) -> String {
+    let Some(width) = width.filter(|width| *width > 0) else {
+        return line.to_owned();
+    };
+
+    let mut wrapped = String::with_capacity(line.len());
+    let mut line_len = 0;
+
+    for word in line.split(' ').filter(|word| !word.is_empty()) {
+        let word_len = word.chars().count();
+
+        if line_len > 0 {
+            if line_len + 1 + word_len > width {
+                wrapped.push('\n');
+                line_len = 0;
+            } else {
+                wrapped.push(' ');
+                line_len += 1;
+            }
+        }
+
+        wrapped.push_str(word);
+        line_len += word_len;
+    }
+
+    wrapped
+}
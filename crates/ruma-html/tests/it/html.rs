@@ -1,4 +1,6 @@
+mod markdown;
 #[cfg(feature = "matrix")]
 mod matrix;
 mod navigate;
+mod plain_text;
 mod sanitize;
@@ -0,0 +1,76 @@
+use ruma_html::Html;
+
+#[test]
+fn inline_formatting() {
+    let html = Html::parse(
+        "Some <strong>bold</strong>, <em>italic </em>and <del>struck</del> text with <code>co`de</code>, \
+         a <a href=\"https://ruma.dev\">link</a> and a <span data-mx-color=\"#ff0000\">span</span>.<br>\
+         Characters like * and [ are escaped, but not snake_case.",
+    );
+    assert_eq!(
+        html.to_markdown(),
+        "Some **bold**, *italic* and ~~struck~~ text with ``co`de``, a [link](https://ruma.dev) \
+         and a span.\nCharacters like \\* and \\[ are escaped, but not snake_case."
+    );
+}
+
+#[test]
+fn block_markers_in_text() {
+    let html = Html::parse(
+        "<p># not a heading</p>\
+         <p>&gt; not a quote</p>\
+         <p>- not a list<br>+ nor this<br>1. nor this<br>2) nor this<br>---<br>===</p>\
+         <ul><li>- not nested</li></ul>\
+         <p>But -5, #hashtag, 1.5 and a &gt; b are left as is.</p>",
+    );
+    assert_eq!(
+        html.to_markdown(),
+        "\\# not a heading\n\n\
+         \\> not a quote\n\n\
+         \\- not a list\n\\+ nor this\n1\\. nor this\n2\\) nor this\n\\---\n\\===\n\n\
+         - \\- not nested\n\n\
+         But -5, #hashtag, 1.5 and a > b are left as is."
+    );
+}
+
+#[test]
+fn blocks() {
+    let html = Html::parse(
+        "<mx-reply><blockquote>In reply to</blockquote></mx-reply>\
+         <h2>Title</h2>\n\
+         <p>First paragraph</p>\n\
+         <blockquote>\n<p>Quote</p>\n<p>Second line</p>\n</blockquote>\n\
+         <ul>\n<li>One</li>\n<li>Two<ul><li>Nested</li></ul></li>\n</ul>\n\
+         <ol start=\"3\">\n<li>Three</li>\n<li>Four</li>\n</ol>\n\
+         <pre><code class=\"language-rust\">let a = 1;\n</code></pre>\n\
+         <hr>\n\
+         <table><thead><tr><th>A</th><th>B</th></tr></thead>\
+         <tbody><tr><td>1</td><td>2|3</td></tr></tbody></table>",
+    );
+    assert_eq!(
+        html.to_markdown(),
+        "## Title\n\n\
+         First paragraph\n\n\
+         > Quote\n\
+         >\n\
+         > Second line\n\n\
+         - One\n\
+         - Two\n\n  - Nested\n\n\
+         3. Three\n\
+         4. Four\n\n\
+         ```rust\nlet a = 1;\n```\n\n\
+         ---\n\n\
+         | A | B |\n\
+         | --- | --- |\n\
+         | 1 | 2\\|3 |"
+    );
+}
+
+#[test]
+fn mentions() {
+    let html = Html::parse(
+        "Hello <a href=\"https://matrix.to/#/@alice:localhost\">Alice</a>, welcome to \
+         <a href=\"matrix:r/room:localhost\">#room:localhost</a>!",
+    );
+    assert_eq!(html.to_markdown(), "Hello @alice:localhost, welcome to #room:localhost!");
+}
@@ -0,0 +1,102 @@
+use ruma_html::{Html, LinkStyle, PlainTextConfig};
+
+#[test]
+fn inline_content() {
+    let html = Html::parse(
+        "Some <strong>bold</strong> text with <code>code</code>, an \
+         <img src=\"mxc://localhost/abc\" alt=\":smile:\"> and a \
+         <span data-mx-spoiler>secret</span> or <span data-mx-spoiler=\"movie\">another</span>.<br>\
+         Second   line",
+    );
+    assert_eq!(
+        html.to_plain_text(),
+        "Some bold text with code, an :smile: and a [Spoiler] or [Spoiler: movie].\nSecond line"
+    );
+}
+
+#[test]
+fn blocks() {
+    let html = Html::parse(
+        "<mx-reply><blockquote>In reply to</blockquote></mx-reply>\
+         <h2>Title</h2>\
+         <p>First paragraph</p>\
+         <blockquote><p>Quote</p><p>Second line</p></blockquote>\
+         <ul>\n<li>One</li>\n<li>Two<ul><li>Nested</li></ul></li>\n</ul>\n\
+         <ol start=\"3\"><li>Three</li><li>Four</li></ol>\
+         <pre><code class=\"language-rust\">fn main() {\n    let a = 1;\n}\n</code></pre>\
+         <hr>\
+         <table><thead><tr><th>A</th><th>B</th></tr></thead>\
+         <tbody><tr><td>1</td><td>2</td></tr></tbody></table>\
+         <details><summary>Summary</summary>Details</details>",
+    );
+    assert_eq!(
+        html.to_plain_text(),
+        "Title\n\n\
+         First paragraph\n\n\
+         > Quote\n>\n> Second line\n\n\
+         - One\n- Two\n\n  - Nested\n\n\
+         3. Three\n4. Four\n\n\
+         fn main() {\n    let a = 1;\n}\n\n\
+         ---\n\n\
+         A | B\n1 | 2\n\n\
+         Summary\n\nDetails"
+    );
+}
+
+#[test]
+fn links() {
+    let html = Html::parse(
+        "See <a href=\"https://ruma.dev\">the website</a>, \
+         <a href=\"https://ruma.dev/news\">the news</a>, <a href=\"https://ruma.dev\">again</a> \
+         and <a href=\"https://ruma.dev/docs\">https://ruma.dev/docs</a>.",
+    );
+
+    assert_eq!(
+        html.to_plain_text(),
+        "See the website <https://ruma.dev>, the news <https://ruma.dev/news>, \
+         again <https://ruma.dev> and https://ruma.dev/docs."
+    );
+    assert_eq!(
+        html.to_plain_text_with(&PlainTextConfig::new().link_style(LinkStyle::TextOnly)),
+        "See the website, the news, again and https://ruma.dev/docs."
+    );
+    assert_eq!(
+        html.to_plain_text_with(&PlainTextConfig::new().link_style(LinkStyle::Footnotes)),
+        "See the website[1], the news[2], again[1] and https://ruma.dev/docs.\n\n\
+         [1]: https://ruma.dev\n\
+         [2]: https://ruma.dev/news"
+    );
+}
+
+#[test]
+fn mentions() {
+    let html = Html::parse(
+        "Hello <a href=\"https://matrix.to/#/@alice:localhost\">Alice</a>, \
+         <a href=\"matrix:u/bob:localhost?action=chat\">Bob</a>, \
+         welcome to <a href=\"https://matrix.to/#/%23room:localhost\">#room:localhost</a> \
+         and <a href=\"matrix:r/other:localhost\">#other:localhost</a>. \
+         See <a href=\"https://matrix.to/#/%23room:localhost/$event\">this message</a>.",
+    );
+    assert_eq!(
+        html.to_plain_text(),
+        "Hello Alice, Bob, welcome to #room:localhost and #other:localhost. \
+         See this message <https://matrix.to/#/%23room:localhost/$event>."
+    );
+}
+
+#[test]
+fn line_width() {
+    let html = Html::parse(
+        "<p>This is a long paragraph that should be wrapped.</p>\
+         <blockquote>This quote should be wrapped too.</blockquote>\
+         <ul><li>And this list item as well.</li></ul>\
+         <pre><code>But this code block is left untouched.</code></pre>",
+    );
+    assert_eq!(
+        html.to_plain_text_with(&PlainTextConfig::new().line_width(16)),
+        "This is a long\nparagraph that\nshould be\nwrapped.\n\n\
+         > This quote\n> should be\n> wrapped too.\n\n\
+         - And this list\n  item as well.\n\n\
+         But this code block is left untouched."
+    );
+}
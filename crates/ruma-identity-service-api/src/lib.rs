@@ -17,6 +17,18 @@ pub mod keys;
 pub mod lookup;
 pub mod tos;
 
+/// Generate an [OpenAPI] description of all the endpoints of the Identity Service API.
+///
+/// [OpenAPI]: https://spec.openapis.org/oas/v3.1.0
+#[cfg(feature = "openapi")]
+pub fn openapi() -> ruma_common::api::openapi::OpenApi {
+    ruma_common::api::openapi::OpenApi::for_crate(
+        module_path!(),
+        "Matrix Identity Service API",
+        env!("CARGO_PKG_VERSION"),
+    )
+}
+
 // Wrapper around `Box<str>` that cannot be used in a meaningful way outside of
 // this crate. Used for string enums because their `_Custom` variant can't be
 // truly private (only `#[doc(hidden)]`).
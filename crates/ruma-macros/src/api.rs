@@ -6,6 +6,7 @@ use proc_macro2::Span;
 use serde::{Deserialize, de::IgnoredAny};
 
 mod attribute;
+pub mod openapi;
 pub mod request;
 pub mod response;
 
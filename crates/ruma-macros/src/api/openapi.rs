@@ -0,0 +1,205 @@
+//! Shared code for generating the OpenAPI descriptions of requests and responses, and
+//! implementation of the `DescribeSchema` derive macro.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Attribute, Field, Fields, Ident, ItemStruct, Type};
+
+use crate::{
+    serde::case::RenameRule,
+    util::{
+        field_has_serde_flatten_attribute, field_is_optional, field_serialized_name,
+        import_ruma_common,
+    },
+};
+
+/// The location of a field, as understood by `ruma_common::api::openapi::FieldLocation`.
+pub(super) enum OpenApiFieldLocation<'a> {
+    Path,
+    Query,
+    QueryAll,
+    Header(&'a Ident),
+    Body,
+    FlattenedBody,
+    NewtypeBody,
+    RawBody,
+}
+
+/// Generate code that pushes the description of the given field in a `Vec` named `fields`.
+pub(super) fn push_field_description(
+    field: &Field,
+    location: OpenApiFieldLocation<'_>,
+    ruma_common: &TokenStream,
+) -> TokenStream {
+    let http = quote! { #ruma_common::exports::http };
+    let openapi = quote! { #ruma_common::api::openapi };
+
+    let name = match location {
+        // Path variables use the name of the field.
+        OpenApiFieldLocation::Path => {
+            field.ident.as_ref().expect("expected field to have an identifier").to_string()
+        }
+        _ => field_serialized_name(field),
+    };
+    let location = match location {
+        OpenApiFieldLocation::Path => quote! { Path },
+        OpenApiFieldLocation::Query => quote! { Query },
+        OpenApiFieldLocation::QueryAll => quote! { QueryAll },
+        OpenApiFieldLocation::Header(header_name) => quote! {
+            Header(
+                <#http::header::HeaderName as ::std::convert::TryFrom<_>>::try_from(#header_name)
+                    .expect("header name should be valid")
+            )
+        },
+        OpenApiFieldLocation::Body => quote! { Body },
+        OpenApiFieldLocation::FlattenedBody => quote! { FlattenedBody },
+        OpenApiFieldLocation::NewtypeBody => quote! { NewtypeBody },
+        OpenApiFieldLocation::RawBody => quote! { RawBody },
+    };
+    let required = !field_is_optional(field);
+    let schema = expand_schema_of(&field.ty, ruma_common);
+    let cfg_attrs = field.attrs.iter().filter(|a| a.path().is_ident("cfg"));
+
+    quote! {
+        #( #cfg_attrs )*
+        fields.push(#openapi::FieldDescription::new(
+            #name,
+            #openapi::FieldLocation::#location,
+            #required,
+            #schema,
+        ));
+    }
+}
+
+/// Generate an expression that evaluates to the schema of the given type, or to an opaque schema
+/// if it doesn't implement `DescribeSchema`.
+fn expand_schema_of(ty: &Type, ruma_common: &TokenStream) -> TokenStream {
+    let openapi = quote! { #ruma_common::api::openapi };
+
+    quote! {{
+        use #openapi::{SchemaOfDescribed as _, SchemaOfOpaque as _};
+        (&#openapi::SchemaOf::<#ty>::new()).schema()
+    }}
+}
+
+/// Generate the `DescribeSchema` implementation for a struct.
+pub fn expand_derive_describe_schema(input: ItemStruct) -> syn::Result<TokenStream> {
+    let ruma_common = import_ruma_common();
+    let openapi = quote! { #ruma_common::api::openapi };
+
+    let schema = match &input.fields {
+        Fields::Named(fields) => {
+            let rename_all = serde_rename_all(&input.attrs)?;
+
+            let properties = fields
+                .named
+                .iter()
+                .filter(|field| !field_has_serde_skip_attribute(field))
+                .map(|field| {
+                    let schema = expand_schema_of(&field.ty, &ruma_common);
+                    let cfg_attrs = field.attrs.iter().filter(|a| a.path().is_ident("cfg"));
+
+                    if field_has_serde_flatten_attribute(field) {
+                        return quote! {
+                            #( #cfg_attrs )*
+                            schema.all_of.push(#schema);
+                        };
+                    }
+
+                    let name = if has_serde_rename_attribute(field) {
+                        field_serialized_name(field)
+                    } else {
+                        let ident = field.ident.as_ref().expect("named fields have an identifier");
+                        rename_all.apply_to_field(&ident.to_string())
+                    };
+                    // Fields that are not deserialized can be omitted.
+                    let required =
+                        !field_is_optional(field) && !has_serde_flag(field, "skip_deserializing");
+
+                    quote! {
+                        #( #cfg_attrs )*
+                        schema.insert_property(#name, #schema, #required);
+                    }
+                });
+
+            quote! {
+                let mut schema = #openapi::Schema::object();
+                #( #properties )*
+                schema
+            }
+        }
+        // Newtype structs are serialized like their inner type.
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            expand_schema_of(&fields.unnamed[0].ty, &ruma_common)
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "DescribeSchema can only be derived for structs with named fields or newtype structs",
+            ));
+        }
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics #openapi::DescribeSchema for #ident #ty_generics #where_clause {
+            fn schema() -> #openapi::Schema {
+                #schema
+            }
+        }
+    })
+}
+
+/// The value of the `#[serde(rename_all = "...")]` attribute of a container, if any.
+fn serde_rename_all(attrs: &[Attribute]) -> syn::Result<RenameRule> {
+    let mut rename_all = RenameRule::None;
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                rename_all = meta.value()?.parse()?;
+            } else if let Ok(value) = meta.value() {
+                // Skip the values of other attributes.
+                value.parse::<proc_macro2::TokenTree>()?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(rename_all)
+}
+
+/// Whether the given field has a `#[serde(rename = "...")]` attribute.
+fn has_serde_rename_attribute(field: &Field) -> bool {
+    has_serde_flag(field, "rename")
+}
+
+/// Whether the given field has a `#[serde(skip)]` or `#[serde(skip_serializing)]` attribute, so it
+/// never appears in the JSON representation.
+fn field_has_serde_skip_attribute(field: &Field) -> bool {
+    has_serde_flag(field, "skip") || has_serde_flag(field, "skip_serializing")
+}
+
+/// Whether the given field has a serde attribute with the given name.
+fn has_serde_flag(field: &Field, name: &str) -> bool {
+    field.attrs.iter().filter(|attr| attr.path().is_ident("serde")).any(|attr| {
+        let mut found = false;
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(name) {
+                found = true;
+            }
+            if let Ok(value) = meta.value() {
+                // Skip the values of other attributes.
+                value.parse::<proc_macro2::TokenTree>()?;
+            }
+
+            Ok(())
+        });
+
+        found
+    })
+}
@@ -14,6 +14,7 @@ use super::{
 use crate::util::{PrivateField, field_has_serde_flatten_attribute, import_ruma_common};
 
 mod incoming;
+mod openapi;
 mod outgoing;
 
 pub fn expand_request(attr: RequestAttr, item: ItemStruct) -> TokenStream {
@@ -204,6 +205,7 @@ impl Request {
 
         let outgoing_request_impl = self.expand_outgoing(ruma_common);
         let incoming_request_impl = self.expand_incoming(ruma_common);
+        let openapi_impl = self.expand_openapi(ruma_common);
 
         quote! {
             #request_body_struct
@@ -214,6 +216,7 @@ impl Request {
                 use super::*;
                 #outgoing_request_impl
                 #incoming_request_impl
+                #openapi_impl
             }
         }
     }
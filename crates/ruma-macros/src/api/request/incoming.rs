@@ -12,6 +12,7 @@ impl Request {
         let serde_json = quote! { #ruma_common::exports::serde_json };
 
         let error_ty = &self.error_ty;
+        let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
 
         // FIXME: the rest of the field initializer expansions are gated `cfg(...)` except this one.
         // If we get errors about missing fields in Request for a path field look here.
@@ -176,10 +177,46 @@ impl Request {
             vars(self.body_fields(), quote! { request_body })
         };
 
+        let raw_body_impl = self.raw_body_field().map(|field| {
+            let field_name = field.ident.as_ref().expect("expected field to have an identifier");
+
+            quote! {
+                #[automatically_derived]
+                #[cfg(feature = "server")]
+                impl #impl_generics #ruma_common::api::IncomingRawBodyRequest for Request #ty_generics #where_clause {
+                    fn try_from_http_request_with_body<B, S>(
+                        request: #http::Request<B>,
+                        path_args: &[S],
+                    ) -> ::std::result::Result<(Self, B), #ruma_common::api::error::FromHttpRequestError>
+                    where
+                        S: ::std::convert::AsRef<::std::primitive::str>,
+                    {
+                        <Self as #ruma_common::api::IncomingRequest>::check_request_method(request.method())?;
+
+                        #parse_request_path
+                        #parse_query
+                        #parse_headers
+
+                        let #field_name = ::std::vec::Vec::new();
+
+                        ::std::result::Result::Ok((
+                            Self {
+                                #path_vars
+                                #query_vars
+                                #header_vars
+                                #body_vars
+                            },
+                            request.into_body(),
+                        ))
+                    }
+                }
+            }
+        });
+
         quote! {
             #[automatically_derived]
             #[cfg(feature = "server")]
-            impl #ruma_common::api::IncomingRequest for Request {
+            impl #impl_generics #ruma_common::api::IncomingRequest for Request #ty_generics #where_clause {
                 type EndpointError = #error_ty;
                 type OutgoingResponse = Response;
 
@@ -208,6 +245,8 @@ impl Request {
                     })
                 }
             }
+
+            #raw_body_impl
         }
     }
 }
@@ -0,0 +1,71 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use super::{Request, RequestFieldKind};
+use crate::{
+    api::openapi::{OpenApiFieldLocation, push_field_description},
+    util::field_has_serde_flatten_attribute,
+};
+
+impl Request {
+    pub fn expand_openapi(&self, ruma_common: &TokenStream) -> TokenStream {
+        let http = quote! { #ruma_common::exports::http };
+        let openapi = quote! { #ruma_common::api::openapi };
+
+        let fields = self.fields.iter().map(|field| {
+            let location = match &field.kind {
+                RequestFieldKind::Body if field_has_serde_flatten_attribute(&field.inner) => {
+                    OpenApiFieldLocation::FlattenedBody
+                }
+                RequestFieldKind::Body => OpenApiFieldLocation::Body,
+                RequestFieldKind::Header(header_name) => OpenApiFieldLocation::Header(header_name),
+                RequestFieldKind::NewtypeBody => OpenApiFieldLocation::NewtypeBody,
+                RequestFieldKind::RawBody => OpenApiFieldLocation::RawBody,
+                RequestFieldKind::Path => OpenApiFieldLocation::Path,
+                RequestFieldKind::Query if field_has_serde_flatten_attribute(&field.inner) => {
+                    OpenApiFieldLocation::QueryAll
+                }
+                RequestFieldKind::Query => OpenApiFieldLocation::Query,
+                RequestFieldKind::QueryAll => OpenApiFieldLocation::QueryAll,
+            };
+
+            push_field_description(&field.inner, location, ruma_common)
+        });
+
+        let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
+
+        // Endpoints can only be registered if the type is not generic.
+        let registration = self.generics.params.is_empty().then(|| {
+            quote! {
+                #[cfg(feature = "openapi")]
+                #ruma_common::exports::inventory::submit! {
+                    #openapi::EndpointRegistration::new::<Request>(::std::module_path!())
+                }
+            }
+        });
+
+        quote! {
+            #[automatically_derived]
+            #[cfg(feature = "openapi")]
+            impl #impl_generics #openapi::DescribeRequest for Request #ty_generics #where_clause {
+                fn request_fields() -> ::std::vec::Vec<#openapi::FieldDescription> {
+                    let mut fields = ::std::vec::Vec::new();
+                    #( #fields )*
+                    fields
+                }
+
+                fn response_description() -> ::std::option::Option<(
+                    #http::StatusCode,
+                    ::std::vec::Vec<#openapi::FieldDescription>,
+                )> {
+                    // Don't require the response to be described, it can be implemented manually
+                    // or be invalid, in which case its macro already reported an error.
+                    use #openapi::{ResponseOfDescribed as _, ResponseOfUndescribed as _};
+                    (&#openapi::ResponseOf::<Response>::new()).description()
+                }
+            }
+
+            #registration
+        }
+    }
+}
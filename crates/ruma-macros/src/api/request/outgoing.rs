@@ -12,8 +12,10 @@ impl Request {
 
         let error_ty = &self.error_ty;
 
-        let path_fields =
-            self.path_fields().map(|f| f.ident.as_ref().expect("path fields have a name"));
+        let path_fields: Vec<_> = self
+            .path_fields()
+            .map(|f| f.ident.as_ref().expect("path fields have a name"))
+            .collect();
 
         let request_query_string = if let Some(field) = self.query_all_field() {
             let field_name = field.ident.as_ref().expect("expected field to have identifier");
@@ -117,6 +119,58 @@ impl Request {
 
         let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
 
+        let build_http_request = |request_body: TokenStream| {
+            quote! {
+                let request_query_string = #request_query_string;
+
+                let mut http_request = #http::Request::builder()
+                    .method(<Self as #ruma_common::api::Metadata>::METHOD)
+                    .uri(<Self as #ruma_common::api::Metadata>::make_endpoint_url(
+                        path_builder_input,
+                        base_url,
+                        &[ #( &self.#path_fields ),* ],
+                        &request_query_string,
+                    )?)
+                    .body(#request_body)?;
+
+                #header_kvs
+
+                <<Self as #ruma_common::api::Metadata>::Authentication as #ruma_common::api::auth_scheme::AuthScheme>::add_authentication(
+                    &mut http_request,
+                    authentication_input
+                )
+                    .map_err(|error| #ruma_common::api::error::IntoHttpError::Authentication(error.into()))?;
+            }
+        };
+
+        let build_buffered_http_request = build_http_request(request_body);
+
+        let raw_body_impl = self.raw_body_field().is_some().then(|| {
+            // The authentication is computed with an empty body, the body is only swapped at the
+            // end.
+            let build_http_request_with_body = build_http_request(quote! {
+                ::std::vec::Vec::<::std::primitive::u8>::new()
+            });
+
+            quote! {
+                #[automatically_derived]
+                #[cfg(feature = "client")]
+                impl #impl_generics #ruma_common::api::OutgoingRawBodyRequest for Request #ty_generics #where_clause {
+                    fn try_into_http_request_with_body<B>(
+                        self,
+                        base_url: &::std::primitive::str,
+                        authentication_input: <<Self as #ruma_common::api::Metadata>::Authentication as #ruma_common::api::auth_scheme::AuthScheme>::Input<'_>,
+                        path_builder_input: <<Self as #ruma_common::api::Metadata>::PathBuilder as #ruma_common::api::path_builder::PathBuilder>::Input<'_>,
+                        body: B,
+                    ) -> ::std::result::Result<#http::Request<B>, #ruma_common::api::error::IntoHttpError> {
+                        #build_http_request_with_body
+
+                        Ok(http_request.map(|_| body))
+                    }
+                }
+            }
+        });
+
         quote! {
             #[automatically_derived]
             #[cfg(feature = "client")]
@@ -130,29 +184,13 @@ impl Request {
                     authentication_input: <<Self as #ruma_common::api::Metadata>::Authentication as #ruma_common::api::auth_scheme::AuthScheme>::Input<'_>,
                     path_builder_input: <<Self as #ruma_common::api::Metadata>::PathBuilder as #ruma_common::api::path_builder::PathBuilder>::Input<'_>,
                 ) -> ::std::result::Result<#http::Request<T>, #ruma_common::api::error::IntoHttpError> {
-                    let request_query_string = #request_query_string;
-
-                    let mut http_request = #http::Request::builder()
-                        .method(<Self as #ruma_common::api::Metadata>::METHOD)
-                        .uri(<Self as #ruma_common::api::Metadata>::make_endpoint_url(
-                            path_builder_input,
-                            base_url,
-                            &[ #( &self.#path_fields ),* ],
-                            &request_query_string,
-                        )?)
-                        .body(#request_body)?;
-
-                    #header_kvs
-
-                    <<Self as #ruma_common::api::Metadata>::Authentication as #ruma_common::api::auth_scheme::AuthScheme>::add_authentication(
-                        &mut http_request,
-                        authentication_input
-                    )
-                        .map_err(|error| #ruma_common::api::error::IntoHttpError::Authentication(error.into()))?;
+                    #build_buffered_http_request
 
                     Ok(http_request)
                 }
             }
+
+            #raw_body_impl
         }
     }
 }
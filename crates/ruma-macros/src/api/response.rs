@@ -17,6 +17,7 @@ use super::{
 use crate::util::{PrivateField, field_has_serde_flatten_attribute, import_ruma_common};
 
 mod incoming;
+mod openapi;
 mod outgoing;
 
 pub fn expand_response(attr: ResponseAttr, item: ItemStruct) -> TokenStream {
@@ -181,12 +182,14 @@ impl Response {
 
         let outgoing_response_impl = self.expand_outgoing(&self.status_ident, &ruma_common);
         let incoming_response_impl = self.expand_incoming(&self.error_ty, &ruma_common);
+        let openapi_impl = self.expand_openapi(&self.status_ident, &ruma_common);
 
         quote! {
             #response_body_struct
 
             #outgoing_response_impl
             #incoming_response_impl
+            #openapi_impl
         }
     }
 
@@ -33,6 +33,7 @@ impl Response {
             }
         });
 
+        let mut raw_body_field = None;
         let response_init_fields = {
             let mut fields = vec![];
             let mut raw_body = None;
@@ -109,6 +110,7 @@ impl Response {
                     // We are guaranteed only one new body field because of a check in
                     // `parse_response`.
                     ResponseFieldKind::RawBody => {
+                        raw_body_field = Some((field_name, cfg_attrs.clone()));
                         raw_body = Some(quote! {
                             #( #cfg_attrs )*
                             #field_name: {
@@ -126,10 +128,63 @@ impl Response {
 
             fields.extend(raw_body);
 
+            fields
+        };
+
+        let raw_body_impl = raw_body_field.map(|(field_name, cfg_attrs)| {
+            // The raw body field must be last.
+            let init_fields = &response_init_fields[..response_init_fields.len() - 1];
+
             quote! {
-                #(#fields,)*
+                #[automatically_derived]
+                #[cfg(feature = "client")]
+                #[allow(deprecated)]
+                impl #ruma_common::api::IncomingRawBodyResponse for Response {
+                    type RawBody<B> = B;
+
+                    fn try_from_http_response_with_body<B>(
+                        response: #http::Response<B>,
+                    ) -> impl ::std::future::Future<
+                        Output = ::std::result::Result<
+                            (Self, B),
+                            #ruma_common::api::error::FromHttpResponseError<#error_ty>,
+                        >,
+                    > + ::std::marker::Send
+                    where
+                        B: #ruma_common::exports::http_body::Body
+                            + ::std::marker::Send
+                            + ::std::marker::Unpin,
+                        B::Data: ::std::marker::Send,
+                        B::Error: ::std::convert::Into<
+                            ::std::boxed::Box<
+                                dyn ::std::error::Error
+                                    + ::std::marker::Send
+                                    + ::std::marker::Sync
+                            >,
+                        >,
+                    {
+                        async move {
+                            if response.status().as_u16() < 400 {
+                                #extract_response_headers
+
+                                let response_head = Self {
+                                    #(#init_fields,)*
+                                    #( #cfg_attrs )*
+                                    #field_name: ::std::vec::Vec::new(),
+                                };
+
+                                ::std::result::Result::Ok((response_head, response.into_body()))
+                            } else {
+                                ::std::result::Result::Err(
+                                    #ruma_common::api::error_from_http_response_with_body(response)
+                                        .await,
+                                )
+                            }
+                        }
+                    }
+                }
             }
-        };
+        });
 
         quote! {
             #[automatically_derived]
@@ -149,7 +204,7 @@ impl Response {
                         #typed_response_body_decl
 
                         ::std::result::Result::Ok(Self {
-                            #response_init_fields
+                            #(#response_init_fields,)*
                         })
                     } else {
                         Err(#ruma_common::api::error::FromHttpResponseError::Server(
@@ -160,6 +215,8 @@ impl Response {
                     }
                 }
             }
+
+            #raw_body_impl
         }
     }
 }
@@ -0,0 +1,46 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Ident;
+
+use super::{Response, ResponseFieldKind};
+use crate::{
+    api::openapi::{OpenApiFieldLocation, push_field_description},
+    util::field_has_serde_flatten_attribute,
+};
+
+impl Response {
+    pub fn expand_openapi(&self, status_ident: &Ident, ruma_common: &TokenStream) -> TokenStream {
+        let http = quote! { #ruma_common::exports::http };
+        let openapi = quote! { #ruma_common::api::openapi };
+
+        let fields = self.fields.iter().map(|field| {
+            let location = match &field.kind {
+                ResponseFieldKind::Body if field_has_serde_flatten_attribute(&field.inner) => {
+                    OpenApiFieldLocation::FlattenedBody
+                }
+                ResponseFieldKind::Body => OpenApiFieldLocation::Body,
+                ResponseFieldKind::Header(header_name) => OpenApiFieldLocation::Header(header_name),
+                ResponseFieldKind::NewtypeBody => OpenApiFieldLocation::NewtypeBody,
+                ResponseFieldKind::RawBody => OpenApiFieldLocation::RawBody,
+            };
+
+            push_field_description(&field.inner, location, ruma_common)
+        });
+
+        quote! {
+            #[automatically_derived]
+            #[cfg(feature = "openapi")]
+            impl #openapi::DescribeResponse for Response {
+                fn response_status() -> #http::StatusCode {
+                    #http::StatusCode::#status_ident
+                }
+
+                fn response_fields() -> ::std::vec::Vec<#openapi::FieldDescription> {
+                    let mut fields = ::std::vec::Vec::new();
+                    #( #fields )*
+                    fields
+                }
+            }
+        }
+    }
+}
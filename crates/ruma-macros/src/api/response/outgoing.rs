@@ -123,6 +123,35 @@ impl Response {
             }
         };
 
+        let build_response_builder = quote! {
+            let mut resp_builder = #http::Response::builder()
+                .status(#http::StatusCode::#status_ident);
+
+            if let Some(mut headers) = resp_builder.headers_mut() {
+                #headers
+            }
+        };
+
+        let raw_body_impl = self.has_raw_body().then(|| {
+            quote! {
+                #[automatically_derived]
+                #[cfg(feature = "server")]
+                #[allow(deprecated)]
+                impl #ruma_common::api::OutgoingRawBodyResponse for Response {
+                    type HttpBody<B> = B;
+
+                    fn try_into_http_response_with_body<B>(
+                        self,
+                        body: B,
+                    ) -> ::std::result::Result<#http::Response<B>, #ruma_common::api::error::IntoHttpError> {
+                        #build_response_builder
+
+                        ::std::result::Result::Ok(resp_builder.body(body)?)
+                    }
+                }
+            }
+        });
+
         quote! {
             #[automatically_derived]
             #[cfg(feature = "server")]
@@ -134,16 +163,13 @@ impl Response {
                     static APPLICATION_JSON: #http::header::HeaderValue =
                            #http::header::HeaderValue::from_static("application/json");
 
-                    let mut resp_builder = #http::Response::builder()
-                        .status(#http::StatusCode::#status_ident);
-
-                    if let Some(mut headers) = resp_builder.headers_mut() {
-                        #headers
-                    }
+                    #build_response_builder
 
                     ::std::result::Result::Ok(resp_builder.body(#body)?)
                 }
             }
+
+            #raw_body_impl
         }
     }
 }
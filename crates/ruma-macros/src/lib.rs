@@ -27,6 +27,7 @@ mod util;
 
 use self::{
     api::{
+        openapi::expand_derive_describe_schema,
         request::{expand_derive_request, expand_request},
         response::{expand_derive_response, expand_response},
     },
@@ -832,6 +833,14 @@ pub fn response(attr: TokenStream, item: TokenStream) -> TokenStream {
     expand_response(attr, item).into()
 }
 
+/// > ⚠ If this is the only documentation you see, please navigate to the docs for
+/// > `ruma_common::api::openapi::DescribeSchema`, where actual documentation can be found.
+#[proc_macro_derive(DescribeSchema)]
+pub fn derive_describe_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ItemStruct);
+    expand_derive_describe_schema(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
 /// Internal helper that the request macro delegates most of its work to.
 #[proc_macro_derive(Request, attributes(ruma_api))]
 pub fn derive_request(input: TokenStream) -> TokenStream {
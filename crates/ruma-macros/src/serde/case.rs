@@ -53,6 +53,30 @@ impl RenameRule {
             KebabCase => SnakeCase.apply_to_variant(variant).replace('_', "-"),
         }
     }
+
+    /// Apply a renaming rule to a struct field, returning the version expected in the source.
+    pub fn apply_to_field(&self, field: &str) -> String {
+        match *self {
+            None | LowerCase | SnakeCase => field.to_owned(),
+            Uppercase | ScreamingSnakeCase => field.to_ascii_uppercase(),
+            CamelCase => {
+                let mut camel = String::new();
+                let mut capitalize = false;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        camel.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        camel.push(ch);
+                    }
+                }
+                camel
+            }
+            KebabCase => field.replace('_', "-"),
+        }
+    }
 }
 
 impl Parse for RenameRule {
@@ -103,4 +127,22 @@ mod tests {
             assert_eq!(KebabCase.apply_to_variant(original), kebab);
         }
     }
+
+    #[test]
+    fn rename_fields() {
+        for &(original, upper, camel, screaming, kebab) in &[
+            ("outcome", "OUTCOME", "outcome", "OUTCOME", "outcome"),
+            ("very_tasty", "VERY_TASTY", "veryTasty", "VERY_TASTY", "very-tasty"),
+            ("a", "A", "a", "A", "a"),
+            ("z42", "Z42", "z42", "Z42", "z42"),
+        ] {
+            assert_eq!(None.apply_to_field(original), original);
+            assert_eq!(LowerCase.apply_to_field(original), original);
+            assert_eq!(Uppercase.apply_to_field(original), upper);
+            assert_eq!(CamelCase.apply_to_field(original), camel);
+            assert_eq!(SnakeCase.apply_to_field(original), original);
+            assert_eq!(ScreamingSnakeCase.apply_to_field(original), screaming);
+            assert_eq!(KebabCase.apply_to_field(original), kebab);
+        }
+    }
 }
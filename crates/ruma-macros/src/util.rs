@@ -221,3 +221,55 @@ fn is_serde_flatten_attribute(attr: &Attribute) -> bool {
 
     contains_flatten
 }
+
+/// The name of the given field after serialization, taking `#[serde(rename = "...")]` into
+/// account.
+pub fn field_serialized_name(field: &Field) -> String {
+    let mut name = None;
+
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                if let Ok(value) = meta.value() {
+                    name = Some(value.parse::<LitStr>()?.value());
+                }
+            } else if let Ok(value) = meta.value() {
+                // Skip the values of other attributes.
+                value.parse::<proc_macro2::TokenTree>()?;
+            }
+
+            Ok(())
+        });
+    }
+
+    name.unwrap_or_else(|| {
+        field.ident.as_ref().expect("expected field to have an identifier").to_string()
+    })
+}
+
+/// Whether the given field can be omitted during deserialization, i.e. it is an `Option` or it
+/// has a `#[serde(default)]` attribute.
+pub fn field_is_optional(field: &Field) -> bool {
+    if let syn::Type::Path(syn::TypePath { path: syn::Path { segments, .. }, .. }) = &field.ty {
+        if segments.last().is_some_and(|s| s.ident == "Option") {
+            return true;
+        }
+    }
+
+    field.attrs.iter().filter(|attr| attr.path().is_ident("serde")).any(|attr| {
+        let mut has_default = false;
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("default") {
+                has_default = true;
+            }
+            if let Ok(value) = meta.value() {
+                // Skip the values of other attributes.
+                value.parse::<proc_macro2::TokenTree>()?;
+            }
+
+            Ok(())
+        });
+
+        has_default
+    })
+}
@@ -15,6 +15,8 @@ pub mod v1 {
         push::{PushFormat, Tweak},
         serde::{JsonObject, StringEnum},
     };
+    #[cfg(feature = "client")]
+    use ruma_common::{UserId, push::Action, serde::Raw};
     use ruma_events::TimelineEventType;
     use serde::{Deserialize, Serialize};
     use serde_json::value::RawValue as RawJsonValue;
@@ -213,7 +215,7 @@ pub mod v1 {
         /// A dictionary of customisations made to the way this notification is to be presented.
         ///
         /// These are added by push rules.
-        #[serde(with = "tweak_serde", skip_serializing_if = "Vec::is_empty")]
+        #[serde(default, with = "tweak_serde", skip_serializing_if = "Vec::is_empty")]
         pub tweaks: Vec<Tweak>,
     }
 
@@ -268,6 +270,192 @@ pub mod v1 {
         }
     }
 
+    /// A builder for a [`Notification`] about an event.
+    ///
+    /// This computes the fields of the notification from the event, the push actions that apply
+    /// to it and the data of the pusher, as described in the [homeserver behaviour] of the push
+    /// gateway API:
+    ///
+    /// * If the format of the pusher is [`PushFormat::EventIdOnly`], only the `event_id`,
+    ///   `room_id`, `counts`, `prio` and `devices` fields are set.
+    /// * The priority is `high` if the actions set the `highlight` or `sound` tweaks, or if the
+    ///   event is encrypted, since its content is unknown. Otherwise it is `low`.
+    /// * The tweaks set by the actions are added to every device.
+    ///
+    /// [homeserver behaviour]: https://spec.matrix.org/latest/push-gateway-api/#homeserver-behaviour
+    #[cfg(feature = "client")]
+    #[derive(Debug)]
+    pub struct NotificationBuilder<'a> {
+        /// The JSON of the event.
+        event: &'a RawJsonValue,
+
+        /// The push actions that apply to the event.
+        actions: &'a [Action],
+
+        /// The data of the pusher.
+        pusher_data: &'a ruma_common::push::HttpPusherData,
+
+        /// The ID of the room, if it is not in the event.
+        room_id: Option<OwnedRoomId>,
+
+        /// The ID of the user receiving the notification.
+        user_id: Option<&'a UserId>,
+
+        /// The display name of the sender.
+        sender_display_name: Option<String>,
+
+        /// The name of the room.
+        room_name: Option<String>,
+
+        /// The alias of the room.
+        room_alias: Option<OwnedRoomAliasId>,
+
+        /// The counts of unacknowledged communications.
+        counts: NotificationCounts,
+
+        /// The devices to send the notification to.
+        devices: Vec<Device>,
+    }
+
+    #[cfg(feature = "client")]
+    impl<'a> NotificationBuilder<'a> {
+        /// Creates a new `NotificationBuilder` for the given event, push actions and pusher data.
+        pub fn new<T>(
+            event: &'a Raw<T>,
+            actions: &'a [Action],
+            pusher_data: &'a ruma_common::push::HttpPusherData,
+        ) -> Self {
+            Self {
+                event: event.json(),
+                actions,
+                pusher_data,
+                room_id: None,
+                user_id: None,
+                sender_display_name: None,
+                room_name: None,
+                room_alias: None,
+                counts: NotificationCounts::default(),
+                devices: Vec::new(),
+            }
+        }
+
+        /// Set the ID of the room in which the event occurred.
+        ///
+        /// This is only necessary if the event doesn't have a `room_id` field, like events
+        /// received via the `/sync` endpoint.
+        pub fn room_id(self, room_id: OwnedRoomId) -> Self {
+            Self { room_id: Some(room_id), ..self }
+        }
+
+        /// Set the ID of the user receiving the notification.
+        ///
+        /// This is used to know if the user is the target of a member event.
+        pub fn user_id(self, user_id: &'a UserId) -> Self {
+            Self { user_id: Some(user_id), ..self }
+        }
+
+        /// Set the current display name of the sender in the room.
+        pub fn sender_display_name(self, sender_display_name: String) -> Self {
+            Self { sender_display_name: Some(sender_display_name), ..self }
+        }
+
+        /// Set the name of the room.
+        pub fn room_name(self, room_name: String) -> Self {
+            Self { room_name: Some(room_name), ..self }
+        }
+
+        /// Set an alias to display for the room.
+        pub fn room_alias(self, room_alias: OwnedRoomAliasId) -> Self {
+            Self { room_alias: Some(room_alias), ..self }
+        }
+
+        /// Set the current number of unacknowledged communications for the user.
+        pub fn counts(self, counts: NotificationCounts) -> Self {
+            Self { counts, ..self }
+        }
+
+        /// Add a device to send the notification to.
+        ///
+        /// The `data` and `tweaks` fields of the device are replaced by the data of the pusher and
+        /// the tweaks set by the push actions.
+        pub fn device(mut self, device: Device) -> Self {
+            self.devices.push(device);
+            self
+        }
+
+        /// Build the `Notification`.
+        ///
+        /// Returns an error if the event is missing required fields or if they are invalid.
+        pub fn build(self) -> serde_json::Result<Notification> {
+            let event: NotificationEventDeHelper = serde_json::from_str(self.event.get())?;
+
+            let tweaks: Vec<Tweak> = self
+                .actions
+                .iter()
+                .filter_map(|action| match action {
+                    Action::SetTweak(tweak) => Some(tweak.clone()),
+                    _ => None,
+                })
+                .collect();
+
+            let is_high_priority = event.event_type == TimelineEventType::RoomEncrypted
+                || tweaks
+                    .iter()
+                    .any(|tweak| matches!(tweak, Tweak::Highlight(true) | Tweak::Sound(_)));
+            let prio = if is_high_priority {
+                NotificationPriority::High
+            } else {
+                NotificationPriority::Low
+            };
+
+            let data = PusherData::from(self.pusher_data.clone());
+            let devices = self
+                .devices
+                .into_iter()
+                .map(|device| Device { data: data.clone(), tweaks: tweaks.clone(), ..device })
+                .collect();
+
+            let mut notification = Notification {
+                event_id: Some(event.event_id),
+                room_id: self.room_id.or(event.room_id),
+                counts: self.counts,
+                prio,
+                devices,
+                ..Default::default()
+            };
+
+            if self.pusher_data.format == Some(PushFormat::EventIdOnly) {
+                return Ok(notification);
+            }
+
+            notification.user_is_target = event.event_type == TimelineEventType::RoomMember
+                && self
+                    .user_id
+                    .is_some_and(|user_id| event.state_key.as_deref() == Some(user_id.as_str()));
+            notification.event_type = Some(event.event_type);
+            notification.sender = Some(event.sender);
+            notification.sender_display_name = self.sender_display_name;
+            notification.room_name = self.room_name;
+            notification.room_alias = self.room_alias;
+            notification.content = event.content;
+
+            Ok(notification)
+        }
+    }
+
+    /// Helper type to deserialize the fields of an event used in a notification.
+    #[cfg(feature = "client")]
+    #[derive(Deserialize)]
+    struct NotificationEventDeHelper {
+        event_id: OwnedEventId,
+        room_id: Option<OwnedRoomId>,
+        #[serde(rename = "type")]
+        event_type: TimelineEventType,
+        sender: OwnedUserId,
+        state_key: Option<String>,
+        content: Option<Box<RawJsonValue>>,
+    }
+
     mod tweak_serde {
         use std::fmt;
 
@@ -423,5 +611,91 @@ pub mod v1 {
 
             assert_eq!(expected, to_json_value(notice).unwrap());
         }
+
+        #[cfg(feature = "client")]
+        #[test]
+        fn build_notification() {
+            use ruma_common::{
+                push::{Action, HttpPusherData, PushFormat},
+                serde::Raw,
+                user_id,
+            };
+
+            use super::NotificationBuilder;
+
+            let event = Raw::new(&json!({
+                "event_id": "$3957tyerfgewrf384",
+                "type": "m.room.member",
+                "sender": "@exampleuser:matrix.org",
+                "state_key": "@alice:matrix.org",
+                "content": { "membership": "invite" },
+            }))
+            .unwrap();
+            let actions = [Action::Notify, Action::SetTweak(Tweak::Sound("default".into()))];
+            let mut pusher_data = HttpPusherData::new("https://push.example.com".to_owned());
+            pusher_data.data.insert("custom".to_owned(), "value".into());
+
+            let device = Device::new("org.example.app".into(), "pushkey".into());
+
+            let notification = NotificationBuilder::new(&event, &actions, &pusher_data)
+                .room_id(owned_room_id!("!slw48wfj34rtnrf:example.com"))
+                .user_id(user_id!("@alice:matrix.org"))
+                .sender_display_name("Major Tom".to_owned())
+                .room_name("Mission Control".to_owned())
+                .counts(NotificationCounts::new(uint!(2), uint!(0)))
+                .device(device.clone())
+                .build()
+                .unwrap();
+
+            assert_eq!(
+                to_json_value(notification).unwrap(),
+                json!({
+                    "event_id": "$3957tyerfgewrf384",
+                    "room_id": "!slw48wfj34rtnrf:example.com",
+                    "type": "m.room.member",
+                    "sender": "@exampleuser:matrix.org",
+                    "sender_display_name": "Major Tom",
+                    "room_name": "Mission Control",
+                    "user_is_target": true,
+                    "content": { "membership": "invite" },
+                    "counts": { "unread": 2 },
+                    "devices": [
+                        {
+                            "app_id": "org.example.app",
+                            "pushkey": "pushkey",
+                            "data": { "custom": "value" },
+                            "tweaks": { "sound": "default" },
+                        },
+                    ],
+                })
+            );
+
+            // Only send the event ID and low priority without highlight or sound.
+            let actions = [Action::Notify];
+            pusher_data.format = Some(PushFormat::EventIdOnly);
+
+            let notification = NotificationBuilder::new(&event, &actions, &pusher_data)
+                .room_id(owned_room_id!("!slw48wfj34rtnrf:example.com"))
+                .sender_display_name("Major Tom".to_owned())
+                .device(device)
+                .build()
+                .unwrap();
+
+            assert_eq!(
+                to_json_value(notification).unwrap(),
+                json!({
+                    "event_id": "$3957tyerfgewrf384",
+                    "room_id": "!slw48wfj34rtnrf:example.com",
+                    "prio": "low",
+                    "devices": [
+                        {
+                            "app_id": "org.example.app",
+                            "pushkey": "pushkey",
+                            "data": { "format": "event_id_only", "custom": "value" },
+                        },
+                    ],
+                })
+            );
+        }
     }
 }
@@ -0,0 +1,284 @@
+//! The push gateway routing notifications to the push providers.
+
+use std::{collections::BTreeMap, error::Error as StdError, fmt, sync::Arc};
+
+use ruma_common::api::{
+    IncomingRequest, OutgoingResponse,
+    error::{FromHttpRequestError, MatrixError, MatrixErrorBody},
+};
+use ruma_push_gateway_api::send_event_notification::v1::{Notification, Request, Response};
+use serde_json::json;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::{PushError, PushProvider};
+
+/// A push gateway delivering notifications to devices with the [`PushProvider`] registered for
+/// their `app_id`.
+///
+/// The pushkeys of devices with an `app_id` that has no provider are rejected.
+#[derive(Clone, Default)]
+pub struct PushGateway {
+    /// The push providers, by app ID.
+    providers: BTreeMap<String, Arc<dyn PushProvider>>,
+}
+
+impl PushGateway {
+    /// Creates a new `PushGateway` without any push provider.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the given push provider for the given app ID.
+    ///
+    /// Replaces the provider that was previously registered for this app ID, if any.
+    pub fn add_provider(&mut self, app_id: impl Into<String>, provider: Arc<dyn PushProvider>) {
+        self.providers.insert(app_id.into(), provider);
+    }
+
+    /// Register the given push provider for the given app ID, and return this gateway.
+    pub fn with_provider(
+        mut self,
+        app_id: impl Into<String>,
+        provider: impl PushProvider + 'static,
+    ) -> Self {
+        self.add_provider(app_id, Arc::new(provider));
+        self
+    }
+
+    /// The push provider registered for the given app ID, if any.
+    pub fn provider(&self, app_id: &str) -> Option<&Arc<dyn PushProvider>> {
+        self.providers.get(app_id)
+    }
+
+    /// Deliver the given notification to all its devices.
+    ///
+    /// Returns the pushkeys that were rejected by their push provider, or that don't have a push
+    /// provider. Delivery is attempted for all the devices even if it fails for some of them.
+    ///
+    /// The pushkeys of the devices for which delivery failed are not rejected, since the failure
+    /// might be temporary. Returns an error if delivery failed for all the devices, so the
+    /// notification can be sent again later.
+    pub async fn send_notification(
+        &self,
+        notification: &Notification,
+    ) -> Result<Vec<String>, SendNotificationError> {
+        let mut rejected = Vec::new();
+        let mut failures = Vec::new();
+
+        for device in &notification.devices {
+            let Some(provider) = self.providers.get(&device.app_id) else {
+                warn!(app_id = device.app_id, "No push provider for app ID, rejecting pushkey");
+                rejected.push(device.pushkey.clone());
+                continue;
+            };
+
+            match provider.push(notification, device).await {
+                Ok(()) => {
+                    debug!(app_id = device.app_id, "Delivered notification");
+                }
+                Err(PushError::Rejected) => {
+                    debug!(app_id = device.app_id, "Pushkey rejected by push provider");
+                    rejected.push(device.pushkey.clone());
+                }
+                Err(PushError::Delivery(error)) => {
+                    warn!(app_id = device.app_id, "Failed to deliver notification: {error}");
+                    failures.push((device.pushkey.clone(), error));
+                }
+            }
+        }
+
+        if !failures.is_empty() && failures.len() == notification.devices.len() {
+            return Err(SendNotificationError { failures });
+        }
+
+        Ok(rejected)
+    }
+
+    /// Handle the given `send_event_notification` request.
+    pub async fn handle_request(
+        &self,
+        request: Request,
+    ) -> Result<Response, SendNotificationError> {
+        let rejected = self.send_notification(&request.notification).await?;
+        Ok(Response::new(rejected))
+    }
+
+    /// Handle the given HTTP request to the `send_event_notification` endpoint.
+    ///
+    /// The routing of the request must be done by the caller. This returns a `400 Bad Request`
+    /// error response if the request is invalid, and a `502 Bad Gateway` error response if the
+    /// notification could not be delivered to any device, so the homeserver tries again later.
+    pub async fn handle_http_request<T: AsRef<[u8]>>(
+        &self,
+        request: http::Request<T>,
+    ) -> http::Response<Vec<u8>> {
+        let request = match Request::try_from_http_request(request, &[] as &[&str]) {
+            Ok(request) => request,
+            Err(error) => {
+                let (status_code, errcode) = match error {
+                    FromHttpRequestError::MethodMismatch { .. } => {
+                        (http::StatusCode::METHOD_NOT_ALLOWED, "M_UNRECOGNIZED")
+                    }
+                    _ => (http::StatusCode::BAD_REQUEST, "M_BAD_JSON"),
+                };
+
+                return error_response(status_code, errcode, &error);
+            }
+        };
+
+        match self.handle_request(request).await {
+            Ok(response) => response.try_into_http_response().unwrap_or_else(|error| {
+                error_response(http::StatusCode::INTERNAL_SERVER_ERROR, "M_UNKNOWN", &error)
+            }),
+            Err(error) => error_response(http::StatusCode::BAD_GATEWAY, "M_UNKNOWN", &error),
+        }
+    }
+}
+
+impl fmt::Debug for PushGateway {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PushGateway").field("app_ids", &self.providers.keys()).finish()
+    }
+}
+
+/// Construct an HTTP error response with the given status code, error code and error.
+fn error_response(
+    status_code: http::StatusCode,
+    errcode: &str,
+    error: &dyn fmt::Display,
+) -> http::Response<Vec<u8>> {
+    let error = MatrixError {
+        status_code,
+        body: MatrixErrorBody::Json(json!({ "errcode": errcode, "error": error.to_string() })),
+    };
+
+    error.try_into_http_response().unwrap_or_else(|_| {
+        let mut response = http::Response::new(Vec::new());
+        *response.status_mut() = status_code;
+        response
+    })
+}
+
+/// An error when a notification could not be delivered to any device.
+#[derive(Debug, Error)]
+#[error("failed to deliver the notification to all {} device(s)", self.failures.len())]
+#[non_exhaustive]
+pub struct SendNotificationError {
+    /// The pushkeys of the devices, with the corresponding error.
+    pub failures: Vec<(String, Box<dyn StdError + Send + Sync>)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use macro_rules_attribute::apply;
+    use ruma_common::api::{IncomingResponse, OutgoingRequest, auth_scheme::SendAccessToken};
+    use ruma_push_gateway_api::send_event_notification::v1::{
+        Device, Notification, Request, Response,
+    };
+    use smol_macros::test;
+
+    use super::PushGateway;
+    use crate::{PushError, PushFuture, PushProvider};
+
+    /// A push provider recording the pushkeys it receives.
+    #[derive(Clone, Default)]
+    struct TestProvider {
+        pushed: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PushProvider for TestProvider {
+        fn push<'a>(
+            &'a self,
+            _notification: &'a Notification,
+            device: &'a Device,
+        ) -> PushFuture<'a> {
+            Box::pin(async move {
+                match device.pushkey.as_str() {
+                    "invalid" => Err(PushError::Rejected),
+                    "unreachable" => Err(PushError::delivery("service unavailable")),
+                    pushkey => {
+                        self.pushed.lock().unwrap().push(pushkey.to_owned());
+                        Ok(())
+                    }
+                }
+            })
+        }
+    }
+
+    fn device(app_id: &str, pushkey: &str) -> Device {
+        Device::new(app_id.to_owned(), pushkey.to_owned())
+    }
+
+    #[apply(test!)]
+    async fn route_by_app_id() {
+        let provider = TestProvider::default();
+        let gateway = PushGateway::new().with_provider("org.example.app", provider.clone());
+
+        let notification = Notification::new(vec![
+            device("org.example.app", "first"),
+            device("org.example.unknown", "second"),
+            device("org.example.app", "invalid"),
+            device("org.example.app", "third"),
+        ]);
+
+        let rejected = gateway.send_notification(&notification).await.unwrap();
+        assert_eq!(rejected, ["second", "invalid"]);
+        assert_eq!(*provider.pushed.lock().unwrap(), ["first", "third"]);
+    }
+
+    #[apply(test!)]
+    async fn delivery_failures() {
+        let provider = TestProvider::default();
+        let gateway = PushGateway::new().with_provider("org.example.app", provider.clone());
+
+        // The pushkeys for which delivery failed are not rejected.
+        let notification = Notification::new(vec![
+            device("org.example.app", "unreachable"),
+            device("org.example.app", "invalid"),
+            device("org.example.app", "valid"),
+        ]);
+
+        let rejected = gateway.send_notification(&notification).await.unwrap();
+        assert_eq!(rejected, ["invalid"]);
+        assert_eq!(*provider.pushed.lock().unwrap(), ["valid"]);
+
+        // The notification is not delivered to any device.
+        let notification = Notification::new(vec![device("org.example.app", "unreachable")]);
+
+        let error = gateway.send_notification(&notification).await.unwrap_err();
+        assert_eq!(error.failures.len(), 1);
+        assert_eq!(error.failures[0].0, "unreachable");
+
+        let request = Request::new(notification)
+            .try_into_http_request::<Vec<u8>>("https://push.example.org", SendAccessToken::None, ())
+            .unwrap();
+        let http_response = gateway.handle_http_request(request).await;
+        assert_eq!(http_response.status(), http::StatusCode::BAD_GATEWAY);
+    }
+
+    #[apply(test!)]
+    async fn handle_http_request() {
+        let gateway = PushGateway::new().with_provider("org.example.app", TestProvider::default());
+
+        let request = Request::new(Notification::new(vec![
+            device("org.example.app", "valid"),
+            device("org.example.app", "invalid"),
+        ]))
+        .try_into_http_request::<Vec<u8>>("https://push.example.org", SendAccessToken::None, ())
+        .unwrap();
+
+        let http_response = gateway.handle_http_request(request).await;
+        assert_eq!(http_response.status(), http::StatusCode::OK);
+        let response = Response::try_from_http_response(http_response).unwrap();
+        assert_eq!(response.rejected, ["invalid"]);
+
+        let request = http::Request::post("https://push.example.org/_matrix/push/v1/notify")
+            .body(b"{}".to_vec())
+            .unwrap();
+        let http_response = gateway.handle_http_request(request).await;
+        assert_eq!(http_response.status(), http::StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,21 @@
+#![doc(html_favicon_url = "https://ruma.dev/favicon.ico")]
+#![doc(html_logo_url = "https://ruma.dev/images/logo.png")]
+//! A reference implementation of a [Matrix push gateway][push-api].
+//!
+//! The [`PushGateway`] accepts the notifications sent by homeservers with the
+//! `send_event_notification` endpoint, and delivers them to the devices with the
+//! [`PushProvider`] registered for their `app_id`.
+//!
+//! [push-api]: https://spec.matrix.org/latest/push-gateway-api/
+
+#![warn(missing_docs)]
+
+mod gateway;
+mod log_provider;
+mod provider;
+
+pub use self::{
+    gateway::{PushGateway, SendNotificationError},
+    log_provider::LogPushProvider,
+    provider::{PushError, PushFuture, PushProvider},
+};
@@ -0,0 +1,152 @@
+//! A push provider writing the notifications to a log, for local testing.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use ruma_push_gateway_api::send_event_notification::v1::{Device, Notification};
+use serde_json::json;
+use tracing::info;
+
+use crate::{PushError, PushFuture, PushProvider};
+
+/// A [`PushProvider`] that writes the notifications it receives as JSON lines, for local testing.
+///
+/// Each line is a JSON object with a `device` field containing the device that was notified, and
+/// a `notification` field containing the notification.
+///
+/// Pushkeys can be configured to be rejected, to test how the homeserver handles rejections.
+pub struct LogPushProvider {
+    /// The writer of the notifications.
+    writer: Mutex<Box<dyn Write + Send>>,
+
+    /// The pushkeys to reject.
+    rejected_pushkeys: BTreeSet<String>,
+}
+
+impl LogPushProvider {
+    /// Creates a new `LogPushProvider` writing the notifications to the given writer.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self { writer: Mutex::new(Box::new(writer)), rejected_pushkeys: BTreeSet::new() }
+    }
+
+    /// Creates a new `LogPushProvider` appending the notifications to the file at the given path.
+    ///
+    /// The file is created if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+
+    /// Creates a new `LogPushProvider` writing the notifications to the standard output.
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+
+    /// Reject the given pushkey instead of writing the notifications sent to it.
+    pub fn reject_pushkey(mut self, pushkey: impl Into<String>) -> Self {
+        self.rejected_pushkeys.insert(pushkey.into());
+        self
+    }
+
+    /// Write the given notification for the given device.
+    fn write(&self, notification: &Notification, device: &Device) -> Result<(), PushError> {
+        let mut line = serde_json::to_vec(&json!({
+            "device": device,
+            "notification": notification,
+        }))
+        .map_err(PushError::delivery)?;
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().map_err(|_| PushError::delivery("poisoned lock"))?;
+        writer.write_all(&line).and_then(|()| writer.flush()).map_err(PushError::delivery)
+    }
+}
+
+impl PushProvider for LogPushProvider {
+    fn push<'a>(&'a self, notification: &'a Notification, device: &'a Device) -> PushFuture<'a> {
+        Box::pin(async move {
+            if self.rejected_pushkeys.contains(&device.pushkey) {
+                info!(app_id = device.app_id, pushkey = device.pushkey, "Rejecting pushkey");
+                return Err(PushError::Rejected);
+            }
+
+            info!(
+                app_id = device.app_id,
+                pushkey = device.pushkey,
+                event_id = ?notification.event_id,
+                "Received notification"
+            );
+            self.write(notification, device)
+        })
+    }
+}
+
+impl fmt::Debug for LogPushProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogPushProvider")
+            .field("rejected_pushkeys", &self.rejected_pushkeys)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use macro_rules_attribute::apply;
+    use ruma_common::owned_event_id;
+    use ruma_push_gateway_api::send_event_notification::v1::{Device, Notification};
+    use serde_json::{Value as JsonValue, from_slice as from_json_slice, json};
+    use smol_macros::test;
+
+    use super::LogPushProvider;
+    use crate::PushGateway;
+
+    /// A writer to a shared buffer.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[apply(test!)]
+    async fn write_notifications() {
+        let buffer = SharedBuffer::default();
+        let provider = LogPushProvider::new(buffer.clone()).reject_pushkey("invalid");
+        let gateway = PushGateway::new().with_provider("org.example.app", provider);
+
+        let mut notification = Notification::new(vec![
+            Device::new("org.example.app".to_owned(), "valid".to_owned()),
+            Device::new("org.example.app".to_owned(), "invalid".to_owned()),
+        ]);
+        notification.event_id = Some(owned_event_id!("$event"));
+
+        let rejected = gateway.send_notification(&notification).await.unwrap();
+        assert_eq!(rejected, ["invalid"]);
+
+        let buffer = buffer.0.lock().unwrap();
+        let lines = buffer.split(|b| *b == b'\n').filter(|line| !line.is_empty());
+        let lines =
+            lines.map(|line| from_json_slice::<JsonValue>(line).unwrap()).collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["device"]["pushkey"], json!("valid"));
+        assert_eq!(lines[0]["notification"]["event_id"], json!("$event"));
+    }
+}
@@ -0,0 +1,44 @@
+//! The trait implemented by the delivery backends of the push gateway.
+
+use std::{error::Error as StdError, future::Future, pin::Pin};
+
+use ruma_push_gateway_api::send_event_notification::v1::{Device, Notification};
+use thiserror::Error;
+
+/// The future returned by [`PushProvider::push()`].
+pub type PushFuture<'a> = Pin<Box<dyn Future<Output = Result<(), PushError>> + Send + 'a>>;
+
+/// A backend that delivers notifications to devices, like a platform push service.
+pub trait PushProvider: Send + Sync {
+    /// Deliver the given notification to the given device.
+    ///
+    /// The `devices` field of the notification contains all the devices of the request, but only
+    /// `device` should be notified. The tweaks of the notification are in the `tweaks` field of
+    /// `device`.
+    fn push<'a>(&'a self, notification: &'a Notification, device: &'a Device) -> PushFuture<'a>;
+}
+
+/// An error returned by a [`PushProvider`] when a notification could not be delivered.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PushError {
+    /// The pushkey of the device was rejected by the push service.
+    ///
+    /// It is returned to the homeserver, which should stop sending notifications for it.
+    #[error("the pushkey was rejected")]
+    Rejected,
+
+    /// The notification could not be delivered for another reason.
+    ///
+    /// The pushkey is not rejected. If delivery fails for all the devices of the notification, the
+    /// homeserver is asked to try again later.
+    #[error("failed to deliver the notification: {0}")]
+    Delivery(Box<dyn StdError + Send + Sync>),
+}
+
+impl PushError {
+    /// Construct a `PushError::Delivery` from the given error.
+    pub fn delivery(error: impl Into<Box<dyn StdError + Send + Sync>>) -> Self {
+        Self::Delivery(error.into())
+    }
+}